@@ -0,0 +1,145 @@
+//! Subcommands run from the command line instead of the server
+
+use chrono::{DateTime, Utc};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{ApiKey, Scope};
+use crate::services::{generate_key, generate_key_id, hash_key, key_prefix, parse_scopes, validate_key_id};
+
+pub const GENERATE_KEY_USAGE: &str = "Usage: elicit generate-key --name NAME --scopes extract,ocr,jobs,admin \
+     [--id ID] [--expires-at RFC3339] [--count N] [--format json|sql]";
+
+/// How `generate-key` prints the records to store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// An entry for `API_KEYS_FILE`
+    Json,
+    /// An `INSERT` into the `api_keys` table of `API_KEYS_DB`
+    Sql,
+}
+
+/// Arguments of `elicit generate-key`
+#[derive(Debug, Clone)]
+pub struct GenerateKeyArgs {
+    pub id: Option<String>,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub count: usize,
+    pub format: RecordFormat,
+}
+
+impl GenerateKeyArgs {
+    pub fn parse(args: &[String]) -> AppResult<Self> {
+        let (mut id, mut name, mut scopes, mut expires_at) = (None, None, None, None);
+        let (mut count, mut format) = (1, RecordFormat::Json);
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| AppError::validation(format!("{} needs a value", flag)))?;
+            match flag.as_str() {
+                "--id" => id = Some(validate_key_id(value.clone())?),
+                "--name" => name = Some(value.clone()),
+                "--scopes" => scopes = Some(parse_scopes(value)?),
+                "--expires-at" => {
+                    let timestamp = DateTime::parse_from_rfc3339(value)
+                        .map_err(|e| AppError::validation(format!("Invalid --expires-at '{}': {}", value, e)))?;
+                    expires_at = Some(timestamp.with_timezone(&Utc));
+                }
+                "--count" => {
+                    count = value
+                        .parse()
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or_else(|| AppError::validation("--count must be a positive number"))?;
+                }
+                "--format" => {
+                    format = match value.as_str() {
+                        "json" => RecordFormat::Json,
+                        "sql" => RecordFormat::Sql,
+                        _ => return Err(AppError::validation("--format must be json or sql")),
+                    };
+                }
+                _ => return Err(AppError::validation(format!("Unknown option {}", flag))),
+            }
+        }
+
+        let name = name
+            .filter(|name| !name.trim().is_empty())
+            .ok_or_else(|| AppError::validation("--name is required"))?;
+        let scopes = scopes
+            .filter(|scopes| !scopes.is_empty())
+            .ok_or_else(|| AppError::validation("--scopes is required"))?;
+        if id.is_some() && count > 1 {
+            return Err(AppError::validation("--id can only be used with a single key"));
+        }
+        Ok(Self {
+            id,
+            name,
+            scopes,
+            expires_at,
+            count,
+            format,
+        })
+    }
+}
+
+/// A new key and the record holding its hash. The key itself is not kept anywhere.
+#[derive(Debug, Clone)]
+pub struct GeneratedKey {
+    pub key: String,
+    pub record: ApiKey,
+}
+
+/// Generate `args.count` keys with their records
+pub fn generate_keys(args: &GenerateKeyArgs) -> Vec<GeneratedKey> {
+    (0..args.count)
+        .map(|_| {
+            let key = generate_key();
+            let record = ApiKey {
+                id: args.id.clone().unwrap_or_else(generate_key_id),
+                name: args.name.clone(),
+                prefix: key_prefix(&key).to_string(),
+                key_hash: hash_key(&key),
+                scopes: args.scopes.clone(),
+                created_at: Utc::now(),
+                expires_at: args.expires_at,
+                disabled: false,
+            };
+            GeneratedKey { key, record }
+        })
+        .collect()
+}
+
+/// The key, to hand to the client, and the record to store
+pub fn render_generated_key(generated: &GeneratedKey, format: RecordFormat) -> String {
+    let record = &generated.record;
+    let stored = match format {
+        RecordFormat::Json => serde_json::to_string_pretty(record).unwrap_or_default(),
+        RecordFormat::Sql => format!(
+            "INSERT INTO api_keys (id, name, prefix, key_hash, scopes, created_at, expires_at, disabled) \
+             VALUES ({}, {}, {}, {}, {}, {}, {}, 0);",
+            sql_text(&record.id),
+            sql_text(&record.name),
+            sql_text(&record.prefix),
+            sql_text(&record.key_hash),
+            sql_text(&record.scopes.iter().map(Scope::as_str).collect::<Vec<_>>().join(",")),
+            sql_text(&record.created_at.to_rfc3339()),
+            record
+                .expires_at
+                .map(|expires_at| sql_text(&expires_at.to_rfc3339()))
+                .unwrap_or_else(|| "NULL".to_string()),
+        ),
+    };
+    format!(
+        "# Key '{}' for {}. It is shown only this once and can't be recovered from the hash.\n{}\n\
+         # Record to store:\n{}\n",
+        record.id, record.name, generated.key, stored
+    )
+}
+
+fn sql_text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -1,8 +1,8 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use anyhow::{Result, Context};
 use once_cell::sync::Lazy;
-use tracing::{info, warn, error};
+use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -12,9 +12,49 @@ pub struct Config {
     pub max_concurrent_requests: usize,
     pub request_timeout_seconds: u64,
     pub worker_threads: usize,
+    pub ocr_engine: String,
+    pub subprocess_memory_limit_mb: u64,
+    pub ocr_cache_capacity: usize,
+    pub ocr_cache_dir: Option<String>,
+    pub job_workers: usize,
+    pub job_queue_capacity: usize,
+    pub job_ttl_seconds: u64,
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_base_ms: u64,
+    pub webhook_timeout_seconds: u64,
+    /// When non-empty, callbacks may only go to these hosts (and their subdomains)
+    pub webhook_allowed_hosts: Vec<String>,
+    pub webhook_denied_hosts: Vec<String>,
+    /// Allow callbacks to loopback, private and link-local addresses, e.g. for local receivers
+    pub webhook_allow_private: bool,
+    pub max_batch_files: usize,
+    /// Decompressed size of all ZIP entries in one batch request
+    pub max_batch_unzipped_mb: usize,
+    pub batch_concurrency: usize,
+    pub url_fetch_timeout_seconds: u64,
+    pub url_fetch_max_redirects: usize,
+    pub url_fetch_allowed_hosts: Vec<String>,
+    pub url_fetch_denied_hosts: Vec<String>,
+    pub url_fetch_allow_private: bool,
+    pub idempotency_capacity: usize,
+    pub idempotency_ttl_seconds: u64,
+    /// Memory all stored responses may take together
+    pub idempotency_max_memory_mb: usize,
+    /// Larger responses aren't stored, so retrying them runs the request again
+    pub idempotency_max_response_mb: usize,
+    /// Port of the gRPC API, 0 to serve HTTP only
+    pub grpc_port: u16,
+    pub session_idle_timeout_seconds: u64,
+    pub session_max_memory_mb: usize,
+    /// WebSocket sessions open at once, across all clients
+    pub session_max_concurrent: usize,
+    /// JSON file of API key records, see `services::api_keys`
+    pub api_keys_file: Option<String>,
+    /// SQLite database holding API key records, instead of `api_keys_file`
+    pub api_keys_db: Option<String>,
 }
 
-// Global API keys loaded from environment
+// API keys from the environment, used when no key registry file or database is configured
 pub static VALID_API_KEYS: Lazy<HashSet<String>> = Lazy::new(|| {
     env::var("VALID_API_KEYS")
         .unwrap_or_default()
@@ -24,6 +64,19 @@ pub static VALID_API_KEYS: Lazy<HashSet<String>> = Lazy::new(|| {
         .collect()
 });
 
+// Webhook signing secrets per API key, as `key_id=secret` pairs (or `api_key=secret` for `VALID_API_KEYS`)
+pub static WEBHOOK_SECRETS: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    env::var("WEBHOOK_SECRETS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| {
+            let (key, secret) = pair.split_once('=')?;
+            let (key, secret) = (key.trim(), secret.trim());
+            (!key.is_empty() && !secret.is_empty()).then(|| (key.to_string(), secret.to_string()))
+        })
+        .collect()
+});
+
 impl Config {
     pub fn from_env() -> Result<Self> {
         info!("Loading configuration from environment variables");
@@ -43,18 +96,68 @@ impl Config {
                 .context("Failed to parse REQUEST_TIMEOUT_SECONDS")?,
             worker_threads: Self::parse_env_var("WORKER_THREADS", 4)
                 .context("Failed to parse WORKER_THREADS")?,
+            ocr_engine: env::var("OCR_ENGINE").unwrap_or_else(|_| {
+                info!("OCR_ENGINE not set, using default: tesseract");
+                "tesseract".to_string()
+            }),
+            subprocess_memory_limit_mb: Self::parse_env_var("SUBPROCESS_MEMORY_LIMIT_MB", 2048)
+                .context("Failed to parse SUBPROCESS_MEMORY_LIMIT_MB")?,
+            ocr_cache_capacity: Self::parse_env_var("OCR_CACHE_CAPACITY", 256)
+                .context("Failed to parse OCR_CACHE_CAPACITY")?,
+            ocr_cache_dir: env::var("OCR_CACHE_DIR").ok().filter(|dir| !dir.trim().is_empty()),
+            job_workers: Self::parse_env_var("JOB_WORKERS", 2)
+                .context("Failed to parse JOB_WORKERS")?,
+            job_queue_capacity: Self::parse_env_var("JOB_QUEUE_CAPACITY", 100)
+                .context("Failed to parse JOB_QUEUE_CAPACITY")?,
+            job_ttl_seconds: Self::parse_env_var("JOB_TTL_SECONDS", 3600)
+                .context("Failed to parse JOB_TTL_SECONDS")?,
+            webhook_max_attempts: Self::parse_env_var("WEBHOOK_MAX_ATTEMPTS", 5)
+                .context("Failed to parse WEBHOOK_MAX_ATTEMPTS")?,
+            webhook_retry_base_ms: Self::parse_env_var("WEBHOOK_RETRY_BASE_MS", 1000)
+                .context("Failed to parse WEBHOOK_RETRY_BASE_MS")?,
+            webhook_timeout_seconds: Self::parse_env_var("WEBHOOK_TIMEOUT_SECONDS", 10)
+                .context("Failed to parse WEBHOOK_TIMEOUT_SECONDS")?,
+            webhook_allowed_hosts: Self::parse_list_var("WEBHOOK_ALLOWED_HOSTS"),
+            webhook_denied_hosts: Self::parse_list_var("WEBHOOK_DENIED_HOSTS"),
+            webhook_allow_private: Self::parse_env_var("WEBHOOK_ALLOW_PRIVATE", false)
+                .context("Failed to parse WEBHOOK_ALLOW_PRIVATE")?,
+            max_batch_files: Self::parse_env_var("MAX_BATCH_FILES", 20)
+                .context("Failed to parse MAX_BATCH_FILES")?,
+            max_batch_unzipped_mb: Self::parse_env_var("MAX_BATCH_UNZIPPED_MB", 200)
+                .context("Failed to parse MAX_BATCH_UNZIPPED_MB")?,
+            batch_concurrency: Self::parse_env_var("BATCH_CONCURRENCY", 4)
+                .context("Failed to parse BATCH_CONCURRENCY")?,
+            url_fetch_timeout_seconds: Self::parse_env_var("URL_FETCH_TIMEOUT_SECONDS", 15)
+                .context("Failed to parse URL_FETCH_TIMEOUT_SECONDS")?,
+            url_fetch_max_redirects: Self::parse_env_var("URL_FETCH_MAX_REDIRECTS", 3)
+                .context("Failed to parse URL_FETCH_MAX_REDIRECTS")?,
+            url_fetch_allowed_hosts: Self::parse_list_var("URL_FETCH_ALLOWED_HOSTS"),
+            url_fetch_denied_hosts: Self::parse_list_var("URL_FETCH_DENIED_HOSTS"),
+            url_fetch_allow_private: Self::parse_env_var("URL_FETCH_ALLOW_PRIVATE", false)
+                .context("Failed to parse URL_FETCH_ALLOW_PRIVATE")?,
+            idempotency_capacity: Self::parse_env_var("IDEMPOTENCY_CAPACITY", 1000)
+                .context("Failed to parse IDEMPOTENCY_CAPACITY")?,
+            idempotency_ttl_seconds: Self::parse_env_var("IDEMPOTENCY_TTL_SECONDS", 86400)
+                .context("Failed to parse IDEMPOTENCY_TTL_SECONDS")?,
+            idempotency_max_memory_mb: Self::parse_env_var("IDEMPOTENCY_MAX_MEMORY_MB", 64)
+                .context("Failed to parse IDEMPOTENCY_MAX_MEMORY_MB")?,
+            idempotency_max_response_mb: Self::parse_env_var("IDEMPOTENCY_MAX_RESPONSE_MB", 4)
+                .context("Failed to parse IDEMPOTENCY_MAX_RESPONSE_MB")?,
+            grpc_port: Self::parse_env_var("GRPC_PORT", 50051)
+                .context("Failed to parse GRPC_PORT")?,
+            session_idle_timeout_seconds: Self::parse_env_var("SESSION_IDLE_TIMEOUT_SECONDS", 300)
+                .context("Failed to parse SESSION_IDLE_TIMEOUT_SECONDS")?,
+            session_max_memory_mb: Self::parse_env_var("SESSION_MAX_MEMORY_MB", 64)
+                .context("Failed to parse SESSION_MAX_MEMORY_MB")?,
+            session_max_concurrent: Self::parse_env_var("SESSION_MAX_CONCURRENT", 32)
+                .context("Failed to parse SESSION_MAX_CONCURRENT")?,
+            api_keys_file: env::var("API_KEYS_FILE").ok().filter(|path| !path.trim().is_empty()),
+            api_keys_db: env::var("API_KEYS_DB").ok().filter(|path| !path.trim().is_empty()),
         };
         
         // Validate configuration values
         config.validate()?;
 
-        // Validate that we have at least one API key
-        if VALID_API_KEYS.is_empty() {
-            warn!("No valid API keys configured. Set VALID_API_KEYS environment variable.");
-        } else {
-            info!("Loaded {} valid API keys", VALID_API_KEYS.len());
-        }
-
         info!("Configuration loaded successfully: {:?}", config);
         Ok(config)
     }
@@ -79,6 +182,16 @@ impl Config {
         }
     }
     
+    /// Comma-separated, lowercased list; empty when the variable is unset
+    fn parse_list_var(var_name: &str) -> Vec<String> {
+        env::var(var_name)
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+    
     fn validate(&self) -> Result<()> {
         if self.server_port == 0 {
             return Err(anyhow::anyhow!("SERVER_PORT must be greater than 0"));
@@ -95,10 +208,70 @@ impl Config {
         if self.worker_threads == 0 {
             return Err(anyhow::anyhow!("WORKER_THREADS must be greater than 0"));
         }
+        if self.subprocess_memory_limit_mb == 0 {
+            return Err(anyhow::anyhow!("SUBPROCESS_MEMORY_LIMIT_MB must be greater than 0"));
+        }
+        if self.job_workers == 0 {
+            return Err(anyhow::anyhow!("JOB_WORKERS must be greater than 0"));
+        }
+        if self.job_queue_capacity == 0 {
+            return Err(anyhow::anyhow!("JOB_QUEUE_CAPACITY must be greater than 0"));
+        }
+        if self.job_ttl_seconds == 0 {
+            return Err(anyhow::anyhow!("JOB_TTL_SECONDS must be greater than 0"));
+        }
+        if self.webhook_max_attempts == 0 {
+            return Err(anyhow::anyhow!("WEBHOOK_MAX_ATTEMPTS must be greater than 0"));
+        }
+        if self.webhook_timeout_seconds == 0 {
+            return Err(anyhow::anyhow!("WEBHOOK_TIMEOUT_SECONDS must be greater than 0"));
+        }
+        if self.max_batch_files == 0 {
+            return Err(anyhow::anyhow!("MAX_BATCH_FILES must be greater than 0"));
+        }
+        if self.max_batch_unzipped_mb == 0 {
+            return Err(anyhow::anyhow!("MAX_BATCH_UNZIPPED_MB must be greater than 0"));
+        }
+        if self.batch_concurrency == 0 {
+            return Err(anyhow::anyhow!("BATCH_CONCURRENCY must be greater than 0"));
+        }
+        if self.url_fetch_timeout_seconds == 0 {
+            return Err(anyhow::anyhow!("URL_FETCH_TIMEOUT_SECONDS must be greater than 0"));
+        }
+        if self.idempotency_ttl_seconds == 0 {
+            return Err(anyhow::anyhow!("IDEMPOTENCY_TTL_SECONDS must be greater than 0"));
+        }
+        if self.idempotency_max_response_mb > self.idempotency_max_memory_mb {
+            return Err(anyhow::anyhow!(
+                "IDEMPOTENCY_MAX_RESPONSE_MB must not exceed IDEMPOTENCY_MAX_MEMORY_MB"
+            ));
+        }
+        if self.session_idle_timeout_seconds == 0 {
+            return Err(anyhow::anyhow!("SESSION_IDLE_TIMEOUT_SECONDS must be greater than 0"));
+        }
+        if self.session_max_memory_mb == 0 {
+            return Err(anyhow::anyhow!("SESSION_MAX_MEMORY_MB must be greater than 0"));
+        }
+        if self.session_max_concurrent == 0 {
+            return Err(anyhow::anyhow!("SESSION_MAX_CONCURRENT must be greater than 0"));
+        }
+        if self.api_keys_file.is_some() && self.api_keys_db.is_some() {
+            return Err(anyhow::anyhow!("Set only one of API_KEYS_FILE and API_KEYS_DB"));
+        }
+        if self.ocr_engine.trim().is_empty() {
+            return Err(anyhow::anyhow!("OCR_ENGINE must not be empty"));
+        }
         Ok(())
     }
 
+    /// Whether `key` belongs to an enabled, unexpired key in the registry
     pub fn validate_api_key(key: &str) -> bool {
-        VALID_API_KEYS.contains(key)
+        crate::services::api_keys().resolve(key).is_ok()
+    }
+
+    /// Secret used to sign webhook callbacks for jobs created with the key `key_id`,
+    /// or with `key` itself as older configurations name it
+    pub fn webhook_secret(key_id: &str, key: &str) -> Option<&'static str> {
+        WEBHOOK_SECRETS.get(key_id).or_else(|| WEBHOOK_SECRETS.get(key)).map(String::as_str)
     }
 }
\ No newline at end of file
@@ -3,11 +3,12 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
 use thiserror::Error;
 use uuid::Uuid;
 use chrono;
 
+use crate::models::{ErrorDetail, ErrorResponse};
+
 pub type AppResult<T> = Result<T, AppError>;
 
 #[derive(Error, Debug)]
@@ -15,6 +16,15 @@ pub enum AppError {
     #[error("Invalid API key")]
     InvalidApiKey,
     
+    #[error("API key has expired")]
+    ApiKeyExpired,
+    
+    #[error("API key is disabled")]
+    ApiKeyDisabled,
+    
+    #[error("API key lacks the '{scope}' scope")]
+    InsufficientScope { scope: String },
+    
     #[error("File too large: {size}MB exceeds limit of {limit}MB")]
     FileTooLarge { size: usize, limit: usize },
     
@@ -30,8 +40,8 @@ pub enum AppError {
     #[error("OCR processing failed: {message}")]
     OcrError { message: String },
     
-    #[error("Request timeout")]
-    Timeout,
+    #[error("Request timeout during {stage}")]
+    Timeout { stage: String },
     
     #[error("Internal server error: {message}")]
     Internal { message: String },
@@ -42,6 +52,27 @@ pub enum AppError {
     #[error("Missing file in request")]
     MissingFile,
     
+    #[error("Not found: {resource}")]
+    NotFound { resource: String },
+    
+    #[error("URL not allowed: {reason}")]
+    UrlNotAllowed { reason: String },
+    
+    #[error("Failed to fetch document: {message}")]
+    FetchFailed { message: String },
+    
+    #[error("A request with this Idempotency-Key is still being processed")]
+    IdempotencyKeyInUse,
+    
+    #[error("Idempotency-Key was already used for a different request")]
+    IdempotencyKeyReused,
+    
+    #[error("Conflict: {message}")]
+    Conflict { message: String },
+    
+    #[error("Session limit exceeded: {message}")]
+    SessionLimitExceeded { message: String },
+    
     #[error("Validation error: {message}")]
     ValidationError { message: String },
     
@@ -59,15 +90,25 @@ impl AppError {
     pub fn error_code(&self) -> &'static str {
         match self {
             AppError::InvalidApiKey => "INVALID_API_KEY",
+            AppError::ApiKeyExpired => "API_KEY_EXPIRED",
+            AppError::ApiKeyDisabled => "API_KEY_DISABLED",
+            AppError::InsufficientScope { .. } => "INSUFFICIENT_SCOPE",
             AppError::FileTooLarge { .. } => "FILE_TOO_LARGE",
             AppError::InvalidFile { .. } => "INVALID_FILE",
             AppError::RateLimitExceeded => "RATE_LIMIT_EXCEEDED",
             AppError::ProcessingError { .. } => "PROCESSING_ERROR",
             AppError::OcrError { .. } => "OCR_ERROR",
-            AppError::Timeout => "REQUEST_TIMEOUT",
+            AppError::Timeout { .. } => "REQUEST_TIMEOUT",
             AppError::Internal { .. } => "INTERNAL_ERROR",
             AppError::InvalidContentType => "INVALID_CONTENT_TYPE",
             AppError::MissingFile => "MISSING_FILE",
+            AppError::NotFound { .. } => "NOT_FOUND",
+            AppError::UrlNotAllowed { .. } => "URL_NOT_ALLOWED",
+            AppError::FetchFailed { .. } => "FETCH_FAILED",
+            AppError::IdempotencyKeyInUse => "IDEMPOTENCY_KEY_IN_USE",
+            AppError::IdempotencyKeyReused => "IDEMPOTENCY_KEY_REUSED",
+            AppError::Conflict { .. } => "CONFLICT",
+            AppError::SessionLimitExceeded { .. } => "SESSION_LIMIT_EXCEEDED",
             AppError::ValidationError { .. } => "VALIDATION_ERROR",
             AppError::ConfigError { .. } => "CONFIG_ERROR",
             AppError::ServiceUnavailable { .. } => "SERVICE_UNAVAILABLE",
@@ -78,21 +119,94 @@ impl AppError {
     pub fn status_code(&self) -> StatusCode {
         match self {
             AppError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            AppError::ApiKeyExpired => StatusCode::UNAUTHORIZED,
+            AppError::ApiKeyDisabled => StatusCode::UNAUTHORIZED,
+            AppError::InsufficientScope { .. } => StatusCode::FORBIDDEN,
             AppError::FileTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             AppError::InvalidFile { .. } => StatusCode::BAD_REQUEST,
             AppError::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
             AppError::ProcessingError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::OcrError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::Timeout => StatusCode::REQUEST_TIMEOUT,
+            AppError::Timeout { .. } => StatusCode::REQUEST_TIMEOUT,
             AppError::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::InvalidContentType => StatusCode::BAD_REQUEST,
             AppError::MissingFile => StatusCode::BAD_REQUEST,
+            AppError::NotFound { .. } => StatusCode::NOT_FOUND,
+            AppError::UrlNotAllowed { .. } => StatusCode::FORBIDDEN,
+            AppError::FetchFailed { .. } => StatusCode::BAD_GATEWAY,
+            AppError::IdempotencyKeyInUse => StatusCode::CONFLICT,
+            AppError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::SessionLimitExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             AppError::ValidationError { .. } => StatusCode::BAD_REQUEST,
             AppError::ConfigError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
             AppError::DatabaseError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// Status code of the error for the gRPC API, see [`grpc_code`]
+    pub fn grpc_code(&self) -> tonic::Code {
+        grpc_code(self.error_code())
+    }
+}
+
+/// gRPC metadata entry carrying the error code of a failed call
+pub const GRPC_ERROR_CODE_KEY: &str = "x-elicit-error-code";
+
+/// gRPC status code for an error code.
+///
+/// Keyed on the code rather than the variant so that stream errors, which
+/// only carry the code, map the same way as `AppError`s.
+pub fn grpc_code(error_code: &str) -> tonic::Code {
+    match error_code {
+        "INVALID_API_KEY" => tonic::Code::Unauthenticated,
+        "API_KEY_EXPIRED" => tonic::Code::Unauthenticated,
+        "API_KEY_DISABLED" => tonic::Code::Unauthenticated,
+        "INSUFFICIENT_SCOPE" => tonic::Code::PermissionDenied,
+        "FILE_TOO_LARGE" => tonic::Code::InvalidArgument,
+        "INVALID_FILE" => tonic::Code::InvalidArgument,
+        "RATE_LIMIT_EXCEEDED" => tonic::Code::ResourceExhausted,
+        "PROCESSING_ERROR" => tonic::Code::Internal,
+        "OCR_ERROR" => tonic::Code::Internal,
+        "REQUEST_TIMEOUT" => tonic::Code::DeadlineExceeded,
+        "INTERNAL_ERROR" => tonic::Code::Internal,
+        "INVALID_CONTENT_TYPE" => tonic::Code::InvalidArgument,
+        "MISSING_FILE" => tonic::Code::InvalidArgument,
+        "NOT_FOUND" => tonic::Code::NotFound,
+        "URL_NOT_ALLOWED" => tonic::Code::PermissionDenied,
+        "FETCH_FAILED" => tonic::Code::Unavailable,
+        "IDEMPOTENCY_KEY_IN_USE" => tonic::Code::Aborted,
+        "IDEMPOTENCY_KEY_REUSED" => tonic::Code::FailedPrecondition,
+        "CONFLICT" => tonic::Code::FailedPrecondition,
+        "SESSION_LIMIT_EXCEEDED" => tonic::Code::ResourceExhausted,
+        "VALIDATION_ERROR" => tonic::Code::InvalidArgument,
+        "CONFIG_ERROR" => tonic::Code::Internal,
+        "SERVICE_UNAVAILABLE" => tonic::Code::Unavailable,
+        "DATABASE_ERROR" => tonic::Code::Internal,
+        _ => tonic::Code::Unknown,
+    }
+}
+
+/// gRPC status for an error code, with the code in the `x-elicit-error-code` metadata
+pub fn grpc_status(error_code: &str, message: impl Into<String>) -> tonic::Status {
+    let mut status = tonic::Status::new(grpc_code(error_code), message);
+    if let Ok(value) = error_code.parse() {
+        status.metadata_mut().insert(GRPC_ERROR_CODE_KEY, value);
+    }
+    status
+}
+
+impl From<AppError> for tonic::Status {
+    fn from(err: AppError) -> Self {
+        tracing::error!(
+            error_code = err.error_code(),
+            grpc_code = ?err.grpc_code(),
+            error_message = %err,
+            "gRPC error occurred"
+        );
+        grpc_status(err.error_code(), err.to_string())
+    }
 }
 
 impl IntoResponse for AppError {
@@ -112,18 +226,17 @@ impl IntoResponse for AppError {
             "API error occurred"
         );
 
-        let body = Json(json!({
-            "success": false,
-            "error": {
-                "code": error_code,
-                "message": message,
-                "request_id": request_id,
-                "timestamp": timestamp
+        let body = ErrorResponse {
+            success: false,
+            error: ErrorDetail {
+                request_id: Some(request_id),
+                timestamp: Some(timestamp),
+                ..ErrorDetail::new(error_code, message)
             },
-            "data": null
-        }));
+            data: None,
+        };
 
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
@@ -154,7 +267,7 @@ impl From<serde_json::Error> for AppError {
 
 impl From<tokio::time::error::Elapsed> for AppError {
     fn from(_: tokio::time::error::Elapsed) -> Self {
-        AppError::Timeout
+        AppError::timeout("request")
     }
 }
 
@@ -184,6 +297,48 @@ impl AppError {
         }
     }
     
+    pub fn not_found(resource: impl Into<String>) -> Self {
+        AppError::NotFound {
+            resource: resource.into(),
+        }
+    }
+    
+    pub fn url_not_allowed(reason: impl Into<String>) -> Self {
+        AppError::UrlNotAllowed {
+            reason: reason.into(),
+        }
+    }
+    
+    pub fn fetch_failed(message: impl Into<String>) -> Self {
+        AppError::FetchFailed {
+            message: message.into(),
+        }
+    }
+    
+    pub fn timeout(stage: impl Into<String>) -> Self {
+        AppError::Timeout {
+            stage: stage.into(),
+        }
+    }
+    
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict {
+            message: message.into(),
+        }
+    }
+    
+    pub fn session_limit(message: impl Into<String>) -> Self {
+        AppError::SessionLimitExceeded {
+            message: message.into(),
+        }
+    }
+    
+    pub fn insufficient_scope(scope: impl ToString) -> Self {
+        AppError::InsufficientScope {
+            scope: scope.to_string(),
+        }
+    }
+    
     pub fn internal(message: impl Into<String>) -> Self {
         AppError::Internal {
             message: message.into(),
@@ -0,0 +1,170 @@
+//! Conversions from the API models to their protobuf messages
+
+use tonic::Status;
+
+use crate::error::grpc_status;
+use crate::grpc::proto;
+use crate::models::{
+    ExtractResponse, JobError, JobResponse, JobStatus, PageAnalysis, PageKind, PdfMetadata, StreamEvent, TextSource,
+    WebhookDelivery,
+};
+
+impl From<PdfMetadata> for proto::Metadata {
+    fn from(metadata: PdfMetadata) -> Self {
+        Self {
+            title: metadata.title,
+            author: metadata.author,
+            creation_date: metadata.creation_date.map(|date| date.to_rfc3339()),
+            modification_date: metadata.modification_date.map(|date| date.to_rfc3339()),
+            file_size_bytes: metadata.file_size_bytes as u64,
+            ocr_used: metadata.ocr_used,
+            preprocessing_applied: metadata
+                .preprocessing_applied
+                .iter()
+                .map(|step| step.as_str().to_string())
+                .collect(),
+            page_analysis: metadata.page_analysis.into_iter().map(Into::into).collect(),
+            ocr_cache_hits: metadata.ocr_cache_hits as u64,
+        }
+    }
+}
+
+impl From<PageAnalysis> for proto::PageAnalysis {
+    fn from(analysis: PageAnalysis) -> Self {
+        Self {
+            page: analysis.page,
+            kind: proto::PageKind::from(analysis.kind) as i32,
+            image_coverage: analysis.image_coverage,
+            image_count: analysis.image_count as u64,
+            text_operators: analysis.text_operators as u64,
+            invisible_text_operators: analysis.invisible_text_operators as u64,
+            reasons: analysis.reasons,
+        }
+    }
+}
+
+impl From<PageKind> for proto::PageKind {
+    fn from(kind: PageKind) -> Self {
+        match kind {
+            PageKind::Scanned => proto::PageKind::Scanned,
+            PageKind::Native => proto::PageKind::Native,
+            PageKind::Mixed => proto::PageKind::Mixed,
+            PageKind::Empty => proto::PageKind::Empty,
+        }
+    }
+}
+
+impl From<TextSource> for proto::TextSource {
+    fn from(source: TextSource) -> Self {
+        match source {
+            TextSource::Native => proto::TextSource::Native,
+            TextSource::Ocr => proto::TextSource::Ocr,
+        }
+    }
+}
+
+impl From<ExtractResponse> for proto::ExtractResponse {
+    fn from(response: ExtractResponse) -> Self {
+        Self {
+            text: response.data.text,
+            pages: response.data.pages as u64,
+            metadata: Some(response.data.metadata.into()),
+            processing_time_ms: response.processing_time_ms,
+            body: None,
+            content_type: None,
+            options: response
+                .options
+                .map(|options| {
+                    options
+                        .pairs()
+                        .into_iter()
+                        .map(|(name, value)| (name.to_string(), value))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<JobStatus> for proto::JobStatus {
+    fn from(status: JobStatus) -> Self {
+        match status {
+            JobStatus::Queued => proto::JobStatus::Queued,
+            JobStatus::Processing => proto::JobStatus::Processing,
+            JobStatus::Completed => proto::JobStatus::Completed,
+            JobStatus::Failed => proto::JobStatus::Failed,
+        }
+    }
+}
+
+impl From<JobError> for proto::JobError {
+    fn from(error: JobError) -> Self {
+        Self {
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+impl From<JobResponse> for proto::Job {
+    fn from(job: JobResponse) -> Self {
+        Self {
+            job_id: job.job_id,
+            status: proto::JobStatus::from(job.status) as i32,
+            pages_done: job.progress.pages_done as u64,
+            pages_total: job.progress.pages_total.map(|total| total as u64),
+            file_name: job.file_name,
+            callback_url: job.callback_url,
+            created_at: job.created_at.to_rfc3339(),
+            started_at: job.started_at.map(|date| date.to_rfc3339()),
+            finished_at: job.finished_at.map(|date| date.to_rfc3339()),
+            expires_at: job.expires_at.map(|date| date.to_rfc3339()),
+            result: job.result.map(Into::into),
+            error: job.error.map(Into::into),
+        }
+    }
+}
+
+impl From<WebhookDelivery> for proto::WebhookDelivery {
+    fn from(delivery: WebhookDelivery) -> Self {
+        Self {
+            delivery_id: delivery.delivery_id,
+            job_id: delivery.job_id,
+            event: delivery.event,
+            url: delivery.url,
+            attempt: delivery.attempt,
+            attempted_at: delivery.attempted_at.to_rfc3339(),
+            duration_ms: delivery.duration_ms,
+            status_code: delivery.status_code.map(u32::from),
+            error: delivery.error,
+            success: delivery.success,
+        }
+    }
+}
+
+/// A stream event as a message, or the status that ends the stream for an error event
+// Streams of a tonic service yield `Result<_, Status>` items, so the size of `Status` is given
+#[allow(clippy::result_large_err)]
+pub(crate) fn extract_event(event: StreamEvent) -> Result<proto::ExtractEvent, Status> {
+    use proto::extract_event::Event;
+
+    let event = match event {
+        StreamEvent::Started { file_name, pages_total } => Event::Started(proto::Started {
+            file_name,
+            pages_total: pages_total as u64,
+        }),
+        StreamEvent::Progress { stage, pages_done, pages_total } => Event::Progress(proto::Progress {
+            stage,
+            pages_done: pages_done as u64,
+            pages_total: pages_total as u64,
+        }),
+        StreamEvent::Page { page, text, source } => Event::Page(proto::Page {
+            page: page as u64,
+            text,
+            source: proto::TextSource::from(source) as i32,
+        }),
+        StreamEvent::Summary(response) => Event::Summary((*response).into()),
+        StreamEvent::Error { error } => return Err(grpc_status(&error.code, error.message)),
+    };
+    Ok(proto::ExtractEvent { event: Some(event) })
+}
@@ -0,0 +1,38 @@
+//! gRPC API, served on its own port next to the HTTP router.
+//!
+//! The methods mirror the HTTP endpoints: the same API keys, extraction
+//! options and error codes, with `AppError`s mapped onto gRPC status codes.
+
+mod convert;
+mod service;
+
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+
+use crate::config::Config;
+
+pub use service::ElicitService;
+
+/// Types and client/server stubs generated from `proto/elicit/v1/elicit.proto`
+pub mod proto {
+    #![allow(clippy::all)]
+    tonic::include_proto!("elicit.v1");
+}
+
+/// Room for the rest of a request message on top of the document itself
+const MESSAGE_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// The Elicit service, accepting documents up to the configured file size limit
+pub fn service(config: &Config) -> proto::elicit_server::ElicitServer<ElicitService> {
+    proto::elicit_server::ElicitServer::new(ElicitService::new())
+        .max_decoding_message_size(config.max_file_size_mb * 1024 * 1024 + MESSAGE_OVERHEAD_BYTES)
+}
+
+/// Serve the gRPC API on `listener` until the server fails
+pub async fn serve(listener: TcpListener, config: &Config) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(service(config))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
@@ -0,0 +1,271 @@
+use std::pin::Pin;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn, debug, error};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::grpc::convert::extract_event;
+use crate::grpc::proto::{self, elicit_server::Elicit};
+use crate::handlers::extract::{resolve_ocr_engine, validate_upload};
+use crate::middleware::rate_limit::{get_rate_limit_metrics, REQUEST_SEMAPHORE};
+use crate::models::{ApiKeyIdentity, ExtractResponse, ExtractionOptions, OutputFormat, ProcessedFile, Scope};
+use crate::services::{
+    api_keys, global_ocr_cache, job_queue, render_extraction, FetchPolicy, JobRequest, OcrService, PageStreamer, PdfProcessor, RetryPolicy, WebhookTarget, STREAM_EVENT_BUFFER,
+};
+
+/// Implementation of the `elicit.v1.Elicit` service
+#[derive(Debug, Clone, Default)]
+pub struct ElicitService;
+
+impl ElicitService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[tonic::async_trait]
+impl Elicit for ElicitService {
+    type ExtractStreamStream = Pin<Box<dyn Stream<Item = Result<proto::ExtractEvent, Status>> + Send>>;
+
+    async fn extract(
+        &self,
+        request: Request<proto::ExtractRequest>,
+    ) -> Result<Response<proto::ExtractResponse>, Status> {
+        authenticate(&request, "Extract", Scope::Extract)?;
+        let config = load_config()?;
+        let start = Instant::now();
+        let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+        info!(request_id = %request_id, "Starting gRPC extraction request");
+
+        // Acquire rate limiting permit
+        let _permit = REQUEST_SEMAPHORE.try_acquire().map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+        let request = request.into_inner();
+        let file = document_file(request.document, &config, &request_id)?;
+        let options = ExtractionOptions::parse(&request.options)?;
+        let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+        let file_name = file.name.clone();
+        let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine);
+        if let Some(cache) = global_ocr_cache() {
+            processor = processor.with_ocr_cache(cache);
+        }
+        let result = processor.extract_text(file, &options).await.map_err(|e| {
+            error!(request_id = %request_id, error = %e, "PDF processing failed");
+            e
+        })?;
+
+        let total_time = start.elapsed().as_millis() as u64;
+        let output_format = options.output_format;
+        let body = render_extraction(output_format, &result, &file_name);
+
+        info!(
+            request_id = %request_id,
+            text_length = result.text.len(),
+            pages = result.pages,
+            total_time_ms = total_time,
+            output_format = output_format.as_str(),
+            "gRPC extraction completed"
+        );
+
+        let mut response: proto::ExtractResponse =
+            ExtractResponse::new(result.text, result.pages, result.metadata, total_time)
+                .with_options(options)
+                .into();
+        if body.is_some() {
+            response.content_type = Some(output_format.content_type().to_string());
+        }
+        response.body = body;
+        Ok(Response::new(response))
+    }
+
+    async fn extract_stream(
+        &self,
+        request: Request<proto::ExtractRequest>,
+    ) -> Result<Response<Self::ExtractStreamStream>, Status> {
+        authenticate(&request, "ExtractStream", Scope::Extract)?;
+        let config = load_config()?;
+        let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+        info!(request_id = %request_id, "Starting gRPC streamed extraction request");
+
+        // Held until the stream ends, not just until the response starts
+        let permit = REQUEST_SEMAPHORE.try_acquire().map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+        let request = request.into_inner();
+        let file = document_file(request.document, &config, &request_id)?;
+        let options = ExtractionOptions::parse(&request.options)?;
+        if options.output_format != OutputFormat::Json {
+            return Err(AppError::validation("Streaming only supports the json output format").into());
+        }
+        let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+        let mut streamer = PageStreamer::new().with_options(options).with_ocr_engine(ocr_engine);
+        if let Some(cache) = global_ocr_cache() {
+            streamer = streamer.with_ocr_cache(cache);
+        }
+
+        let (sender, receiver) = mpsc::channel(STREAM_EVENT_BUFFER);
+        tokio::spawn(async move {
+            let _permit = permit;
+            streamer.run(file, sender).await;
+            debug!(request_id = %request_id, "gRPC streamed extraction finished, permit released");
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver).map(extract_event))))
+    }
+
+    async fn health(&self, _request: Request<proto::HealthRequest>) -> Result<Response<proto::HealthResponse>, Status> {
+        let pdf_processor = PdfProcessor::default().is_available();
+        let ocr = OcrService::default();
+        let ocr_engine = ocr.engine();
+        let ocr_service = ocr_engine.health_check().await.is_ok();
+        let ocr_languages = if ocr_service {
+            ocr_engine.list_languages().await.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let (_, _, available_permits) = get_rate_limit_metrics();
+
+        Ok(Response::new(proto::HealthResponse {
+            status: if pdf_processor { "healthy" } else { "degraded" }.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pdf_processor,
+            ocr_service,
+            ocr_engine: ocr_engine.name().to_string(),
+            ocr_languages,
+            available_permits: available_permits as u64,
+        }))
+    }
+
+    async fn create_job(&self, request: Request<proto::CreateJobRequest>) -> Result<Response<proto::Job>, Status> {
+        let (api_key, identity) = authenticate(&request, "CreateJob", Scope::Jobs)?;
+        let config = load_config()?;
+        let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+        info!(request_id = %request_id, "Starting gRPC extraction job request");
+
+        let request = request.into_inner();
+        let file = document_file(request.document, &config, &request_id)?;
+
+        // Job results are always JSON
+        let options = ExtractionOptions::parse(&request.options)?;
+        if options.output_format != OutputFormat::Json {
+            return Err(AppError::validation("Jobs only support the json output format").into());
+        }
+        let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+        let mut job = JobRequest::new(file).with_options(options).with_ocr_engine(ocr_engine);
+        if let Some(cache) = global_ocr_cache() {
+            job = job.with_ocr_cache(cache);
+        }
+        if let Some(callback_url) = request.callback_url.filter(|url| !url.trim().is_empty()) {
+            // Signed with the secret of the API key that created the job
+            let secret = Config::webhook_secret(&identity.id, &api_key)
+                .ok_or_else(|| AppError::validation("No webhook secret is configured for this API key"))?;
+            let target = WebhookTarget::new(&callback_url, secret)?
+                .with_policy(RetryPolicy::from_env())
+                .with_destinations(FetchPolicy::for_callbacks(&config));
+            target.check().await?;
+            job = job.with_callback(target);
+        }
+
+        let job = job_queue().submit(&identity, job)?;
+
+        info!(request_id = %request_id, job_id = %job.job_id, "gRPC extraction job accepted");
+
+        Ok(Response::new(job.into()))
+    }
+
+    async fn get_job(&self, request: Request<proto::GetJobRequest>) -> Result<Response<proto::Job>, Status> {
+        let (_, identity) = authenticate(&request, "GetJob", Scope::Jobs)?;
+        let job = job_queue().get(&identity, &request.get_ref().job_id)?;
+        Ok(Response::new(job.into()))
+    }
+
+    async fn list_job_deliveries(
+        &self,
+        request: Request<proto::GetJobRequest>,
+    ) -> Result<Response<proto::ListJobDeliveriesResponse>, Status> {
+        let (_, identity) = authenticate(&request, "ListJobDeliveries", Scope::Jobs)?;
+        let deliveries = job_queue().deliveries(&identity, &request.get_ref().job_id)?;
+        Ok(Response::new(proto::ListJobDeliveriesResponse {
+            deliveries: deliveries.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
+
+/// Check the `authorization: Bearer` metadata against the key registry and the scope `method` needs,
+/// returning the key and its identity
+fn authenticate<T>(request: &Request<T>, method: &str, scope: Scope) -> AppResult<(String, ApiKeyIdentity)> {
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|token| !token.is_empty());
+
+    match token {
+        Some(token) => {
+            let identity = api_keys().resolve(token).map_err(|e| {
+                warn!("Rejected API key for gRPC {}: {} ({})", method,
+                      if token.len() > 8 { &token[..8] } else { token }, e);
+                e
+            })?;
+            if let Err(e) = identity.require(scope) {
+                warn!(key_id = %identity.id, "API key lacks the {} scope for gRPC {}", scope, method);
+                return Err(e);
+            }
+            debug!(key_id = %identity.id, "Valid API key authenticated for gRPC {}", method);
+            Ok((token.to_string(), identity))
+        }
+        None => {
+            warn!("Missing or malformed authorization metadata for gRPC {}", method);
+            Err(AppError::InvalidApiKey)
+        }
+    }
+}
+
+fn load_config() -> AppResult<Config> {
+    Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))
+}
+
+/// The uploaded document as a file, checked against the size limit and the supported formats
+fn document_file(document: Option<proto::Document>, config: &Config, request_id: &str) -> AppResult<ProcessedFile> {
+    let document = document.ok_or(AppError::MissingFile)?;
+    let name = if document.file_name.trim().is_empty() {
+        "document".to_string()
+    } else {
+        document.file_name
+    };
+    let mut file = ProcessedFile::new(name, document.content);
+    if !document.content_type.trim().is_empty() {
+        file = file.with_mime_type(document.content_type);
+    }
+
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    if file.size > max_size_bytes {
+        warn!(
+            request_id = %request_id,
+            file_size = file.size,
+            max_size = max_size_bytes,
+            "File size exceeds limit"
+        );
+        return Err(AppError::FileTooLarge {
+            size: file.size / (1024 * 1024),
+            limit: config.max_file_size_mb,
+        });
+    }
+    validate_upload(&file)?;
+    Ok(file)
+}
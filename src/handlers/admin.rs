@@ -0,0 +1,156 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use std::collections::HashMap;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{ApiKeyIdentity, ApiKeyView, AuditEvent, CreateApiKeyRequest, IssuedApiKey};
+use crate::services::{api_keys, ApiKeyRegistry};
+
+/// Audit events returned when `limit` is not given
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+
+/// Make a registry change on the blocking pool, since it writes the key file or database
+async fn change_keys<T: Send + 'static>(
+    change: impl FnOnce(&ApiKeyRegistry) -> AppResult<T> + Send + 'static,
+) -> AppResult<T> {
+    let registry = api_keys();
+    tokio::task::spawn_blocking(move || change(&registry))
+        .await
+        .map_err(|e| AppError::internal(format!("API key change failed: {}", e)))?
+}
+
+/// Every API key with its usage since the server started
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/keys",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Keys, oldest first. Hashes are never returned.", body = Vec<ApiKeyView>),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn list_api_keys_handler() -> Json<Vec<ApiKeyView>> {
+    Json(api_keys().list())
+}
+
+/// Issue a new API key
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/keys",
+    tag = "admin",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "The key, returned only this once, and its record", body = IssuedApiKey),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn create_api_key_handler(
+    Extension(actor): Extension<ApiKeyIdentity>,
+    body: Bytes,
+) -> AppResult<Response> {
+    let request: CreateApiKeyRequest = serde_json::from_slice(&body)?;
+    let issued = change_keys(move |keys| keys.create(&actor, request)).await?;
+    Ok((StatusCode::CREATED, Json(issued)).into_response())
+}
+
+/// One API key with its usage since the server started
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/keys/{id}",
+    tag = "admin",
+    params(("id" = String, Path, description = "Id of the key")),
+    responses(
+        (status = 200, description = "The key", body = ApiKeyView),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn get_api_key_handler(Path(id): Path<String>) -> AppResult<Json<ApiKeyView>> {
+    api_keys().get(&id).map(Json)
+}
+
+/// Delete an API key; requests with it fail straight away
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/keys/{id}",
+    tag = "admin",
+    params(("id" = String, Path, description = "Id of the key")),
+    responses(
+        (status = 204, description = "The key was deleted"),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn delete_api_key_handler(
+    Extension(actor): Extension<ApiKeyIdentity>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    change_keys(move |keys| keys.delete(&actor, &id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Disable an API key; requests with it fail straight away with `API_KEY_DISABLED`
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/keys/{id}/disable",
+    tag = "admin",
+    params(("id" = String, Path, description = "Id of the key")),
+    responses(
+        (status = 200, description = "The disabled key", body = ApiKeyView),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn disable_api_key_handler(
+    Extension(actor): Extension<ApiKeyIdentity>,
+    Path(id): Path<String>,
+) -> AppResult<Json<ApiKeyView>> {
+    change_keys(move |keys| keys.disable(&actor, &id)).await.map(Json)
+}
+
+/// Replace the key behind an id; the old key stops working straight away
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/keys/{id}/rotate",
+    tag = "admin",
+    params(("id" = String, Path, description = "Id of the key")),
+    responses(
+        (status = 200, description = "The new key, returned only this once, and its record", body = IssuedApiKey),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn rotate_api_key_handler(
+    Extension(actor): Extension<ApiKeyIdentity>,
+    Path(id): Path<String>,
+) -> AppResult<Json<IssuedApiKey>> {
+    change_keys(move |keys| keys.rotate(&actor, &id)).await.map(Json)
+}
+
+/// Changes made to the API keys
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = "admin",
+    params(
+        ("key_id" = Option<String>, Query, description = "Only changes to this key"),
+        ("limit" = Option<usize>, Query, description = "Most events returned, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Audit events, newest first", body = Vec<AuditEvent>),
+    ),
+    security(("api_key" = ["admin"]))
+)]
+pub async fn api_key_audit_handler(Query(params): Query<HashMap<String, String>>) -> AppResult<Json<Vec<AuditEvent>>> {
+    let limit = match params.get("limit") {
+        Some(limit) => limit
+            .parse::<usize>()
+            .ok()
+            .filter(|limit| (1..=MAX_AUDIT_LIMIT).contains(limit))
+            .ok_or_else(|| AppError::validation(format!("limit must be between 1 and {}", MAX_AUDIT_LIMIT)))?,
+        None => DEFAULT_AUDIT_LIMIT,
+    };
+    Ok(Json(api_keys().audit(params.get("key_id").map(String::as_str), limit)))
+}
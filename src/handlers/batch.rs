@@ -0,0 +1,157 @@
+use axum::{extract::Multipart, http::HeaderMap, response::Json};
+use std::collections::HashMap;
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{parse_options, read_file_field, resolve_ocr_engine};
+use crate::models::{BatchResponse, OutputFormat};
+use crate::openapi::BatchUploadForm;
+use crate::services::{expand_zip, global_ocr_cache, BatchEntry, BatchExtractor};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// Files and text fields of a batch upload, ZIP archives already expanded
+struct MultipartBatch {
+    entries: Vec<BatchEntry>,
+    fields: HashMap<String, String>,
+}
+
+/// Extract text from every `file` field of the form, and every file inside uploaded ZIP archives
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract/batch",
+    tag = "extraction",
+    request_body(content = BatchUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "One result per file; a failed file doesn't fail the batch", body = BatchResponse),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn batch_extract_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Json<BatchResponse>> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting batch extraction request");
+
+    // Acquire rate limiting permit
+    let _permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    let max_unzipped_bytes = config.max_batch_unzipped_mb * 1024 * 1024;
+    let batch = read_batch(&mut multipart, config.max_batch_files, max_size_bytes, max_unzipped_bytes)
+        .await
+        .map_err(|e| {
+            error!(request_id = %request_id, error = %e, "Failed to read batch upload");
+            e
+        })?;
+
+    let options = parse_options(&headers, &batch.fields)?;
+    if options.output_format != OutputFormat::Json {
+        return Err(AppError::validation("Batch extraction only supports the json output format"));
+    }
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    // Files beyond the first only run in parallel on permits nobody else is using,
+    // so a batch never takes more of the concurrency budget than is free
+    let wanted = config.batch_concurrency.min(batch.entries.len()).saturating_sub(1);
+    let extra_permits: Vec<_> = (0..wanted)
+        .map_while(|_| REQUEST_SEMAPHORE.try_acquire().ok())
+        .collect();
+    let concurrency = 1 + extra_permits.len();
+
+    debug!(
+        request_id = %request_id,
+        files = batch.entries.len(),
+        concurrency,
+        "Batch permits acquired"
+    );
+
+    let mut extractor = BatchExtractor::new()
+        .with_options(options)
+        .with_ocr_engine(ocr_engine)
+        .with_concurrency(concurrency);
+    if let Some(cache) = global_ocr_cache() {
+        extractor = extractor.with_ocr_cache(cache);
+    }
+    let response = extractor.extract_all(batch.entries).await;
+
+    info!(
+        request_id = %request_id,
+        succeeded = response.succeeded,
+        failed = response.failed,
+        processing_time_ms = response.processing_time_ms,
+        "Batch request completed"
+    );
+
+    Ok(Json(response))
+}
+
+/// Collect every `file` field; oversized files are kept as rejected entries so they show up in the results.
+///
+/// ZIP archives share `max_unzipped_bytes` of decompressed data between them.
+async fn read_batch(
+    multipart: &mut Multipart,
+    max_files: usize,
+    max_size_bytes: usize,
+    max_unzipped_bytes: usize,
+) -> AppResult<MultipartBatch> {
+    let mut entries = Vec::new();
+    let mut unzipped_bytes = 0;
+    let mut fields = HashMap::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::InvalidFile {
+        message: format!("Failed to read multipart field: {}", e),
+    })? {
+        let field_name = field.name().unwrap_or("").to_string();
+
+        if field_name == "file" {
+            let file = read_file_field(field).await?;
+            let remaining = max_files.saturating_sub(entries.len());
+
+            if file.is_zip() && !file.is_pdf() {
+                let budget = max_unzipped_bytes - unzipped_bytes;
+                let expanded = tokio::task::spawn_blocking(move || expand_zip(&file, remaining, max_size_bytes, budget))
+                    .await
+                    .map_err(|e| AppError::internal(format!("ZIP expansion failed: {}", e)))??;
+                unzipped_bytes += expanded
+                    .iter()
+                    .map(|entry| match entry {
+                        BatchEntry::File(file) => file.size,
+                        BatchEntry::Rejected { .. } => 0,
+                    })
+                    .sum::<usize>();
+                entries.extend(expanded);
+                continue;
+            }
+            if remaining == 0 {
+                return Err(AppError::validation(format!("A batch may contain at most {} files", max_files)));
+            }
+            if file.size > max_size_bytes {
+                entries.push(BatchEntry::Rejected {
+                    file_name: file.name,
+                    error: AppError::FileTooLarge {
+                        size: file.size / (1024 * 1024),
+                        limit: max_size_bytes / (1024 * 1024),
+                    },
+                });
+            } else {
+                entries.push(BatchEntry::File(file));
+            }
+        } else if !field_name.is_empty() {
+            let value = field.text().await.map_err(|e| AppError::InvalidFile {
+                message: format!("Failed to read field {}: {}", field_name, e),
+            })?;
+            fields.insert(field_name, value);
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(AppError::MissingFile);
+    }
+    Ok(MultipartBatch { entries, fields })
+}
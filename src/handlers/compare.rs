@@ -0,0 +1,243 @@
+use axum::{
+    extract::Multipart,
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Json, Response},
+};
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{negotiate_options, read_file_field, resolve_ocr_engine, validate_upload};
+use crate::models::{CompareData, CompareResponse, ComparedDocument, ExtractionOptions, OutputFormat, ProcessedFile};
+use crate::openapi::CompareUploadForm;
+use crate::services::{
+    document_paragraphs, global_ocr_cache, DocumentDiff, ExtractionResult, Paragraph, PdfProcessor,
+};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// The two versions and the text fields of a comparison upload
+struct ComparisonUpload {
+    original: ProcessedFile,
+    revised: ProcessedFile,
+    fields: HashMap<String, String>,
+}
+
+/// Compare two versions of a document paragraph by paragraph and word by word
+#[utoipa::path(
+    post,
+    path = "/api/v1/compare",
+    tag = "extraction",
+    params(
+        ("Accept" = Option<String>, Header, description = "`application/json` (default) or `text/plain` for a unified diff; an `output_format` option of `json` or `text` takes precedence"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body(content = CompareUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The changes with their pages in both versions, as JSON or as a unified diff \
+            with one line per paragraph", content(
+            (CompareResponse = "application/json"),
+            (String = "text/plain"),
+        ),
+            headers(
+                ("X-Elicit-Similarity" = f64, description = "Share of words in common (0.0 - 1.0), unified diff only"),
+                ("X-Elicit-Changes" = usize, description = "Number of changed paragraphs, unified diff only"),
+                ("Vary" = String, description = "Always `accept`"),
+            )
+        ),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn compare_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let start = Instant::now();
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting document comparison request");
+
+    // Acquire rate limiting permit
+    let _permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    debug!(request_id = %request_id, "Rate limit permit acquired");
+
+    let upload = read_comparison(&mut multipart).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "Failed to read comparison upload");
+        e
+    })?;
+
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    for file in [&upload.original, &upload.revised] {
+        if file.size > max_size_bytes {
+            warn!(
+                request_id = %request_id,
+                file_name = %file.name,
+                file_size = file.size,
+                max_size = max_size_bytes,
+                "File size exceeds limit"
+            );
+            return Err(AppError::FileTooLarge {
+                size: file.size / (1024 * 1024),
+                limit: config.max_file_size_mb,
+            });
+        }
+    }
+
+    let options = negotiate_options(&headers, &upload.fields)?;
+    let output_format = options.output_format;
+    if !matches!(output_format, OutputFormat::Json | OutputFormat::Text) {
+        return Err(AppError::validation(
+            "Comparisons support the json and text (unified diff) output formats",
+        ));
+    }
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    // Paragraph pages come from the layout, whatever the output format
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine).with_layout();
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+
+    let (original_name, original) = extract_version(&processor, upload.original, &options, &request_id).await?;
+    let (revised_name, revised) = extract_version(&processor, upload.revised, &options, &request_id).await?;
+
+    // The LCS tables grow with the product of both documents' lengths, so the diff
+    // runs on the blocking pool rather than stalling an async worker
+    let paragraphs = (
+        document_paragraphs(&original.text, original.layout.as_ref()),
+        document_paragraphs(&revised.text, revised.layout.as_ref()),
+    );
+    let names = (original_name.clone(), revised_name.clone());
+    let wants_unified = output_format == OutputFormat::Text;
+    let (diff, changes, unified) = tokio::task::spawn_blocking(move || {
+        let diff = DocumentDiff::new(paragraphs.0, paragraphs.1);
+        let changes = diff.changes();
+        let unified = wants_unified.then(|| diff.unified(&names.0, &names.1));
+        (diff, changes, unified)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Document comparison failed: {}", e)))?;
+    let similarity = diff.similarity();
+    let total_time = start.elapsed().as_millis() as u64;
+
+    info!(
+        request_id = %request_id,
+        similarity,
+        changes = changes.len(),
+        total_time_ms = total_time,
+        output_format = output_format.as_str(),
+        "Document comparison completed"
+    );
+
+    let mut response = if let Some(unified) = unified {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(output_format.content_type()));
+        for (name, value) in [
+            ("x-elicit-similarity", similarity.to_string()),
+            ("x-elicit-changes", changes.len().to_string()),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        }
+        (headers, unified).into_response()
+    } else {
+        let data = CompareData {
+            original: compared_document(original_name, original, diff.original()),
+            revised: compared_document(revised_name, revised, diff.revised()),
+            similarity,
+            summary: diff.summary(),
+            changes,
+        };
+        Json(CompareResponse {
+            success: true,
+            data,
+            processing_time_ms: total_time,
+        })
+        .into_response()
+    };
+    // The format can follow the Accept header, so caches must key on it
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    Ok(response)
+}
+
+/// Read the `original` and `revised` file fields and the option fields of the form
+async fn read_comparison(multipart: &mut Multipart) -> AppResult<ComparisonUpload> {
+    let (mut original, mut revised) = (None, None);
+    let mut fields = HashMap::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::InvalidFile {
+        message: format!("Failed to read multipart field: {}", e),
+    })? {
+        let field_name = field.name().unwrap_or("").to_string();
+        let slot = match field_name.as_str() {
+            "original" => &mut original,
+            "revised" => &mut revised,
+            "" => continue,
+            _ => {
+                let value = field.text().await.map_err(|e| AppError::InvalidFile {
+                    message: format!("Failed to read field {}: {}", field_name, e),
+                })?;
+                fields.insert(field_name, value);
+                continue;
+            }
+        };
+        if slot.is_some() {
+            return Err(AppError::validation(format!("Only one {} document can be compared", field_name)));
+        }
+        let file = read_file_field(field).await?;
+        validate_upload(&file)?;
+        *slot = Some(file);
+    }
+
+    match (original, revised) {
+        (Some(original), Some(revised)) => Ok(ComparisonUpload {
+            original,
+            revised,
+            fields,
+        }),
+        _ => Err(AppError::validation("Upload both versions, as the original and revised fields")),
+    }
+}
+
+/// Extract one version of the document, returning its file name with the result
+async fn extract_version(
+    processor: &PdfProcessor,
+    file: ProcessedFile,
+    options: &ExtractionOptions,
+    request_id: &str,
+) -> AppResult<(String, ExtractionResult)> {
+    let file_name = file.name.clone();
+    match processor.extract_text(file, options).await {
+        Ok(result) => {
+            info!(
+                request_id = %request_id,
+                file_name = %file_name,
+                text_length = result.text.len(),
+                pages = result.pages,
+                processing_time_ms = result.processing_time_ms,
+                "PDF processing completed successfully"
+            );
+            Ok((file_name, result))
+        }
+        Err(e) => {
+            error!(request_id = %request_id, file_name = %file_name, error = %e, "PDF processing failed");
+            Err(e)
+        }
+    }
+}
+
+fn compared_document(file_name: String, result: ExtractionResult, paragraphs: &[Paragraph]) -> ComparedDocument {
+    ComparedDocument {
+        file_name,
+        pages: result.pages,
+        paragraphs: paragraphs.len(),
+        words: paragraphs.iter().map(|paragraph| paragraph.word_count()).sum(),
+        metadata: result.metadata,
+    }
+}
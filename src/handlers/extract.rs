@@ -1,18 +1,59 @@
 use axum::{
-    extract::Multipart,
-    http::{HeaderMap, StatusCode},
-    response::Json,
+    body::Bytes,
+    extract::{multipart::Field, FromRequest, Multipart, Query, Request},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Json, Response},
 };
+use std::collections::HashMap;
 use std::time::Instant;
 use tracing::{info, warn, debug, error};
 
 use crate::error::{AppError, AppResult};
-use crate::models::{ProcessedFile, ExtractResponse};
-use crate::services::PdfProcessor;
+use crate::models::{ProcessedFile, ExtractRequest, ExtractResponse, ExtractionOptions, OutputFormat, PdfMetadata};
+use crate::openapi::{OptionParams, UploadForm};
+use crate::services::{
+    global_ocr_cache, ocr_engine_by_name, render_extraction, ExtractionResult, OcrEngine, PdfProcessor,
+};
+use std::sync::Arc;
 use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
 use crate::config::Config;
 
-pub async fn extract_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Json<ExtractResponse>> {
+/// Extract text from a multipart upload, or from a JSON body with the document base64 encoded
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract",
+    tag = "extraction",
+    params(
+        ("Accept" = Option<String>, Header, description = "`application/json` (default), `text/plain` or `text/markdown`; an `output_format` option takes precedence"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body(
+        description = "The document as a multipart upload, or base64 encoded in a JSON body",
+        content(
+            (UploadForm = "multipart/form-data"),
+            (ExtractRequest = "application/json"),
+        )
+    ),
+    responses(
+        (status = 200, description = "Extracted text in the format `output_format` or, failing that, `Accept` asks for. \
+            Formats other than JSON carry the metadata in `X-Elicit-*` headers.", content(
+            (ExtractResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/markdown"),
+            (String = "application/xml"),
+            (String = "text/html"),
+        ),
+            headers(
+                ("X-Elicit-Pages" = usize, description = "Page count, non-JSON formats only"),
+                ("X-Elicit-OCR-Used" = bool, description = "Whether OCR produced the text, non-JSON formats only"),
+                ("X-Elicit-Processing-Time-Ms" = u64, description = "Non-JSON formats only"),
+                ("Vary" = String, description = "Always `accept`"),
+            )
+        ),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn extract_handler(request: Request) -> AppResult<Response> {
     let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
     let start = Instant::now();
     let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
@@ -29,25 +70,30 @@ pub async fn extract_handler(headers: HeaderMap, mut multipart: Multipart) -> Ap
     
     debug!(request_id = %request_id, "Rate limit permit acquired");
     
-    // Extract file from multipart form
-    let file = match extract_file_from_multipart(&mut multipart).await {
-        Ok(file) => {
+    // Extract file from the multipart form or JSON body
+    let headers = request.headers().clone();
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    let upload = match read_upload(request, max_size_bytes).await {
+        Ok(upload) => {
             info!(
                 request_id = %request_id,
-                file_name = %file.name,
-                file_size = file.size,
-                "File extracted from multipart form"
+                file_name = %upload.file.name,
+                file_size = upload.file.size,
+                "File extracted from request body"
             );
-            file
+            upload
         }
         Err(e) => {
-            error!(request_id = %request_id, error = %e, "Failed to extract file from multipart");
+            error!(request_id = %request_id, error = %e, "Failed to extract file from request body");
             return Err(e);
         }
     };
     
+    let file = upload.file;
+    let options = negotiate_options(&headers, &upload.fields)?;
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
     // Validate file size
-    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
     if file.size > max_size_bytes {
         warn!(
             request_id = %request_id,
@@ -62,8 +108,12 @@ pub async fn extract_handler(headers: HeaderMap, mut multipart: Multipart) -> Ap
     }
     
     // Process the PDF
-    let processor = PdfProcessor::new();
-    let result = match processor.extract_text(file).await {
+    let file_name = file.name.clone();
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine);
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+    let result = match processor.extract_text(file, &options).await {
         Ok(result) => {
             info!(
                 request_id = %request_id,
@@ -82,78 +132,299 @@ pub async fn extract_handler(headers: HeaderMap, mut multipart: Multipart) -> Ap
     
     let total_time = start.elapsed().as_millis() as u64;
     
-    let response = ExtractResponse::new(
-        result.text,
-        result.pages,
-        result.metadata,
-        total_time,
-    );
-    
     info!(
         request_id = %request_id,
         total_time_ms = total_time,
+        output_format = options.output_format.as_str(),
         "Request completed successfully"
     );
     
-    Ok(Json(response))
+    Ok(extraction_response(options, result, &file_name, total_time))
+}
+
+/// File and accompanying text fields from a multipart upload
+pub(crate) struct MultipartUpload {
+    pub(crate) file: ProcessedFile,
+    pub(crate) fields: HashMap<String, String>,
+}
+
+/// Read the document and options from a JSON body when the client sent one, otherwise from the multipart form
+async fn read_upload(request: Request, max_size_bytes: usize) -> AppResult<MultipartUpload> {
+    if !is_json(request.headers()) {
+        let mut multipart = Multipart::from_request(request, &()).await.map_err(|e| AppError::InvalidFile {
+            message: format!("Expected a multipart form or JSON body: {}", e),
+        })?;
+        return extract_file_from_multipart(&mut multipart).await;
+    }
+
+    let body = Bytes::from_request(request, &()).await.map_err(|e| AppError::InvalidFile {
+        message: format!("Failed to read request body: {}", e),
+    })?;
+    let request: ExtractRequest = serde_json::from_slice(&body)
+        .map_err(|e| AppError::validation(format!("Invalid request body: {}", e)))?;
+
+    let fields = request.option_pairs().into_iter().collect();
+    let file = request.into_file(max_size_bytes)?;
+    validate_upload(&file)?;
+    Ok(MultipartUpload { file, fields })
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
 }
 
-async fn extract_file_from_multipart(multipart: &mut Multipart) -> AppResult<ProcessedFile> {
+/// Reject empty files and anything that isn't a PDF or an image we can OCR
+pub(crate) fn validate_upload(file: &ProcessedFile) -> AppResult<()> {
+    if file.content.is_empty() {
+        return Err(AppError::InvalidFile {
+            message: "File is empty".to_string(),
+        });
+    }
+    if !file.is_pdf() && !file.is_image() {
+        return Err(AppError::InvalidFile {
+            message: "File is not a valid PDF document or supported image (PNG, JPEG, TIFF, WebP)".to_string(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) async fn extract_file_from_multipart(multipart: &mut Multipart) -> AppResult<MultipartUpload> {
+    let mut file = None;
+    let mut fields = HashMap::new();
+
     while let Some(field) = multipart.next_field().await.map_err(|e| AppError::InvalidFile {
         message: format!("Failed to read multipart field: {}", e),
     })? {
-        let field_name = field.name().unwrap_or("");
+        let field_name = field.name().unwrap_or("").to_string();
         
-        if field_name == "file" {
-            let file_name = field.file_name()
-                .unwrap_or("unknown.pdf")
-                .to_string();
-            
-            let content_type = field.content_type()
-                .map(|ct| ct.to_string());
-            
-            let data = field.bytes().await.map_err(|e| AppError::InvalidFile {
-                message: format!("Failed to read file data: {}", e),
-            })?;
-            
-            if data.is_empty() {
-                return Err(AppError::InvalidFile {
-                    message: "File is empty".to_string(),
-                });
-            }
-            
-            let mut file = ProcessedFile::new(file_name, data.to_vec());
-            
-            if let Some(mime_type) = content_type {
-                file = file.with_mime_type(mime_type);
-            }
-            
-            // Validate it's a PDF
-            if !file.is_pdf() {
-                return Err(AppError::InvalidFile {
-                    message: "File is not a valid PDF document".to_string(),
-                });
-            }
+        if field_name == "file" && file.is_none() {
+            let processed = read_file_field(field).await?;
+            validate_upload(&processed)?;
             
             tracing::debug!(
                 "Extracted file: {} ({} bytes, type: {:?})",
-                file.name,
-                file.size,
-                file.mime_type
+                processed.name,
+                processed.size,
+                processed.mime_type
             );
             
-            return Ok(file);
+            file = Some(processed);
+        } else if !field_name.is_empty() {
+            let value = field.text().await.map_err(|e| AppError::InvalidFile {
+                message: format!("Failed to read field {}: {}", field_name, e),
+            })?;
+            fields.insert(field_name, value);
         }
     }
     
-    Err(AppError::MissingFile)
+    match file {
+        Some(file) => Ok(MultipartUpload { file, fields }),
+        None => Err(AppError::MissingFile),
+    }
+}
+
+/// Read a multipart file field, keeping the name and content type the client sent
+pub(crate) async fn read_file_field(field: Field<'_>) -> AppResult<ProcessedFile> {
+    let file_name = field.file_name()
+        .unwrap_or("unknown.pdf")
+        .to_string();
+    
+    let content_type = field.content_type()
+        .map(|ct| ct.to_string());
+    
+    let data = field.bytes().await.map_err(|e| AppError::InvalidFile {
+        message: format!("Failed to read file data: {}", e),
+    })?;
+    
+    let processed = ProcessedFile::new(file_name, data.to_vec());
+    Ok(match content_type {
+        Some(mime_type) => processed.with_mime_type(mime_type),
+        None => processed,
+    })
+}
+
+/// Render an extraction result in the requested output format.
+///
+/// JSON echoes the options in the body; every other format carries the
+/// metadata in `X-Elicit-*` response headers instead.
+pub(crate) fn extraction_response(
+    options: ExtractionOptions,
+    result: ExtractionResult,
+    file_name: &str,
+    total_time: u64,
+) -> Response {
+    let output_format = options.output_format;
+    let mut response = match render_extraction(output_format, &result, file_name) {
+        Some(body) => {
+            let mut headers = metadata_headers(&result.metadata, result.pages, total_time);
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(output_format.content_type()));
+            (headers, body).into_response()
+        }
+        None => Json(
+            ExtractResponse::new(result.text, result.pages, result.metadata, total_time).with_options(options),
+        )
+        .into_response(),
+    };
+    // The format can follow the Accept header, so caches must key on it
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// `PdfMetadata` as `X-Elicit-*` response headers, for the formats with no room for it in the body
+fn metadata_headers(metadata: &PdfMetadata, pages: usize, total_time: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let mut add = |name: &'static str, value: String| {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    };
+
+    add("x-elicit-pages", pages.to_string());
+    add("x-elicit-file-size", metadata.file_size_bytes.to_string());
+    add("x-elicit-ocr-used", metadata.ocr_used.to_string());
+    add("x-elicit-ocr-cache-hits", metadata.ocr_cache_hits.to_string());
+    add("x-elicit-processing-time-ms", total_time.to_string());
+    if !metadata.preprocessing_applied.is_empty() {
+        let steps = metadata.preprocessing_applied.iter().map(|step| step.as_str()).collect::<Vec<_>>();
+        add("x-elicit-preprocessing-applied", steps.join(","));
+    }
+    if let Some(title) = &metadata.title {
+        add("x-elicit-title", percent_encode(title));
+    }
+    if let Some(author) = &metadata.author {
+        add("x-elicit-author", percent_encode(author));
+    }
+    if let Some(date) = metadata.creation_date {
+        add("x-elicit-creation-date", date.to_rfc3339());
+    }
+    if let Some(date) = metadata.modification_date {
+        add("x-elicit-modification-date", date.to_rfc3339());
+    }
+    headers
+}
+
+/// Header-safe form of free text: UTF-8 with `%`, control and non-ASCII bytes percent-encoded
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'%' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Prefix of the headers that carry extraction options, e.g. `X-Elicit-Language: deu`
+const OPTION_HEADER_PREFIX: &str = "x-elicit-";
+
+/// Options from `X-Elicit-*` headers, overridden by the form fields, query or JSON body of the request
+pub(crate) fn parse_options<K, V>(
+    headers: &HeaderMap,
+    fields: impl IntoIterator<Item = (K, V)>,
+) -> AppResult<ExtractionOptions>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    apply_options(ExtractionOptions::default(), headers, fields)
+}
+
+/// Like [`parse_options`], except that the output format defaults to the one the `Accept` header prefers
+pub(crate) fn negotiate_options<K, V>(
+    headers: &HeaderMap,
+    fields: impl IntoIterator<Item = (K, V)>,
+) -> AppResult<ExtractionOptions>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let accepted = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .and_then(OutputFormat::from_accept)
+        .unwrap_or_default();
+    apply_options(ExtractionOptions::default().with_output_format(accepted), headers, fields)
+}
+
+fn apply_options<K, V>(
+    mut options: ExtractionOptions,
+    headers: &HeaderMap,
+    fields: impl IntoIterator<Item = (K, V)>,
+) -> AppResult<ExtractionOptions>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    for name in ExtractionOptions::NAMES {
+        let header_name = format!("{}{}", OPTION_HEADER_PREFIX, name.replace('_', "-"));
+        if let Some(value) = headers.get(header_name.as_str()) {
+            let value = value
+                .to_str()
+                .map_err(|_| AppError::validation(format!("Header {} is not valid text", header_name)))?;
+            options.set(name, value)?;
+        }
+    }
+    options.apply(fields)?;
+    Ok(options)
+}
+
+/// The engine the options ask for, or the configured default
+pub(crate) fn resolve_ocr_engine(options: &ExtractionOptions, config: &Config) -> AppResult<Arc<dyn OcrEngine>> {
+    ocr_engine_by_name(options.ocr_engine.as_deref().unwrap_or(&config.ocr_engine))
 }
 
 // Alternative handler for direct binary upload
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract/binary",
+    tag = "extraction",
+    params(
+        OptionParams,
+        ("Accept" = Option<String>, Header, description = "`application/json` (default), `text/plain` or `text/markdown`; an `output_format` option takes precedence"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body(
+        description = "The raw document, typed by its Content-Type",
+        content(
+            (Vec<u8> = "application/pdf"),
+            (Vec<u8> = "image/png"),
+            (Vec<u8> = "image/jpeg"),
+            (Vec<u8> = "image/tiff"),
+            (Vec<u8> = "image/webp"),
+        )
+    ),
+    responses(
+        (status = 200, description = "Extracted text in the format `output_format` or, failing that, `Accept` asks for. \
+            Formats other than JSON carry the metadata in `X-Elicit-*` headers.", content(
+            (ExtractResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/markdown"),
+            (String = "application/xml"),
+            (String = "text/html"),
+        ),
+            headers(
+                ("X-Elicit-Pages" = usize, description = "Page count, non-JSON formats only"),
+                ("X-Elicit-OCR-Used" = bool, description = "Whether OCR produced the text, non-JSON formats only"),
+                ("X-Elicit-Processing-Time-Ms" = u64, description = "Non-JSON formats only"),
+                ("Vary" = String, description = "Always `accept`"),
+            )
+        ),
+    ),
+    security(("api_key" = ["extract"]))
+)]
 pub async fn extract_binary_handler(
     headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
     body: axum::body::Bytes,
-) -> AppResult<Json<ExtractResponse>> {
+) -> AppResult<Response> {
     let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
     let start = Instant::now();
     let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
@@ -176,14 +447,17 @@ pub async fn extract_binary_handler(
         .and_then(|ct| ct.to_str().ok())
         .unwrap_or("");
     
-    if !content_type.contains("application/pdf") {
-        warn!(
-            request_id = %request_id,
-            content_type = content_type,
-            "Invalid content type for binary upload"
-        );
-        return Err(AppError::InvalidContentType);
-    }
+    let file_name = match binary_upload_extension(content_type) {
+        Some(extension) => format!("uploaded.{}", extension),
+        None => {
+            warn!(
+                request_id = %request_id,
+                content_type = content_type,
+                "Invalid content type for binary upload"
+            );
+            return Err(AppError::InvalidContentType);
+        }
+    };
     
     if body.is_empty() {
         warn!(request_id = %request_id, "Empty body received");
@@ -205,20 +479,36 @@ pub async fn extract_binary_handler(
         });
     }
     
+    let options = negotiate_options(&headers, &params)?;
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    let mime_type = content_type.split(';').next().unwrap_or("").trim().to_string();
     let file = ProcessedFile::new(
-        "uploaded.pdf".to_string(),
+        file_name,
         body.to_vec(),
-    ).with_mime_type("application/pdf".to_string());
+    ).with_mime_type(mime_type);
+    
+    if !file.is_pdf() && !file.is_image() {
+        warn!(request_id = %request_id, "Binary body does not match its content type");
+        return Err(AppError::InvalidFile {
+            message: "Body is not a valid PDF document or supported image".to_string(),
+        });
+    }
     
     info!(
         request_id = %request_id,
         file_size = file.size,
-        "Processing binary PDF"
+        mime_type = ?file.mime_type,
+        "Processing binary upload"
     );
     
     // Process the PDF
-    let processor = PdfProcessor::new();
-    let result = match processor.extract_text(file).await {
+    let file_name = file.name.clone();
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine);
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+    let result = match processor.extract_text(file, &options).await {
         Ok(result) => {
             info!(
                 request_id = %request_id,
@@ -237,18 +527,24 @@ pub async fn extract_binary_handler(
     
     let total_time = start.elapsed().as_millis() as u64;
     
-    let response = ExtractResponse::new(
-        result.text,
-        result.pages,
-        result.metadata,
-        total_time,
-    );
-    
     info!(
         request_id = %request_id,
         total_time_ms = total_time,
+        output_format = options.output_format.as_str(),
         "Binary request completed successfully"
     );
     
-    Ok(Json(response))
-}
\ No newline at end of file
+    Ok(extraction_response(options, result, &file_name, total_time))
+}
+/// File extension for the content types accepted by the binary endpoint
+fn binary_upload_extension(content_type: &str) -> Option<&'static str> {
+    let mime_type = content_type.split(';').next().unwrap_or("").trim();
+    match mime_type {
+        "application/pdf" => Some("pdf"),
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/tiff" => Some("tiff"),
+        "image/webp" => Some("webp"),
+        _ => None,
+    }
+}
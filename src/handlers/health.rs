@@ -7,10 +7,18 @@ use std::time::SystemTime;
 use tracing::info;
 
 use crate::error::AppResult;
-use crate::services::{PdfProcessor, OcrService};
+use crate::services::{ocr_engine_names, PdfProcessor, OcrService};
 use crate::middleware::rate_limit::get_rate_limit_metrics;
 
 /// Health check endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "service",
+    responses(
+        (status = 200, description = "Service status, OCR engine and rate limiting counters", body = Object),
+    )
+)]
 pub async fn health_handler() -> AppResult<Json<Value>> {
     info!("Health check requested");
     
@@ -21,7 +29,14 @@ pub async fn health_handler() -> AppResult<Json<Value>> {
     
     // Check service availability
     let pdf_service = PdfProcessor::default().is_available();
-    let ocr_service = OcrService::is_available();
+    let ocr = OcrService::default();
+    let ocr_engine = ocr.engine();
+    let ocr_service = ocr_engine.health_check().await.is_ok();
+    let ocr_languages = if ocr_service {
+        ocr_engine.list_languages().await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
     
     // Get rate limiting metrics
     let (total_requests, rejected_requests, available_permits) = get_rate_limit_metrics();
@@ -38,7 +53,12 @@ pub async fn health_handler() -> AppResult<Json<Value>> {
         "version": env!("CARGO_PKG_VERSION"),
         "services": {
             "pdf_processor": pdf_service,
-            "ocr_service": ocr_service
+            "ocr_service": ocr_service,
+            "ocr_engine": {
+                "name": ocr_engine.name(),
+                "languages": ocr_languages,
+                "registered": ocr_engine_names()
+            }
         },
         "rate_limiting": {
             "total_requests": total_requests,
@@ -64,6 +84,15 @@ pub async fn health_handler() -> AppResult<Json<Value>> {
 }
 
 /// Readiness check endpoint (for Kubernetes/Railway)
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "service",
+    responses(
+        (status = 200, description = "Ready to take requests"),
+        (status = 503, description = "PDF processing is unavailable"),
+    )
+)]
 pub async fn ready_handler() -> Result<StatusCode, StatusCode> {
     let pdf_service = PdfProcessor::default().is_available();
     
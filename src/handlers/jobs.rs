@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Multipart, Path},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use tracing::{info, warn, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{extract_file_from_multipart, parse_options, resolve_ocr_engine};
+use crate::middleware::auth::bearer_token;
+use crate::models::{ApiKeyIdentity, JobResponse, OutputFormat, WebhookDelivery};
+use crate::openapi::JobUploadForm;
+use crate::services::{global_ocr_cache, job_queue, FetchPolicy, JobRequest, RetryPolicy, WebhookTarget};
+use crate::config::Config;
+
+/// Queue an extraction and return its job id straight away
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs",
+    tag = "jobs",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body(content = JobUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 202, description = "The job was queued", body = JobResponse,
+            headers(("Location" = String, description = "URL of the job"))),
+    ),
+    security(("api_key" = ["jobs"]))
+)]
+pub async fn create_job_handler(
+    Extension(identity): Extension<ApiKeyIdentity>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting extraction job request");
+
+    let upload = extract_file_from_multipart(&mut multipart).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "Failed to extract file from multipart");
+        e
+    })?;
+
+    let file = upload.file;
+    let mut fields = upload.fields;
+    let callback_url = fields.remove("callback_url");
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    if file.size > max_size_bytes {
+        warn!(
+            request_id = %request_id,
+            file_size = file.size,
+            max_size = max_size_bytes,
+            "File size exceeds limit"
+        );
+        return Err(AppError::FileTooLarge {
+            size: file.size / (1024 * 1024),
+            limit: config.max_file_size_mb,
+        });
+    }
+
+    // Job results are always JSON
+    let options = parse_options(&headers, &fields)?;
+    if options.output_format != OutputFormat::Json {
+        return Err(AppError::validation("Jobs only support the json output format"));
+    }
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    let mut request = JobRequest::new(file)
+        .with_options(options)
+        .with_ocr_engine(ocr_engine);
+    if let Some(cache) = global_ocr_cache() {
+        request = request.with_ocr_cache(cache);
+    }
+    if let Some(callback_url) = callback_url.filter(|url| !url.trim().is_empty()) {
+        request = request.with_callback(webhook_target(&headers, &identity, &callback_url, &config).await?);
+    }
+
+    let job = job_queue().submit(&identity, request)?;
+
+    info!(request_id = %request_id, job_id = %job.job_id, "Extraction job accepted");
+
+    let location = format!("/api/v1/jobs/{}", job.job_id);
+    Ok((StatusCode::ACCEPTED, [(header::LOCATION, location)], Json(job)).into_response())
+}
+
+/// Callback for a job, signed with the secret of the API key that created it.
+/// Fails with `URL_NOT_ALLOWED` when the callback host may not be contacted.
+async fn webhook_target(
+    headers: &HeaderMap,
+    identity: &ApiKeyIdentity,
+    callback_url: &str,
+    config: &Config,
+) -> AppResult<WebhookTarget> {
+    let secret = bearer_token(headers)
+        .and_then(|key| Config::webhook_secret(&identity.id, key))
+        .ok_or_else(|| AppError::validation("No webhook secret is configured for this API key"))?;
+    let target = WebhookTarget::new(callback_url, secret)?
+        .with_policy(RetryPolicy::from_env())
+        .with_destinations(FetchPolicy::for_callbacks(config));
+    target.check().await?;
+    Ok(target)
+}
+
+/// Status, progress and, once finished, the result of an extraction job.
+/// Only the key that created the job, or an admin key, can see it.
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job id returned when the job was created")),
+    responses(
+        (status = 200, description = "The job", body = JobResponse),
+    ),
+    security(("api_key" = ["jobs"]))
+)]
+pub async fn get_job_handler(
+    Extension(identity): Extension<ApiKeyIdentity>,
+    Path(id): Path<String>,
+) -> AppResult<Json<JobResponse>> {
+    job_queue().get(&identity, &id).map(Json)
+}
+
+/// Webhook delivery attempts made for a job
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}/deliveries",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job id returned when the job was created")),
+    responses(
+        (status = 200, description = "Delivery attempts, oldest first", body = Vec<WebhookDelivery>),
+    ),
+    security(("api_key" = ["jobs"]))
+)]
+pub async fn get_job_deliveries_handler(
+    Extension(identity): Extension<ApiKeyIdentity>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<WebhookDelivery>>> {
+    job_queue().deliveries(&identity, &id).map(Json)
+}
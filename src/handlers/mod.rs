@@ -1,9 +1,27 @@
+pub mod admin;
+pub mod batch;
+pub mod compare;
 pub mod extract;
 pub mod health;
+pub mod jobs;
+pub mod search;
+pub mod searchable;
+pub mod session;
+pub mod stream;
+pub mod url;
 pub mod waitlist;
 
+pub use admin::*;
+pub use batch::*;
+pub use compare::*;
 pub use extract::*;
 pub use health::*;
+pub use jobs::*;
+pub use search::*;
+pub use searchable::*;
+pub use session::*;
+pub use stream::*;
+pub use url::*;
 pub use waitlist::*;
 
 #[cfg(test)]
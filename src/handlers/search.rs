@@ -0,0 +1,140 @@
+use axum::{extract::Multipart, http::HeaderMap, response::Json};
+use std::time::Instant;
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{extract_file_from_multipart, parse_options, resolve_ocr_engine};
+use crate::models::{OutputFormat, SearchData, SearchQuery, SearchResponse};
+use crate::openapi::SearchUploadForm;
+use crate::services::{global_ocr_cache, page_texts, CompiledQueries, PdfProcessor};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// Find every hit of a list of literal or regex queries in a document
+#[utoipa::path(
+    post,
+    path = "/api/v1/search",
+    tag = "extraction",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body(content = SearchUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The hits of each query with their pages, offsets, snippets and, \
+            when the page has a layout, bounding boxes", body = SearchResponse),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn search_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Json<SearchResponse>> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let start = Instant::now();
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting document search request");
+
+    // Acquire rate limiting permit
+    let _permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    debug!(request_id = %request_id, "Rate limit permit acquired");
+
+    let upload = extract_file_from_multipart(&mut multipart).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "Failed to extract file from multipart");
+        e
+    })?;
+
+    let file = upload.file;
+    let mut fields = upload.fields;
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    if file.size > max_size_bytes {
+        warn!(
+            request_id = %request_id,
+            file_size = file.size,
+            max_size = max_size_bytes,
+            "File size exceeds limit"
+        );
+        return Err(AppError::FileTooLarge {
+            size: file.size / (1024 * 1024),
+            limit: config.max_file_size_mb,
+        });
+    }
+
+    let queries: Vec<SearchQuery> = match fields.remove("queries") {
+        Some(queries) => serde_json::from_str(&queries).map_err(|e| {
+            AppError::validation(format!(
+                "queries must be a JSON array of patterns or {{\"pattern\", \"regex\", \"case_sensitive\", \"whole_word\"}} objects: {}",
+                e
+            ))
+        })?,
+        None => return Err(AppError::validation("Send the search terms in a queries field")),
+    };
+    // Bad patterns are refused before paying for the extraction
+    let queries = CompiledQueries::new(queries)?;
+    let query_count = queries.queries().len();
+
+    // Search results are always JSON
+    let options = parse_options(&headers, &fields)?;
+    if options.output_format != OutputFormat::Json {
+        return Err(AppError::validation("Search only supports the json output format"));
+    }
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    // Hits are located through the layout whatever the output format
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine).with_layout();
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+    let file_name = file.name.clone();
+    let mut result = match processor.extract_text(file, &options).await {
+        Ok(result) => {
+            info!(
+                request_id = %request_id,
+                text_length = result.text.len(),
+                pages = result.pages,
+                processing_time_ms = result.processing_time_ms,
+                "PDF processing completed successfully"
+            );
+            result
+        }
+        Err(e) => {
+            error!(request_id = %request_id, error = %e, "PDF processing failed");
+            return Err(e);
+        }
+    };
+
+    let (text, layout) = (std::mem::take(&mut result.text), result.layout.take());
+    let (results, layout) = tokio::task::spawn_blocking(move || {
+        let results = queries.search(&page_texts(&text, layout.as_ref()));
+        (results, layout)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Document search failed: {}", e)))?;
+    let total_hits = results.iter().map(|result| result.hit_count).sum();
+    let located = results.iter().flat_map(|result| &result.hits).any(|hit| !hit.bboxes.is_empty());
+    let total_time = start.elapsed().as_millis() as u64;
+
+    info!(
+        request_id = %request_id,
+        queries = query_count,
+        total_hits,
+        total_time_ms = total_time,
+        "Document search completed"
+    );
+
+    Ok(Json(SearchResponse {
+        success: true,
+        data: SearchData {
+            file_name,
+            pages: result.pages,
+            results,
+            total_hits,
+            unit: layout.filter(|_| located).map(|layout| layout.unit),
+            metadata: result.metadata,
+        },
+        processing_time_ms: total_time,
+    }))
+}
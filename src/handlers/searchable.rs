@@ -0,0 +1,117 @@
+use axum::{
+    extract::Multipart,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{extract_file_from_multipart, parse_options, resolve_ocr_engine};
+use crate::openapi::UploadForm;
+use crate::services::{global_ocr_cache, OcrSettings, SearchablePdfBuilder};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// Return the uploaded PDF with an invisible OCR text layer on its scanned pages
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract/searchable",
+    tag = "extraction",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The PDF with a text layer on its scanned pages", content_type = "application/pdf", body = Vec<u8>,
+            headers(
+                ("X-Elicit-OCR-Pages" = String, description = "Comma-separated pages that were OCRed"),
+                ("X-Elicit-Skipped-Pages" = String, description = "Comma-separated scanned pages left without a text layer, \
+                    because they are rotated or OCR failed on them"),
+            )),
+    ),
+    security(("api_key" = ["ocr"]))
+)]
+pub async fn searchable_pdf_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting searchable PDF request");
+
+    // Acquire rate limiting permit
+    let _permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    debug!(request_id = %request_id, "Rate limit permit acquired");
+
+    let upload = extract_file_from_multipart(&mut multipart).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "Failed to extract file from multipart");
+        e
+    })?;
+
+    let file = upload.file;
+    if !file.is_pdf() {
+        return Err(AppError::InvalidFile {
+            message: "Searchable output is only available for PDF documents".to_string(),
+        });
+    }
+
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    if file.size > max_size_bytes {
+        warn!(
+            request_id = %request_id,
+            file_size = file.size,
+            max_size = max_size_bytes,
+            "File size exceeds limit"
+        );
+        return Err(AppError::FileTooLarge {
+            size: file.size / (1024 * 1024),
+            limit: config.max_file_size_mb,
+        });
+    }
+
+    let options = parse_options(&headers, &upload.fields)?;
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    let mut builder = SearchablePdfBuilder::new(ocr_engine, options.preprocessing())
+        .with_settings(OcrSettings::from(&options));
+    if let Some(cache) = global_ocr_cache() {
+        builder = builder.with_cache(cache);
+    }
+    let searchable = builder
+        .build(&file.content)
+        .await
+        .map_err(|e| {
+            error!(request_id = %request_id, error = %e, "Searchable PDF creation failed");
+            e
+        })?;
+
+    info!(
+        request_id = %request_id,
+        ocr_pages = ?searchable.ocr_pages,
+        skipped_pages = searchable.skipped_pages.len(),
+        "Searchable PDF request completed"
+    );
+
+    let stem = file.name.strip_suffix(".pdf").unwrap_or(&file.name).replace('"', "");
+    let ocr_pages = page_list(searchable.ocr_pages.iter().copied());
+    let skipped_pages = page_list(searchable.skipped_pages.iter().map(|(page, _)| *page));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-searchable.pdf\"", stem),
+            ),
+            (header::HeaderName::from_static("x-elicit-ocr-pages"), ocr_pages),
+            (header::HeaderName::from_static("x-elicit-skipped-pages"), skipped_pages),
+        ],
+        searchable.content,
+    )
+        .into_response())
+}
+
+fn page_list(pages: impl Iterator<Item = u32>) -> String {
+    pages.map(|page| page.to_string()).collect::<Vec<_>>().join(",")
+}
@@ -0,0 +1,266 @@
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    response::Response,
+};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{resolve_ocr_engine, validate_upload};
+use crate::models::{
+    ErrorDetail, ExtractRequest, ExtractionOptions, OutputFormat, ProcessedFile, SessionCommand, SessionMessage,
+    SessionReply, SessionRequest,
+};
+use crate::services::{global_ocr_cache, DocumentSession, PdfProcessor, SessionLimits};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+// Each open session may hold a document of up to `SESSION_MAX_MEMORY_MB` until it
+// goes idle, so the number of sessions is capped across all clients
+static SESSION_SEMAPHORE: Lazy<Arc<Semaphore>> = Lazy::new(|| {
+    let max_sessions = Config::from_env()
+        .map(|config| config.session_max_concurrent)
+        .unwrap_or(32);
+    info!(max_concurrent_sessions = max_sessions, "Initializing session semaphore");
+    Arc::new(Semaphore::new(max_sessions))
+});
+
+/// Open an interactive session that extracts a document once and answers commands about it
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions",
+    tag = "extraction",
+    responses(
+        (status = 101, description = "Switches to WebSocket. Each text message is a `SessionRequest` and gets one \
+            `SessionReply` back; a `load` without `content` takes the next binary message as the document. \
+            The session closes after `SESSION_IDLE_TIMEOUT_SECONDS` without a message.", body = SessionReply),
+        (status = 503, description = "`SERVICE_UNAVAILABLE`: `SESSION_MAX_CONCURRENT` sessions are already open"),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn session_handler(ws: WebSocketUpgrade) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let limits = SessionLimits::from_config(&config);
+    // Taken before upgrading, so a full server turns the client away with a plain HTTP error
+    let permit = SESSION_SEMAPHORE.clone().try_acquire_owned().map_err(|_| {
+        warn!("Session limit reached");
+        AppError::service_unavailable("too many sessions are open, try again later")
+    })?;
+    // Room for a maximum size file base64 encoded in a load command
+    let max_message_bytes = config.max_file_size_mb * 1024 * 1024 / 3 * 4 + 64 * 1024;
+    Ok(ws
+        .max_message_size(max_message_bytes)
+        .on_upgrade(move |socket| run_session(socket, config, limits, permit)))
+}
+
+/// A load command waiting for its document in the next binary message
+struct PendingLoad {
+    id: Option<serde_json::Value>,
+    file_name: String,
+    options: ExtractionOptions,
+}
+
+/// Serve one session; `_permit` holds its place among the open sessions until it ends
+async fn run_session(mut socket: WebSocket, config: Config, limits: SessionLimits, _permit: OwnedSemaphorePermit) {
+    let session_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    let mut document: Option<DocumentSession> = None;
+    let mut pending: Option<PendingLoad> = None;
+
+    info!(session_id = %session_id, "Session opened");
+
+    let ready = SessionMessage::Ready {
+        session_id: session_id.clone(),
+        idle_timeout_seconds: limits.idle_timeout.as_secs(),
+        max_memory_bytes: limits.max_memory_bytes,
+    };
+    if send(&mut socket, SessionReply::new(None, ready)).await.is_err() {
+        return;
+    }
+
+    let reason = loop {
+        let message = match tokio::time::timeout(limits.idle_timeout, socket.recv()).await {
+            Err(_) => break "idle_timeout",
+            Ok(Some(Ok(message))) => message,
+            Ok(Some(Err(e))) => {
+                debug!(session_id = %session_id, error = %e, "Session connection failed");
+                break "disconnected";
+            }
+            Ok(None) => break "disconnected",
+        };
+
+        let (id, outcome) = match message {
+            Message::Text(text) => match serde_json::from_str::<SessionRequest>(&text) {
+                Ok(SessionRequest {
+                    id,
+                    command: SessionCommand::Close,
+                }) => {
+                    let closed = SessionMessage::Closed {
+                        reason: "client".to_string(),
+                    };
+                    let _ = send(&mut socket, SessionReply::new(id, closed)).await;
+                    let _ = socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::NORMAL,
+                            reason: "".into(),
+                        })))
+                        .await;
+                    break "client";
+                }
+                Ok(SessionRequest {
+                    id,
+                    command: SessionCommand::Load {
+                        file_name,
+                        content,
+                        options,
+                    },
+                }) => {
+                    let request = ExtractRequest {
+                        file_name,
+                        file_size: None,
+                        content: content.unwrap_or_default(),
+                        options,
+                    };
+                    let outcome = match request.options() {
+                        // Without content the document follows as a binary message
+                        Ok(options) if request.content.is_empty() => {
+                            pending = Some(PendingLoad {
+                                id,
+                                file_name: request
+                                    .file_name
+                                    .filter(|name| !name.trim().is_empty())
+                                    .unwrap_or_else(|| "document".to_string()),
+                                options,
+                            });
+                            continue;
+                        }
+                        Ok(options) => {
+                            // The old document goes first, so a session never holds two
+                            document = None;
+                            match request.into_file(max_size_bytes) {
+                                Ok(file) => load(file, &options, &config, limits, &session_id).await,
+                                Err(e) => Err(e),
+                            }
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (id, outcome.map(|loaded| replace(&mut document, loaded)))
+                }
+                Ok(SessionRequest { id, command }) => (id, answer(document.as_ref(), command)),
+                Err(e) => (None, Err(AppError::validation(format!("Invalid session command: {}", e)))),
+            },
+            Message::Binary(content) => match pending.take() {
+                Some(load_request) => {
+                    let outcome = if content.len() > max_size_bytes {
+                        Err(AppError::FileTooLarge {
+                            size: content.len() / (1024 * 1024),
+                            limit: config.max_file_size_mb,
+                        })
+                    } else {
+                        document = None;
+                        let file = ProcessedFile::new(load_request.file_name, content);
+                        load(file, &load_request.options, &config, limits, &session_id).await
+                    };
+                    (load_request.id, outcome.map(|loaded| replace(&mut document, loaded)))
+                }
+                None => (None, Err(AppError::validation("Send a load command before the document"))),
+            },
+            Message::Close(_) => break "client",
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+
+        let message = outcome.unwrap_or_else(|e| {
+            warn!(session_id = %session_id, error_code = e.error_code(), error = %e, "Session command failed");
+            SessionMessage::Error {
+                error: ErrorDetail::new(e.error_code(), e.to_string()),
+            }
+        });
+        if send(&mut socket, SessionReply::new(id, message)).await.is_err() {
+            break "disconnected";
+        }
+    };
+
+    if reason == "idle_timeout" {
+        let closed = SessionMessage::Closed {
+            reason: reason.to_string(),
+        };
+        let _ = send(&mut socket, SessionReply::new(None, closed)).await;
+        let _ = socket
+            .send(Message::Close(Some(CloseFrame {
+                code: close_code::AWAY,
+                reason: "idle timeout".into(),
+            })))
+            .await;
+    }
+
+    info!(session_id = %session_id, reason, "Session closed");
+}
+
+/// Extract a document for the session, holding a rate limiting permit while it runs
+async fn load(
+    file: ProcessedFile,
+    options: &ExtractionOptions,
+    config: &Config,
+    limits: SessionLimits,
+    session_id: &str,
+) -> AppResult<DocumentSession> {
+    let _permit = REQUEST_SEMAPHORE.try_acquire().map_err(|_| {
+        warn!(session_id = %session_id, "Rate limit exceeded");
+        AppError::RateLimitExceeded
+    })?;
+
+    validate_upload(&file)?;
+    let ocr_engine = resolve_ocr_engine(options, config)?;
+
+    // Pages, tables and search all work from the layout
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine).with_layout();
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+    let file_name = file.name.clone();
+    let result = processor.extract_text(file, options).await.map_err(|e| {
+        error!(session_id = %session_id, error = %e, "PDF processing failed");
+        e
+    })?;
+
+    let loaded = DocumentSession::new(file_name, result, limits.max_memory_bytes)?;
+    info!(
+        session_id = %session_id,
+        pages = loaded.document().pages,
+        memory_bytes = loaded.memory_bytes(),
+        "Document loaded into session"
+    );
+    Ok(loaded)
+}
+
+/// Keep a newly loaded document
+fn replace(document: &mut Option<DocumentSession>, loaded: DocumentSession) -> SessionMessage {
+    let message = SessionMessage::Loaded(loaded.document());
+    *document = Some(loaded);
+    message
+}
+
+/// Answer a command about the loaded document
+fn answer(document: Option<&DocumentSession>, command: SessionCommand) -> AppResult<SessionMessage> {
+    let document = document.ok_or_else(|| AppError::validation("No document is loaded, send a load command first"))?;
+    match command {
+        SessionCommand::Info => Ok(SessionMessage::Info(document.document())),
+        SessionCommand::Text { format } => document.text(format.unwrap_or(OutputFormat::Text)),
+        SessionCommand::Pages { pages } => Ok(SessionMessage::Pages {
+            pages: document.pages(pages.as_deref())?,
+        }),
+        SessionCommand::Tables { pages } => document.tables(pages.as_deref()),
+        SessionCommand::Search { queries } => document.search(&queries),
+        SessionCommand::Load { .. } | SessionCommand::Close => {
+            Err(AppError::internal("load and close are handled by the session loop"))
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, reply: SessionReply) -> Result<(), axum::Error> {
+    // Replies are plain data, serializing them can't fail
+    let text = serde_json::to_string(&reply).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
@@ -0,0 +1,119 @@
+use axum::{
+    body::Body,
+    extract::Multipart,
+    http::{header, HeaderMap},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{extract_file_from_multipart, parse_options, resolve_ocr_engine};
+use crate::models::{OutputFormat, StreamEvent};
+use crate::openapi::UploadForm;
+use crate::services::{global_ocr_cache, PageStreamer, STREAM_EVENT_BUFFER};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// Stream per-page results as NDJSON, or as Server-Sent Events when the client accepts `text/event-stream`
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract/stream",
+    tag = "extraction",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "One `StreamEvent` per line, or per SSE event named after its `type`", content(
+            (StreamEvent = "application/x-ndjson"),
+            (StreamEvent = "text/event-stream"),
+        )),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn stream_extract_handler(headers: HeaderMap, mut multipart: Multipart) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting streamed extraction request");
+
+    // Held until the stream ends, not just until the response starts
+    let permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    debug!(request_id = %request_id, "Rate limit permit acquired");
+
+    let upload = extract_file_from_multipart(&mut multipart).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "Failed to extract file from multipart");
+        e
+    })?;
+
+    let file = upload.file;
+    let max_size_bytes = config.max_file_size_mb * 1024 * 1024;
+    if file.size > max_size_bytes {
+        warn!(
+            request_id = %request_id,
+            file_size = file.size,
+            max_size = max_size_bytes,
+            "File size exceeds limit"
+        );
+        return Err(AppError::FileTooLarge {
+            size: file.size / (1024 * 1024),
+            limit: config.max_file_size_mb,
+        });
+    }
+
+    let options = parse_options(&headers, &upload.fields)?;
+    if options.output_format != OutputFormat::Json {
+        return Err(AppError::validation("Streaming only supports the json output format"));
+    }
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    let mut streamer = PageStreamer::new()
+        .with_options(options)
+        .with_ocr_engine(ocr_engine);
+    if let Some(cache) = global_ocr_cache() {
+        streamer = streamer.with_ocr_cache(cache);
+    }
+
+    let (sender, receiver) = mpsc::channel(STREAM_EVENT_BUFFER);
+    tokio::spawn(async move {
+        let _permit = permit;
+        streamer.run(file, sender).await;
+        debug!(request_id = %request_id, "Streamed extraction finished, permit released");
+    });
+
+    let events = ReceiverStream::new(receiver);
+    if wants_event_stream(&headers) {
+        let stream = events.map(|event| Event::default().event(event.name()).json_data(&event));
+        Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+    } else {
+        let stream = events.map(|event| Ok::<_, Infallible>(ndjson_line(&event)));
+        Ok((
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+            Body::from_stream(stream),
+        )
+            .into_response())
+    }
+}
+
+fn wants_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+fn ndjson_line(event: &StreamEvent) -> String {
+    // Events are plain data, serializing them can't fail
+    let mut line = serde_json::to_string(event).unwrap_or_default();
+    line.push('\n');
+    line
+}
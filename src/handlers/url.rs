@@ -0,0 +1,96 @@
+use axum::{body::Bytes, http::HeaderMap, response::Response};
+use std::time::Instant;
+use tracing::{info, warn, debug, error};
+
+use crate::error::{AppError, AppResult};
+use crate::handlers::extract::{extraction_response, negotiate_options, resolve_ocr_engine};
+use crate::models::{ExtractResponse, ExtractUrlRequest};
+use crate::services::{fetch_document, global_ocr_cache, FetchPolicy, PdfProcessor};
+use crate::middleware::rate_limit::REQUEST_SEMAPHORE;
+use crate::config::Config;
+
+/// Download the document at `url` and extract it like an upload
+#[utoipa::path(
+    post,
+    path = "/api/v1/extract/url",
+    tag = "extraction",
+    params(
+        ("Accept" = Option<String>, Header, description = "`application/json` (default), `text/plain` or `text/markdown`; an `output_format` option takes precedence"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the stored response; see the API description"),
+    ),
+    request_body = ExtractUrlRequest,
+    responses(
+        (status = 200, description = "Extracted text in the format `output_format` or, failing that, `Accept` asks for. \
+            Formats other than JSON carry the metadata in `X-Elicit-*` headers.", content(
+            (ExtractResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/markdown"),
+            (String = "application/xml"),
+            (String = "text/html"),
+        ),
+            headers(
+                ("X-Elicit-Pages" = usize, description = "Page count, non-JSON formats only"),
+                ("X-Elicit-OCR-Used" = bool, description = "Whether OCR produced the text, non-JSON formats only"),
+                ("X-Elicit-Processing-Time-Ms" = u64, description = "Non-JSON formats only"),
+                ("Vary" = String, description = "Always `accept`"),
+            )
+        ),
+    ),
+    security(("api_key" = ["extract"]))
+)]
+pub async fn extract_url_handler(headers: HeaderMap, body: Bytes) -> AppResult<Response> {
+    let config = Config::from_env().map_err(|e| AppError::config(format!("Failed to load config: {}", e)))?;
+    let start = Instant::now();
+    let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+
+    info!(request_id = %request_id, "Starting URL extraction request");
+
+    let request: ExtractUrlRequest = serde_json::from_slice(&body)
+        .map_err(|e| AppError::validation(format!("Invalid request body: {}", e)))?;
+    let options = negotiate_options(&headers, request.option_pairs())?;
+    let ocr_engine = resolve_ocr_engine(&options, &config)?;
+
+    // Acquire rate limiting permit
+    let _permit = REQUEST_SEMAPHORE
+        .try_acquire()
+        .map_err(|_| {
+            warn!(request_id = %request_id, "Rate limit exceeded");
+            AppError::RateLimitExceeded
+        })?;
+
+    debug!(request_id = %request_id, "Rate limit permit acquired");
+
+    let file = fetch_document(&request.url, &FetchPolicy::from_config(&config))
+        .await
+        .map_err(|e| {
+            error!(request_id = %request_id, error = %e, "Failed to fetch document");
+            e
+        })?;
+
+    if !file.is_pdf() && !file.is_image() {
+        return Err(AppError::InvalidFile {
+            message: "URL did not point to a valid PDF document or supported image (PNG, JPEG, TIFF, WebP)".to_string(),
+        });
+    }
+
+    let file_name = file.name.clone();
+    let mut processor = PdfProcessor::new().with_ocr_engine(ocr_engine);
+    if let Some(cache) = global_ocr_cache() {
+        processor = processor.with_ocr_cache(cache);
+    }
+    let result = processor.extract_text(file, &options).await.map_err(|e| {
+        error!(request_id = %request_id, error = %e, "PDF processing failed");
+        e
+    })?;
+
+    let total_time = start.elapsed().as_millis() as u64;
+
+    info!(
+        request_id = %request_id,
+        total_time_ms = total_time,
+        output_format = options.output_format.as_str(),
+        "URL request completed successfully"
+    );
+
+    Ok(extraction_response(options, result, &file_name, total_time))
+}
@@ -7,19 +7,30 @@ use serde::{Deserialize, Serialize};
 use std::fs::OpenOptions;
 use std::io::Write;
 use tracing::{error, info};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct WaitlistRequest {
     pub email: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct WaitlistResponse {
     pub success: bool,
     pub message: String,
 }
 
 /// Handler for waitlist email collection
+#[utoipa::path(
+    post,
+    path = "/api/waitlist",
+    tag = "service",
+    request_body = WaitlistRequest,
+    responses(
+        (status = 200, description = "`success` is false for an invalid address", body = WaitlistResponse),
+        (status = 500, description = "The address could not be stored"),
+    )
+)]
 pub async fn waitlist_handler(
     Json(payload): Json<WaitlistRequest>,
 ) -> Result<ResponseJson<WaitlistResponse>, StatusCode> {
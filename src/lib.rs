@@ -3,11 +3,15 @@
 //! A high-performance Rust service for extracting text from PDF documents
 //! with OCR support for scanned documents.
 
+pub mod cli;
 pub mod config;
 pub mod error;
+pub mod grpc;
 pub mod handlers;
 pub mod middleware;
 pub mod models;
+pub mod openapi;
+pub mod routes;
 pub mod services;
 
 pub use config::Config;
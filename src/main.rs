@@ -1,44 +1,28 @@
-use axum::{
-    extract::DefaultBodyLimit,
-    http::StatusCode,
-    response::{Html, Json},
-    routing::{get, post},
-    Router,
-};
-use serde_json::{json, Value};
-use std::{env, fs};
+use std::env;
 use tokio::net::TcpListener;
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::CorsLayer,
-    trace::TraceLayer,
-};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod config;
-mod error;
-mod handlers;
-mod middleware;
-mod models;
-mod services;
-
-use config::Config;
-use handlers::{extract_handler, extract_binary_handler, health_handler, ready_handler, waitlist_handler};
-use middleware::auth::auth_middleware;
-
-/// Serve the landing page HTML
-async fn serve_landing_page() -> Html<String> {
-    // Try local path first (for development), then container path (for deployment)
-    let html_content = fs::read_to_string("elicit-landing.html")
-        .or_else(|_| fs::read_to_string("/app/elicit-landing.html"))
-        .unwrap_or_else(|_| "<h1>Landing page not found</h1>".to_string());
-    Html(html_content)
-}
+use elicit::cli::{self, GenerateKeyArgs};
+use elicit::config::Config;
+use elicit::{grpc, routes, services};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
+    // `elicit generate-key ...` prints new keys and exits instead of serving
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("generate-key") {
+        let args = GenerateKeyArgs::parse(&args[1..]).unwrap_or_else(|e| {
+            eprintln!("{}\n{}", e, cli::GENERATE_KEY_USAGE);
+            std::process::exit(2);
+        });
+        for generated in cli::generate_keys(&args) {
+            print!("{}", cli::render_generated_key(&generated, args.format));
+        }
+        return Ok(());
+    }
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -55,29 +39,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Max file size: {}MB", config.max_file_size_mb);
     tracing::info!("Max concurrent requests: {}", config.max_concurrent_requests);
 
-    // Build our application with routes
-    // Routes that don't require authentication
-    let public_routes = Router::new()
-        .route("/", get(serve_landing_page))
-        .route("/health", get(health_handler))
-        .route("/ready", get(ready_handler))
-        .route("/api/waitlist", post(waitlist_handler));
+    // Load the API key registry now, so a broken key file shows up in the startup logs
+    services::api_keys();
 
-    // Routes that require authentication
-    let protected_routes = Router::new()
-        .route("/api/v1/extract", post(extract_handler))
-        .route("/api/v1/extract/binary", post(extract_binary_handler))
-        .layer(axum::middleware::from_fn(auth_middleware));
-
-    let app = Router::new()
-        .merge(public_routes)
-        .merge(protected_routes)
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive())
-                .layer(DefaultBodyLimit::max(config.max_file_size_mb * 1024 * 1024))
-        );
+    let app = routes::app(&config);
 
     // Determine port from environment (Railway compatibility)
     let port = env::var("PORT")
@@ -85,13 +50,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse::<u16>()
         .unwrap_or(config.server_port);
 
-    let host = config.server_host;
+    let host = config.server_host.clone();
     let addr = format!("{}:{}", host, port);
     
     tracing::info!("Server listening on {}", addr);
     
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let http = async { axum::serve(listener, app).await.map_err(Box::<dyn std::error::Error>::from) };
+
+    // The gRPC API gets its own port, next to the HTTP one
+    if config.grpc_port == 0 {
+        return http.await;
+    }
+    let grpc_addr = format!("{}:{}", host, config.grpc_port);
+    tracing::info!("gRPC server listening on {}", grpc_addr);
+    let grpc_listener = TcpListener::bind(&grpc_addr).await?;
+    let grpc = async { grpc::serve(grpc_listener, &config).await.map_err(Box::<dyn std::error::Error>::from) };
 
+    tokio::try_join!(http, grpc)?;
     Ok(())
-}
\ No newline at end of file
+}
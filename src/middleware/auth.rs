@@ -1,15 +1,17 @@
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::HeaderMap,
     middleware::Next,
     response::Response,
 };
-use tracing::{debug, warn, info};
+use tracing::{debug, warn, info, Instrument};
 
-use crate::config::Config;
 use crate::error::AppError;
+use crate::models::{ApiKeyIdentity, Scope};
+use crate::services::api_keys;
 
-pub async fn auth_middleware(headers: HeaderMap, request: Request, next: Next) -> Result<Response, AppError> {
+/// Resolve the bearer token to its key and attach the `ApiKeyIdentity` to the request
+pub async fn auth_middleware(headers: HeaderMap, mut request: Request, next: Next) -> Result<Response, AppError> {
     let path = request.uri().path();
     let method = request.method();
     
@@ -50,13 +52,43 @@ pub async fn auth_middleware(headers: HeaderMap, request: Request, next: Next) -
         return Err(AppError::InvalidApiKey);
     }
 
-    // Validate the API key
-    if !Config::validate_api_key(token) {
-        warn!("Invalid API key attempted for {} {}: {}", method, path, 
-              if token.len() > 8 { &token[..8] } else { token });
-        return Err(AppError::InvalidApiKey);
-    }
+    // Look the key up in the registry
+    let identity = match api_keys().resolve(token) {
+        Ok(identity) => identity,
+        Err(e) => {
+            warn!("Rejected API key for {} {}: {} ({})", method, path,
+                  if token.len() > 8 { &token[..8] } else { token }, e);
+            return Err(e);
+        }
+    };
 
-    info!("Valid API key authenticated for {} {}", method, path);
+    info!(key_id = %identity.id, key_name = %identity.name, "Valid API key authenticated for {} {}", method, path);
+
+    // Everything the handler logs carries the key id
+    let span = tracing::info_span!("api_key", key_id = %identity.id);
+    request.extensions_mut().insert(identity);
+    Ok(next.run(request).instrument(span).await)
+}
+
+/// Reject requests whose key wasn't granted `scope`; runs after `auth_middleware`
+pub async fn require_scope(State(scope): State<Scope>, request: Request, next: Next) -> Result<Response, AppError> {
+    let identity = request
+        .extensions()
+        .get::<ApiKeyIdentity>()
+        .ok_or(AppError::InvalidApiKey)?;
+    if let Err(e) = identity.require(scope) {
+        warn!(key_id = %identity.id, "API key lacks the {} scope for {} {}", scope, request.method(), request.uri().path());
+        return Err(e);
+    }
     Ok(next.run(request).await)
-}
\ No newline at end of file
+}
+
+/// The token of an `Authorization: Bearer` header, if there is one
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .filter(|token| !token.is_empty())
+}
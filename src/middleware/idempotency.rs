@@ -0,0 +1,201 @@
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::error::AppError;
+use crate::middleware::auth::bearer_token;
+use crate::services::{
+    idempotency_store, request_fingerprint, validate_idempotency_key, IdempotencyOutcome, IdempotencyStore,
+    StoredResponse,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set to `true` on responses answered from the idempotency store
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Answer a retried request from the idempotency store instead of running it again.
+///
+/// Only requests with an `Idempotency-Key` header are affected. Successful
+/// responses are kept for the retention window; failures, streamed responses and
+/// responses over the store's size limit release the key so a retry runs the
+/// request again. The state is the largest request body to buffer.
+pub async fn idempotency_middleware(
+    State(max_body_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .map_err(|_| AppError::validation("Idempotency-Key must be visible ASCII"))?
+        .trim()
+        .to_string();
+    validate_idempotency_key(&key)?;
+
+    let (Some(store), Some(token)) = (idempotency_store(), bearer_token(request.headers())) else {
+        return Ok(next.run(request).await);
+    };
+    // Keys belong to the API key that sent them; only a hash of it is kept
+    let scope = hex::encode(Sha256::digest(token.as_bytes()));
+
+    let (parts, body) = request.into_parts();
+    if let Some(length) = content_length(&parts.headers) {
+        if length > max_body_bytes {
+            return Err(AppError::FileTooLarge {
+                size: length / (1024 * 1024),
+                limit: max_body_bytes / (1024 * 1024),
+            });
+        }
+    }
+    let body = to_bytes(body, max_body_bytes)
+        .await
+        .map_err(|e| AppError::InvalidFile {
+            message: format!("Failed to read request body: {}", e),
+        })?;
+
+    let fingerprint = fingerprint(&parts, &body);
+    match store.begin(&scope, &key, &fingerprint)? {
+        IdempotencyOutcome::Replay(stored) => {
+            info!(idempotency_key = %key, status = stored.status, "Replaying stored response");
+            return Ok(replay(stored));
+        }
+        IdempotencyOutcome::Proceed => debug!(idempotency_key = %key, "Idempotency key claimed"),
+    }
+
+    let claim = Claim {
+        store,
+        scope,
+        key,
+        fingerprint,
+        completed: false,
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        // Dropping the claim releases the key
+        return Ok(response);
+    }
+
+    // Only bodies known to fit are buffered; anything else goes straight to the client
+    let limit = claim.store.max_response_bytes();
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|upper| upper <= limit as u64);
+    if !fits {
+        debug!(idempotency_key = %claim.key, limit, "Response not stored, the key is released");
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, limit).await.map_err(|e| {
+        warn!(error = %e, "Failed to buffer response for the idempotency store");
+        AppError::internal(format!("Failed to read response body: {}", e))
+    })?;
+    claim.complete(StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect(),
+        body: body.to_vec(),
+    });
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// A claimed key, released on drop unless the request completed
+struct Claim {
+    store: Arc<IdempotencyStore>,
+    scope: String,
+    key: String,
+    fingerprint: String,
+    completed: bool,
+}
+
+impl Claim {
+    fn complete(mut self, response: StoredResponse) {
+        self.store.complete(&self.scope, &self.key, &self.fingerprint, response);
+        self.completed = true;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        // Also runs when the client goes away mid-request and the handler is cancelled
+        if !self.completed {
+            self.store.release(&self.scope, &self.key);
+        }
+    }
+}
+
+/// Method, path, query, content type, `Accept` and option headers, plus the body
+fn fingerprint(parts: &axum::http::request::Parts, body: &[u8]) -> String {
+    let header = |name: header::HeaderName| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string()
+    };
+    let content_type = header(header::CONTENT_TYPE);
+    let media_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    let boundary = content_type
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"'))
+        .next();
+
+    let mut option_headers: Vec<String> = parts
+        .headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-elicit-"))
+        .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+    option_headers.sort();
+
+    let method = parts.method.to_string();
+    let accept = header(header::ACCEPT);
+    let mut fields = vec![
+        method.as_str(),
+        parts.uri.path(),
+        parts.uri.query().unwrap_or(""),
+        media_type.as_str(),
+        accept.as_str(),
+    ];
+    fields.extend(option_headers.iter().map(String::as_str));
+    request_fingerprint(&fields, body, boundary)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_bytes(&value)) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(
+        HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+        HeaderValue::from_static("true"),
+    );
+    response
+}
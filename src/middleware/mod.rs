@@ -1,7 +1,9 @@
 pub mod auth;
+pub mod idempotency;
 pub mod rate_limit;
 pub mod logging;
 
 pub use auth::*;
+pub use idempotency::*;
 pub use rate_limit::*;
 pub use logging::*;
\ No newline at end of file
@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use crate::error::{AppError, AppResult};
+
+/// What an API key may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Extraction, search, comparison and sessions
+    Extract,
+    /// Searchable PDFs, which OCR every page
+    Ocr,
+    /// Extraction jobs and their deliveries
+    Jobs,
+    /// Key management
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Extract, Scope::Ocr, Scope::Jobs, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Extract => "extract",
+            Scope::Ocr => "ocr",
+            Scope::Jobs => "jobs",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> AppResult<Self> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value.trim().to_lowercase())
+            .ok_or_else(|| {
+                AppError::validation(format!(
+                    "Unknown scope '{}', expected one of: extract, ocr, jobs, admin",
+                    value.trim()
+                ))
+            })
+    }
+}
+
+/// One entry of the API key registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Stable identifier, shown in logs in place of the key
+    pub id: String,
+    /// The client or person the key was issued to
+    pub name: String,
+    /// First characters of the key, used to find its record without knowing the key
+    pub prefix: String,
+    /// Salted hash of the key, `sha256$<salt>$<hash>` as printed by `elicit generate-key`
+    pub key_hash: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// The key stops working at this time, if set
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn identity(&self) -> ApiKeyIdentity {
+        ApiKeyIdentity {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+/// The key a request was authenticated with, stored in the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyIdentity {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiKeyIdentity {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Fail with `INSUFFICIENT_SCOPE` unless the key was granted `scope`
+    pub fn require(&self, scope: Scope) -> AppResult<()> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(AppError::insufficient_scope(scope))
+        }
+    }
+}
+
+/// Requests made with a key since the server started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct KeyUsage {
+    pub requests: u64,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// An API key as the admin API shows it: everything but the hash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyView {
+    pub id: String,
+    pub name: String,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub disabled: bool,
+    pub usage: KeyUsage,
+}
+
+impl ApiKeyView {
+    pub fn new(key: &ApiKey, usage: KeyUsage) -> Self {
+        Self {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            prefix: key.prefix.clone(),
+            scopes: key.scopes.clone(),
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            disabled: key.disabled,
+            usage,
+        }
+    }
+}
+
+/// A newly created or rotated key. `key` is only ever returned here.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IssuedApiKey {
+    pub key: String,
+    pub api_key: ApiKeyView,
+}
+
+/// Body of `POST /api/v1/admin/keys`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Generated when absent
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The client or person the key is for
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Created,
+    Disabled,
+    Rotated,
+    Deleted,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Created => "created",
+            AuditAction::Disabled => "disabled",
+            AuditAction::Rotated => "rotated",
+            AuditAction::Deleted => "deleted",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> AppResult<Self> {
+        [AuditAction::Created, AuditAction::Disabled, AuditAction::Rotated, AuditAction::Deleted]
+            .into_iter()
+            .find(|action| action.as_str() == value)
+            .ok_or_else(|| AppError::validation(format!("Unknown audit action '{}'", value)))
+    }
+}
+
+/// One change made to the key registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEvent {
+    pub event_id: String,
+    pub at: DateTime<Utc>,
+    /// Id of the admin key that made the change
+    pub actor: String,
+    pub action: AuditAction,
+    /// Id of the key changed
+    pub key_id: String,
+}
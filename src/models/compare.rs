@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::PdfMetadata;
+
+/// Result of comparing two versions of a document
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CompareResponse {
+    pub success: bool,
+    pub data: CompareData,
+    pub processing_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CompareData {
+    pub original: ComparedDocument,
+    pub revised: ComparedDocument,
+    /// Share of words the two versions have in common, from 0.0 (nothing) to 1.0 (same text)
+    pub similarity: f64,
+    pub summary: DiffSummary,
+    /// Added, removed and modified paragraphs in document order
+    pub changes: Vec<ParagraphChange>,
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ComparedDocument {
+    pub file_name: String,
+    pub pages: usize,
+    pub paragraphs: usize,
+    pub words: usize,
+    pub metadata: PdfMetadata,
+}
+
+/// Paragraph and word counts of a comparison
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DiffSummary {
+    pub paragraphs_unchanged: usize,
+    pub paragraphs_added: usize,
+    pub paragraphs_removed: usize,
+    pub paragraphs_modified: usize,
+    pub words_added: usize,
+    pub words_removed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only in the revised version
+    Added,
+    /// Only in the original version
+    Removed,
+    /// In both versions with some words changed
+    Modified,
+}
+
+/// A paragraph that differs between the two versions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ParagraphChange {
+    pub kind: ChangeKind,
+    pub original: ParagraphLocation,
+    pub revised: ParagraphLocation,
+    /// Word-level diff; a single `insert` or `delete` for added and removed paragraphs
+    pub words: Vec<WordChange>,
+}
+
+/// Where a change sits in one version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ParagraphLocation {
+    /// 1-based paragraph number; on the side without the paragraph, the number it would have
+    pub paragraph: usize,
+    /// 1-based page; on the side without the paragraph, the page of the text before it
+    pub page: u32,
+    /// Absent on the side without the paragraph
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WordOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of words with the same fate, separated by single spaces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WordChange {
+    pub op: WordOp,
+    pub text: String,
+}
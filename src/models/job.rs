@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ExtractResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// Pages processed so far; the total is unknown until the document has been opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct JobProgress {
+    pub pages_done: usize,
+    pub pages_total: Option<usize>,
+}
+
+/// Why a job failed, using the same codes as error responses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct JobError {
+    pub code: String,
+    pub message: String,
+}
+
+/// State of an extraction job as returned by the jobs API
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobResponse {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub file_name: String,
+    /// Where the outcome is POSTed once the job finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// When a finished job will be forgotten
+    pub expires_at: Option<DateTime<Utc>>,
+    pub result: Option<ExtractResponse>,
+    pub error: Option<JobError>,
+}
+
+/// One attempt at POSTing a finished job to its callback URL
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    /// Shared by every attempt at delivering the same event
+    pub delivery_id: String,
+    pub job_id: String,
+    pub event: String,
+    pub url: String,
+    pub attempt: u32,
+    pub attempted_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub success: bool,
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Unit that layout coordinates are expressed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutUnit {
+    /// PDF points (1/72 inch), used for every PDF page
+    #[default]
+    Point,
+    /// Image pixels, used for direct image uploads where the resolution is unknown
+    Pixel,
+}
+
+/// Where the text of a page came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextSource {
+    Native,
+    Ocr,
+}
+
+/// Axis-aligned box with a top-left origin
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BoundingBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl BoundingBox {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        BoundingBox {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+
+    /// Smallest box containing all the given boxes
+    pub fn enclosing<'a>(boxes: impl IntoIterator<Item = &'a BoundingBox>) -> BoundingBox {
+        let mut boxes = boxes.into_iter();
+        match boxes.next() {
+            Some(first) => boxes.fold(*first, |acc, b| acc.union(b)),
+            None => BoundingBox::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutWord {
+    pub text: String,
+    pub bbox: BoundingBox,
+    /// OCR confidence in percent, absent for native text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutLine {
+    pub bbox: BoundingBox,
+    pub words: Vec<LayoutWord>,
+}
+
+impl LayoutLine {
+    pub fn new(words: Vec<LayoutWord>) -> Self {
+        let bbox = BoundingBox::enclosing(words.iter().map(|w| &w.bbox));
+        Self { bbox, words }
+    }
+
+    pub fn text(&self) -> String {
+        self.words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutBlock {
+    pub bbox: BoundingBox,
+    pub lines: Vec<LayoutLine>,
+}
+
+impl LayoutBlock {
+    pub fn new(lines: Vec<LayoutLine>) -> Self {
+        let bbox = BoundingBox::enclosing(lines.iter().map(|l| &l.bbox));
+        Self { bbox, lines }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageLayout {
+    /// 1-based page number
+    pub number: u32,
+    pub width: f32,
+    pub height: f32,
+    pub source: TextSource,
+    pub blocks: Vec<LayoutBlock>,
+}
+
+impl PageLayout {
+    pub fn words(&self) -> impl Iterator<Item = &LayoutWord> {
+        self.blocks
+            .iter()
+            .flat_map(|b| b.lines.iter())
+            .flat_map(|l| l.words.iter())
+    }
+}
+
+/// Page / block / line / word structure of an extracted document with coordinates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentLayout {
+    pub unit: LayoutUnit,
+    pub pages: Vec<PageLayout>,
+}
+
+impl DocumentLayout {
+    /// Whether no page has a single word
+    pub fn is_empty(&self) -> bool {
+        self.pages.iter().all(|page| page.words().next().is_none())
+    }
+}
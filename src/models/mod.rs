@@ -1,5 +1,21 @@
+pub mod api_key;
+pub mod compare;
+pub mod job;
+pub mod layout;
+pub mod options;
 pub mod request;
 pub mod response;
+pub mod search;
+pub mod session;
+pub mod table;
 
+pub use api_key::*;
+pub use compare::*;
+pub use job::*;
+pub use layout::*;
+pub use options::*;
 pub use request::*;
-pub use response::*;
\ No newline at end of file
+pub use response::*;
+pub use search::*;
+pub use session::*;
+pub use table::*;
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::HashMap;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{OutputFormat, PreprocessingOptions, PreprocessingStep};
+
+/// Language list used when a request doesn't pick one
+pub const DEFAULT_OCR_LANGUAGE: &str = "spa+eng";
+/// Tesseract page segmentation mode used when a request doesn't pick one
+pub const DEFAULT_OCR_PSM: u8 = 1;
+
+/// Everything a caller can tune about one extraction.
+///
+/// Multipart fields, query parameters, JSON body fields and `X-Elicit-*`
+/// headers all go through [`ExtractionOptions::set`], so an option is spelled,
+/// parsed and validated the same way whichever way it was sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default, try_from = "HashMap<String, serde_json::Value>")]
+pub struct ExtractionOptions {
+    /// Image cleanup steps run before OCR
+    pub preprocess: Vec<PreprocessingStep>,
+    /// OCR engine, omitted for the configured `OCR_ENGINE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ocr_engine: Option<String>,
+    pub output_format: OutputFormat,
+    /// Tesseract-style language list, e.g. `deu+eng`
+    pub language: String,
+    /// Page segmentation mode (1 - 13)
+    #[schema(minimum = 1, maximum = 13)]
+    pub psm: u8,
+    /// Native text shorter than this is retried with OCR
+    pub ocr_min_chars: usize,
+    /// Native text with fewer letters than this is retried with OCR
+    pub ocr_min_letters: usize,
+}
+
+impl Default for ExtractionOptions {
+    fn default() -> Self {
+        Self {
+            preprocess: Vec::new(),
+            ocr_engine: None,
+            output_format: OutputFormat::default(),
+            language: DEFAULT_OCR_LANGUAGE.to_string(),
+            psm: DEFAULT_OCR_PSM,
+            ocr_min_chars: 100,
+            ocr_min_letters: 50,
+        }
+    }
+}
+
+impl ExtractionOptions {
+    /// Names accepted by [`set`](Self::set)
+    pub const NAMES: [&'static str; 7] = [
+        "preprocess",
+        "ocr_engine",
+        "output_format",
+        "language",
+        "psm",
+        "ocr_min_chars",
+        "ocr_min_letters",
+    ];
+
+    /// Defaults overridden by `pairs`, rejecting unknown names
+    pub fn parse<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> AppResult<Self>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut options = Self::default();
+        options.apply(pairs)?;
+        Ok(options)
+    }
+
+    /// Override the options named in `pairs`
+    pub fn apply<K, V>(&mut self, pairs: impl IntoIterator<Item = (K, V)>) -> AppResult<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (name, value) in pairs {
+            self.set(name.as_ref(), value.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Set one option from its string form, as sent in a form field, query or header
+    pub fn set(&mut self, name: &str, value: &str) -> AppResult<()> {
+        let value = value.trim();
+        match name {
+            "preprocess" => self.preprocess = PreprocessingOptions::parse(value)?.steps,
+            "ocr_engine" => self.ocr_engine = (!value.is_empty()).then(|| value.to_lowercase()),
+            "output_format" => self.output_format = value.parse()?,
+            "language" => self.language = parse_language(value)?,
+            "psm" => self.psm = parse_psm(value)?,
+            "ocr_min_chars" => self.ocr_min_chars = parse_count(name, value)?,
+            "ocr_min_letters" => self.ocr_min_letters = parse_count(name, value)?,
+            other => {
+                return Err(AppError::validation(format!(
+                    "Unknown option '{}', expected one of: {}",
+                    other,
+                    Self::NAMES.join(", ")
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Every option in the string form [`set`](Self::set) accepts, as echoed over gRPC
+    pub fn pairs(&self) -> Vec<(&'static str, String)> {
+        let preprocess = if self.preprocess.is_empty() {
+            "none".to_string()
+        } else {
+            self.preprocess.iter().map(|step| step.as_str()).collect::<Vec<_>>().join(",")
+        };
+        let mut pairs = vec![("preprocess", preprocess)];
+        if let Some(engine) = &self.ocr_engine {
+            pairs.push(("ocr_engine", engine.clone()));
+        }
+        pairs.extend([
+            ("output_format", self.output_format.as_str().to_string()),
+            ("language", self.language.clone()),
+            ("psm", self.psm.to_string()),
+            ("ocr_min_chars", self.ocr_min_chars.to_string()),
+            ("ocr_min_letters", self.ocr_min_letters.to_string()),
+        ]);
+        pairs
+    }
+
+    pub fn with_preprocessing(mut self, preprocessing: PreprocessingOptions) -> Self {
+        self.preprocess = preprocessing.steps;
+        self
+    }
+
+    pub fn with_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    pub fn preprocessing(&self) -> PreprocessingOptions {
+        PreprocessingOptions {
+            steps: self.preprocess.clone(),
+        }
+    }
+
+    /// Whether native text is too thin to trust on its own
+    pub fn wants_ocr_for(&self, native_text: &str) -> bool {
+        native_text.len() < self.ocr_min_chars
+            || native_text.chars().filter(|c| c.is_alphabetic()).count() < self.ocr_min_letters
+    }
+}
+
+impl TryFrom<HashMap<String, serde_json::Value>> for ExtractionOptions {
+    type Error = AppError;
+
+    fn try_from(fields: HashMap<String, serde_json::Value>) -> AppResult<Self> {
+        Self::parse(json_option_pairs(&fields))
+    }
+}
+
+/// JSON option values in the string form the other transports send: arrays
+/// become comma-separated lists and `null` leaves the default in place
+pub fn json_option_pairs(fields: &HashMap<String, serde_json::Value>) -> Vec<(String, String)> {
+    fields
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::Null => return None,
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string).unwrap_or_else(|| item.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                other => other.to_string(),
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// `+`-separated language codes such as `eng` or `chi_sim+eng`
+fn parse_language(value: &str) -> AppResult<String> {
+    let valid = !value.is_empty()
+        && value.split('+').all(|code| {
+            !code.is_empty() && code.len() <= 32 && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    if !valid {
+        return Err(AppError::validation(format!(
+            "Invalid language '{}', expected codes like eng or deu+eng",
+            value
+        )));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_psm(value: &str) -> AppResult<u8> {
+    // 0 only detects orientation and script, it never returns text
+    match value.parse::<u8>() {
+        Ok(psm @ 1..=13) => Ok(psm),
+        _ => Err(AppError::validation(format!(
+            "Invalid psm '{}', expected a page segmentation mode from 1 to 13",
+            value
+        ))),
+    }
+}
+
+fn parse_count(name: &str, value: &str) -> AppResult<usize> {
+    value
+        .parse()
+        .map_err(|_| AppError::validation(format!("Invalid {} '{}', expected a whole number", name, value)))
+}
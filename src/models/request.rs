@@ -1,9 +1,90 @@
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::error::{AppError, AppResult};
+use crate::models::options::{json_option_pairs, ExtractionOptions};
+
+/// JSON body of `POST /api/v1/extract`, for callers that can't build multipart forms
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct ExtractRequest {
     pub file_name: Option<String>,
+    /// Decoded size in bytes; when given it must match `content`
     pub file_size: Option<usize>,
+    /// The document, base64 encoded (line breaks are ignored)
+    pub content: String,
+    /// Every other field, checked against [`ExtractionOptions::NAMES`]
+    #[serde(flatten)]
+    #[schema(value_type = ExtractionOptions)]
+    pub options: HashMap<String, serde_json::Value>,
+}
+
+impl ExtractRequest {
+    /// The option fields in the string form shared with forms, queries and headers
+    pub fn option_pairs(&self) -> Vec<(String, String)> {
+        json_option_pairs(&self.options)
+    }
+
+    /// The option fields on top of the defaults
+    pub fn options(&self) -> AppResult<ExtractionOptions> {
+        ExtractionOptions::parse(self.option_pairs())
+    }
+
+    /// Decode and validate the document, refusing anything over `max_bytes` before decoding it
+    pub fn into_file(self, max_bytes: usize) -> AppResult<ProcessedFile> {
+        let encoded: String = self.content.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        if encoded.is_empty() {
+            return Err(AppError::validation("content must be a non-empty base64 string"));
+        }
+
+        let decoded_len = encoded.len() / 4 * 3;
+        if decoded_len > max_bytes + 2 {
+            return Err(AppError::FileTooLarge {
+                size: decoded_len / (1024 * 1024),
+                limit: max_bytes / (1024 * 1024),
+            });
+        }
+
+        let content = base64::engine::general_purpose::STANDARD
+            .decode(encoded.as_bytes())
+            .map_err(|e| AppError::validation(format!("content is not valid base64: {}", e)))?;
+        if let Some(file_size) = self.file_size {
+            if file_size != content.len() {
+                return Err(AppError::validation(format!(
+                    "file_size is {} but content decodes to {} bytes",
+                    file_size,
+                    content.len()
+                )));
+            }
+        }
+
+        let file_name = match self.file_name {
+            Some(name) if name.trim().is_empty() => {
+                return Err(AppError::validation("file_name must not be empty"));
+            }
+            Some(name) => name,
+            None => "document".to_string(),
+        };
+        Ok(ProcessedFile::new(file_name, content))
+    }
+}
+
+/// JSON body of `POST /api/v1/extract/url`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ExtractUrlRequest {
+    pub url: String,
+    /// Every other field, checked against [`ExtractionOptions::NAMES`]
+    #[serde(flatten)]
+    #[schema(value_type = ExtractionOptions)]
+    pub options: HashMap<String, serde_json::Value>,
+}
+
+impl ExtractUrlRequest {
+    /// The option fields in the string form shared with forms, queries and headers
+    pub fn option_pairs(&self) -> Vec<(String, String)> {
+        json_option_pairs(&self.options)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +111,37 @@ impl ProcessedFile {
         self
     }
 
+    /// Image format of the upload, if it is one of the formats we can OCR
+    pub fn image_format(&self) -> Option<image::ImageFormat> {
+        if let Some(mime_type) = &self.mime_type {
+            if mime_type == "application/pdf" {
+                return None;
+            }
+        }
+
+        match image::guess_format(&self.content) {
+            Ok(format @ (image::ImageFormat::Png
+            | image::ImageFormat::Jpeg
+            | image::ImageFormat::Tiff
+            | image::ImageFormat::WebP)) => Some(format),
+            _ => None,
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.image_format().is_some()
+    }
+
+    /// ZIP archive, expanded into its entries by the batch endpoint
+    pub fn is_zip(&self) -> bool {
+        self.mime_type
+            .as_deref()
+            .map(|mt| mt == "application/zip" || mt == "application/x-zip-compressed")
+            .unwrap_or(false)
+            || self.content.starts_with(b"PK\x03\x04")
+            || self.content.starts_with(b"PK\x05\x06")
+    }
+
     pub fn is_pdf(&self) -> bool {
         self.mime_type
             .as_ref()
@@ -39,4 +151,193 @@ impl ProcessedFile {
                     || self.content.starts_with(b"%PDF")
             })
     }
-}
\ No newline at end of file
+}
+/// Individual image preprocessing steps that can run before OCR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PreprocessingStep {
+    Upscale,
+    AutoRotate,
+    Grayscale,
+    Deskew,
+    Binarize,
+    Despeckle,
+}
+
+impl PreprocessingStep {
+    /// All steps, in the order they are applied
+    pub const ALL: [PreprocessingStep; 6] = [
+        PreprocessingStep::Upscale,
+        PreprocessingStep::AutoRotate,
+        PreprocessingStep::Grayscale,
+        PreprocessingStep::Deskew,
+        PreprocessingStep::Binarize,
+        PreprocessingStep::Despeckle,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PreprocessingStep::Upscale => "upscale",
+            PreprocessingStep::AutoRotate => "auto_rotate",
+            PreprocessingStep::Grayscale => "grayscale",
+            PreprocessingStep::Deskew => "deskew",
+            PreprocessingStep::Binarize => "binarize",
+            PreprocessingStep::Despeckle => "despeckle",
+        }
+    }
+}
+
+impl std::str::FromStr for PreprocessingStep {
+    type Err = crate::error::AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "upscale" => Ok(PreprocessingStep::Upscale),
+            "auto_rotate" | "rotate" => Ok(PreprocessingStep::AutoRotate),
+            "grayscale" | "greyscale" => Ok(PreprocessingStep::Grayscale),
+            "deskew" => Ok(PreprocessingStep::Deskew),
+            "binarize" | "binarization" => Ok(PreprocessingStep::Binarize),
+            "despeckle" => Ok(PreprocessingStep::Despeckle),
+            other => Err(crate::error::AppError::validation(format!(
+                "Unknown preprocessing step: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Per-request selection of image preprocessing steps.
+///
+/// Nothing is enabled by default so existing requests keep their behaviour.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreprocessingOptions {
+    pub steps: Vec<PreprocessingStep>,
+}
+
+impl PreprocessingOptions {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Self {
+            steps: PreprocessingStep::ALL.to_vec(),
+        }
+    }
+
+    /// Parse a comma-separated list such as `"grayscale,deskew"`, `"all"` or `"none"`
+    pub fn parse(value: &str) -> crate::error::AppResult<Self> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("none") {
+            return Ok(Self::none());
+        }
+        if value.eq_ignore_ascii_case("all") {
+            return Ok(Self::all());
+        }
+
+        let mut steps = value
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.parse())
+            .collect::<crate::error::AppResult<Vec<PreprocessingStep>>>()?;
+        steps.sort();
+        steps.dedup();
+        Ok(Self { steps })
+    }
+
+    pub fn is_enabled(&self, step: PreprocessingStep) -> bool {
+        self.steps.contains(&step)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Shape of a successful extraction response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// The standard JSON envelope with plain text
+    #[default]
+    Json,
+    /// ALTO XML (v4) with page, block, line and word coordinates
+    Alto,
+    /// hOCR (XHTML) with page, block, line and word coordinates
+    Hocr,
+    /// Just the extracted text; metadata goes in response headers
+    Text,
+    /// The extracted text as Markdown paragraphs under a title heading
+    Markdown,
+}
+
+impl OutputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Alto => "alto",
+            OutputFormat::Hocr => "hocr",
+            OutputFormat::Text => "text",
+            OutputFormat::Markdown => "markdown",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Alto => "application/xml; charset=utf-8",
+            OutputFormat::Hocr => "text/html; charset=utf-8",
+            OutputFormat::Text => "text/plain; charset=utf-8",
+            OutputFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    /// The format an `Accept` header prefers among `application/json`, `text/plain` and
+    /// `text/markdown`, `None` when it names none of them
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut best: Option<(f32, Self)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let media_type = parts.next().unwrap_or("").trim().to_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match media_type.as_str() {
+                "application/json" | "application/*" | "*/*" => OutputFormat::Json,
+                "text/plain" | "text/*" => OutputFormat::Text,
+                "text/markdown" | "text/x-markdown" => OutputFormat::Markdown,
+                _ => continue,
+            };
+            // Earlier ranges win ties
+            if quality > 0.0 && best.is_none_or(|(best_quality, _)| quality > best_quality) {
+                best = Some((quality, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    /// Whether the format needs word coordinates from the extraction
+    pub fn needs_layout(&self) -> bool {
+        matches!(self, OutputFormat::Alto | OutputFormat::Hocr)
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = crate::error::AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "json" => Ok(OutputFormat::Json),
+            "alto" | "alto_xml" => Ok(OutputFormat::Alto),
+            "hocr" => Ok(OutputFormat::Hocr),
+            "text" | "txt" | "plain" => Ok(OutputFormat::Text),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            other => Err(crate::error::AppError::validation(format!(
+                "Unknown output format: {} (expected json, text, markdown, alto or hocr)",
+                other
+            ))),
+        }
+    }
+}
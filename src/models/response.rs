@@ -1,21 +1,27 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::models::{ExtractionOptions, PreprocessingStep, TextSource};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ExtractResponse {
     pub success: bool,
     pub data: ExtractData,
     pub processing_time_ms: u64,
+    /// The options the extraction ran with, defaults filled in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ExtractionOptions>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ExtractData {
     pub text: String,
     pub pages: usize,
     pub metadata: PdfMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PdfMetadata {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -23,9 +29,51 @@ pub struct PdfMetadata {
     pub modification_date: Option<DateTime<Utc>>,
     pub file_size_bytes: usize,
     pub ocr_used: bool,
+    #[serde(default)]
+    pub preprocessing_applied: Vec<PreprocessingStep>,
+    #[serde(default)]
+    pub page_analysis: Vec<PageAnalysis>,
+    /// Pages whose OCR result came from the cache
+    #[serde(default)]
+    pub ocr_cache_hits: usize,
+}
+
+/// How a PDF page was produced, judged from its content stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PageKind {
+    /// Page image with no real text, needs OCR
+    Scanned,
+    /// Text drawn with fonts
+    Native,
+    /// Page image combined with text, e.g. a scan with an OCR layer
+    Mixed,
+    /// Neither text nor images
+    Empty,
+}
+
+/// Per-page result of scanned content detection
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PageAnalysis {
+    pub page: u32,
+    pub kind: PageKind,
+    /// Fraction of the page area covered by images (0.0 - 1.0)
+    pub image_coverage: f32,
+    pub image_count: usize,
+    /// Number of text-showing operators (`Tj`, `TJ`, `'`, `"`)
+    pub text_operators: usize,
+    /// Text-showing operators drawn with render mode 3 (invisible)
+    pub invisible_text_operators: usize,
+    pub reasons: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl PageAnalysis {
+    pub fn needs_ocr(&self) -> bool {
+        self.kind == PageKind::Scanned
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
@@ -33,16 +81,91 @@ pub struct HealthResponse {
     pub uptime_seconds: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Body of every error response, built from `AppError`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ErrorResponse {
+    /// Always `false`
     pub success: bool,
     pub error: ErrorDetail,
+    /// Always `null`
+    #[schema(value_type = Option<Object>)]
+    pub data: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct ErrorDetail {
+    /// Machine readable code such as `FILE_TOO_LARGE`
     pub code: String,
     pub message: String,
+    /// Set on error responses, absent on per-file batch and stream errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(format = DateTime)]
+    pub timestamp: Option<String>,
+}
+
+impl ErrorDetail {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            request_id: None,
+            timestamp: None,
+        }
+    }
+}
+
+/// Outcome of one file in a batch, successful or not
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchFileResult {
+    pub file_name: String,
+    pub success: bool,
+    pub data: Option<ExtractData>,
+    pub error: Option<ErrorDetail>,
+    pub processing_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchResponse {
+    pub success: bool,
+    /// One entry per uploaded file (or ZIP entry), in upload order
+    pub files: Vec<BatchFileResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub processing_time_ms: u64,
+    /// The options every file was extracted with
+    pub options: ExtractionOptions,
+}
+
+/// One message of a streamed extraction, sent as an NDJSON line or an SSE event
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// The upload was accepted; `pages_total` is known for PDFs and images alike
+    Started { file_name: String, pages_total: usize },
+    /// OCR is about to work on the next page
+    Progress { stage: String, pages_done: usize, pages_total: usize },
+    /// Text of one finished page (1-based)
+    Page { page: usize, text: String, source: TextSource },
+    /// Everything `ExtractResponse` carries, sent last
+    Summary(Box<ExtractResponse>),
+    /// Extraction stopped; no summary follows
+    Error { error: ErrorDetail },
+}
+
+impl StreamEvent {
+    /// SSE event name, matching the `type` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Started { .. } => "started",
+            StreamEvent::Progress { .. } => "progress",
+            StreamEvent::Page { .. } => "page",
+            StreamEvent::Summary(_) => "summary",
+            StreamEvent::Error { .. } => "error",
+        }
+    }
 }
 
 impl ExtractResponse {
@@ -55,8 +178,14 @@ impl ExtractResponse {
                 metadata,
             },
             processing_time_ms,
+            options: None,
         }
     }
+
+    pub fn with_options(mut self, options: ExtractionOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
 }
 
 impl PdfMetadata {
@@ -68,6 +197,9 @@ impl PdfMetadata {
             modification_date: None,
             file_size_bytes,
             ocr_used: false,
+            preprocessing_applied: Vec::new(),
+            page_analysis: Vec::new(),
+            ocr_cache_hits: 0,
         }
     }
 
@@ -76,6 +208,21 @@ impl PdfMetadata {
         self
     }
 
+    pub fn with_preprocessing(mut self, steps: Vec<PreprocessingStep>) -> Self {
+        self.preprocessing_applied = steps;
+        self
+    }
+
+    pub fn with_cache_hits(mut self, hits: usize) -> Self {
+        self.ocr_cache_hits = hits;
+        self
+    }
+
+    pub fn with_page_analysis(mut self, page_analysis: Vec<PageAnalysis>) -> Self {
+        self.page_analysis = page_analysis;
+        self
+    }
+
     pub fn with_title(mut self, title: Option<String>) -> Self {
         self.title = title;
         self
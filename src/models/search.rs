@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::{BoundingBox, LayoutUnit, PdfMetadata};
+
+/// One term to look for. A plain string is a case-insensitive literal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(from = "SearchQueryInput")]
+pub struct SearchQuery {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Only match at word boundaries
+    pub whole_word: bool,
+}
+
+impl SearchQuery {
+    /// Case-insensitive literal text
+    pub fn literal(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+        }
+    }
+
+    /// Case-insensitive regular expression
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        Self {
+            regex: true,
+            ..Self::literal(pattern)
+        }
+    }
+
+    pub fn case_sensitive(mut self) -> Self {
+        self.case_sensitive = true;
+        self
+    }
+
+    pub fn whole_word(mut self) -> Self {
+        self.whole_word = true;
+        self
+    }
+}
+
+/// A query as sent: either just the pattern or the pattern with its flags
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SearchQueryInput {
+    Pattern(String),
+    Query {
+        pattern: String,
+        #[serde(default)]
+        regex: bool,
+        #[serde(default)]
+        case_sensitive: bool,
+        #[serde(default)]
+        whole_word: bool,
+    },
+}
+
+impl From<SearchQueryInput> for SearchQuery {
+    fn from(input: SearchQueryInput) -> Self {
+        match input {
+            SearchQueryInput::Pattern(pattern) => SearchQuery::literal(pattern),
+            SearchQueryInput::Query {
+                pattern,
+                regex,
+                case_sensitive,
+                whole_word,
+            } => SearchQuery {
+                pattern,
+                regex,
+                case_sensitive,
+                whole_word,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchResponse {
+    pub success: bool,
+    pub data: SearchData,
+    pub processing_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchData {
+    pub file_name: String,
+    pub pages: usize,
+    /// One entry per query, in request order
+    pub results: Vec<QueryResult>,
+    pub total_hits: usize,
+    /// Unit of the hit bounding boxes, absent when there are none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<LayoutUnit>,
+    pub metadata: PdfMetadata,
+}
+
+/// The hits of one query
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueryResult {
+    pub query: SearchQuery,
+    pub hit_count: usize,
+    /// Pages with at least one hit
+    pub pages: Vec<u32>,
+    /// Whether hits beyond the per-query limit were left out
+    pub truncated: bool,
+    pub hits: Vec<SearchHit>,
+}
+
+/// One match of a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    /// 1-based page number
+    pub page: u32,
+    /// Character offset of the match in the page text (lines separated by `\n`, blocks by a blank line)
+    pub start: usize,
+    /// Character offset just past the match
+    pub end: usize,
+    /// The matched text
+    pub text: String,
+    /// The match with the text around it, line breaks as spaces
+    pub snippet: String,
+    /// Character offset of the snippet in the page text
+    pub snippet_start: usize,
+    /// One box per line the match covers, when the page has a layout
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bboxes: Vec<BoundingBox>,
+}
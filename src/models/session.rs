@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::models::{
+    ErrorDetail, ExtractionOptions, LayoutUnit, OutputFormat, PdfMetadata, QueryResult, SearchQuery, Table, TextSource,
+};
+
+/// A text message sent to `/api/v1/sessions`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionRequest {
+    /// Echoed in the reply, so answers can be matched to commands
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub command: SessionCommand,
+}
+
+/// What a session can be asked to do
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionCommand {
+    /// Extract a document into the session, replacing the one loaded before.
+    /// Without `content`, the next binary message is the document.
+    Load {
+        #[serde(default)]
+        file_name: Option<String>,
+        /// The document, base64 encoded
+        #[serde(default)]
+        content: Option<String>,
+        /// Extraction options, as in the JSON body of `/api/v1/extract`
+        #[serde(default)]
+        #[schema(value_type = ExtractionOptions)]
+        options: HashMap<String, serde_json::Value>,
+    },
+    /// File name, page count and metadata of the loaded document
+    Info,
+    /// The whole text, as `text` (default), `markdown`, `alto` or `hocr`
+    Text {
+        #[serde(default)]
+        format: Option<OutputFormat>,
+    },
+    /// Text of each page, or of the pages listed
+    Pages {
+        #[serde(default)]
+        pages: Option<Vec<u32>>,
+    },
+    /// Tables found on each page, or on the pages listed
+    Tables {
+        #[serde(default)]
+        pages: Option<Vec<u32>>,
+    },
+    /// The same queries as `/api/v1/search`
+    Search { queries: Vec<SearchQuery> },
+    /// End the session
+    Close,
+}
+
+/// A text message sent by `/api/v1/sessions`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionReply {
+    /// The `id` of the command answered, absent for messages the server starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub message: SessionMessage,
+}
+
+impl SessionReply {
+    pub fn new(id: Option<serde_json::Value>, message: SessionMessage) -> Self {
+        Self { id, message }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionMessage {
+    /// Sent once the connection is open, with the limits the session runs under
+    Ready {
+        session_id: String,
+        /// The session is closed after this long without a message
+        idle_timeout_seconds: u64,
+        /// Most memory a loaded document may take
+        max_memory_bytes: usize,
+    },
+    /// The document was extracted and replaces the one loaded before
+    Loaded(SessionDocument),
+    Info(SessionDocument),
+    Text {
+        format: OutputFormat,
+        content_type: String,
+        text: String,
+    },
+    Pages { pages: Vec<SessionPage> },
+    Tables {
+        tables: Vec<Table>,
+        /// Unit of the table bounding boxes
+        unit: LayoutUnit,
+    },
+    Search {
+        /// One entry per query, in request order
+        results: Vec<QueryResult>,
+        total_hits: usize,
+        /// Unit of the hit bounding boxes, absent when there are none
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<LayoutUnit>,
+    },
+    /// The command failed; the session stays open
+    Error { error: ErrorDetail },
+    /// The session is over, e.g. `client` or `idle_timeout`
+    Closed { reason: String },
+}
+
+/// The document loaded in a session
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionDocument {
+    pub file_name: String,
+    pub pages: usize,
+    /// Estimated memory the session holds for the document
+    pub memory_bytes: usize,
+    pub metadata: PdfMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SessionPage {
+    /// 1-based page number
+    pub page: u32,
+    /// Lines separated by `\n`, blocks by a blank line
+    pub text: String,
+    /// Absent when the page has no layout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<TextSource>,
+}
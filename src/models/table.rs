@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::BoundingBox;
+
+/// A table found in a page layout, its columns told apart by whitespace alignment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Table {
+    /// 1-based page number
+    pub page: u32,
+    pub bbox: BoundingBox,
+    pub columns: usize,
+    /// Cell text row by row, every row `columns` long; cells a row leaves out are empty
+    pub rows: Vec<Vec<String>>,
+}
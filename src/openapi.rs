@@ -0,0 +1,196 @@
+//! OpenAPI 3 document generated from the handlers and the request/response models
+//!
+//! Served at `/api/openapi.json`, with Swagger UI at `/api/docs`.
+
+use serde::Deserialize;
+use utoipa::openapi::response::ResponseBuilder;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use crate::handlers;
+use crate::models::{ErrorResponse, ExtractionOptions, SessionRequest};
+
+/// Where the document is served
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+/// Where the docs UI is served
+pub const DOCS_PATH: &str = "/api/docs";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Elicit",
+        description = "Text extraction from PDFs and images, with OCR for scanned pages.\n\n\
+            Every extraction option can also be sent as an `X-Elicit-<option>` header \
+            (e.g. `X-Elicit-Output-Format: hocr`); form fields, query parameters and JSON \
+            fields override headers. Errors always use the `ErrorResponse` body.\n\n\
+            Operations with an `Idempotency-Key` parameter remember successful responses per API key \
+            for `IDEMPOTENCY_TTL_SECONDS`: a retry with the same key and request gets the stored \
+            response back with `Idempotent-Replayed: true`, the same key with a different request \
+            fails with `422 IDEMPOTENCY_KEY_REUSED`, and a retry while the first attempt is still \
+            running fails with `409 IDEMPOTENCY_KEY_IN_USE`."
+    ),
+    paths(
+        handlers::extract_handler,
+        handlers::extract_binary_handler,
+        handlers::searchable_pdf_handler,
+        handlers::stream_extract_handler,
+        handlers::extract_url_handler,
+        handlers::batch_extract_handler,
+        handlers::compare_handler,
+        handlers::search_handler,
+        handlers::session_handler,
+        handlers::create_job_handler,
+        handlers::get_job_handler,
+        handlers::get_job_deliveries_handler,
+        handlers::list_api_keys_handler,
+        handlers::create_api_key_handler,
+        handlers::get_api_key_handler,
+        handlers::delete_api_key_handler,
+        handlers::disable_api_key_handler,
+        handlers::rotate_api_key_handler,
+        handlers::api_key_audit_handler,
+        handlers::health_handler,
+        handlers::ready_handler,
+        handlers::waitlist_handler,
+    ),
+    components(schemas(ErrorResponse, SessionRequest)),
+    modifiers(&ApiKeyAuth, &ErrorResponses),
+    tags(
+        (name = "extraction", description = "Synchronous and streamed extraction"),
+        (name = "jobs", description = "Asynchronous extraction jobs and their webhooks"),
+        (name = "admin", description = "API key management and its audit trail"),
+        (name = "service", description = "Health checks and the landing page waitlist"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Bearer API keys, as checked by `auth_middleware`
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "A key from the API key registry. Each operation lists the scope the key needs.",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Every `AppError` renders as `ErrorResponse`, so each operation gets it as its default response
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let error = ResponseBuilder::new()
+            .description("Error; `error.code` says which, e.g. `FILE_TOO_LARGE` or `RATE_LIMIT_EXCEEDED`")
+            .content(
+                "application/json",
+                ContentBuilder::new().schema(Some(Ref::from_schema_name("ErrorResponse"))).build(),
+            )
+            .build();
+
+        for item in openapi.paths.paths.values_mut() {
+            for operation in [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| error.clone().into());
+            }
+        }
+    }
+}
+
+/// Multipart form of the single file upload endpoints
+#[derive(Deserialize, ToSchema)]
+pub struct UploadForm {
+    /// The PDF or image
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    #[serde(flatten)]
+    pub options: ExtractionOptions,
+}
+
+/// Multipart form of `POST /api/v1/extract/batch`
+#[derive(Deserialize, ToSchema)]
+pub struct BatchUploadForm {
+    /// Repeat the field once per document; ZIP archives are expanded
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>,
+    #[serde(flatten)]
+    pub options: ExtractionOptions,
+}
+
+/// Multipart form of `POST /api/v1/compare`
+#[derive(Deserialize, ToSchema)]
+pub struct CompareUploadForm {
+    /// The earlier version, a PDF or image
+    #[schema(value_type = String, format = Binary)]
+    pub original: Vec<u8>,
+    /// The later version, a PDF or image
+    #[schema(value_type = String, format = Binary)]
+    pub revised: Vec<u8>,
+    #[serde(flatten)]
+    pub options: ExtractionOptions,
+}
+
+/// Multipart form of `POST /api/v1/search`
+#[derive(Deserialize, ToSchema)]
+pub struct SearchUploadForm {
+    /// The PDF or image
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    /// JSON array of queries, e.g. `["indemnify", {"pattern": "\\d+ days", "regex": true}]`;
+    /// a plain string is a case-insensitive literal
+    #[schema(example = json!(r#"["indemnify", {"pattern": "\\d+ days", "regex": true}]"#))]
+    pub queries: String,
+    #[serde(flatten)]
+    pub options: ExtractionOptions,
+}
+
+/// Multipart form of `POST /api/v1/jobs`
+#[derive(Deserialize, ToSchema)]
+pub struct JobUploadForm {
+    /// The PDF or image
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    /// Receives the finished job, signed with the API key's webhook secret
+    pub callback_url: Option<String>,
+    #[serde(flatten)]
+    pub options: ExtractionOptions,
+}
+
+/// Extraction options sent as query parameters, see [`ExtractionOptions`]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OptionParams {
+    /// Comma-separated preprocessing steps, `all` or `none`
+    pub preprocess: Option<String>,
+    pub ocr_engine: Option<String>,
+    /// `json`, `text`, `markdown`, `alto` or `hocr`
+    pub output_format: Option<String>,
+    /// Tesseract-style language list, e.g. `deu+eng`
+    pub language: Option<String>,
+    /// Page segmentation mode (1 - 13)
+    pub psm: Option<u8>,
+    pub ocr_min_chars: Option<usize>,
+    pub ocr_min_letters: Option<usize>,
+}
@@ -0,0 +1,229 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    handler::Handler,
+    http::Method,
+    middleware::from_fn_with_state,
+    response::Html,
+    routing::{self, MethodRouter},
+    Router,
+};
+use std::fs;
+use tower::ServiceBuilder;
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::config::Config;
+use crate::handlers::{
+    api_key_audit_handler, create_api_key_handler, delete_api_key_handler, disable_api_key_handler, get_api_key_handler,
+    list_api_keys_handler, rotate_api_key_handler, batch_extract_handler, compare_handler, create_job_handler, extract_binary_handler, extract_handler, extract_url_handler,
+    get_job_deliveries_handler, get_job_handler, health_handler, ready_handler, search_handler,
+    searchable_pdf_handler, session_handler, stream_extract_handler, waitlist_handler,
+};
+use crate::middleware::{
+    auth::{auth_middleware, require_scope},
+    idempotency::idempotency_middleware,
+};
+use crate::models::Scope;
+use crate::openapi::{ApiDoc, DOCS_PATH, OPENAPI_PATH};
+
+/// One API route. The router is built from these, and the OpenAPI document must describe each of them.
+pub struct Endpoint {
+    pub method: Method,
+    /// Axum path syntax, e.g. `/api/v1/jobs/:id`
+    pub path: &'static str,
+    /// Reachable without an API key
+    pub public: bool,
+    /// Scope the API key must have been granted
+    pub scope: Option<Scope>,
+    /// Retries with the same `Idempotency-Key` are answered from the idempotency store
+    pub idempotent: bool,
+    /// Request body limit, `None` for the global `MAX_FILE_SIZE_MB`
+    pub body_limit: Option<usize>,
+    route: MethodRouter,
+}
+
+impl Endpoint {
+    pub fn get<H, T>(path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self {
+            method: Method::GET,
+            path,
+            public: false,
+            scope: None,
+            idempotent: false,
+            body_limit: None,
+            route: routing::get(handler),
+        }
+    }
+
+    pub fn post<H, T>(path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self {
+            method: Method::POST,
+            path,
+            public: false,
+            scope: None,
+            idempotent: false,
+            body_limit: None,
+            route: routing::post(handler),
+        }
+    }
+
+    pub fn delete<H, T>(path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self {
+            method: Method::DELETE,
+            path,
+            public: false,
+            scope: None,
+            idempotent: false,
+            body_limit: None,
+            route: routing::delete(handler),
+        }
+    }
+
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+
+    pub fn requires(mut self, scope: Scope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Replace the global request body limit for this route
+    pub fn with_body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = Some(bytes);
+        self
+    }
+
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// The handler with this endpoint's body limit, idempotency and scope check applied
+    fn into_route(self, default_body_limit: usize) -> MethodRouter {
+        let body_limit = self.body_limit.unwrap_or(default_body_limit);
+        let mut route = self.route;
+        if self.idempotent {
+            route = route.layer(from_fn_with_state(body_limit, idempotency_middleware));
+        }
+        if self.body_limit.is_some() {
+            route = route.layer(DefaultBodyLimit::max(body_limit));
+        }
+        // Outermost, so a key without the scope is turned away before anything else runs
+        if let Some(scope) = self.scope {
+            route = route.layer(from_fn_with_state(scope, require_scope));
+        }
+        route
+    }
+
+    /// The path in OpenAPI syntax, e.g. `/api/v1/jobs/{id}`
+    pub fn openapi_path(&self) -> String {
+        self.path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Every documented API route
+pub fn endpoints(config: &Config) -> Vec<Endpoint> {
+    let max_file_bytes = config.max_file_size_mb * 1024 * 1024;
+    vec![
+        Endpoint::get("/health", health_handler).public(),
+        Endpoint::get("/ready", ready_handler).public(),
+        Endpoint::post("/api/waitlist", waitlist_handler).public(),
+        // Room for a maximum size file base64 encoded in a JSON body
+        Endpoint::post("/api/v1/extract", extract_handler)
+            .requires(Scope::Extract)
+            .with_body_limit(max_file_bytes / 3 * 4 + 64 * 1024)
+            .idempotent(),
+        Endpoint::post("/api/v1/extract/binary", extract_binary_handler)
+            .requires(Scope::Extract)
+            .idempotent(),
+        Endpoint::post("/api/v1/extract/searchable", searchable_pdf_handler).requires(Scope::Ocr),
+        Endpoint::post("/api/v1/extract/stream", stream_extract_handler).requires(Scope::Extract),
+        Endpoint::post("/api/v1/extract/url", extract_url_handler)
+            .requires(Scope::Extract)
+            .idempotent(),
+        Endpoint::post("/api/v1/extract/batch", batch_extract_handler)
+            .requires(Scope::Extract)
+            .with_body_limit(max_file_bytes * config.max_batch_files),
+        // Two documents in one form
+        Endpoint::post("/api/v1/compare", compare_handler)
+            .requires(Scope::Extract)
+            .with_body_limit(max_file_bytes * 2 + 64 * 1024)
+            .idempotent(),
+        Endpoint::post("/api/v1/search", search_handler)
+            .requires(Scope::Extract)
+            .idempotent(),
+        Endpoint::get("/api/v1/sessions", session_handler).requires(Scope::Extract),
+        Endpoint::post("/api/v1/jobs", create_job_handler)
+            .requires(Scope::Jobs)
+            .idempotent(),
+        Endpoint::get("/api/v1/jobs/:id", get_job_handler).requires(Scope::Jobs),
+        Endpoint::get("/api/v1/jobs/:id/deliveries", get_job_deliveries_handler).requires(Scope::Jobs),
+        Endpoint::get("/api/v1/admin/keys", list_api_keys_handler).requires(Scope::Admin),
+        Endpoint::post("/api/v1/admin/keys", create_api_key_handler).requires(Scope::Admin),
+        Endpoint::get("/api/v1/admin/keys/:id", get_api_key_handler).requires(Scope::Admin),
+        Endpoint::delete("/api/v1/admin/keys/:id", delete_api_key_handler).requires(Scope::Admin),
+        Endpoint::post("/api/v1/admin/keys/:id/disable", disable_api_key_handler).requires(Scope::Admin),
+        Endpoint::post("/api/v1/admin/keys/:id/rotate", rotate_api_key_handler).requires(Scope::Admin),
+        Endpoint::get("/api/v1/admin/audit", api_key_audit_handler).requires(Scope::Admin),
+    ]
+}
+
+/// The whole service: API routes, landing page, OpenAPI document and docs UI
+pub fn app(config: &Config) -> Router {
+    let mut public_routes = Router::new()
+        .route("/", routing::get(serve_landing_page))
+        .merge(SwaggerUi::new(DOCS_PATH).url(OPENAPI_PATH, ApiDoc::openapi()));
+    let mut protected_routes = Router::new();
+
+    let default_body_limit = config.max_file_size_mb * 1024 * 1024;
+    for endpoint in endpoints(config) {
+        let (path, public) = (endpoint.path, endpoint.public);
+        let route = endpoint.into_route(default_body_limit);
+        if public {
+            public_routes = public_routes.route(path, route);
+        } else {
+            protected_routes = protected_routes.route(path, route);
+        }
+    }
+
+    Router::new()
+        .merge(public_routes)
+        .merge(protected_routes.layer(axum::middleware::from_fn(auth_middleware)))
+        .layer(
+            ServiceBuilder::new()
+                .layer(TraceLayer::new_for_http())
+                .layer(CorsLayer::permissive())
+                .layer(DefaultBodyLimit::max(default_body_limit)),
+        )
+}
+
+/// Serve the landing page HTML
+async fn serve_landing_page() -> Html<String> {
+    // Try local path first (for development), then container path (for deployment)
+    let html_content = fs::read_to_string("elicit-landing.html")
+        .or_else(|_| fs::read_to_string("/app/elicit-landing.html"))
+        .unwrap_or_else(|_| "<h1>Landing page not found</h1>".to_string());
+    Html(html_content)
+}
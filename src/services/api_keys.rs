@@ -0,0 +1,696 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng, RngCore};
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tempfile::NamedTempFile;
+use tracing::{error, info, warn};
+
+use crate::config::{Config, VALID_API_KEYS};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    ApiKey, ApiKeyIdentity, ApiKeyView, AuditAction, AuditEvent, CreateApiKeyRequest, IssuedApiKey, KeyUsage, Scope,
+};
+
+/// Scopes of the keys in `VALID_API_KEYS`: everything but key management
+const ENV_KEY_SCOPES: [Scope; 3] = [Scope::Extract, Scope::Ocr, Scope::Jobs];
+
+/// Start of every generated key, so leaked keys are easy to spot
+pub const KEY_TAG: &str = "elk_";
+
+/// Characters of a key kept in the clear to find its record: the tag and 8 random characters
+pub const KEY_PREFIX_LEN: usize = 12;
+
+/// Random characters after the prefix of a generated key
+const KEY_SECRET_LEN: usize = 32;
+
+const SALT_BYTES: usize = 16;
+
+const HASH_SCHEME: &str = "sha256";
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    disabled INTEGER NOT NULL DEFAULT 0
+)";
+
+const CREATE_AUDIT_TABLE: &str = "CREATE TABLE IF NOT EXISTS api_key_audit (
+    event_id TEXT PRIMARY KEY,
+    at TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    key_id TEXT NOT NULL
+)";
+
+static API_KEYS: Lazy<Arc<ApiKeyRegistry>> = Lazy::new(|| {
+    let registry = Config::from_env()
+        .map_err(|e| AppError::config(format!("Failed to load config: {}", e)))
+        .and_then(|config| ApiKeyRegistry::from_config(&config));
+    let registry = match registry {
+        Ok(registry) => registry,
+        Err(e) => {
+            // Without its keys the service can't tell clients apart, so nobody gets in
+            error!("Failed to load API keys, rejecting every key: {}", e);
+            ApiKeyRegistry::empty()
+        }
+    };
+
+    if registry.is_empty() {
+        warn!("No valid API keys configured. Set API_KEYS_FILE, API_KEYS_DB or VALID_API_KEYS.");
+    } else {
+        info!(keys = registry.len(), source = ?registry.source, "API key registry loaded");
+    }
+    Arc::new(registry)
+});
+
+/// The process-wide registry configured through `API_KEYS_FILE`, `API_KEYS_DB` or `VALID_API_KEYS`
+pub fn api_keys() -> Arc<ApiKeyRegistry> {
+    API_KEYS.clone()
+}
+
+/// Where the registry's keys were loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// The comma-separated `VALID_API_KEYS`
+    Env,
+    /// A JSON array of key records
+    File(PathBuf),
+    /// The `api_keys` table of a SQLite database
+    Sqlite(PathBuf),
+}
+
+/// Records by id, and the ids sharing each key prefix
+#[derive(Default, Clone)]
+struct KeyIndex {
+    by_id: HashMap<String, ApiKey>,
+    by_prefix: HashMap<String, Vec<String>>,
+}
+
+impl KeyIndex {
+    fn insert(&mut self, key: ApiKey) {
+        self.by_prefix.entry(key.prefix.clone()).or_default().push(key.id.clone());
+        self.by_id.insert(key.id.clone(), key);
+    }
+
+    fn remove(&mut self, id: &str) -> Option<ApiKey> {
+        let key = self.by_id.remove(id)?;
+        if let Some(ids) = self.by_prefix.get_mut(&key.prefix) {
+            ids.retain(|other| other != id);
+            if ids.is_empty() {
+                self.by_prefix.remove(&key.prefix);
+            }
+        }
+        Some(key)
+    }
+
+    /// Put `key` in place of the record `id`, or drop the record when `key` is `None`
+    fn replace(&mut self, id: &str, key: Option<ApiKey>) -> Option<ApiKey> {
+        let previous = self.remove(id);
+        if let Some(key) = key {
+            self.insert(key);
+        }
+        previous
+    }
+
+    /// Records in a stable order, oldest first
+    fn sorted(&self) -> Vec<&ApiKey> {
+        let mut keys: Vec<&ApiKey> = self.by_id.values().collect();
+        keys.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        keys
+    }
+
+    /// Whether `id` is the only enabled, unexpired key that can still manage keys
+    fn is_last_admin(&self, id: &str) -> bool {
+        let now = Utc::now();
+        let is_admin = |key: &ApiKey| key.scopes.contains(&Scope::Admin) && !key.disabled && !key.is_expired(now);
+        self.by_id.get(id).is_some_and(is_admin)
+            && !self.by_id.values().any(|key| key.id != id && is_admin(key))
+    }
+}
+
+/// API keys with their owner, scopes and validity.
+///
+/// Only salted hashes of the keys are kept. A bearer token is matched by its
+/// prefix first, then verified against the hashes of the records sharing it.
+/// Changes made through the admin API are written back to the file or database
+/// the keys came from, each with an entry in the audit trail. Those writes
+/// block, so changes should be made from the blocking pool; lookups never wait
+/// for them.
+pub struct ApiKeyRegistry {
+    keys: RwLock<KeyIndex>,
+    /// Held for the whole of a change, so changes apply one after another
+    changes: Mutex<()>,
+    usage: Mutex<HashMap<String, KeyUsage>>,
+    audit: Mutex<Vec<AuditEvent>>,
+    source: KeySource,
+}
+
+impl ApiKeyRegistry {
+    /// Fails when two records share an id or a hash can't be read
+    pub fn new(keys: Vec<ApiKey>, source: KeySource) -> AppResult<Self> {
+        let mut index = KeyIndex::default();
+        for key in keys {
+            if key.id.trim().is_empty() || key.prefix.is_empty() {
+                return Err(AppError::config("API key records need an id and a prefix"));
+            }
+            if index.by_id.contains_key(&key.id) {
+                return Err(AppError::config(format!("Duplicate API key id '{}'", key.id)));
+            }
+            if parse_key_hash(&key.key_hash).is_none() {
+                return Err(AppError::config(format!(
+                    "API key '{}' has an invalid key_hash, expected {}$<salt>$<hash>",
+                    key.id, HASH_SCHEME
+                )));
+            }
+            index.insert(key);
+        }
+        Ok(Self {
+            keys: RwLock::new(index),
+            changes: Mutex::new(()),
+            usage: Mutex::new(HashMap::new()),
+            audit: Mutex::new(Vec::new()),
+            source,
+        })
+    }
+
+    /// A registry that rejects every key
+    pub fn empty() -> Self {
+        Self {
+            keys: RwLock::new(KeyIndex::default()),
+            changes: Mutex::new(()),
+            usage: Mutex::new(HashMap::new()),
+            audit: Mutex::new(Vec::new()),
+            source: KeySource::Env,
+        }
+    }
+
+    fn with_audit(self, events: Vec<AuditEvent>) -> Self {
+        *self.audit.lock().unwrap_or_else(|e| e.into_inner()) = events;
+        self
+    }
+
+    /// The file or database named in the config, or else the keys in `VALID_API_KEYS`
+    pub fn from_config(config: &Config) -> AppResult<Self> {
+        if let Some(path) = &config.api_keys_file {
+            return Self::load_file(path);
+        }
+        if let Some(path) = &config.api_keys_db {
+            return Self::load_sqlite(path);
+        }
+        Self::from_env_keys(VALID_API_KEYS.iter())
+    }
+
+    /// Keys from `VALID_API_KEYS`, which carry no owner or expiry. They are hashed as they are loaded.
+    pub fn from_env_keys<'a>(keys: impl IntoIterator<Item = &'a String>) -> AppResult<Self> {
+        let now = Utc::now();
+        let keys = keys
+            .into_iter()
+            .map(|key| ApiKey {
+                // Derived from the key so it stays the same across restarts
+                id: format!("env-{}", &hex::encode(Sha256::digest(key.as_bytes()))[..8]),
+                name: "VALID_API_KEYS".to_string(),
+                prefix: key_prefix(key).to_string(),
+                key_hash: hash_key(key),
+                scopes: ENV_KEY_SCOPES.to_vec(),
+                created_at: now,
+                expires_at: None,
+                disabled: false,
+            })
+            .collect();
+        Self::new(keys, KeySource::Env)
+    }
+
+    /// Records from a JSON file holding an array of keys, with the audit trail
+    /// kept next to it in `<file>.audit.jsonl`
+    pub fn load_file(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| AppError::config(format!("Failed to read API_KEYS_FILE {}: {}", path.display(), e)))?;
+        let keys: Vec<ApiKey> = serde_json::from_str(&content)
+            .map_err(|e| AppError::config(format!("Invalid API_KEYS_FILE {}: {}", path.display(), e)))?;
+
+        let audit_path = audit_file(path);
+        let events = match std::fs::read_to_string(&audit_path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<AuditEvent>, _>>()
+                .map_err(|e| AppError::config(format!("Invalid audit trail {}: {}", audit_path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(AppError::config(format!(
+                    "Failed to read audit trail {}: {}",
+                    audit_path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self::new(keys, KeySource::File(path.to_path_buf()))?.with_audit(events))
+    }
+
+    /// Records from the `api_keys` table and the audit trail from `api_key_audit`,
+    /// both created empty when the database doesn't have them
+    pub fn load_sqlite(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = path.as_ref();
+        let db = Connection::open(path).map_err(database_error)?;
+        db.execute(CREATE_TABLE, []).map_err(database_error)?;
+        db.execute(CREATE_AUDIT_TABLE, []).map_err(database_error)?;
+
+        let mut statement = db
+            .prepare("SELECT id, name, prefix, key_hash, scopes, created_at, expires_at, disabled FROM api_keys")
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, bool>(7)?,
+                ))
+            })
+            .map_err(database_error)?;
+
+        let mut keys = Vec::new();
+        for row in rows {
+            let (id, name, prefix, key_hash, scopes, created_at, expires_at, disabled) =
+                row.map_err(database_error)?;
+            keys.push(ApiKey {
+                scopes: parse_scopes(&scopes)
+                    .map_err(|e| AppError::config(format!("API key '{}': {}", id, e)))?,
+                created_at: parse_timestamp(&id, &created_at)?,
+                expires_at: expires_at.map(|value| parse_timestamp(&id, &value)).transpose()?,
+                id,
+                name,
+                prefix,
+                key_hash,
+                disabled,
+            });
+        }
+
+        let mut statement = db
+            .prepare("SELECT event_id, at, actor, action, key_id FROM api_key_audit ORDER BY at, rowid")
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })
+            .map_err(database_error)?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (event_id, at, actor, action, key_id) = row.map_err(database_error)?;
+            events.push(AuditEvent {
+                at: parse_timestamp(&key_id, &at)?,
+                action: action.parse().map_err(|e| AppError::config(format!("Audit event {}: {}", event_id, e)))?,
+                event_id,
+                actor,
+                key_id,
+            });
+        }
+
+        Ok(Self::new(keys, KeySource::Sqlite(path.to_path_buf()))?.with_audit(events))
+    }
+
+    /// The identity behind a bearer token, if the key exists, is enabled and hasn't expired.
+    /// Counts the request towards the key's usage.
+    pub fn resolve(&self, token: &str) -> AppResult<ApiKeyIdentity> {
+        let index = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let key = index
+            .by_prefix
+            .get(key_prefix(token))
+            .into_iter()
+            .flatten()
+            .filter_map(|id| index.by_id.get(id))
+            .find(|key| verify_key(token, &key.key_hash))
+            .ok_or(AppError::InvalidApiKey)?;
+        if key.disabled {
+            return Err(AppError::ApiKeyDisabled);
+        }
+        let now = Utc::now();
+        if key.is_expired(now) {
+            return Err(AppError::ApiKeyExpired);
+        }
+
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = usage.entry(key.id.clone()).or_default();
+        usage.requests += 1;
+        usage.last_used_at = Some(now);
+        Ok(key.identity())
+    }
+
+    pub fn source(&self) -> &KeySource {
+        &self.source
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap_or_else(|e| e.into_inner()).by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every key with its usage, oldest first
+    pub fn list(&self) -> Vec<ApiKeyView> {
+        let index = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        index.sorted().into_iter().map(|key| self.view(key)).collect()
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<ApiKeyView> {
+        let index = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let key = index.by_id.get(id).ok_or_else(|| key_not_found(id))?;
+        Ok(self.view(key))
+    }
+
+    /// Issue a new key. The key is returned once and only its hash is kept.
+    pub fn create(&self, actor: &ApiKeyIdentity, request: CreateApiKeyRequest) -> AppResult<IssuedApiKey> {
+        let id = match request.id {
+            Some(id) => validate_key_id(id)?,
+            None => generate_key_id(),
+        };
+        if request.name.trim().is_empty() {
+            return Err(AppError::validation("name must not be empty"));
+        }
+        if request.scopes.is_empty() {
+            return Err(AppError::validation("scopes must not be empty"));
+        }
+        let now = Utc::now();
+        if request.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(AppError::validation("expires_at must be in the future"));
+        }
+
+        let key = generate_key();
+        let mut scopes = request.scopes;
+        scopes.sort();
+        scopes.dedup();
+        let record = ApiKey {
+            id: id.clone(),
+            name: request.name.trim().to_string(),
+            prefix: key_prefix(&key).to_string(),
+            key_hash: hash_key(&key),
+            scopes,
+            created_at: now,
+            expires_at: request.expires_at,
+            disabled: false,
+        };
+
+        let _change = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        if self.keys.read().unwrap_or_else(|e| e.into_inner()).by_id.contains_key(&id) {
+            return Err(AppError::conflict(format!("An API key with id '{}' already exists", id)));
+        }
+        let view = self.view(&record);
+        self.commit(actor, AuditAction::Created, &id, Some(record))?;
+        Ok(IssuedApiKey { key, api_key: view })
+    }
+
+    /// Stop accepting the key; its record stays for the audit trail and usage
+    pub fn disable(&self, actor: &ApiKeyIdentity, id: &str) -> AppResult<ApiKeyView> {
+        let _change = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut record = {
+            let index = self.keys.read().unwrap_or_else(|e| e.into_inner());
+            let record = index.by_id.get(id).cloned().ok_or_else(|| key_not_found(id))?;
+            if index.is_last_admin(id) {
+                return Err(AppError::conflict("The last admin key can't be disabled"));
+            }
+            record
+        };
+        record.disabled = true;
+        let view = self.view(&record);
+        self.commit(actor, AuditAction::Disabled, id, Some(record))?;
+        Ok(view)
+    }
+
+    /// Replace the key of a record. The old key stops working at once; id, scopes and expiry stay.
+    pub fn rotate(&self, actor: &ApiKeyIdentity, id: &str) -> AppResult<IssuedApiKey> {
+        let _change = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut record = self.keys.read().unwrap_or_else(|e| e.into_inner()).by_id.get(id).cloned()
+            .ok_or_else(|| key_not_found(id))?;
+        let key = generate_key();
+        record.prefix = key_prefix(&key).to_string();
+        record.key_hash = hash_key(&key);
+        let view = self.view(&record);
+        self.commit(actor, AuditAction::Rotated, id, Some(record))?;
+        Ok(IssuedApiKey { key, api_key: view })
+    }
+
+    pub fn delete(&self, actor: &ApiKeyIdentity, id: &str) -> AppResult<()> {
+        let _change = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        {
+            let index = self.keys.read().unwrap_or_else(|e| e.into_inner());
+            if !index.by_id.contains_key(id) {
+                return Err(key_not_found(id));
+            }
+            if index.is_last_admin(id) {
+                return Err(AppError::conflict("The last admin key can't be deleted"));
+            }
+        }
+        self.commit(actor, AuditAction::Deleted, id, None)?;
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+        Ok(())
+    }
+
+    /// Changes made to the registry, newest first, optionally only those to `key_id`
+    pub fn audit(&self, key_id: Option<&str>, limit: usize) -> Vec<AuditEvent> {
+        let audit = self.audit.lock().unwrap_or_else(|e| e.into_inner());
+        audit
+            .iter()
+            .rev()
+            .filter(|event| key_id.is_none_or(|key_id| event.key_id == key_id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn view(&self, key: &ApiKey) -> ApiKeyView {
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        ApiKeyView::new(key, usage.get(&key.id).copied().unwrap_or_default())
+    }
+
+    /// Write a change to record `id` and its audit event to the key store, then
+    /// swap the changed index in. The caller holds `changes`; lookups keep using
+    /// the current index while the write is in progress.
+    fn commit(
+        &self,
+        actor: &ApiKeyIdentity,
+        action: AuditAction,
+        id: &str,
+        record: Option<ApiKey>,
+    ) -> AppResult<()> {
+        let event = AuditEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            at: Utc::now(),
+            actor: actor.id.clone(),
+            action,
+            key_id: id.to_string(),
+        };
+
+        let mut index = self.keys.read().unwrap_or_else(|e| e.into_inner()).clone();
+        index.replace(id, record.clone());
+        if let Err(e) = self.persist(&index, id, record.as_ref(), &event) {
+            error!(key_id = %id, action = action.as_str(), error = %e, "Failed to save API key change");
+            return Err(e);
+        }
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = index;
+
+        info!(
+            actor = %event.actor,
+            key_id = %event.key_id,
+            action = event.action.as_str(),
+            event_id = %event.event_id,
+            "API key registry changed"
+        );
+        self.audit.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+        Ok(())
+    }
+
+    /// Write a change to where the keys came from
+    fn persist(&self, index: &KeyIndex, id: &str, record: Option<&ApiKey>, event: &AuditEvent) -> AppResult<()> {
+        match &self.source {
+            KeySource::Env => {
+                warn!(key_id = %id, "Keys from VALID_API_KEYS are not stored, the change is lost on restart");
+                Ok(())
+            }
+            KeySource::File(path) => {
+                write_key_file(path, &index.sorted())?;
+                append_audit(&audit_file(path), event)
+            }
+            KeySource::Sqlite(path) => {
+                let mut db = Connection::open(path).map_err(database_error)?;
+                let tx = db.transaction().map_err(database_error)?;
+                match record {
+                    Some(key) => tx.execute(
+                        "INSERT OR REPLACE INTO api_keys (id, name, prefix, key_hash, scopes, created_at, expires_at, disabled) \
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![
+                            key.id,
+                            key.name,
+                            key.prefix,
+                            key.key_hash,
+                            key.scopes.iter().map(Scope::as_str).collect::<Vec<_>>().join(","),
+                            key.created_at.to_rfc3339(),
+                            key.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+                            key.disabled,
+                        ],
+                    ),
+                    None => tx.execute("DELETE FROM api_keys WHERE id = ?1", params![id]),
+                }
+                .map_err(database_error)?;
+                tx.execute(
+                    "INSERT INTO api_key_audit (event_id, at, actor, action, key_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![event.event_id, event.at.to_rfc3339(), event.actor, event.action.as_str(), event.key_id],
+                )
+                .map_err(database_error)?;
+                tx.commit().map_err(database_error)
+            }
+        }
+    }
+}
+
+/// Where the audit trail of `API_KEYS_FILE` is kept
+pub fn audit_file(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".audit.jsonl");
+    PathBuf::from(name)
+}
+
+/// Replace the key file in one step, so a crash never leaves it half written
+fn write_key_file(path: &Path, keys: &[&ApiKey]) -> AppResult<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut file = NamedTempFile::new_in(dir).map_err(|e| store_error(path, e))?;
+    serde_json::to_writer_pretty(&mut file, keys).map_err(|e| store_error(path, e))?;
+    file.write_all(b"\n").map_err(|e| store_error(path, e))?;
+    file.as_file().sync_all().map_err(|e| store_error(path, e))?;
+    file.persist(path).map_err(|e| store_error(path, e))?;
+    Ok(())
+}
+
+fn append_audit(path: &Path, event: &AuditEvent) -> AppResult<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| store_error(path, e))?;
+    let line = serde_json::to_string(event).map_err(|e| store_error(path, e))?;
+    writeln!(file, "{}", line).map_err(|e| store_error(path, e))
+}
+
+/// Ids appear in URLs, so they are kept to letters, digits, `.`, `_` and `-`
+pub fn validate_key_id(id: String) -> AppResult<String> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(id)
+    } else {
+        Err(AppError::validation(
+            "id must be 1 to 64 letters, digits, '.', '_' or '-'",
+        ))
+    }
+}
+
+fn key_not_found(id: &str) -> AppError {
+    AppError::not_found(format!("API key {}", id))
+}
+
+fn store_error(path: &Path, e: impl std::fmt::Display) -> AppError {
+    AppError::DatabaseError {
+        message: format!("Failed to write {}: {}", path.display(), e),
+    }
+}
+
+/// A new random key, e.g. `elk_3fk9x0qa_...`
+pub fn generate_key() -> String {
+    let mut random = OsRng.sample_iter(&Alphanumeric).map(char::from);
+    let prefix: String = random.by_ref().take(KEY_PREFIX_LEN - KEY_TAG.len()).collect();
+    let secret: String = random.take(KEY_SECRET_LEN).collect();
+    format!("{}{}_{}", KEY_TAG, prefix, secret)
+}
+
+/// Id for a key created without one, e.g. `key-3f9c2a1b`
+pub fn generate_key_id() -> String {
+    format!("key-{}", &uuid::Uuid::new_v4().simple().to_string()[..8])
+}
+
+/// The part of a key stored in the clear; short keys are their own prefix
+pub fn key_prefix(key: &str) -> &str {
+    key.get(..KEY_PREFIX_LEN).unwrap_or(key)
+}
+
+/// Salted hash of a key to store in its record, as `sha256$<salt>$<hash>` in hex
+pub fn hash_key(key: &str) -> String {
+    let mut salt = [0u8; SALT_BYTES];
+    OsRng.fill_bytes(&mut salt);
+    let hash = key_mac(&salt, key).finalize().into_bytes();
+    format!("{}${}${}", HASH_SCHEME, hex::encode(salt), hex::encode(hash))
+}
+
+/// Whether `key` matches a hash made by [`hash_key`], compared in constant time
+pub fn verify_key(key: &str, key_hash: &str) -> bool {
+    match parse_key_hash(key_hash) {
+        Some((salt, hash)) => key_mac(&salt, key).verify_slice(&hash).is_ok(),
+        None => false,
+    }
+}
+
+/// HMAC-SHA256 of the key, keyed by the salt
+fn key_mac(salt: &[u8], key: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt).expect("HMAC accepts keys of any length");
+    mac.update(key.as_bytes());
+    mac
+}
+
+/// Salt and hash of a stored `sha256$<salt>$<hash>`
+fn parse_key_hash(key_hash: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut parts = key_hash.split('$');
+    let (scheme, salt, hash) = (parts.next()?, parts.next()?, parts.next()?);
+    if scheme != HASH_SCHEME || parts.next().is_some() {
+        return None;
+    }
+    let (salt, hash) = (hex::decode(salt).ok()?, hex::decode(hash).ok()?);
+    (!salt.is_empty() && hash.len() == 32).then_some((salt, hash))
+}
+
+/// Comma-separated scope names, e.g. `extract,ocr`
+pub fn parse_scopes(value: &str) -> AppResult<Vec<Scope>> {
+    let mut scopes = value
+        .split(',')
+        .filter(|scope| !scope.trim().is_empty())
+        .map(str::parse)
+        .collect::<AppResult<Vec<Scope>>>()?;
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+fn parse_timestamp(id: &str, value: &str) -> AppResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| AppError::config(format!("API key '{}' has an invalid timestamp '{}': {}", id, value, e)))
+}
+
+fn database_error(e: rusqlite::Error) -> AppError {
+    AppError::DatabaseError {
+        message: format!("API key database: {}", e),
+    }
+}
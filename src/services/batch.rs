@@ -0,0 +1,276 @@
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    BatchFileResult, BatchResponse, ErrorDetail, ExtractData, ExtractionOptions, ProcessedFile,
+};
+use crate::services::ocr_cache::OcrCache;
+use crate::services::ocr_engine::OcrEngine;
+use crate::services::pdf_processor::PdfProcessor;
+
+/// One file of a batch, or why it was turned away before extraction
+#[derive(Debug)]
+pub enum BatchEntry {
+    File(ProcessedFile),
+    Rejected { file_name: String, error: AppError },
+}
+
+impl BatchEntry {
+    pub fn file_name(&self) -> &str {
+        match self {
+            BatchEntry::File(file) => &file.name,
+            BatchEntry::Rejected { file_name, .. } => file_name,
+        }
+    }
+}
+
+/// Expand a ZIP upload into one entry per file it contains.
+///
+/// Directories, dotfiles and macOS resource forks are skipped. Entries are named
+/// `<archive>/<path>` and read no further than `max_entry_bytes`, so a
+/// compression bomb ends up as a rejected entry rather than in memory. All
+/// entries together decompress to at most `max_total_bytes`; once that is
+/// spent, the remaining entries are rejected without being read.
+///
+/// Decompression is CPU-bound, so call this from the blocking pool.
+pub fn expand_zip(
+    archive: &ProcessedFile,
+    max_entries: usize,
+    max_entry_bytes: usize,
+    max_total_bytes: usize,
+) -> AppResult<Vec<BatchEntry>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive.content.as_slice())).map_err(|e| AppError::InvalidFile {
+        message: format!("{} is not a readable ZIP archive: {}", archive.name, e),
+    })?;
+
+    let mut entries = Vec::new();
+    let mut total_bytes = 0;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| AppError::InvalidFile {
+            message: format!("Failed to read entry {} of {}: {}", index, archive.name, e),
+        })?;
+        let path = entry.name().to_string();
+        let base_name = path.rsplit('/').next().unwrap_or_default();
+        if entry.is_dir() || path.starts_with("__MACOSX/") || base_name.is_empty() || base_name.starts_with('.') {
+            continue;
+        }
+
+        if entries.len() == max_entries {
+            return Err(AppError::validation(format!(
+                "{} contains more than the {} files allowed",
+                archive.name, max_entries
+            )));
+        }
+
+        let file_name = format!("{}/{}", archive.name, path);
+        let budget_spent = || BatchEntry::Rejected {
+            file_name: file_name.clone(),
+            error: AppError::validation(format!(
+                "The batch's {} MB of decompressed ZIP entries is used up",
+                max_total_bytes / (1024 * 1024)
+            )),
+        };
+        let remaining = max_total_bytes - total_bytes;
+        if remaining == 0 {
+            entries.push(budget_spent());
+            continue;
+        }
+
+        let mut content = Vec::new();
+        let read = (&mut entry)
+            .take(max_entry_bytes.min(remaining) as u64 + 1)
+            .read_to_end(&mut content);
+
+        entries.push(match read {
+            Ok(_) if content.len() > max_entry_bytes => BatchEntry::Rejected {
+                file_name,
+                error: AppError::FileTooLarge {
+                    size: (entry.size() as usize) / (1024 * 1024),
+                    limit: max_entry_bytes / (1024 * 1024),
+                },
+            },
+            Ok(_) if content.len() > remaining => {
+                total_bytes = max_total_bytes;
+                budget_spent()
+            }
+            Ok(_) => {
+                total_bytes += content.len();
+                BatchEntry::File(ProcessedFile::new(file_name, content))
+            }
+            Err(e) => BatchEntry::Rejected {
+                file_name,
+                error: AppError::InvalidFile {
+                    message: format!("Failed to decompress entry: {}", e),
+                },
+            },
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Runs the extraction pipeline over many files, a bounded number at a time
+pub struct BatchExtractor {
+    options: ExtractionOptions,
+    ocr_engine: Option<Arc<dyn OcrEngine>>,
+    ocr_cache: Option<Arc<OcrCache>>,
+    concurrency: usize,
+}
+
+impl BatchExtractor {
+    pub fn new() -> Self {
+        Self {
+            options: ExtractionOptions::default(),
+            ocr_engine: None,
+            ocr_cache: None,
+            concurrency: 1,
+        }
+    }
+
+    /// Options applied to every file of the batch
+    pub fn with_options(mut self, options: ExtractionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_ocr_engine(mut self, engine: Arc<dyn OcrEngine>) -> Self {
+        self.ocr_engine = Some(engine);
+        self
+    }
+
+    pub fn with_ocr_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.ocr_cache = Some(cache);
+        self
+    }
+
+    /// Files processed at the same time, at least one
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn processor(&self) -> PdfProcessor {
+        let mut processor = PdfProcessor::new();
+        if let Some(engine) = &self.ocr_engine {
+            processor = processor.with_ocr_engine(engine.clone());
+        }
+        if let Some(cache) = &self.ocr_cache {
+            processor = processor.with_ocr_cache(cache.clone());
+        }
+        processor
+    }
+
+    /// Extract every entry; a file that fails only fails its own result
+    pub async fn extract_all(&self, entries: Vec<BatchEntry>) -> BatchResponse {
+        let start = Instant::now();
+        let slots = Arc::new(Semaphore::new(self.concurrency));
+        info!(files = entries.len(), concurrency = self.concurrency, "Starting batch extraction");
+
+        let handles: Vec<_> = entries
+            .into_iter()
+            .map(|entry| {
+                let file_name = entry.file_name().to_string();
+                let processor = self.processor();
+                let options = self.options.clone();
+                let slots = slots.clone();
+                // Each file on its own task so a panic is contained to its result
+                let handle = tokio::spawn(async move {
+                    let _slot = slots.acquire_owned().await.expect("batch semaphore closed");
+                    extract_entry(processor, entry, &options).await
+                });
+                (file_name, handle)
+            })
+            .collect();
+
+        let mut files = Vec::with_capacity(handles.len());
+        for (file_name, handle) in handles {
+            files.push(match handle.await {
+                Ok(result) => result,
+                Err(e) => failed_result(
+                    file_name,
+                    AppError::internal(format!("Extraction crashed: {}", e)),
+                    0,
+                ),
+            });
+        }
+
+        let succeeded = files.iter().filter(|file| file.success).count();
+        let failed = files.len() - succeeded;
+        info!(succeeded, failed, "Batch extraction completed");
+
+        BatchResponse {
+            success: true,
+            files,
+            succeeded,
+            failed,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl Default for BatchExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn extract_entry(processor: PdfProcessor, entry: BatchEntry, options: &ExtractionOptions) -> BatchFileResult {
+    let start = Instant::now();
+    let file = match entry {
+        BatchEntry::File(file) => file,
+        BatchEntry::Rejected { file_name, error } => return failed_result(file_name, error, 0),
+    };
+    let file_name = file.name.clone();
+
+    if file.content.is_empty() {
+        return failed_result(
+            file_name,
+            AppError::InvalidFile {
+                message: "File is empty".to_string(),
+            },
+            0,
+        );
+    }
+    if !file.is_pdf() && !file.is_image() {
+        return failed_result(
+            file_name,
+            AppError::InvalidFile {
+                message: "File is not a valid PDF document or supported image (PNG, JPEG, TIFF, WebP)".to_string(),
+            },
+            0,
+        );
+    }
+
+    match processor.extract_text(file, options).await {
+        Ok(result) => BatchFileResult {
+            file_name,
+            success: true,
+            data: Some(ExtractData {
+                text: result.text,
+                pages: result.pages,
+                metadata: result.metadata,
+            }),
+            error: None,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        },
+        Err(e) => {
+            warn!(file_name = %file_name, error = %e, "Batch file failed");
+            failed_result(file_name, e, start.elapsed().as_millis() as u64)
+        }
+    }
+}
+
+fn failed_result(file_name: String, error: AppError, processing_time_ms: u64) -> BatchFileResult {
+    BatchFileResult {
+        file_name,
+        success: false,
+        data: None,
+        error: Some(ErrorDetail::new(error.error_code(), error.to_string())),
+        processing_time_ms,
+    }
+}
@@ -0,0 +1,488 @@
+use std::fmt::Write;
+
+use crate::models::{
+    ChangeKind, DiffSummary, DocumentLayout, LayoutLine, ParagraphChange, ParagraphLocation, WordChange, WordOp,
+};
+
+/// Above this many `original × revised` items the middle of a diff is reported as a whole replacement
+const MAX_DIFF_CELLS: usize = 4_000_000;
+/// Removed and added paragraphs sharing at least this share of their words are one modified paragraph
+const MODIFIED_MIN_SIMILARITY: f64 = 0.5;
+/// How many added paragraphs ahead a removed paragraph looks for its modified version
+const PAIRING_WINDOW: usize = 8;
+/// Unchanged paragraphs shown around each hunk of the unified diff
+const CONTEXT_PARAGRAPHS: usize = 1;
+
+/// A paragraph of extracted text and the page it starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paragraph {
+    /// 1-based page number
+    pub page: u32,
+    /// Words separated by single spaces
+    pub text: String,
+}
+
+impl Paragraph {
+    pub fn new(page: u32, text: &str) -> Self {
+        Self {
+            page,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words().count()
+    }
+
+    fn words(&self) -> impl Iterator<Item = &str> {
+        self.text.split(' ').filter(|word| !word.is_empty())
+    }
+}
+
+/// Split an extraction into paragraphs: the layout's blocks when it has words,
+/// otherwise runs of non-blank lines, with form feeds starting a new page
+pub fn document_paragraphs(text: &str, layout: Option<&DocumentLayout>) -> Vec<Paragraph> {
+    let paragraphs: Vec<Paragraph> = match layout.filter(|layout| !layout.is_empty()) {
+        Some(layout) => layout
+            .pages
+            .iter()
+            .flat_map(|page| {
+                page.blocks.iter().map(|block| {
+                    let lines: Vec<String> = block.lines.iter().map(LayoutLine::text).collect();
+                    Paragraph::new(page.number, &lines.join(" "))
+                })
+            })
+            .collect(),
+        None => text
+            .split('\x0c')
+            .zip(1u32..)
+            .flat_map(|(page_text, page)| {
+                let mut paragraphs = Vec::new();
+                let mut lines = Vec::new();
+                for line in page_text.lines().map(str::trim).chain([""]) {
+                    if !line.is_empty() {
+                        lines.push(line);
+                    } else if !lines.is_empty() {
+                        paragraphs.push(Paragraph::new(page, &lines.join(" ")));
+                        lines.clear();
+                    }
+                }
+                paragraphs
+            })
+            .collect(),
+    };
+    paragraphs.into_iter().filter(|paragraph| !paragraph.text.is_empty()).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Shortest edit script turning `a` into `b`, from their longest common subsequence.
+///
+/// The common prefix and suffix are matched first; a middle too large to
+/// compare item by item is reported as deleted and inserted as a whole.
+fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (a.len(), b.len());
+
+    let mut edits = vec![Edit::Equal; prefix];
+    let (mut i, mut j) = (0, 0);
+    if n.saturating_mul(m) <= MAX_DIFF_CELLS {
+        // lengths[i * width + j] is the longest common subsequence of a[i..] and b[j..]
+        let width = m + 1;
+        let mut lengths = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * width + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        while i < n && j < m {
+            if a[i] == b[j] {
+                edits.push(Edit::Equal);
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                edits.push(Edit::Delete);
+                i += 1;
+            } else {
+                edits.push(Edit::Insert);
+                j += 1;
+            }
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Delete, n - i));
+    edits.extend(std::iter::repeat_n(Edit::Insert, m - j));
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+    edits
+}
+
+/// Word-level diff of two paragraphs, with runs of the same operation merged, and the number of words they share
+fn word_diff(original: &Paragraph, revised: &Paragraph) -> (Vec<WordChange>, usize) {
+    let original: Vec<&str> = original.words().collect();
+    let revised: Vec<&str> = revised.words().collect();
+
+    let mut changes: Vec<WordChange> = Vec::new();
+    let mut common = 0;
+    let (mut i, mut j) = (0, 0);
+    for edit in diff(&original, &revised) {
+        let (op, word) = match edit {
+            Edit::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+                (WordOp::Equal, original[i - 1])
+            }
+            Edit::Delete => {
+                i += 1;
+                (WordOp::Delete, original[i - 1])
+            }
+            Edit::Insert => {
+                j += 1;
+                (WordOp::Insert, revised[j - 1])
+            }
+        };
+        match changes.last_mut() {
+            Some(run) if run.op == op => {
+                run.text.push(' ');
+                run.text.push_str(word);
+            }
+            _ => changes.push(WordChange {
+                op,
+                text: word.to_string(),
+            }),
+        }
+    }
+    (changes, common)
+}
+
+/// `2 × common / total`, 1.0 when there is nothing to compare
+fn ratio(common: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        2.0 * common as f64 / total as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ParagraphOp {
+    Equal(usize, usize),
+    Removed(usize),
+    Added(usize),
+    Modified {
+        original: usize,
+        revised: usize,
+        words: Vec<WordChange>,
+        common: usize,
+    },
+}
+
+/// Paragraph- and word-level differences between two versions of a document.
+///
+/// Paragraphs are matched on their exact text; a removed paragraph followed
+/// closely by an added one sharing most of its words counts as modified and
+/// gets a word-level diff.
+#[derive(Debug, Clone)]
+pub struct DocumentDiff {
+    original: Vec<Paragraph>,
+    revised: Vec<Paragraph>,
+    ops: Vec<ParagraphOp>,
+}
+
+impl DocumentDiff {
+    pub fn new(original: Vec<Paragraph>, revised: Vec<Paragraph>) -> Self {
+        let original_text: Vec<&str> = original.iter().map(|p| p.text.as_str()).collect();
+        let revised_text: Vec<&str> = revised.iter().map(|p| p.text.as_str()).collect();
+
+        let mut ops = Vec::new();
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        let (mut i, mut j) = (0, 0);
+        for edit in diff(&original_text, &revised_text) {
+            match edit {
+                Edit::Equal => {
+                    pair_changes(&original, &revised, &removed, &added, &mut ops);
+                    removed.clear();
+                    added.clear();
+                    ops.push(ParagraphOp::Equal(i, j));
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => {
+                    removed.push(i);
+                    i += 1;
+                }
+                Edit::Insert => {
+                    added.push(j);
+                    j += 1;
+                }
+            }
+        }
+        pair_changes(&original, &revised, &removed, &added, &mut ops);
+
+        Self { original, revised, ops }
+    }
+
+    pub fn original(&self) -> &[Paragraph] {
+        &self.original
+    }
+
+    pub fn revised(&self) -> &[Paragraph] {
+        &self.revised
+    }
+
+    /// Share of words the versions have in common, from 0.0 to 1.0, rounded to 4 places
+    pub fn similarity(&self) -> f64 {
+        let common: usize = self
+            .ops
+            .iter()
+            .map(|op| match op {
+                ParagraphOp::Equal(i, _) => self.original[*i].word_count(),
+                ParagraphOp::Modified { common, .. } => *common,
+                ParagraphOp::Removed(_) | ParagraphOp::Added(_) => 0,
+            })
+            .sum();
+        let total: usize = self.original.iter().chain(&self.revised).map(Paragraph::word_count).sum();
+        (ratio(common, total) * 10_000.0).round() / 10_000.0
+    }
+
+    pub fn summary(&self) -> DiffSummary {
+        let mut summary = DiffSummary::default();
+        for op in &self.ops {
+            match op {
+                ParagraphOp::Equal(..) => summary.paragraphs_unchanged += 1,
+                ParagraphOp::Removed(i) => {
+                    summary.paragraphs_removed += 1;
+                    summary.words_removed += self.original[*i].word_count();
+                }
+                ParagraphOp::Added(j) => {
+                    summary.paragraphs_added += 1;
+                    summary.words_added += self.revised[*j].word_count();
+                }
+                ParagraphOp::Modified { words, .. } => {
+                    summary.paragraphs_modified += 1;
+                    for run in words {
+                        let count = run.text.split(' ').count();
+                        match run.op {
+                            WordOp::Insert => summary.words_added += count,
+                            WordOp::Delete => summary.words_removed += count,
+                            WordOp::Equal => {}
+                        }
+                    }
+                }
+            }
+        }
+        summary
+    }
+
+    /// Every paragraph that differs, with its location in both versions
+    pub fn changes(&self) -> Vec<ParagraphChange> {
+        let (mut next_original, mut next_revised) = (0, 0);
+        let mut changes = Vec::new();
+        for op in &self.ops {
+            let change = match op {
+                ParagraphOp::Equal(i, j) => {
+                    (next_original, next_revised) = (i + 1, j + 1);
+                    continue;
+                }
+                ParagraphOp::Removed(i) => {
+                    next_original = i + 1;
+                    ParagraphChange {
+                        kind: ChangeKind::Removed,
+                        original: location(&self.original, *i, true),
+                        revised: location(&self.revised, next_revised, false),
+                        words: vec![WordChange {
+                            op: WordOp::Delete,
+                            text: self.original[*i].text.clone(),
+                        }],
+                    }
+                }
+                ParagraphOp::Added(j) => {
+                    next_revised = j + 1;
+                    ParagraphChange {
+                        kind: ChangeKind::Added,
+                        original: location(&self.original, next_original, false),
+                        revised: location(&self.revised, *j, true),
+                        words: vec![WordChange {
+                            op: WordOp::Insert,
+                            text: self.revised[*j].text.clone(),
+                        }],
+                    }
+                }
+                ParagraphOp::Modified {
+                    original,
+                    revised,
+                    words,
+                    ..
+                } => {
+                    (next_original, next_revised) = (original + 1, revised + 1);
+                    ParagraphChange {
+                        kind: ChangeKind::Modified,
+                        original: location(&self.original, *original, true),
+                        revised: location(&self.revised, *revised, true),
+                        words: words.clone(),
+                    }
+                }
+            };
+            changes.push(change);
+        }
+        changes
+    }
+
+    /// The diff in unified format with one line per paragraph.
+    ///
+    /// Hunk ranges count paragraphs, and each hunk header ends with the page
+    /// the hunk starts on in both versions. Only the file header lines are
+    /// written when nothing changed.
+    pub fn unified(&self, original_name: &str, revised_name: &str) -> String {
+        let mut lines = Vec::new();
+        let (mut next_original, mut next_revised) = (0, 0);
+        for op in &self.ops {
+            match op {
+                ParagraphOp::Equal(i, j) => {
+                    lines.push(DiffLine::new(' ', *i, *j, &self.original[*i].text));
+                    (next_original, next_revised) = (i + 1, j + 1);
+                }
+                ParagraphOp::Removed(i) => {
+                    lines.push(DiffLine::new('-', *i, next_revised, &self.original[*i].text));
+                    next_original = i + 1;
+                }
+                ParagraphOp::Added(j) => {
+                    lines.push(DiffLine::new('+', next_original, *j, &self.revised[*j].text));
+                    next_revised = j + 1;
+                }
+                ParagraphOp::Modified { original, revised, .. } => {
+                    lines.push(DiffLine::new('-', *original, *revised, &self.original[*original].text));
+                    lines.push(DiffLine::new('+', original + 1, *revised, &self.revised[*revised].text));
+                    (next_original, next_revised) = (original + 1, revised + 1);
+                }
+            }
+        }
+
+        let mut output = format!("--- {}\n+++ {}\n", original_name, revised_name);
+        let changed: Vec<usize> = (0..lines.len()).filter(|&index| lines[index].tag != ' ').collect();
+        let mut index = 0;
+        while index < changed.len() {
+            // Changes with at most twice the context between them share a hunk
+            let mut last = index;
+            while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * CONTEXT_PARAGRAPHS + 1 {
+                last += 1;
+            }
+            let start = changed[index].saturating_sub(CONTEXT_PARAGRAPHS);
+            let end = (changed[last] + CONTEXT_PARAGRAPHS + 1).min(lines.len());
+            let hunk = &lines[start..end];
+
+            let original_count = hunk.iter().filter(|line| line.tag != '+').count();
+            let revised_count = hunk.iter().filter(|line| line.tag != '-').count();
+            let original_start = hunk[0].original;
+            let revised_start = hunk[0].revised;
+            let _ = writeln!(
+                output,
+                "@@ -{} +{} @@ original page {}, revised page {}",
+                range(original_start, original_count),
+                range(revised_start, revised_count),
+                page_at(&self.original, original_start, original_count > 0),
+                page_at(&self.revised, revised_start, revised_count > 0),
+            );
+            for line in hunk {
+                let _ = writeln!(output, "{}{}", line.tag, line.text);
+            }
+            index = last + 1;
+        }
+        output
+    }
+}
+
+/// Pair the removed and added paragraphs between two unchanged ones into modifications where they are alike
+fn pair_changes(
+    original: &[Paragraph],
+    revised: &[Paragraph],
+    removed: &[usize],
+    added: &[usize],
+    ops: &mut Vec<ParagraphOp>,
+) {
+    let mut next_added = 0;
+    for &i in removed {
+        let window = next_added..added.len().min(next_added + PAIRING_WINDOW);
+        let pair = window.into_iter().find_map(|k| {
+            let (words, common) = word_diff(&original[i], &revised[added[k]]);
+            let total = original[i].word_count() + revised[added[k]].word_count();
+            (ratio(common, total) >= MODIFIED_MIN_SIMILARITY).then_some((k, words, common))
+        });
+        match pair {
+            Some((k, words, common)) => {
+                ops.extend(added[next_added..k].iter().map(|&j| ParagraphOp::Added(j)));
+                ops.push(ParagraphOp::Modified {
+                    original: i,
+                    revised: added[k],
+                    words,
+                    common,
+                });
+                next_added = k + 1;
+            }
+            None => ops.push(ParagraphOp::Removed(i)),
+        }
+    }
+    ops.extend(added[next_added..].iter().map(|&j| ParagraphOp::Added(j)));
+}
+
+/// Paragraph `index` of one version, or the place it would go when the version doesn't have it
+fn location(paragraphs: &[Paragraph], index: usize, present: bool) -> ParagraphLocation {
+    ParagraphLocation {
+        paragraph: index + 1,
+        page: page_at(paragraphs, index, present),
+        text: present.then(|| paragraphs[index].text.clone()),
+    }
+}
+
+/// Page of paragraph `index`, or when it isn't there, of the paragraph before it (the first page at the start)
+fn page_at(paragraphs: &[Paragraph], index: usize, present: bool) -> u32 {
+    let paragraph = if present {
+        paragraphs.get(index)
+    } else {
+        index.checked_sub(1).and_then(|before| paragraphs.get(before))
+    };
+    paragraph.or(paragraphs.first()).map_or(1, |paragraph| paragraph.page)
+}
+
+/// `start,count` of a unified diff hunk; an empty range starts at the line before it
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// One line of the unified diff, with the paragraph it is at in each version
+struct DiffLine<'a> {
+    tag: char,
+    original: usize,
+    revised: usize,
+    text: &'a str,
+}
+
+impl<'a> DiffLine<'a> {
+    fn new(tag: char, original: usize, revised: usize, text: &'a str) -> Self {
+        Self {
+            tag,
+            original,
+            revised,
+            text,
+        }
+    }
+}
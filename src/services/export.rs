@@ -0,0 +1,275 @@
+use std::fmt::Write;
+
+use crate::models::{BoundingBox, DocumentLayout, LayoutUnit, OutputFormat, PageLayout};
+use crate::services::pdf_processor::ExtractionResult;
+
+/// ALTO measurement units per PDF point (`inch1200` / 72)
+const INCH1200_PER_POINT: f32 = 1200.0 / 72.0;
+
+/// Serialize a layout into the requested document format, `None` for JSON
+pub fn export_layout(format: OutputFormat, layout: &DocumentLayout, file_name: &str) -> Option<String> {
+    match format {
+        OutputFormat::Json | OutputFormat::Text | OutputFormat::Markdown => None,
+        OutputFormat::Alto => Some(to_alto(layout, file_name)),
+        OutputFormat::Hocr => Some(to_hocr(layout, file_name)),
+    }
+}
+
+/// The body of a non-JSON output format, or `None` for JSON and for layout
+/// formats when the extraction has no layout
+pub fn render_extraction(output_format: OutputFormat, result: &ExtractionResult, file_name: &str) -> Option<String> {
+    match output_format {
+        OutputFormat::Json => None,
+        OutputFormat::Text => Some(text_body(&result.text)),
+        OutputFormat::Markdown => {
+            let title = result
+                .metadata
+                .title
+                .as_deref()
+                .filter(|title| !title.trim().is_empty())
+                .unwrap_or(file_name);
+            Some(to_markdown(&result.text, title))
+        }
+        OutputFormat::Alto | OutputFormat::Hocr => result
+            .layout
+            .as_ref()
+            .and_then(|layout| export_layout(output_format, layout, file_name)),
+    }
+}
+
+/// Plain text ending in a newline, so `curl ... > out.txt` gives a well-formed text file
+fn text_body(text: &str) -> String {
+    let mut body = text.trim_end().to_string();
+    body.push('\n');
+    body
+}
+
+/// Render extracted text as Markdown: a `title` heading, then one paragraph per run of non-blank lines
+pub fn to_markdown(text: &str, title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut markdown = format!("# {}\n", title);
+
+    let mut paragraph = Vec::new();
+    for line in text.lines().map(str::trim).chain([""]) {
+        if !line.is_empty() {
+            paragraph.push(line);
+        } else if !paragraph.is_empty() {
+            markdown.push('\n');
+            markdown.push_str(&paragraph.join("\n"));
+            markdown.push('\n');
+            paragraph.clear();
+        }
+    }
+    markdown
+}
+
+/// Serialize a layout as ALTO v4 XML.
+///
+/// PDF pages are written in `inch1200` units, image uploads in `pixel`.
+pub fn to_alto(layout: &DocumentLayout, file_name: &str) -> String {
+    let (unit, scale) = match layout.unit {
+        LayoutUnit::Point => ("inch1200", INCH1200_PER_POINT),
+        LayoutUnit::Pixel => ("pixel", 1.0),
+    };
+    let position = |bbox: &BoundingBox| {
+        format!(
+            "HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+            (bbox.x * scale).round(),
+            (bbox.y * scale).round(),
+            (bbox.width * scale).round(),
+            (bbox.height * scale).round()
+        )
+    };
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(
+        "<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# \
+         http://www.loc.gov/alto/v4/alto-4-2.xsd\">\n",
+    );
+    xml.push_str("  <Description>\n");
+    let _ = writeln!(xml, "    <MeasurementUnit>{}</MeasurementUnit>", unit);
+    xml.push_str("    <sourceImageInformation>\n");
+    let _ = writeln!(xml, "      <fileName>{}</fileName>", escape_xml(file_name));
+    xml.push_str("    </sourceImageInformation>\n");
+    xml.push_str("    <OCRProcessing ID=\"OCR_0\">\n      <ocrProcessingStep>\n        <processingSoftware>\n");
+    xml.push_str("          <softwareName>elicit</softwareName>\n");
+    let _ = writeln!(xml, "          <softwareVersion>{}</softwareVersion>", env!("CARGO_PKG_VERSION"));
+    xml.push_str("        </processingSoftware>\n      </ocrProcessingStep>\n    </OCRProcessing>\n");
+    xml.push_str("  </Description>\n  <Layout>\n");
+
+    for page in &layout.pages {
+        let page_box = page_box(page);
+        let _ = writeln!(
+            xml,
+            "    <Page ID=\"page_{n}\" PHYSICAL_IMG_NR=\"{n}\" WIDTH=\"{w}\" HEIGHT=\"{h}\">",
+            n = page.number,
+            w = (page.width * scale).round(),
+            h = (page.height * scale).round()
+        );
+        let _ = writeln!(xml, "      <PrintSpace {}>", position(&page_box));
+
+        for (b, block) in page.blocks.iter().enumerate() {
+            let _ = writeln!(
+                xml,
+                "        <TextBlock ID=\"block_{}_{}\" {}>",
+                page.number,
+                b + 1,
+                position(&block.bbox)
+            );
+            for (l, line) in block.lines.iter().enumerate() {
+                let _ = writeln!(
+                    xml,
+                    "          <TextLine ID=\"line_{}_{}_{}\" {}>",
+                    page.number,
+                    b + 1,
+                    l + 1,
+                    position(&line.bbox)
+                );
+                for (w, word) in line.words.iter().enumerate() {
+                    if w > 0 {
+                        xml.push_str("            <SP/>\n");
+                    }
+                    let confidence = word
+                        .confidence
+                        .map(|c| format!(" WC=\"{:.2}\"", (c / 100.0).clamp(0.0, 1.0)))
+                        .unwrap_or_default();
+                    let _ = writeln!(
+                        xml,
+                        "            <String ID=\"word_{}_{}_{}_{}\" CONTENT=\"{}\" {}{}/>",
+                        page.number,
+                        b + 1,
+                        l + 1,
+                        w + 1,
+                        escape_xml(&word.text),
+                        position(&word.bbox),
+                        confidence
+                    );
+                }
+                xml.push_str("          </TextLine>\n");
+            }
+            xml.push_str("        </TextBlock>\n");
+        }
+
+        xml.push_str("      </PrintSpace>\n    </Page>\n");
+    }
+
+    xml.push_str("  </Layout>\n</alto>\n");
+    xml
+}
+
+/// Serialize a layout as hOCR.
+///
+/// hOCR boxes are in pixels; PDF pages are written at 72 DPI so one pixel is one point.
+pub fn to_hocr(layout: &DocumentLayout, file_name: &str) -> String {
+    let bbox = |b: &BoundingBox| {
+        format!(
+            "bbox {} {} {} {}",
+            b.x.round(),
+            b.y.round(),
+            b.right().round(),
+            b.bottom().round()
+        )
+    };
+
+    let mut html = String::new();
+    html.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    html.push_str(
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \
+         \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+    );
+    html.push_str("<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"en\" lang=\"en\">\n<head>\n");
+    let _ = writeln!(html, "  <title>{}</title>", escape_xml(file_name));
+    html.push_str("  <meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>\n");
+    let _ = writeln!(html, "  <meta name=\"ocr-system\" content=\"elicit {}\"/>", env!("CARGO_PKG_VERSION"));
+    html.push_str(
+        "  <meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word\"/>\n",
+    );
+    html.push_str("</head>\n<body>\n");
+
+    for page in &layout.pages {
+        let resolution = match layout.unit {
+            LayoutUnit::Point => "; scan_res 72 72",
+            LayoutUnit::Pixel => "",
+        };
+        let _ = writeln!(
+            html,
+            "  <div class=\"ocr_page\" id=\"page_{}\" title=\"image &quot;{}&quot;; {}; ppageno {}{}\">",
+            page.number,
+            escape_xml(file_name),
+            bbox(&BoundingBox::new(0.0, 0.0, page.width, page.height)),
+            page.number.saturating_sub(1),
+            resolution
+        );
+
+        for (b, block) in page.blocks.iter().enumerate() {
+            let id = format!("{}_{}", page.number, b + 1);
+            let _ = writeln!(
+                html,
+                "   <div class=\"ocr_carea\" id=\"block_{id}\" title=\"{bbox}\">\n    <p class=\"ocr_par\" id=\"par_{id}\" title=\"{bbox}\">",
+                id = id,
+                bbox = bbox(&block.bbox)
+            );
+            for (l, line) in block.lines.iter().enumerate() {
+                let _ = writeln!(
+                    html,
+                    "     <span class=\"ocr_line\" id=\"line_{}_{}\" title=\"{}\">",
+                    id,
+                    l + 1,
+                    bbox(&line.bbox)
+                );
+                for (w, word) in line.words.iter().enumerate() {
+                    let confidence = word
+                        .confidence
+                        .map(|c| format!("; x_wconf {}", c.round().clamp(0.0, 100.0)))
+                        .unwrap_or_default();
+                    let _ = writeln!(
+                        html,
+                        "      <span class=\"ocrx_word\" id=\"word_{}_{}_{}\" title=\"{}{}\">{}</span>",
+                        id,
+                        l + 1,
+                        w + 1,
+                        bbox(&word.bbox),
+                        confidence,
+                        escape_xml(&word.text)
+                    );
+                }
+                html.push_str("     </span>\n");
+            }
+            html.push_str("    </p>\n   </div>\n");
+        }
+
+        html.push_str("  </div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Area covered by the page's text, or the whole page when it has none
+fn page_box(page: &PageLayout) -> BoundingBox {
+    if page.blocks.is_empty() {
+        BoundingBox::new(0.0, 0.0, page.width, page.height)
+    } else {
+        BoundingBox::enclosing(page.blocks.iter().map(|b| &b.bbox))
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
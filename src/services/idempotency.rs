@@ -0,0 +1,290 @@
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+
+/// Memory stored responses may take together, unless configured
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Largest response stored, unless configured
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+
+static IDEMPOTENCY_STORE: Lazy<Option<Arc<IdempotencyStore>>> = Lazy::new(|| {
+    let (capacity, ttl_seconds, max_memory_mb, max_response_mb) = match Config::from_env() {
+        Ok(config) => (
+            config.idempotency_capacity,
+            config.idempotency_ttl_seconds,
+            config.idempotency_max_memory_mb,
+            config.idempotency_max_response_mb,
+        ),
+        Err(e) => {
+            warn!("Failed to load config for idempotency keys, using defaults: {}", e);
+            (1000, 86400, 64, 4)
+        }
+    };
+
+    if capacity == 0 {
+        info!("Idempotency keys disabled");
+        return None;
+    }
+    info!(capacity, ttl_seconds, max_memory_mb, max_response_mb, "Idempotency key store created");
+    Some(Arc::new(
+        IdempotencyStore::new(capacity, Duration::from_secs(ttl_seconds))
+            .with_memory_limits(max_memory_mb * 1024 * 1024, max_response_mb * 1024 * 1024),
+    ))
+});
+
+/// The process-wide store configured through the `IDEMPOTENCY_*` variables, `None` when disabled
+pub fn idempotency_store() -> Option<Arc<IdempotencyStore>> {
+    IDEMPOTENCY_STORE.clone()
+}
+
+/// A response kept so a retry can be answered without redoing the work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    /// Bytes the response takes in the store
+    pub fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len())
+                .sum::<usize>()
+    }
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdempotencyOutcome {
+    /// First time the key is seen: run the request, then `complete` or `release` the key
+    Proceed,
+    /// The key already finished with this response
+    Replay(StoredResponse),
+}
+
+#[derive(Debug)]
+enum EntryState {
+    InFlight,
+    Completed(StoredResponse),
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: String,
+    state: EntryState,
+    expires_at: Instant,
+}
+
+type EntryId = (String, String);
+
+impl Entry {
+    fn size(&self, id: &EntryId) -> usize {
+        let response = match &self.state {
+            EntryState::InFlight => 0,
+            EntryState::Completed(response) => response.size(),
+        };
+        id.0.len() + id.1.len() + self.fingerprint.len() + response
+    }
+}
+
+/// The entries and the bytes they take, kept in step
+struct Entries {
+    lru: LruCache<EntryId, Entry>,
+    bytes: usize,
+}
+
+impl Entries {
+    fn get(&mut self, id: &EntryId) -> Option<&Entry> {
+        self.lru.get(id)
+    }
+
+    fn put(&mut self, id: EntryId, entry: Entry) {
+        self.bytes += entry.size(&id);
+        // Returns the entry replaced under the same key or evicted for capacity
+        if let Some((old_id, old)) = self.lru.push(id, entry) {
+            self.bytes -= old.size(&old_id);
+        }
+    }
+
+    fn pop(&mut self, id: &EntryId) {
+        if let Some(old) = self.lru.pop(id) {
+            self.bytes -= old.size(id);
+        }
+    }
+
+    /// Drop the least recently used entries until they fit in `max_bytes`
+    fn shrink_to(&mut self, max_bytes: usize) {
+        while self.bytes > max_bytes {
+            let Some((id, old)) = self.lru.pop_lru() else {
+                break;
+            };
+            self.bytes -= old.size(&id);
+        }
+    }
+}
+
+/// Idempotency keys and the responses they produced.
+///
+/// Keys are scoped by the caller (a hash of its API key) so two clients can
+/// pick the same key. Entries expire after the retention window, and the least
+/// recently used ones are dropped beyond `capacity` entries or once the stored
+/// responses take more than the memory budget together.
+pub struct IdempotencyStore {
+    entries: Mutex<Entries>,
+    ttl: Duration,
+    max_bytes: usize,
+    max_response_bytes: usize,
+}
+
+impl IdempotencyStore {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                lru: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+                bytes: 0,
+            }),
+            ttl,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+        }
+    }
+
+    /// Bound the memory of all stored responses together and of any one of them
+    pub fn with_memory_limits(mut self, max_bytes: usize, max_response_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self.max_response_bytes = max_response_bytes.min(max_bytes);
+        self
+    }
+
+    /// Largest response `complete` keeps
+    pub fn max_response_bytes(&self) -> usize {
+        self.max_response_bytes
+    }
+
+    /// Bytes the stored entries take
+    pub fn memory_used(&self) -> usize {
+        self.entries.lock().expect("idempotency store poisoned").bytes
+    }
+
+    /// Claim `key` for a request with the given fingerprint.
+    ///
+    /// Fails while another request holds the key, and when the key was used
+    /// for a different request.
+    pub fn begin(&self, scope: &str, key: &str, fingerprint: &str) -> AppResult<IdempotencyOutcome> {
+        let mut entries = self.entries.lock().expect("idempotency store poisoned");
+        let id = (scope.to_string(), key.to_string());
+
+        if let Some(entry) = entries.get(&id).filter(|entry| entry.expires_at > Instant::now()) {
+            if entry.fingerprint != fingerprint {
+                return Err(AppError::IdempotencyKeyReused);
+            }
+            return match &entry.state {
+                EntryState::InFlight => Err(AppError::IdempotencyKeyInUse),
+                EntryState::Completed(response) => Ok(IdempotencyOutcome::Replay(response.clone())),
+            };
+        }
+
+        entries.put(
+            id,
+            Entry {
+                fingerprint: fingerprint.to_string(),
+                state: EntryState::InFlight,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+        Ok(IdempotencyOutcome::Proceed)
+    }
+
+    /// Keep the response of a claimed key for the retention window.
+    ///
+    /// A response larger than the per-response limit isn't kept: the key is
+    /// released instead, so a retry runs the request again.
+    pub fn complete(&self, scope: &str, key: &str, fingerprint: &str, response: StoredResponse) {
+        let id = (scope.to_string(), key.to_string());
+        let entry = Entry {
+            fingerprint: fingerprint.to_string(),
+            state: EntryState::Completed(response),
+            expires_at: Instant::now() + self.ttl,
+        };
+        let size = entry.size(&id);
+
+        let mut entries = self.entries.lock().expect("idempotency store poisoned");
+        if size > self.max_response_bytes {
+            debug!(size, limit = self.max_response_bytes, "Response too large to keep for retries");
+            entries.pop(&id);
+            return;
+        }
+        entries.put(id, entry);
+        entries.shrink_to(self.max_bytes);
+    }
+
+    /// Forget a claimed key without a response, so a retry runs the request again
+    pub fn release(&self, scope: &str, key: &str) {
+        let mut entries = self.entries.lock().expect("idempotency store poisoned");
+        let id = (scope.to_string(), key.to_string());
+        if entries.lru.peek(&id).is_some_and(|entry| matches!(entry.state, EntryState::InFlight)) {
+            entries.pop(&id);
+        }
+    }
+}
+
+/// Check an `Idempotency-Key` value: 1 to 255 visible ASCII characters
+pub fn validate_idempotency_key(key: &str) -> AppResult<()> {
+    if key.is_empty() || key.len() > 255 || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(AppError::validation(
+            "Idempotency-Key must be 1 to 255 visible ASCII characters",
+        ));
+    }
+    Ok(())
+}
+
+/// SHA-256 of everything that decides a request's outcome.
+///
+/// `parts` are the method, path, query and option headers; the multipart
+/// `boundary`, which clients pick at random on every attempt, is blanked out of
+/// the body so a plain retry matches.
+pub fn request_fingerprint(parts: &[&str], body: &[u8], boundary: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+
+    match boundary.map(str::as_bytes).filter(|boundary| !boundary.is_empty()) {
+        Some(boundary) => {
+            let mut rest = body;
+            while let Some(position) = find(rest, boundary) {
+                hasher.update(&rest[..position]);
+                hasher.update([0u8]);
+                rest = &rest[position + boundary.len()..];
+            }
+            hasher.update(rest);
+        }
+        None => hasher.update(body),
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Position of the first `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(offset) = haystack[start..].iter().position(|&b| b == needle[0]) {
+        let position = start + offset;
+        if haystack[position..].starts_with(needle) {
+            return Some(position);
+        }
+        start = position + 1;
+    }
+    None
+}
@@ -0,0 +1,361 @@
+use chrono::Utc;
+use lopdf::Document;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Once, RwLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    ApiKeyIdentity, ExtractResponse, ExtractionOptions, JobError, JobProgress, JobResponse, JobStatus,
+    ProcessedFile, Scope, WebhookDelivery,
+};
+use crate::services::ocr_cache::OcrCache;
+use crate::services::ocr_engine::OcrEngine;
+use crate::services::pdf_processor::PdfProcessor;
+use crate::services::webhook::{deliver_webhook, WebhookTarget};
+
+// Queue shared by the HTTP handlers, sized through config
+static JOB_QUEUE: Lazy<Arc<JobQueue>> = Lazy::new(|| {
+    let (workers, capacity, ttl_seconds) = match Config::from_env() {
+        Ok(config) => (config.job_workers, config.job_queue_capacity, config.job_ttl_seconds),
+        Err(e) => {
+            warn!("Failed to load config for the job queue, using defaults: {}", e);
+            (2, 100, 3600)
+        }
+    };
+    info!(workers, capacity, ttl_seconds, "Extraction job queue created");
+    JobQueue::new(workers, capacity, Duration::from_secs(ttl_seconds))
+});
+
+/// The process-wide job queue configured through `JOB_WORKERS`, `JOB_QUEUE_CAPACITY` and `JOB_TTL_SECONDS`
+pub fn job_queue() -> Arc<JobQueue> {
+    JOB_QUEUE.clone()
+}
+
+/// An upload waiting to be extracted in the background
+pub struct JobRequest {
+    file: ProcessedFile,
+    options: ExtractionOptions,
+    ocr_engine: Option<Arc<dyn OcrEngine>>,
+    ocr_cache: Option<Arc<OcrCache>>,
+    callback: Option<WebhookTarget>,
+}
+
+impl JobRequest {
+    pub fn new(file: ProcessedFile) -> Self {
+        Self {
+            file,
+            options: ExtractionOptions::default(),
+            ocr_engine: None,
+            ocr_cache: None,
+            callback: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: ExtractionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_ocr_engine(mut self, engine: Arc<dyn OcrEngine>) -> Self {
+        self.ocr_engine = Some(engine);
+        self
+    }
+
+    pub fn with_ocr_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.ocr_cache = Some(cache);
+        self
+    }
+
+    /// POST the finished job to this target instead of waiting to be polled
+    pub fn with_callback(mut self, callback: WebhookTarget) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    fn processor(&self) -> PdfProcessor {
+        let mut processor = PdfProcessor::new();
+        if let Some(engine) = &self.ocr_engine {
+            processor = processor.with_ocr_engine(engine.clone());
+        }
+        if let Some(cache) = &self.ocr_cache {
+            processor = processor.with_ocr_cache(cache.clone());
+        }
+        processor
+    }
+}
+
+struct QueuedJob {
+    id: String,
+    request: JobRequest,
+}
+
+struct JobRecord {
+    /// Id of the API key that submitted the job
+    owner: String,
+    job: JobResponse,
+    deliveries: Vec<WebhookDelivery>,
+}
+
+/// Extraction jobs run by a fixed pool of background workers.
+///
+/// Jobs are kept in memory; finished jobs are dropped once their TTL has passed.
+/// A job can only be read with the API key that submitted it, or an admin key.
+pub struct JobQueue {
+    jobs: RwLock<HashMap<String, JobRecord>>,
+    sender: mpsc::Sender<QueuedJob>,
+    receiver: Arc<Mutex<mpsc::Receiver<QueuedJob>>>,
+    workers: usize,
+    workers_started: Once,
+    ttl: Duration,
+}
+
+impl JobQueue {
+    /// `capacity` bounds the jobs waiting for a worker, not the jobs kept for polling
+    pub fn new(workers: usize, capacity: usize, ttl: Duration) -> Arc<Self> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        Arc::new(Self {
+            jobs: RwLock::new(HashMap::new()),
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            workers: workers.max(1),
+            workers_started: Once::new(),
+            ttl,
+        })
+    }
+
+    /// Queue an extraction for `owner` and return its initial state right away
+    pub fn submit(self: &Arc<Self>, owner: &ApiKeyIdentity, request: JobRequest) -> AppResult<JobResponse> {
+        self.purge_expired();
+        self.start_workers();
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let job = JobResponse {
+            job_id: id.clone(),
+            status: JobStatus::Queued,
+            progress: JobProgress::default(),
+            file_name: request.file.name.clone(),
+            callback_url: request.callback.as_ref().map(|callback| callback.url().to_string()),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            expires_at: None,
+            result: None,
+            error: None,
+        };
+
+        // Register before queueing so a fast worker always finds the job
+        self.jobs_mut().insert(
+            id.clone(),
+            JobRecord {
+                owner: owner.id.clone(),
+                job: job.clone(),
+                deliveries: Vec::new(),
+            },
+        );
+        if self.sender.try_send(QueuedJob { id: id.clone(), request }).is_err() {
+            self.jobs_mut().remove(&id);
+            warn!("Job queue is full, rejecting job");
+            return Err(AppError::service_unavailable("extraction job queue is full"));
+        }
+
+        info!(job_id = %id, file_name = %job.file_name, "Extraction job queued");
+        Ok(job)
+    }
+
+    /// Current state of a job, `NotFound` once it has expired or when `caller` may not see it
+    pub fn get(&self, caller: &ApiKeyIdentity, id: &str) -> AppResult<JobResponse> {
+        self.read(caller, id, |record| record.job.clone())
+    }
+
+    /// Webhook delivery attempts made for a job so far, oldest first
+    pub fn deliveries(&self, caller: &ApiKeyIdentity, id: &str) -> AppResult<Vec<WebhookDelivery>> {
+        self.read(caller, id, |record| record.deliveries.clone())
+    }
+
+    /// Number of jobs currently tracked, including finished ones not yet expired
+    pub fn len(&self) -> usize {
+        self.purge_expired();
+        self.jobs.read().expect("job table poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn jobs_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, JobRecord>> {
+        self.jobs.write().expect("job table poisoned")
+    }
+
+    /// Another client's job looks the same as a missing one, so ids can't be probed
+    fn read<T>(&self, caller: &ApiKeyIdentity, id: &str, f: impl FnOnce(&JobRecord) -> T) -> AppResult<T> {
+        self.purge_expired();
+        self.jobs
+            .read()
+            .expect("job table poisoned")
+            .get(id)
+            .filter(|record| record.owner == caller.id || caller.has_scope(Scope::Admin))
+            .map(f)
+            .ok_or_else(|| AppError::not_found(format!("job {}", id)))
+    }
+
+    /// The job as it stands, for the queue's own use
+    fn snapshot(&self, id: &str) -> Option<JobResponse> {
+        self.jobs.read().expect("job table poisoned").get(id).map(|record| record.job.clone())
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut JobResponse)) {
+        if let Some(record) = self.jobs_mut().get_mut(id) {
+            f(&mut record.job);
+        }
+    }
+
+    fn record_delivery(&self, delivery: WebhookDelivery) {
+        if let Some(record) = self.jobs_mut().get_mut(&delivery.job_id) {
+            record.deliveries.push(delivery);
+        }
+    }
+
+    fn purge_expired(&self) {
+        let now = Utc::now();
+        self.jobs_mut()
+            .retain(|_, record| record.job.expires_at.is_none_or(|expires_at| expires_at > now));
+    }
+
+    fn finish(&self, id: &str, outcome: Result<ExtractResponse, AppError>) {
+        let now = Utc::now();
+        let expires_at = chrono::Duration::from_std(self.ttl)
+            .ok()
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .unwrap_or(chrono::DateTime::<Utc>::MAX_UTC);
+
+        self.update(id, |job| {
+            job.finished_at = Some(now);
+            job.expires_at = Some(expires_at);
+            match outcome {
+                Ok(response) => {
+                    job.status = JobStatus::Completed;
+                    job.progress.pages_done = response.data.pages;
+                    job.progress.pages_total = Some(response.data.pages);
+                    job.result = Some(response);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(JobError {
+                        code: e.error_code().to_string(),
+                        message: e.to_string(),
+                    });
+                }
+            }
+        });
+    }
+
+    /// Workers need a runtime, so they are spawned on first use rather than at construction
+    fn start_workers(self: &Arc<Self>) {
+        self.workers_started.call_once(|| {
+            for worker in 0..self.workers {
+                let queue = Arc::downgrade(self);
+                let receiver = self.receiver.clone();
+                tokio::spawn(run_worker(worker, queue, receiver));
+            }
+        });
+    }
+}
+
+async fn run_worker(worker: usize, queue: Weak<JobQueue>, receiver: Arc<Mutex<mpsc::Receiver<QueuedJob>>>) {
+    loop {
+        // The channel closes when the queue is dropped
+        let Some(QueuedJob { id, mut request }) = receiver.lock().await.recv().await else {
+            break;
+        };
+        let Some(queue) = queue.upgrade() else {
+            break;
+        };
+
+        info!(worker, job_id = %id, "Extraction job started");
+        let callback = request.callback.take();
+        // Run on its own task so a panic fails the job instead of killing the worker
+        let outcome = match tokio::spawn(run_job(queue.clone(), id.clone(), request)).await {
+            Ok(outcome) => outcome,
+            Err(e) => Err(AppError::internal(format!("Extraction job crashed: {}", e))),
+        };
+
+        match &outcome {
+            Ok(_) => info!(worker, job_id = %id, "Extraction job completed"),
+            Err(e) => error!(worker, job_id = %id, error = %e, "Extraction job failed"),
+        }
+        queue.finish(&id, outcome);
+
+        if let Some(callback) = callback {
+            // Retries can take minutes, don't hold up the next job
+            tokio::spawn(notify_callback(Arc::downgrade(&queue), id, callback));
+        }
+    }
+}
+
+/// POST the finished job to its callback URL, logging every attempt on the job
+async fn notify_callback(queue: Weak<JobQueue>, id: String, callback: WebhookTarget) {
+    let Some(job) = queue.upgrade().and_then(|queue| queue.snapshot(&id)) else {
+        return;
+    };
+    let event = match job.status {
+        JobStatus::Completed => "job.completed",
+        _ => "job.failed",
+    };
+    let body = match serde_json::to_vec(&serde_json::json!({ "event": event, "job": job })) {
+        Ok(body) => body,
+        Err(e) => {
+            error!(job_id = %id, error = %e, "Failed to serialize webhook payload");
+            return;
+        }
+    };
+
+    deliver_webhook(&callback, &id, event, body, |delivery| {
+        if let Some(queue) = queue.upgrade() {
+            queue.record_delivery(delivery);
+        }
+    })
+    .await;
+}
+
+async fn run_job(queue: Arc<JobQueue>, id: String, request: JobRequest) -> AppResult<ExtractResponse> {
+    let start = Instant::now();
+
+    // PDFs report their page count up front, images once their frames are decoded
+    let pages_total = if request.file.is_pdf() {
+        Document::load_mem(&request.file.content)
+            .ok()
+            .map(|doc| doc.get_pages().len())
+    } else {
+        None
+    };
+    queue.update(&id, |job| {
+        job.status = JobStatus::Processing;
+        job.started_at = Some(Utc::now());
+        job.progress.pages_total = pages_total;
+    });
+
+    let progress_queue = Arc::downgrade(&queue);
+    let progress_id = id.clone();
+    let processor = request.processor().with_progress(Arc::new(move |done, total| {
+        if let Some(queue) = progress_queue.upgrade() {
+            queue.update(&progress_id, |job| {
+                job.progress.pages_done = done;
+                job.progress.pages_total = Some(job.progress.pages_total.unwrap_or(0).max(total));
+            });
+        }
+    }));
+
+    let result = processor.extract_text(request.file, &request.options).await?;
+    Ok(ExtractResponse::new(
+        result.text,
+        result.pages,
+        result.metadata,
+        start.elapsed().as_millis() as u64,
+    )
+    .with_options(request.options))
+}
@@ -0,0 +1,268 @@
+use pdf_extract::{MediaBox, OutputDev, OutputError, Transform};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    BoundingBox, DocumentLayout, LayoutBlock, LayoutLine, LayoutUnit, LayoutWord, PageLayout,
+    TextSource,
+};
+use crate::services::ocr_engine::RecognizedPage;
+
+/// Baselines closer than this fraction of the font size belong to the same line
+const SAME_LINE_TOLERANCE: f32 = 0.5;
+/// Gaps wider than this fraction of the font size separate words
+const WORD_GAP: f32 = 0.1;
+/// Vertical gaps larger than this many line heights start a new block
+const BLOCK_GAP: f32 = 1.5;
+
+/// Build the layout of OCR'd pages.
+///
+/// `page_sizes` gives each page's size in points for PDFs; word boxes are then
+/// scaled from image pixels to points. Without it coordinates stay in pixels.
+pub fn ocr_layout(pages: &[RecognizedPage], page_sizes: Option<&[(f32, f32)]>) -> DocumentLayout {
+    let unit = if page_sizes.is_some() { LayoutUnit::Point } else { LayoutUnit::Pixel };
+
+    let pages = pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let size = page_sizes.and_then(|sizes| sizes.get(index).copied());
+            ocr_page_layout(index as u32 + 1, page, size)
+        })
+        .collect();
+
+    DocumentLayout { unit, pages }
+}
+
+/// Group recognised words into blocks (Tesseract paragraphs) and lines
+pub fn ocr_page_layout(number: u32, page: &RecognizedPage, size: Option<(f32, f32)>) -> PageLayout {
+    let (width, height) = size.unwrap_or((page.width as f32, page.height as f32));
+    let (scale_x, scale_y) = if page.width > 0 && page.height > 0 {
+        (width / page.width as f32, height / page.height as f32)
+    } else {
+        (1.0, 1.0)
+    };
+
+    let mut blocks: Vec<LayoutBlock> = Vec::new();
+    let mut lines: Vec<LayoutLine> = Vec::new();
+    let mut words: Vec<LayoutWord> = Vec::new();
+    let mut previous: Option<(u32, u32, u32)> = None;
+
+    for word in &page.words {
+        let position = (word.block, word.paragraph, word.line);
+        if let Some(last) = previous {
+            if last != position && !words.is_empty() {
+                lines.push(LayoutLine::new(std::mem::take(&mut words)));
+            }
+            if (last.0, last.1) != (position.0, position.1) && !lines.is_empty() {
+                blocks.push(LayoutBlock::new(std::mem::take(&mut lines)));
+            }
+        }
+
+        words.push(LayoutWord {
+            text: word.text.clone(),
+            bbox: BoundingBox::new(
+                word.left as f32 * scale_x,
+                word.top as f32 * scale_y,
+                word.width as f32 * scale_x,
+                word.height as f32 * scale_y,
+            ),
+            confidence: Some(word.confidence),
+        });
+        previous = Some(position);
+    }
+
+    if !words.is_empty() {
+        lines.push(LayoutLine::new(words));
+    }
+    if !lines.is_empty() {
+        blocks.push(LayoutBlock::new(lines));
+    }
+
+    PageLayout {
+        number,
+        width,
+        height,
+        source: TextSource::Ocr,
+        blocks,
+    }
+}
+
+/// Build the layout of a PDF's native text from the glyph positions in its content streams
+pub fn native_layout(pdf_data: &[u8]) -> AppResult<DocumentLayout> {
+    let doc = pdf_extract::Document::load_mem(pdf_data).map_err(|e| AppError::InvalidFile {
+        message: format!("Failed to parse PDF: {}", e),
+    })?;
+
+    let mut collector = LayoutCollector::default();
+    pdf_extract::output_doc(&doc, &mut collector)
+        .map_err(|e| AppError::processing(format!("Failed to read text positions: {}", e)))?;
+
+    Ok(DocumentLayout {
+        unit: LayoutUnit::Point,
+        pages: collector.pages,
+    })
+}
+
+/// A word being assembled from glyphs, in top-left page coordinates
+struct PendingWord {
+    text: String,
+    bbox: BoundingBox,
+    baseline: f32,
+    font_size: f32,
+}
+
+#[derive(Default)]
+struct LayoutCollector {
+    pages: Vec<PageLayout>,
+    number: u32,
+    media_box: Option<MediaBox>,
+    words: Vec<PendingWord>,
+    current: Option<PendingWord>,
+}
+
+impl LayoutCollector {
+    fn finish_word(&mut self) {
+        if let Some(word) = self.current.take() {
+            if !word.text.trim().is_empty() {
+                self.words.push(word);
+            }
+        }
+    }
+}
+
+impl OutputDev for LayoutCollector {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.number = page_num;
+        self.media_box = Some(*media_box);
+        self.words.clear();
+        self.current = None;
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.finish_word();
+        let media_box = self.media_box.take().unwrap_or(MediaBox {
+            llx: 0.0,
+            lly: 0.0,
+            urx: 612.0,
+            ury: 792.0,
+        });
+
+        self.pages.push(PageLayout {
+            number: self.number,
+            width: (media_box.urx - media_box.llx) as f32,
+            height: (media_box.ury - media_box.lly) as f32,
+            source: TextSource::Native,
+            blocks: group_words(std::mem::take(&mut self.words)),
+        });
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        _spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        let Some(media_box) = self.media_box else {
+            return Ok(());
+        };
+
+        if char.trim().is_empty() {
+            self.finish_word();
+            return Ok(());
+        }
+
+        let size_x = (font_size * (trm.m11 * trm.m11 + trm.m12 * trm.m12).sqrt()) as f32;
+        let size_y = (font_size * (trm.m21 * trm.m21 + trm.m22 * trm.m22).sqrt()) as f32;
+        let x = (trm.m31 - media_box.llx) as f32;
+        let baseline = (media_box.ury - trm.m32) as f32;
+        let advance = width as f32 * size_x;
+        // Approximate the glyph box from the font size: 80% ascent, 20% descent
+        let glyph = BoundingBox::new(x, baseline - size_y * 0.8, advance, size_y);
+
+        if let Some(word) = &self.current {
+            let gap = x - word.bbox.right();
+            let moved_line = (baseline - word.baseline).abs() > word.font_size * SAME_LINE_TOLERANCE;
+            if moved_line || gap > word.font_size * WORD_GAP || gap < -word.font_size {
+                self.finish_word();
+            }
+        }
+
+        match &mut self.current {
+            Some(word) => {
+                word.text.push_str(char);
+                word.bbox = word.bbox.union(&glyph);
+            }
+            None => {
+                self.current = Some(PendingWord {
+                    text: char.to_string(),
+                    bbox: glyph,
+                    baseline,
+                    font_size: size_y.max(1.0),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.finish_word();
+        Ok(())
+    }
+}
+
+/// Group words in content-stream order into lines by baseline, and lines into blocks by spacing
+fn group_words(words: Vec<PendingWord>) -> Vec<LayoutBlock> {
+    let mut lines: Vec<(f32, f32, Vec<LayoutWord>)> = Vec::new();
+    for word in words {
+        let layout_word = LayoutWord {
+            text: word.text,
+            bbox: word.bbox,
+            confidence: None,
+        };
+        match lines.last_mut() {
+            Some((baseline, size, line_words))
+                if (word.baseline - *baseline).abs() <= *size * SAME_LINE_TOLERANCE =>
+            {
+                line_words.push(layout_word);
+            }
+            _ => lines.push((word.baseline, word.font_size, vec![layout_word])),
+        }
+    }
+
+    let mut blocks = Vec::new();
+    let mut current: Vec<LayoutLine> = Vec::new();
+    let mut previous: Option<(f32, f32)> = None;
+    for (baseline, size, words) in lines {
+        if let Some((last_baseline, last_size)) = previous {
+            let spacing = baseline - last_baseline;
+            // A new block on a large gap, or when the text jumps back up the page
+            if spacing > last_size.max(size) * (1.0 + BLOCK_GAP) || spacing < 0.0 {
+                blocks.push(LayoutBlock::new(std::mem::take(&mut current)));
+            }
+        }
+        current.push(LayoutLine::new(words));
+        previous = Some((baseline, size));
+    }
+    if !current.is_empty() {
+        blocks.push(LayoutBlock::new(current));
+    }
+
+    blocks
+}
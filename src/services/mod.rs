@@ -1,5 +1,43 @@
+pub mod api_keys;
+pub mod batch;
+pub mod compare;
+pub mod export;
+pub mod idempotency;
+pub mod jobs;
+pub mod layout;
 pub mod pdf_processor;
+pub mod ocr_cache;
+pub mod ocr_engine;
 pub mod ocr_service;
+pub mod preprocessing;
+pub mod scan_detector;
+pub mod search;
+pub mod searchable_pdf;
+pub mod session;
+pub mod streaming;
+pub mod subprocess;
+pub mod tables;
+pub mod url_fetch;
+pub mod webhook;
 
+pub use api_keys::*;
+pub use batch::*;
+pub use compare::*;
+pub use export::*;
+pub use idempotency::*;
+pub use jobs::*;
+pub use layout::*;
 pub use pdf_processor::*;
-pub use ocr_service::*;
\ No newline at end of file
+pub use ocr_cache::*;
+pub use ocr_engine::*;
+pub use ocr_service::*;
+pub use preprocessing::*;
+pub use scan_detector::*;
+pub use search::*;
+pub use searchable_pdf::*;
+pub use session::*;
+pub use streaming::*;
+pub use subprocess::*;
+pub use tables::*;
+pub use url_fetch::*;
+pub use webhook::*;
@@ -0,0 +1,168 @@
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::models::{PreprocessingOptions, PreprocessingStep};
+use crate::services::ocr_engine::{OcrSettings, RecognizedPage};
+
+/// Bumped whenever the cached data or the way it's produced changes
+const CACHE_KEY_VERSION: &str = "elicit-ocr-v1";
+
+// Shared cache used by the HTTP handlers, `None` when disabled through config
+static GLOBAL_OCR_CACHE: Lazy<Option<Arc<OcrCache>>> = Lazy::new(|| {
+    let (capacity, dir) = match Config::from_env() {
+        Ok(config) => (config.ocr_cache_capacity, config.ocr_cache_dir),
+        Err(e) => {
+            warn!("Failed to load config for the OCR cache, using defaults: {}", e);
+            (256, None)
+        }
+    };
+
+    if capacity == 0 && dir.is_none() {
+        info!("OCR result cache disabled");
+        return None;
+    }
+
+    info!(capacity, dir = ?dir, "OCR result cache enabled");
+    Some(Arc::new(OcrCache::new(capacity, dir.map(PathBuf::from))))
+});
+
+/// The process-wide OCR cache configured through `OCR_CACHE_CAPACITY` and `OCR_CACHE_DIR`
+pub fn global_ocr_cache() -> Option<Arc<OcrCache>> {
+    GLOBAL_OCR_CACHE.clone()
+}
+
+/// What OCR produced for one page image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPage {
+    pub page: RecognizedPage,
+    pub preprocessing_applied: Vec<PreprocessingStep>,
+}
+
+/// OCR results keyed by the SHA-256 of the page image and the settings used to read it.
+///
+/// Entries live in a bounded in-memory LRU and, when a directory is configured,
+/// on disk so they survive restarts and can be shared between instances.
+pub struct OcrCache {
+    memory: Option<Mutex<LruCache<String, CachedPage>>>,
+    dir: Option<PathBuf>,
+}
+
+impl OcrCache {
+    /// A capacity of 0 disables the in-memory layer
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> Self {
+        Self {
+            memory: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            dir,
+        }
+    }
+
+    /// Cache key for a page image read by `engine` with the given settings
+    pub fn key(
+        image: &[u8],
+        engine: &str,
+        settings: &OcrSettings,
+        preprocessing: &PreprocessingOptions,
+    ) -> String {
+        let steps: Vec<&str> = preprocessing.steps.iter().map(|s| s.as_str()).collect();
+
+        let mut hasher = Sha256::new();
+        for part in [
+            CACHE_KEY_VERSION,
+            engine,
+            settings.language.as_str(),
+            &settings.psm.to_string(),
+            &steps.join(","),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+        hasher.update(image);
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedPage> {
+        if let Some(memory) = &self.memory {
+            if let Some(entry) = memory.lock().expect("OCR cache poisoned").get(key) {
+                debug!(key, "OCR cache hit (memory)");
+                return Some(entry.clone());
+            }
+        }
+
+        let entry = self.read_from_disk(key)?;
+        debug!(key, "OCR cache hit (disk)");
+        if let Some(memory) = &self.memory {
+            memory
+                .lock()
+                .expect("OCR cache poisoned")
+                .put(key.to_string(), entry.clone());
+        }
+        Some(entry)
+    }
+
+    pub fn put(&self, key: &str, entry: CachedPage) {
+        self.write_to_disk(key, &entry);
+        if let Some(memory) = &self.memory {
+            memory.lock().expect("OCR cache poisoned").put(key.to_string(), entry);
+        }
+    }
+
+    /// Number of entries held in memory
+    pub fn len(&self) -> usize {
+        self.memory
+            .as_ref()
+            .map(|memory| memory.lock().expect("OCR cache poisoned").len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry_path(&self, key: &str) -> Option<PathBuf> {
+        // Keys are hex digests; shard by the first byte to keep directories small
+        let dir = self.dir.as_ref()?;
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(dir.join(&key[..2]).join(format!("{}.json", key)))
+    }
+
+    fn read_from_disk(&self, key: &str) -> Option<CachedPage> {
+        let path = self.entry_path(key)?;
+        let data = std::fs::read(&path).ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Ignoring unreadable OCR cache entry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn write_to_disk(&self, key: &str, entry: &CachedPage) {
+        let Some(path) = self.entry_path(key) else {
+            return;
+        };
+        if let Err(e) = write_atomically(&path, entry) {
+            warn!("Failed to store OCR cache entry {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Write through a temporary file so concurrent readers never see a partial entry
+fn write_atomically(path: &Path, entry: &CachedPage) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let data = serde_json::to_vec(entry)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    std::io::Write::write_all(&mut file, &data)?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{ExtractionOptions, DEFAULT_OCR_LANGUAGE, DEFAULT_OCR_PSM};
+use crate::services::ocr_service::parse_osd_rotation;
+use crate::services::subprocess::{run_command, SubprocessStage};
+
+/// Name of the engine used when neither config nor request picks one
+pub const DEFAULT_OCR_ENGINE: &str = "tesseract";
+
+/// Recognition settings passed to an engine for each page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcrSettings {
+    /// Tesseract-style language list, e.g. `spa+eng`
+    pub language: String,
+    /// Page segmentation mode
+    pub psm: u8,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        Self {
+            language: DEFAULT_OCR_LANGUAGE.to_string(),
+            psm: DEFAULT_OCR_PSM,
+        }
+    }
+}
+
+impl From<&ExtractionOptions> for OcrSettings {
+    fn from(options: &ExtractionOptions) -> Self {
+        Self {
+            language: options.language.clone(),
+            psm: options.psm,
+        }
+    }
+}
+
+/// Text recognised on a single page image
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecognizedPage {
+    pub text: String,
+    /// Size of the recognised image in pixels, 0 when the engine doesn't report it
+    pub width: u32,
+    pub height: u32,
+    /// Word boxes in image pixel coordinates (origin top-left), when available
+    pub words: Vec<OcrWord>,
+}
+
+/// A recognised word and where it was found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Confidence in percent (0 - 100)
+    pub confidence: f32,
+    pub block: u32,
+    pub paragraph: u32,
+    pub line: u32,
+}
+
+/// A text recognition backend.
+///
+/// Implementations must be cheap to share: one instance serves every request.
+#[async_trait]
+pub trait OcrEngine: Send + Sync {
+    /// Identifier used in config and requests
+    fn name(&self) -> &'static str;
+
+    /// Recognise the text on one page image
+    async fn recognize_page(&self, image: &Path, settings: &OcrSettings) -> AppResult<RecognizedPage>;
+
+    /// Languages this engine has models for
+    async fn list_languages(&self) -> AppResult<Vec<String>>;
+
+    /// Check the engine can be used, returning the reason if it can't
+    async fn health_check(&self) -> AppResult<()>;
+
+    /// Clockwise rotation (degrees) needed to make the page upright, if the engine can tell
+    async fn detect_orientation(&self, _image: &Path) -> AppResult<Option<u32>> {
+        Ok(None)
+    }
+}
+
+/// Tesseract driven through its command-line interface
+#[derive(Debug, Default)]
+pub struct TesseractCliEngine;
+
+impl TesseractCliEngine {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn is_installed() -> bool {
+        // Check if tesseract command is available in PATH
+        let mut command = Command::new("tesseract");
+        command.arg("--version");
+        run_command(command, SubprocessStage::Probe)
+            .await
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl OcrEngine for TesseractCliEngine {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    async fn recognize_page(&self, image: &Path, settings: &OcrSettings) -> AppResult<RecognizedPage> {
+        // TSV output carries word boxes, the plain text is rebuilt from it
+        let mut command = Command::new("tesseract");
+        command
+            .arg(image)
+            .arg("-") // Output to stdout
+            .arg("-l").arg(&settings.language)
+            .arg("--psm").arg(settings.psm.to_string())
+            .arg("tsv");
+        let output = run_command(command, SubprocessStage::Recognition).await?;
+
+        if !output.status.success() {
+            return Err(AppError::OcrError {
+                message: format!(
+                    "tesseract exited with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+
+        Ok(parse_tesseract_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    async fn list_languages(&self) -> AppResult<Vec<String>> {
+        let mut command = Command::new("tesseract");
+        command.arg("--list-langs");
+        let output = run_command(command, SubprocessStage::Probe).await?;
+
+        // Older releases print the list on stderr
+        let listing = if output.stdout.is_empty() {
+            String::from_utf8_lossy(&output.stderr).to_string()
+        } else {
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+
+        Ok(parse_language_list(&listing))
+    }
+
+    async fn health_check(&self) -> AppResult<()> {
+        if Self::is_installed().await {
+            Ok(())
+        } else {
+            Err(AppError::OcrError {
+                message: "Tesseract is not installed".to_string(),
+            })
+        }
+    }
+
+    async fn detect_orientation(&self, image: &Path) -> AppResult<Option<u32>> {
+        let mut command = Command::new("tesseract");
+        command
+            .arg(image)
+            .arg("-")
+            .arg("--psm").arg("0"); // Orientation and script detection only
+        let output = run_command(command, SubprocessStage::OrientationDetection).await?;
+
+        if !output.status.success() {
+            debug!("Tesseract OSD failed for {:?}", image);
+            return Ok(None);
+        }
+
+        Ok(parse_osd_rotation(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Deterministic engine for tests: every page is recognised as the same text
+#[derive(Debug)]
+pub struct MockOcrEngine {
+    text: String,
+    languages: Vec<String>,
+    healthy: bool,
+    calls: AtomicUsize,
+}
+
+impl MockOcrEngine {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            languages: vec!["eng".to_string()],
+            healthy: true,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Make the engine report itself as unavailable
+    pub fn unhealthy(mut self) -> Self {
+        self.healthy = false;
+        self
+    }
+
+    /// Number of pages recognised so far
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl OcrEngine for MockOcrEngine {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn recognize_page(&self, image: &Path, _settings: &OcrSettings) -> AppResult<RecognizedPage> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let (width, height) = image::image_dimensions(image).unwrap_or((0, 0));
+
+        // Lay the words out left to right, one line per text line, 20px per character
+        let mut words = Vec::new();
+        for (line, text) in self.text.lines().enumerate() {
+            let mut left = 10;
+            for word in text.split_whitespace() {
+                let word_width = 20 * word.chars().count() as u32;
+                words.push(OcrWord {
+                    text: word.to_string(),
+                    left,
+                    top: 10 + 40 * line as u32,
+                    width: word_width,
+                    height: 30,
+                    confidence: 95.0,
+                    block: 1,
+                    paragraph: 1,
+                    line: line as u32 + 1,
+                });
+                left += word_width + 20;
+            }
+        }
+
+        Ok(RecognizedPage {
+            text: self.text.clone(),
+            width,
+            height,
+            words,
+        })
+    }
+
+    async fn list_languages(&self) -> AppResult<Vec<String>> {
+        Ok(self.languages.clone())
+    }
+
+    async fn health_check(&self) -> AppResult<()> {
+        if self.healthy {
+            Ok(())
+        } else {
+            Err(AppError::OcrError {
+                message: "Mock OCR engine is unhealthy".to_string(),
+            })
+        }
+    }
+}
+
+// Engines selectable by name from config or per request
+static OCR_ENGINES: Lazy<RwLock<HashMap<String, Arc<dyn OcrEngine>>>> = Lazy::new(|| {
+    let mut engines: HashMap<String, Arc<dyn OcrEngine>> = HashMap::new();
+    engines.insert(DEFAULT_OCR_ENGINE.to_string(), Arc::new(TesseractCliEngine::new()));
+    RwLock::new(engines)
+});
+
+/// Make an engine selectable by name, replacing any engine with the same name
+pub fn register_ocr_engine(engine: Arc<dyn OcrEngine>) {
+    let name = engine.name().to_string();
+    info!(engine = %name, "Registering OCR engine");
+    OCR_ENGINES
+        .write()
+        .expect("OCR engine registry poisoned")
+        .insert(name, engine);
+}
+
+/// Look up an engine by name
+pub fn ocr_engine_by_name(name: &str) -> AppResult<Arc<dyn OcrEngine>> {
+    let engines = OCR_ENGINES.read().expect("OCR engine registry poisoned");
+    engines
+        .get(name.trim().to_lowercase().as_str())
+        .cloned()
+        .ok_or_else(|| {
+            let mut available: Vec<&str> = engines.keys().map(|k| k.as_str()).collect();
+            available.sort();
+            AppError::validation(format!(
+                "Unknown OCR engine '{}', available engines: {}",
+                name,
+                available.join(", ")
+            ))
+        })
+}
+
+/// Names of all registered engines
+pub fn ocr_engine_names() -> Vec<String> {
+    let mut names: Vec<String> = OCR_ENGINES
+        .read()
+        .expect("OCR engine registry poisoned")
+        .keys()
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// The engine configured through `OCR_ENGINE`, falling back to Tesseract
+pub fn default_ocr_engine() -> AppResult<Arc<dyn OcrEngine>> {
+    let name = std::env::var("OCR_ENGINE").unwrap_or_else(|_| DEFAULT_OCR_ENGINE.to_string());
+    ocr_engine_by_name(&name)
+}
+
+/// Parse `tesseract --list-langs` output, skipping the header line
+pub fn parse_language_list(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("List of available languages"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse Tesseract TSV output into words and the page text.
+///
+/// Paragraphs are separated by a blank line and lines by a newline, matching
+/// Tesseract's plain text renderer.
+pub fn parse_tesseract_tsv(tsv: &str) -> RecognizedPage {
+    let mut page = RecognizedPage::default();
+
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.split('\t').collect();
+        if columns.len() < 12 {
+            continue;
+        }
+        let number = |i: usize| columns[i].trim().parse::<u32>().unwrap_or(0);
+
+        match columns[0] {
+            "1" => {
+                page.width = number(8);
+                page.height = number(9);
+            }
+            "5" => {
+                let text = columns[11..].join("\t").trim().to_string();
+                if text.is_empty() {
+                    continue;
+                }
+                page.words.push(OcrWord {
+                    text,
+                    left: number(6),
+                    top: number(7),
+                    width: number(8),
+                    height: number(9),
+                    confidence: columns[10].trim().parse().unwrap_or(0.0),
+                    block: number(2),
+                    paragraph: number(3),
+                    line: number(4),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let mut text = String::new();
+    let mut previous: Option<(u32, u32, u32)> = None;
+    for word in &page.words {
+        let position = (word.block, word.paragraph, word.line);
+        match previous {
+            None => {}
+            Some((block, paragraph, _)) if block != word.block || paragraph != word.paragraph => {
+                text.push_str("\n\n")
+            }
+            Some(last) if last != position => text.push('\n'),
+            Some(_) => text.push(' '),
+        }
+        text.push_str(&word.text);
+        previous = Some(position);
+    }
+    page.text = text;
+
+    page
+}
@@ -1,25 +1,135 @@
 use crate::error::{AppError, AppResult};
+use crate::models::{PreprocessingOptions, PreprocessingStep};
+use crate::services::ocr_engine::{
+    default_ocr_engine, OcrEngine, OcrSettings, RecognizedPage, TesseractCliEngine,
+};
+use crate::services::ocr_cache::{CachedPage, OcrCache};
+use crate::services::preprocessing::ImagePreprocessor;
+use crate::services::scan_detector::ScanDetector;
+use crate::services::subprocess::{run_command, SubprocessStage};
+use image::{DynamicImage, ImageBuffer, ImageFormat};
+use tiff::decoder::{DecodingResult, Limits};
 use tracing::{info, warn, debug};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
-use std::process::Command;
+use tokio::process::Command;
 use tempfile::{NamedTempFile, TempDir};
 use std::io::Write;
 
-pub struct OcrService;
+/// Called with (pages done, pages total) as OCR works through a document
+pub type ProgressCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+pub struct OcrService {
+    engine: Arc<dyn OcrEngine>,
+    settings: OcrSettings,
+    preprocessing: PreprocessingOptions,
+    cache: Option<Arc<OcrCache>>,
+    progress: Option<ProgressCallback>,
+    /// Rasterize PDF pages at this resolution instead of extracting their images
+    render_dpi: Option<u32>,
+}
+
+/// Text recognised by OCR together with what was done to get it
+#[derive(Debug, Default, Clone)]
+pub struct OcrOutput {
+    pub text: String,
+    pub pages: usize,
+    pub preprocessing_applied: Vec<PreprocessingStep>,
+    /// Recognised words per page image, in page order
+    pub recognized_pages: Vec<RecognizedPage>,
+    /// Pages served from the OCR cache instead of the engine
+    pub cache_hits: usize,
+}
+
+impl OcrOutput {
+    /// Append the next page's text and record its preprocessing
+    fn add_page(&mut self, entry: CachedPage) {
+        self.record_steps(entry.preprocessing_applied);
+        self.text.push_str(&entry.page.text);
+        self.text.push_str("\n\n");
+        self.recognized_pages.push(entry.page);
+        self.pages += 1;
+    }
+
+    /// Count a page the engine couldn't read, keeping page indexes aligned
+    fn add_failed_page(&mut self, preprocessing_applied: Vec<PreprocessingStep>) {
+        self.record_steps(preprocessing_applied);
+        self.recognized_pages.push(RecognizedPage::default());
+        self.pages += 1;
+    }
+
+    fn record_steps(&mut self, steps: Vec<PreprocessingStep>) {
+        for step in steps {
+            if !self.preprocessing_applied.contains(&step) {
+                self.preprocessing_applied.push(step);
+            }
+        }
+    }
+}
 
 impl OcrService {
+    /// Create a service backed by the engine configured through `OCR_ENGINE`
     pub fn new() -> AppResult<Self> {
-        // Check if Tesseract is available
-        if !Self::is_tesseract_available() {
-            return Err(AppError::OcrError {
-                message: "Tesseract OCR not available on this system".to_string()
-            });
+        Ok(Self::with_engine(default_ocr_engine()?))
+    }
+
+    pub fn with_engine(engine: Arc<dyn OcrEngine>) -> Self {
+        Self {
+            engine,
+            settings: OcrSettings::default(),
+            preprocessing: PreprocessingOptions::default(),
+            cache: None,
+            progress: None,
+            render_dpi: None,
+        }
+    }
+
+    pub fn engine(&self) -> &Arc<dyn OcrEngine> {
+        &self.engine
+    }
+
+    /// Language and page segmentation mode passed to the engine
+    pub fn with_settings(mut self, settings: OcrSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Enable image preprocessing steps for every page this service recognises
+    pub fn with_preprocessing(mut self, preprocessing: PreprocessingOptions) -> Self {
+        self.preprocessing = preprocessing;
+        self
+    }
+
+    /// Reuse results for page images this cache has already seen
+    pub fn with_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Report progress after every page image
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// OCR a render of each whole PDF page rather than the images embedded in it.
+    ///
+    /// Needed whenever word positions matter: an extracted image may be one of
+    /// several on its page, or cover only part of it.
+    pub fn with_page_rendering(mut self, dpi: u32) -> Self {
+        self.render_dpi = Some(dpi);
+        self
+    }
+
+    fn report_progress(&self, done: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            progress(done, total);
         }
-        
-        Ok(Self)
     }
 
-    pub async fn extract_text_from_pdf(&self, pdf_data: &[u8]) -> AppResult<String> {
+    pub async fn extract_text_from_pdf(&self, pdf_data: &[u8]) -> AppResult<OcrOutput> {
         let start = Instant::now();
         info!("Starting OCR extraction from PDF ({} bytes)", pdf_data.len());
 
@@ -36,67 +146,84 @@ impl OcrService {
 
         info!("PDF appears to contain scanned content, OCR would be beneficial");
 
-        // Check if we can actually run OCR (Tesseract installed)
-        if !Self::is_tesseract_available() {
-            warn!("Tesseract OCR is not available on this system");
+        // Check if we can actually run OCR (engine installed and healthy)
+        if let Err(e) = self.engine.health_check().await {
+            warn!(engine = self.engine.name(), "OCR engine is not available: {}", e);
             return Err(AppError::OcrError {
-                message: "This PDF appears to be scanned and requires OCR, but Tesseract is not installed. Please install Tesseract OCR to process scanned PDFs.".to_string()
+                message: format!(
+                    "This PDF appears to be scanned and requires OCR, but the {} OCR engine is not available ({}).",
+                    self.engine.name(),
+                    e
+                )
             });
         }
 
-        // Try to perform basic OCR using pdfimages and tesseract
+        // Try to perform basic OCR using pdfimages and the OCR engine
         let ocr_result = self.perform_ocr_on_pdf(pdf_data).await;
 
         let processing_time = start.elapsed().as_millis();
 
         match ocr_result {
-            Ok(text) => {
-                info!("OCR extraction completed successfully ({}ms), extracted {} characters", processing_time, text.len());
-                Ok(text)
+            Ok(output) => {
+                info!("OCR extraction completed successfully ({}ms), extracted {} characters", processing_time, output.text.len());
+                Ok(output)
+            }
+            Err(e @ AppError::Timeout { .. }) => {
+                warn!("OCR extraction timed out after {}ms: {}", processing_time, e);
+                Err(e)
             }
             Err(e) => {
                 warn!("OCR extraction failed after {}ms: {}", processing_time, e);
-                // Return empty string to allow processing to continue
-                Ok(String::new())
+                // Return empty output to allow processing to continue
+                Ok(OcrOutput::default())
             }
         }
     }
 
-    pub async fn extract_text_from_image(&self, _image_data: &[u8]) -> AppResult<String> {
-        // For now, return a placeholder since we need proper image conversion libraries
-        // In a production environment, you would:
-        // 1. Convert PDF pages to images using pdf2image or similar
-        // 2. Use Tesseract OCR to extract text from images
-        // 3. Combine results from all pages
-        
-        warn!("OCR from image data not yet implemented - requires image conversion libraries");
-        Err(AppError::OcrError {
-            message: "OCR from image data requires additional image conversion libraries".to_string()
-        })
+    /// OCR a PNG, JPEG, TIFF or WebP upload. Every TIFF frame is treated as a page.
+    pub async fn extract_text_from_image(&self, image_data: &[u8]) -> AppResult<OcrOutput> {
+        let start = Instant::now();
+        info!("Starting OCR extraction from image ({} bytes)", image_data.len());
+
+        let temp_dir = TempDir::new()
+            .map_err(|e| AppError::OcrError {
+                message: format!("Failed to create temp directory: {}", e)
+            })?;
+
+        let pages = spawn_write_image_pages(image_data.to_vec(), temp_dir.path().to_path_buf()).await?;
+
+        self.engine.health_check().await.map_err(|e| AppError::OcrError {
+            message: format!("The {} OCR engine is not available: {}", self.engine.name(), e)
+        })?;
+
+        let output = self.ocr_page_images(&pages, temp_dir.path()).await?;
+
+        info!(
+            "Image OCR completed ({}ms), {} pages, {} characters",
+            start.elapsed().as_millis(),
+            output.pages,
+            output.text.len()
+        );
+        Ok(output)
     }
 
-    pub fn is_tesseract_available() -> bool {
-        // Check if tesseract command is available in PATH
-        std::process::Command::new("tesseract")
-            .arg("--version")
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
+    pub async fn is_tesseract_available() -> bool {
+        TesseractCliEngine::is_installed().await
     }
 
-    pub fn is_available() -> bool {
-        Self::is_tesseract_available()
+    pub async fn is_available() -> bool {
+        Self::is_tesseract_available().await
     }
 }
 
 impl Default for OcrService {
     fn default() -> Self {
-        Self::new().unwrap_or(Self)
+        Self::new().unwrap_or_else(|_| Self::with_engine(Arc::new(TesseractCliEngine::new())))
     }
 }
 
 impl OcrService {
-    async fn perform_ocr_on_pdf(&self, pdf_data: &[u8]) -> AppResult<String> {
+    async fn perform_ocr_on_pdf(&self, pdf_data: &[u8]) -> AppResult<OcrOutput> {
         // Create a temporary file for the PDF
         let mut pdf_file = NamedTempFile::new()
             .map_err(|e| AppError::OcrError {
@@ -116,128 +243,469 @@ impl OcrService {
                 message: format!("Failed to create temp directory: {}", e)
             })?;
 
+        if let Some(dpi) = self.render_dpi {
+            let page_count = lopdf::Document::load_mem(pdf_data)
+                .map_err(|e| AppError::OcrError {
+                    message: format!("Failed to parse PDF for page rendering: {}", e)
+                })?
+                .get_pages()
+                .len() as u32;
+            let mut pages = Vec::with_capacity(page_count as usize);
+            for number in 1..=page_count {
+                pages.push(Self::render_pdf_page(pdf_path, number, dpi, temp_dir.path()).await?);
+            }
+            return self.ocr_page_images(&pages, temp_dir.path()).await;
+        }
+
         // First, try to extract images from PDF using pdfimages
         let image_prefix = temp_dir.path().join("page");
-        let extract_result = Command::new("pdfimages")
+        let mut pdfimages = Command::new("pdfimages");
+        pdfimages
             .arg("-j") // Extract as JPEG
             .arg("-png") // Also extract PNG images
             .arg(pdf_path)
-            .arg(&image_prefix)
-            .output();
-
-        if extract_result.is_err() {
-            // If pdfimages is not available, try converting with ImageMagick
-            debug!("pdfimages not available, trying ImageMagick convert");
-
-            let convert_result = Command::new("convert")
-                .arg("-density").arg("150")
-                .arg(pdf_path)
-                .arg("-quality").arg("100")
-                .arg(temp_dir.path().join("page-%03d.png").to_str().unwrap())
-                .output();
-
-            if convert_result.is_err() {
-                return Err(AppError::OcrError {
-                    message: "Neither pdfimages nor ImageMagick are available to extract images from PDF".to_string()
-                });
+            .arg(&image_prefix);
+        let extract_result = run_command(pdfimages, SubprocessStage::ImageExtraction).await;
+
+        match extract_result {
+            Err(e @ AppError::Timeout { .. }) => return Err(e),
+            Err(_) => {
+                // If pdfimages is not available, try converting with ImageMagick
+                debug!("pdfimages not available, trying ImageMagick convert");
+
+                let mut convert = Command::new("convert");
+                convert
+                    .arg("-density").arg("150")
+                    .arg(pdf_path)
+                    .arg("-quality").arg("100")
+                    .arg(temp_dir.path().join("page-%03d.png"));
+
+                match run_command(convert, SubprocessStage::ImageExtraction).await {
+                    Ok(_) => {}
+                    Err(e @ AppError::Timeout { .. }) => return Err(e),
+                    Err(_) => {
+                        return Err(AppError::OcrError {
+                            message: "Neither pdfimages nor ImageMagick are available to extract images from PDF".to_string()
+                        });
+                    }
+                }
             }
+            Ok(_) => {}
         }
 
         // Find all extracted images
-        let mut extracted_text = String::new();
         let entries = std::fs::read_dir(temp_dir.path())
             .map_err(|e| AppError::OcrError {
                 message: format!("Failed to read temp directory: {}", e)
             })?;
 
-        let mut page_count = 0;
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()).map(|s| s == "png" || s == "jpg" || s == "jpeg").unwrap_or(false) {
-                    page_count += 1;
-                    debug!("Processing page {}: {:?}", page_count, path);
-
-                    // Run Tesseract on each image
-                    let output = Command::new("tesseract")
-                        .arg(&path)
-                        .arg("-") // Output to stdout
-                        .arg("-l").arg("spa+eng") // Spanish and English
-                        .arg("--psm").arg("1") // Auto page segmentation with OSD
-                        .output();
-
-                    if let Ok(output) = output {
-                        if output.status.success() {
-                            let text = String::from_utf8_lossy(&output.stdout);
-                            extracted_text.push_str(&text);
-                            extracted_text.push_str("\n\n");
-                        }
-                    }
+        let mut images: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s == "png" || s == "jpg" || s == "jpeg")
+                    .unwrap_or(false)
+            })
+            .collect();
+        images.sort();
+
+        if images.is_empty() {
+            return Err(AppError::OcrError {
+                message: "No images could be extracted from the PDF".to_string()
+            });
+        }
+
+        self.ocr_page_images(&images, temp_dir.path()).await
+    }
+
+    /// Preprocess and recognise each page image in order
+    async fn ocr_page_images(&self, images: &[PathBuf], work_dir: &Path) -> AppResult<OcrOutput> {
+        let mut output = OcrOutput::default();
+        for (index, path) in images.iter().enumerate() {
+            debug!("Processing page {}: {:?}", index + 1, path);
+
+            let cache_key = self.cache_key(path).await;
+            if let Some(entry) = self.cached(cache_key.as_deref()) {
+                debug!("Page {} served from the OCR cache", index + 1);
+                output.cache_hits += 1;
+                output.add_page(entry);
+                self.report_progress(index + 1, images.len());
+                continue;
+            }
+
+            let (ocr_path, applied) = self.preprocess_page(path, work_dir, index).await?;
+
+            // Run the OCR engine on each image
+            match self.engine.recognize_page(&ocr_path, &self.settings).await {
+                Ok(page) => {
+                    let entry = CachedPage {
+                        page,
+                        preprocessing_applied: applied,
+                    };
+                    self.store(cache_key.as_deref(), &entry);
+                    output.add_page(entry);
+                }
+                Err(e @ AppError::Timeout { .. }) => return Err(e),
+                Err(e) => {
+                    warn!("OCR failed for page {}: {}", index + 1, e);
+                    output.add_failed_page(applied);
                 }
             }
+            self.report_progress(index + 1, images.len());
         }
 
-        if page_count == 0 {
-            return Err(AppError::OcrError {
-                message: "No images could be extracted from the PDF".to_string()
-            });
+        output.preprocessing_applied.sort();
+        output.text = output.text.trim().to_string();
+
+        info!("OCR processed {} pages", output.pages);
+        Ok(output)
+    }
+
+    /// Preprocess and recognise a single page image
+    pub async fn recognize_image(
+        &self,
+        path: &Path,
+        work_dir: &Path,
+        index: usize,
+    ) -> AppResult<(RecognizedPage, Vec<PreprocessingStep>)> {
+        let (entry, _) = self.recognize_image_with_cache_status(path, work_dir, index).await?;
+        Ok((entry.page, entry.preprocessing_applied))
+    }
+
+    /// Like [`recognize_image`](Self::recognize_image), also telling whether the result came from the cache
+    pub async fn recognize_image_with_cache_status(
+        &self,
+        path: &Path,
+        work_dir: &Path,
+        index: usize,
+    ) -> AppResult<(CachedPage, bool)> {
+        let cache_key = self.cache_key(path).await;
+        if let Some(entry) = self.cached(cache_key.as_deref()) {
+            return Ok((entry, true));
         }
 
-        info!("OCR processed {} pages", page_count);
-        Ok(extracted_text.trim().to_string())
+        let (ocr_path, applied) = self.preprocess_page(path, work_dir, index).await?;
+        let page = self.engine.recognize_page(&ocr_path, &self.settings).await?;
+        let entry = CachedPage {
+            page,
+            preprocessing_applied: applied,
+        };
+        self.store(cache_key.as_deref(), &entry);
+        Ok((entry, false))
+    }
+
+    /// Cache key for a page image, `None` when caching is off or the image can't be read
+    async fn cache_key(&self, path: &Path) -> Option<String> {
+        self.cache.as_ref()?;
+        match tokio::fs::read(path).await {
+            Ok(image) => Some(OcrCache::key(
+                &image,
+                self.engine.name(),
+                &self.settings,
+                &self.preprocessing,
+            )),
+            Err(e) => {
+                warn!("Could not read {:?} for the OCR cache: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn cached(&self, key: Option<&str>) -> Option<CachedPage> {
+        self.cache.as_ref()?.get(key?)
+    }
+
+    fn store(&self, key: Option<&str>, entry: &CachedPage) {
+        if let (Some(cache), Some(key)) = (&self.cache, key) {
+            cache.put(key, entry.clone());
+        }
+    }
+
+    /// Rasterize one PDF page (1-based) to a PNG in `out_dir`.
+    ///
+    /// Uses `pdftoppm` when installed and falls back to ImageMagick.
+    pub async fn render_pdf_page(
+        pdf_path: &Path,
+        page: u32,
+        dpi: u32,
+        out_dir: &Path,
+    ) -> AppResult<PathBuf> {
+        let prefix = out_dir.join(format!("render-{:04}", page));
+        let mut pdftoppm = Command::new("pdftoppm");
+        pdftoppm
+            .arg("-r").arg(dpi.to_string())
+            .arg("-f").arg(page.to_string())
+            .arg("-l").arg(page.to_string())
+            .arg("-png")
+            .arg("-singlefile")
+            .arg(pdf_path)
+            .arg(&prefix);
+        let rendered = run_command(pdftoppm, SubprocessStage::PageRendering).await;
+
+        let output_path = prefix.with_extension("png");
+        match rendered {
+            Ok(output) if output.status.success() && output_path.exists() => return Ok(output_path),
+            Err(e @ AppError::Timeout { .. }) => return Err(e),
+            _ => {}
+        }
+
+        debug!("pdftoppm not available or failed, trying ImageMagick convert");
+        let mut convert = Command::new("convert");
+        convert
+            .arg("-density").arg(dpi.to_string())
+            .arg(format!("{}[{}]", pdf_path.display(), page.saturating_sub(1)))
+            .arg("-background").arg("white")
+            .arg("-flatten")
+            .arg(&output_path);
+
+        match run_command(convert, SubprocessStage::PageRendering).await {
+            Ok(output) if output.status.success() && output_path.exists() => Ok(output_path),
+            Err(e @ AppError::Timeout { .. }) => Err(e),
+            _ => Err(AppError::OcrError {
+                message: format!(
+                    "Failed to render page {}: neither pdftoppm nor ImageMagick could rasterize the PDF",
+                    page
+                ),
+            }),
+        }
+    }
+
+    /// Run the requested preprocessing steps and return the image Tesseract should read
+    async fn preprocess_page(
+        &self,
+        path: &Path,
+        work_dir: &Path,
+        index: usize,
+    ) -> AppResult<(PathBuf, Vec<PreprocessingStep>)> {
+        if self.preprocessing.is_empty() {
+            return Ok((path.to_path_buf(), Vec::new()));
+        }
+
+        let image = match image::open(path) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Could not decode {:?} for preprocessing, using it as-is: {}", path, e);
+                return Ok((path.to_path_buf(), Vec::new()));
+            }
+        };
+
+        let rotation = if self.preprocessing.is_enabled(PreprocessingStep::AutoRotate) {
+            match self.engine.detect_orientation(path).await {
+                Ok(rotation) => rotation,
+                Err(e @ AppError::Timeout { .. }) => return Err(e),
+                Err(e) => {
+                    debug!("Orientation detection failed for {:?}: {}", path, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let processed = ImagePreprocessor::apply(image, &self.preprocessing, rotation);
+        let output_path = work_dir.join(format!("preprocessed-{:04}.png", index));
+        processed.image.save(&output_path)
+            .map_err(|e| AppError::OcrError {
+                message: format!("Failed to write preprocessed image: {}", e)
+            })?;
+
+        debug!("Preprocessing applied to page {}: {:?}", index + 1, processed.applied);
+        Ok((output_path, processed.applied))
     }
 
     fn is_likely_scanned_pdf(pdf_data: &[u8]) -> bool {
-        // Enhanced heuristic to detect scanned PDFs
-        let pdf_str = String::from_utf8_lossy(pdf_data);
-
-        // Check for CamScanner or other scanning app signatures
-        let scan_indicators = [
-            "CamScanner",
-            "Adobe Scan",
-            "TinyScanner",
-            "Scanner Pro",
-            "Genius Scan",
-        ];
-
-        let has_scan_app = scan_indicators.iter()
-            .any(|indicator| pdf_str.contains(indicator));
-
-        // Count image-related markers
-        let image_markers = [
-            "/Image",
-            "/DCTDecode",  // JPEG compression
-            "/CCITTFaxDecode", // Fax/scan compression
-            "/JBIG2Decode", // JBIG2 compression (common in scans)
-            "/JPXDecode",  // JPEG2000
-        ];
-
-        let image_count = image_markers.iter()
-            .map(|marker| pdf_str.matches(marker).count())
-            .sum::<usize>();
-
-        // Count text-related markers
-        let text_markers = [
-            "/Font",
-            "/Text",
-            "BT", // Begin text
-            "ET", // End text
-        ];
-
-        let text_count = text_markers.iter()
-            .map(|marker| pdf_str.matches(marker).count())
-            .sum::<usize>();
-
-        debug!("PDF analysis: {} image markers, {} text markers, scan app: {}",
-               image_count, text_count, has_scan_app);
-
-        // Multiple conditions for scanned PDF detection:
-        // 1. Created by scanning apps (CamScanner, etc.)
-        // 2. High image to text ratio
-        // 3. Many images with few fonts
-        has_scan_app ||
-        (image_count > 0 && text_count == 0) ||
-        (image_count > 10 && image_count >= text_count) ||
-        (image_count > text_count * 2)
-    }
-}
\ No newline at end of file
+        match ScanDetector::analyze(pdf_data) {
+            Ok(analysis) => ScanDetector::needs_ocr(&analysis),
+            Err(e) => {
+                // Text extraction already failed on a PDF we cannot parse, OCR is the last resort
+                debug!("Scanned page detection failed, assuming scanned: {}", e);
+                true
+            }
+        }
+    }
+}
+
+/// Extract the `Rotate: N` line from Tesseract `--psm 0` output
+pub fn parse_osd_rotation(osd: &str) -> Option<u32> {
+    osd.lines()
+        .find_map(|line| line.trim().strip_prefix("Rotate:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Largest TIFF frame we decode, about an A0 sheet scanned at 300 dpi
+const MAX_TIFF_FRAME_PIXELS: u64 = 150_000_000;
+/// Most frames one TIFF may have
+const MAX_TIFF_FRAMES: usize = 500;
+/// Pixels all frames of one TIFF may add up to, about 500 Letter pages at 300 dpi
+const MAX_TIFF_TOTAL_PIXELS: u64 = 4_000_000_000;
+
+/// Decode an uploaded image into one image per page.
+///
+/// The `image` crate only reads the first frame of a TIFF, so multi-page TIFFs are
+/// walked frame by frame with the `tiff` decoder. That also covers bilevel fax
+/// scans (CCITT Group 4), which the `image` crate can't read at all.
+pub fn decode_image_pages(data: &[u8]) -> AppResult<Vec<DynamicImage>> {
+    let mut pages = Vec::new();
+    for_each_image_page(data, |page| {
+        pages.push(page);
+        Ok(())
+    })?;
+    Ok(pages)
+}
+
+/// Decode an uploaded image and write every page to a PNG in `dir`, in page order.
+///
+/// Each page is written before the next one is decoded, so only one decoded
+/// page is held in memory at a time.
+pub fn write_image_pages(data: &[u8], dir: &Path) -> AppResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for_each_image_page(data, |page| {
+        let path = dir.join(format!("page-{:04}.png", paths.len()));
+        page.save(&path).map_err(|e| AppError::OcrError {
+            message: format!("Failed to write page image: {}", e),
+        })?;
+        paths.push(path);
+        Ok(())
+    })?;
+    Ok(paths)
+}
+
+/// [`write_image_pages`] on the blocking pool
+pub async fn spawn_write_image_pages(data: Vec<u8>, dir: PathBuf) -> AppResult<Vec<PathBuf>> {
+    tokio::task::spawn_blocking(move || write_image_pages(&data, &dir))
+        .await
+        .map_err(|e| AppError::internal(format!("Image decoding failed: {}", e)))?
+}
+
+/// Decode the pages of an image one at a time, handing each to `page`
+fn for_each_image_page(data: &[u8], mut page: impl FnMut(DynamicImage) -> AppResult<()>) -> AppResult<()> {
+    let format = image::guess_format(data).map_err(|e| AppError::InvalidFile {
+        message: format!("Unrecognised image format: {}", e),
+    })?;
+
+    if format != ImageFormat::Tiff {
+        let image = image::load_from_memory_with_format(data, format).map_err(|e| {
+            AppError::InvalidFile {
+                message: format!("Failed to decode image: {}", e),
+            }
+        })?;
+        return page(image);
+    }
+
+    let invalid = |e: tiff::TiffError| AppError::InvalidFile {
+        message: format!("Failed to decode TIFF: {}", e),
+    };
+
+    // A compressed frame a few KB long can claim to be gigabytes once decoded
+    let limits = {
+        let mut limits = Limits::default();
+        limits.decoding_buffer_size = 512 * 1024 * 1024;
+        limits.intermediate_buffer_size = 64 * 1024 * 1024;
+        limits
+    };
+    let mut decoder = tiff::decoder::Decoder::new(Cursor::new(data))
+        .map_err(invalid)?
+        .with_limits(limits);
+    let mut frames = 0;
+    let mut total_pixels = 0u64;
+    loop {
+        let (width, height) = decoder.dimensions().map_err(invalid)?;
+        let pixels = u64::from(width) * u64::from(height);
+        if pixels > MAX_TIFF_FRAME_PIXELS {
+            return Err(AppError::InvalidFile {
+                message: format!("TIFF frame of {}x{} pixels is too large", width, height),
+            });
+        }
+        frames += 1;
+        total_pixels += pixels;
+        if frames > MAX_TIFF_FRAMES {
+            return Err(AppError::InvalidFile {
+                message: format!("TIFF has more than {} frames", MAX_TIFF_FRAMES),
+            });
+        }
+        if total_pixels > MAX_TIFF_TOTAL_PIXELS {
+            return Err(AppError::InvalidFile {
+                message: format!("TIFF frames add up to more than {} pixels", MAX_TIFF_TOTAL_PIXELS),
+            });
+        }
+
+        let color = decoder.colortype().map_err(invalid)?;
+        let decoded = decoder.read_image().map_err(invalid)?;
+        page(tiff_frame_to_image(width, height, color, decoded)?)?;
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(invalid)?;
+    }
+
+    debug!("Decoded {} TIFF frames", frames);
+    Ok(())
+}
+
+fn tiff_frame_to_image(
+    width: u32,
+    height: u32,
+    color: tiff::ColorType,
+    pixels: DecodingResult,
+) -> AppResult<DynamicImage> {
+    use tiff::ColorType;
+
+    let image = match (color, pixels) {
+        (ColorType::Gray(1), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, unpack_bilevel(width, height, &buf)?).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+        }
+        (color, _) => {
+            return Err(AppError::InvalidFile {
+                message: format!("Unsupported TIFF color type: {:?}", color),
+            })
+        }
+    };
+
+    image.ok_or_else(|| AppError::InvalidFile {
+        message: "TIFF frame has inconsistent dimensions".to_string(),
+    })
+}
+
+/// Expand 1-bit rows, each padded to a whole byte, into 8-bit gray.
+///
+/// The decoder has already inverted WhiteIsZero frames (the usual photometric
+/// for fax scans), so a set bit is white whichever way the file stores it.
+fn unpack_bilevel(width: u32, height: u32, packed: &[u8]) -> AppResult<Vec<u8>> {
+    let width = width as usize;
+    let row_bytes = width.div_ceil(8);
+    if row_bytes == 0 || packed.len() < row_bytes * height as usize {
+        return Err(AppError::InvalidFile {
+            message: "TIFF frame has inconsistent dimensions".to_string(),
+        });
+    }
+
+    let mut gray = Vec::with_capacity(width * height as usize);
+    for row in packed.chunks_exact(row_bytes).take(height as usize) {
+        gray.extend((0..width).map(|x| if row[x / 8] & (0x80 >> (x % 8)) != 0 { 255 } else { 0 }));
+    }
+    Ok(gray)
+}
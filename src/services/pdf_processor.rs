@@ -5,10 +5,23 @@ use tempfile::NamedTempFile;
 use lopdf::Document;
 
 use crate::error::{AppError, AppResult};
-use crate::models::{ProcessedFile, PdfMetadata};
-use crate::services::ocr_service::OcrService;
-
-pub struct PdfProcessor;
+use crate::models::{DocumentLayout, ExtractionOptions, ProcessedFile, PdfMetadata};
+use crate::services::layout::{native_layout, ocr_layout};
+use crate::services::ocr_cache::OcrCache;
+use crate::services::ocr_engine::{OcrEngine, OcrSettings};
+use crate::services::ocr_service::{OcrOutput, OcrService, ProgressCallback};
+use crate::services::scan_detector::{page_size, ScanDetector};
+use std::sync::Arc;
+
+/// Resolution pages are rasterized at when OCR has to produce a layout
+const LAYOUT_RENDER_DPI: u32 = 300;
+
+pub struct PdfProcessor {
+    ocr_engine: Option<Arc<dyn OcrEngine>>,
+    ocr_cache: Option<Arc<OcrCache>>,
+    progress: Option<ProgressCallback>,
+    layout: bool,
+}
 
 #[derive(Debug)]
 pub struct ExtractionResult {
@@ -16,14 +29,77 @@ pub struct ExtractionResult {
     pub pages: usize,
     pub metadata: PdfMetadata,
     pub processing_time_ms: u64,
+    /// Word-level layout, only built when the output format needs coordinates or `with_layout` asked for it
+    pub layout: Option<DocumentLayout>,
 }
 
 impl PdfProcessor {
     pub fn new() -> Self {
-        Self
+        Self {
+            ocr_engine: None,
+            ocr_cache: None,
+            progress: None,
+            layout: false,
+        }
+    }
+
+    /// OCR engine to use instead of the configured default
+    pub fn with_ocr_engine(mut self, engine: Arc<dyn OcrEngine>) -> Self {
+        self.ocr_engine = Some(engine);
+        self
+    }
+
+    /// Reuse OCR results for page images seen before
+    pub fn with_ocr_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.ocr_cache = Some(cache);
+        self
     }
 
-    pub async fn extract_text(&self, file: ProcessedFile) -> AppResult<ExtractionResult> {
+    /// Report (pages done, pages total) while the document is processed
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Build the word layout whatever the output format, e.g. to find the page a paragraph is on
+    pub fn with_layout(mut self) -> Self {
+        self.layout = true;
+        self
+    }
+
+    fn wants_layout(&self, options: &ExtractionOptions) -> bool {
+        self.layout || options.output_format.needs_layout()
+    }
+
+    fn report_progress(&self, done: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            progress(done, total);
+        }
+    }
+
+    fn ocr_service(&self, options: &ExtractionOptions) -> AppResult<OcrService> {
+        let service = match &self.ocr_engine {
+            Some(engine) => OcrService::with_engine(engine.clone()),
+            None => OcrService::new()?,
+        };
+        let mut service = service
+            .with_preprocessing(options.preprocessing())
+            .with_settings(OcrSettings::from(options));
+        if let Some(cache) = &self.ocr_cache {
+            service = service.with_cache(cache.clone());
+        }
+        if let Some(progress) = &self.progress {
+            service = service.with_progress(progress.clone());
+        }
+        // Layout coordinates are scaled to the page, so each OCR'd image must be a whole page
+        if self.wants_layout(options) {
+            service = service.with_page_rendering(LAYOUT_RENDER_DPI);
+        }
+        Ok(service)
+    }
+
+    /// Extract the text of a PDF or image; OCR, its thresholds and the layout all follow `options`
+    pub async fn extract_text(&self, file: ProcessedFile, options: &ExtractionOptions) -> AppResult<ExtractionResult> {
         let start = Instant::now();
         
         tracing::info!(
@@ -32,6 +108,11 @@ impl PdfProcessor {
             file.size
         );
 
+        // Images skip text extraction and go straight to OCR
+        if file.is_image() {
+            return self.extract_image_text(file, options, start).await;
+        }
+
         // Validate file is PDF
         if !file.is_pdf() {
             return Err(AppError::InvalidFile {
@@ -50,10 +131,14 @@ impl PdfProcessor {
             });
         }
 
-        // Validate PDF structure early
-        if let Err(e) = Document::load_mem(&file.content) {
-            tracing::warn!("PDF structure validation failed: {}, will try text extraction anyway", e);
-        }
+        // Validate PDF structure early and classify its pages
+        let page_analysis = match Document::load_mem(&file.content) {
+            Ok(doc) => ScanDetector::analyze_document(&doc),
+            Err(e) => {
+                tracing::warn!("PDF structure validation failed: {}, will try text extraction anyway", e);
+                Vec::new()
+            }
+        };
 
         // Write PDF content to temporary file for pdf-extract
         let mut temp_file = NamedTempFile::new()
@@ -66,6 +151,9 @@ impl PdfProcessor {
                 message: format!("Failed to write PDF to temporary file: {}", e)
             })?;
         
+        // OCR output, if OCR ends up producing the returned text
+        let mut ocr_used: Option<OcrOutput> = None;
+
         // Try to extract text using pdf-extract
         let extracted_text = match extract_text(temp_file.path()) {
             Ok(text) => {
@@ -76,12 +164,15 @@ impl PdfProcessor {
                 tracing::warn!("PDF text extraction failed: {}, trying OCR fallback", e);
                 
                 // Fallback to OCR if direct text extraction fails
-                let ocr_service = OcrService::new()?;
+                let ocr_service = self.ocr_service(options)?;
                 match ocr_service.extract_text_from_pdf(&file.content).await {
-                    Ok(ocr_text) => {
-                        tracing::info!("OCR extraction successful, {} characters", ocr_text.len());
-                        ocr_text
+                    Ok(mut ocr_output) => {
+                        tracing::info!("OCR extraction successful, {} characters", ocr_output.text.len());
+                        let text = std::mem::take(&mut ocr_output.text);
+                        ocr_used = Some(ocr_output);
+                        text
                     }
+                    Err(ocr_err @ AppError::Timeout { .. }) => return Err(ocr_err),
                     Err(ocr_err) => {
                         tracing::error!("Both PDF extraction and OCR failed: {}", ocr_err);
                         return Err(AppError::ProcessingError {
@@ -97,18 +188,27 @@ impl PdfProcessor {
         if cleaned_text.is_empty() {
             tracing::warn!("No text extracted from PDF, trying OCR");
 
-            let ocr_service = OcrService::new()?;
+            let ocr_service = self.ocr_service(options)?;
             match ocr_service.extract_text_from_pdf(&file.content).await {
-                Ok(ocr_text) => {
+                Ok(ocr_output) => {
+                    let layout = self.pdf_layout(options, &file.content, Some(&ocr_output))?;
                     let processing_time = start.elapsed().as_millis() as u64;
+                    let pages = self.estimate_pages(&file.content);
+                    self.report_progress(pages, pages);
 
                     return Ok(ExtractionResult {
-                        text: ocr_text,
-                        pages: self.estimate_pages(&file.content),
-                        metadata: PdfMetadata::new(file.size).with_ocr(),
+                        text: ocr_output.text,
+                        pages,
+                        metadata: PdfMetadata::new(file.size)
+                            .with_ocr()
+                            .with_preprocessing(ocr_output.preprocessing_applied)
+                            .with_cache_hits(ocr_output.cache_hits)
+                            .with_page_analysis(page_analysis),
                         processing_time_ms: processing_time,
+                        layout,
                     });
                 }
+                Err(ocr_err @ AppError::Timeout { .. }) => return Err(ocr_err),
                 Err(ocr_err) => {
                     // OCR failed - check the reason
                     let err_msg = ocr_err.to_string();
@@ -117,19 +217,24 @@ impl PdfProcessor {
                         tracing::info!("PDF has no extractable text and is not a scanned document");
 
                         // Return empty result with metadata instead of error
+                        let layout = self.pdf_layout(options, &file.content, None)?;
                         let processing_time = start.elapsed().as_millis() as u64;
+                        let pages = self.estimate_pages(&file.content);
+                        self.report_progress(pages, pages);
 
                         return Ok(ExtractionResult {
                             text: String::new(),
-                            pages: self.estimate_pages(&file.content),
+                            pages,
                             metadata: PdfMetadata::new(file.size)
                                 .with_title(self.extract_title(&file.content))
-                                .with_author(self.extract_author(&file.content)),
+                                .with_author(self.extract_author(&file.content))
+                                .with_page_analysis(page_analysis),
                             processing_time_ms: processing_time,
+                            layout,
                         });
-                    } else if err_msg.contains("Tesseract is not installed") {
-                        // PDF needs OCR but Tesseract is not available
-                        tracing::warn!("PDF requires OCR but Tesseract is not installed");
+                    } else if err_msg.contains("OCR engine is not available") {
+                        // PDF needs OCR but the OCR engine is not available
+                        tracing::warn!("PDF requires OCR but the OCR engine is not available");
 
                         return Err(AppError::ProcessingError {
                             message: format!("This PDF appears to be scanned and requires OCR. {}", ocr_err),
@@ -144,22 +249,27 @@ impl PdfProcessor {
         }
 
         // If text is very short, it might be a scanned PDF - try OCR as well
-        let use_ocr = cleaned_text.len() < 100 || 
-                      cleaned_text.chars().filter(|c| c.is_alphabetic()).count() < 50;
+        let use_ocr = options.wants_ocr_for(cleaned_text);
         
         let final_text = if use_ocr {
             tracing::info!("Text extraction yielded minimal results, trying OCR enhancement");
             
-            let ocr_service = OcrService::new()?;
+            let ocr_service = self.ocr_service(options)?;
             match ocr_service.extract_text_from_pdf(&file.content).await {
-                Ok(ocr_text) => {
-                    if ocr_text.len() > cleaned_text.len() {
+                Ok(mut ocr_output) => {
+                    if ocr_output.text.len() > cleaned_text.len() {
                         tracing::info!("OCR provided better results, using OCR text");
-                        ocr_text
+                        let text = std::mem::take(&mut ocr_output.text);
+                        ocr_used = Some(ocr_output);
+                        text
                     } else {
                         cleaned_text.to_string()
                     }
                 }
+                Err(e @ AppError::Timeout { .. }) => {
+                    tracing::warn!("OCR enhancement timed out, using original text: {}", e);
+                    cleaned_text.to_string()
+                }
                 Err(_) => {
                     tracing::debug!("OCR enhancement failed, using original text");
                     cleaned_text.to_string()
@@ -169,6 +279,7 @@ impl PdfProcessor {
             cleaned_text.to_string()
         };
 
+        let layout = self.pdf_layout(options, &file.content, ocr_used.as_ref())?;
         let processing_time = start.elapsed().as_millis() as u64;
         
         tracing::info!(
@@ -177,16 +288,91 @@ impl PdfProcessor {
             final_text.len()
         );
 
+        let mut metadata = PdfMetadata::new(file.size)
+            .with_title(self.extract_title(&file.content))
+            .with_author(self.extract_author(&file.content))
+            .with_page_analysis(page_analysis);
+        if let Some(ocr_output) = ocr_used {
+            metadata = metadata
+                .with_ocr()
+                .with_preprocessing(ocr_output.preprocessing_applied)
+                .with_cache_hits(ocr_output.cache_hits);
+        }
+
+        let pages = self.estimate_pages(&file.content);
+        self.report_progress(pages, pages);
+
         Ok(ExtractionResult {
             text: final_text,
-            pages: self.estimate_pages(&file.content),
+            pages,
+            metadata,
+            processing_time_ms: processing_time,
+            layout,
+        })
+    }
+
+    async fn extract_image_text(
+        &self,
+        file: ProcessedFile,
+        options: &ExtractionOptions,
+        start: Instant,
+    ) -> AppResult<ExtractionResult> {
+        tracing::info!("Upload is an image ({:?}), running OCR", file.image_format());
+
+        let ocr_service = self.ocr_service(options)?;
+        let ocr_output = ocr_service.extract_text_from_image(&file.content).await?;
+
+        let processing_time = start.elapsed().as_millis() as u64;
+
+        tracing::info!(
+            "Image processing completed in {}ms, extracted {} characters",
+            processing_time,
+            ocr_output.text.len()
+        );
+
+        let layout = self
+            .wants_layout(options)
+            .then(|| ocr_layout(&ocr_output.recognized_pages, None));
+
+        Ok(ExtractionResult {
+            text: ocr_output.text,
+            pages: ocr_output.pages,
             metadata: PdfMetadata::new(file.size)
-                .with_title(self.extract_title(&file.content))
-                .with_author(self.extract_author(&file.content)),
+                .with_ocr()
+                .with_preprocessing(ocr_output.preprocessing_applied)
+                .with_cache_hits(ocr_output.cache_hits),
             processing_time_ms: processing_time,
+            layout,
         })
     }
 
+    /// Layout of a PDF when requested: OCR words scaled to the pages, or native text positions
+    fn pdf_layout(
+        &self,
+        options: &ExtractionOptions,
+        pdf_content: &[u8],
+        ocr_output: Option<&OcrOutput>,
+    ) -> AppResult<Option<DocumentLayout>> {
+        if !self.wants_layout(options) {
+            return Ok(None);
+        }
+
+        match ocr_output {
+            Some(ocr_output) => {
+                let page_sizes: Vec<(f32, f32)> = match Document::load_mem(pdf_content) {
+                    Ok(doc) => doc
+                        .get_pages()
+                        .values()
+                        .map(|&page_id| page_size(&doc, page_id))
+                        .collect(),
+                    Err(_) => Vec::new(),
+                };
+                Ok(Some(ocr_layout(&ocr_output.recognized_pages, Some(&page_sizes))))
+            }
+            None => native_layout(pdf_content).map(Some),
+        }
+    }
+
     fn estimate_pages(&self, pdf_content: &[u8]) -> usize {
         match Document::load_mem(pdf_content) {
             Ok(doc) => doc.get_pages().len(),
@@ -198,7 +384,7 @@ impl PdfProcessor {
         }
     }
 
-    fn extract_title(&self, pdf_content: &[u8]) -> Option<String> {
+    pub(crate) fn extract_title(&self, pdf_content: &[u8]) -> Option<String> {
         match Document::load_mem(pdf_content) {
             Ok(doc) => {
                 if let Ok(info_dict) = doc.trailer.get(b"Info") {
@@ -234,7 +420,7 @@ impl PdfProcessor {
         }
     }
 
-    fn extract_author(&self, pdf_content: &[u8]) -> Option<String> {
+    pub(crate) fn extract_author(&self, pdf_content: &[u8]) -> Option<String> {
         match Document::load_mem(pdf_content) {
             Ok(doc) => {
                 if let Ok(info_dict) = doc.trailer.get(b"Info") {
@@ -0,0 +1,311 @@
+use image::{imageops, DynamicImage, GrayImage, Luma};
+use tracing::debug;
+
+use crate::models::{PreprocessingOptions, PreprocessingStep};
+
+/// Pages narrower than this (at an assumed A4/Letter width) are treated as low-DPI
+const LOW_DPI_THRESHOLD: f32 = 200.0;
+/// Resolution low-DPI pages are upscaled to
+const TARGET_DPI: f32 = 300.0;
+/// Width of an A4 page in inches, used to estimate the scan resolution
+const PAGE_WIDTH_INCHES: f32 = 8.27;
+/// Largest skew angle (in degrees) the deskew step searches for
+const MAX_SKEW_DEGREES: f32 = 5.0;
+
+/// Result of running the preprocessing pipeline on a single page image
+#[derive(Debug)]
+pub struct PreprocessedImage {
+    pub image: DynamicImage,
+    pub applied: Vec<PreprocessingStep>,
+}
+
+/// Image cleanup applied to page images before they are handed to Tesseract
+pub struct ImagePreprocessor;
+
+impl ImagePreprocessor {
+    /// Run the enabled steps in their canonical order.
+    ///
+    /// `rotation` is the clockwise correction in degrees reported by Tesseract OSD,
+    /// it is only used when auto-rotation is enabled.
+    pub fn apply(
+        image: DynamicImage,
+        options: &PreprocessingOptions,
+        rotation: Option<u32>,
+    ) -> PreprocessedImage {
+        let mut image = image;
+        let mut applied = Vec::new();
+
+        if options.is_enabled(PreprocessingStep::Upscale) {
+            if let Some(upscaled) = Self::upscale(&image) {
+                image = upscaled;
+                applied.push(PreprocessingStep::Upscale);
+            }
+        }
+
+        if options.is_enabled(PreprocessingStep::AutoRotate) {
+            if let Some(degrees) = rotation.filter(|d| d % 360 != 0) {
+                image = Self::rotate(image, degrees);
+                applied.push(PreprocessingStep::AutoRotate);
+            }
+        }
+
+        let needs_gray = options.is_enabled(PreprocessingStep::Grayscale)
+            || options.is_enabled(PreprocessingStep::Deskew)
+            || options.is_enabled(PreprocessingStep::Binarize)
+            || options.is_enabled(PreprocessingStep::Despeckle);
+
+        if !needs_gray {
+            return PreprocessedImage { image, applied };
+        }
+
+        let mut gray = image.to_luma8();
+        if options.is_enabled(PreprocessingStep::Grayscale) {
+            applied.push(PreprocessingStep::Grayscale);
+        }
+
+        if options.is_enabled(PreprocessingStep::Deskew) {
+            let angle = Self::detect_skew(&gray);
+            debug!("Detected skew angle: {:.2} degrees", angle);
+            if angle.abs() >= 0.1 {
+                gray = Self::rotate_gray(&gray, -angle);
+                applied.push(PreprocessingStep::Deskew);
+            }
+        }
+
+        if options.is_enabled(PreprocessingStep::Binarize) {
+            gray = Self::binarize(&gray);
+            applied.push(PreprocessingStep::Binarize);
+        }
+
+        if options.is_enabled(PreprocessingStep::Despeckle) {
+            gray = Self::despeckle(&gray);
+            applied.push(PreprocessingStep::Despeckle);
+        }
+
+        PreprocessedImage {
+            image: DynamicImage::ImageLuma8(gray),
+            applied,
+        }
+    }
+
+    /// Estimate the scan resolution assuming the image spans a full page width
+    pub fn estimate_dpi(width: u32) -> f32 {
+        width as f32 / PAGE_WIDTH_INCHES
+    }
+
+    /// Upscale images whose estimated resolution is below the OCR sweet spot
+    pub fn upscale(image: &DynamicImage) -> Option<DynamicImage> {
+        let dpi = Self::estimate_dpi(image.width());
+        if dpi <= 0.0 || dpi >= LOW_DPI_THRESHOLD {
+            return None;
+        }
+
+        let factor = (TARGET_DPI / dpi).min(4.0);
+        let width = (image.width() as f32 * factor).round() as u32;
+        let height = (image.height() as f32 * factor).round() as u32;
+        debug!("Upscaling image from ~{:.0} DPI by {:.2}x", dpi, factor);
+
+        Some(image.resize_exact(width, height, imageops::FilterType::CatmullRom))
+    }
+
+    /// Rotate clockwise by a multiple of 90 degrees
+    pub fn rotate(image: DynamicImage, degrees: u32) -> DynamicImage {
+        match degrees % 360 {
+            90 => image.rotate90(),
+            180 => image.rotate180(),
+            270 => image.rotate270(),
+            _ => image,
+        }
+    }
+
+    /// Adaptive (Sauvola) thresholding, robust to uneven lighting from phone cameras
+    pub fn binarize(gray: &GrayImage) -> GrayImage {
+        let (width, height) = gray.dimensions();
+        if width == 0 || height == 0 {
+            return gray.clone();
+        }
+
+        let radius = (width.min(height) / 40).clamp(7, 25) as i64;
+        let (sum, sum_sq) = integral_images(gray);
+        let stride = width as usize + 1;
+        let k = 0.2;
+        let dynamic_range = 128.0;
+
+        let mut output = GrayImage::new(width, height);
+        for y in 0..height as i64 {
+            let y0 = (y - radius).max(0) as usize;
+            let y1 = (y + radius + 1).min(height as i64) as usize;
+            for x in 0..width as i64 {
+                let x0 = (x - radius).max(0) as usize;
+                let x1 = (x + radius + 1).min(width as i64) as usize;
+                let count = ((x1 - x0) * (y1 - y0)) as f64;
+
+                let window_sum = sum[y1 * stride + x1] - sum[y0 * stride + x1]
+                    - sum[y1 * stride + x0]
+                    + sum[y0 * stride + x0];
+                let window_sq = sum_sq[y1 * stride + x1] - sum_sq[y0 * stride + x1]
+                    - sum_sq[y1 * stride + x0]
+                    + sum_sq[y0 * stride + x0];
+
+                let mean = window_sum / count;
+                let variance = (window_sq / count - mean * mean).max(0.0);
+                let threshold = mean * (1.0 + k * (variance.sqrt() / dynamic_range - 1.0));
+
+                let value = gray.get_pixel(x as u32, y as u32)[0] as f64;
+                let pixel = if value > threshold { 255 } else { 0 };
+                output.put_pixel(x as u32, y as u32, Luma([pixel]));
+            }
+        }
+
+        output
+    }
+
+    /// 3x3 median filter, removes isolated noise pixels
+    pub fn despeckle(gray: &GrayImage) -> GrayImage {
+        let (width, height) = gray.dimensions();
+        let mut output = gray.clone();
+        if width < 3 || height < 3 {
+            return output;
+        }
+
+        let mut window = [0u8; 9];
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let mut i = 0;
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        window[i] = gray.get_pixel(x + dx - 1, y + dy - 1)[0];
+                        i += 1;
+                    }
+                }
+                window.sort_unstable();
+                output.put_pixel(x, y, Luma([window[4]]));
+            }
+        }
+
+        output
+    }
+
+    /// Find the skew angle in degrees using projection profiles.
+    ///
+    /// The angle uses the same convention as [`ImagePreprocessor::rotate_gray`], so
+    /// rotating by its negation straightens the page.
+    ///
+    /// Text lines produce the sharpest row histogram when they are horizontal, so the
+    /// angle maximising the variance of the dark-pixel row counts wins.
+    pub fn detect_skew(gray: &GrayImage) -> f32 {
+        // Work on a downsampled copy, full-resolution scans are needlessly expensive here
+        let scale = (gray.width().max(gray.height()) as f32 / 1000.0).max(1.0);
+        let small = if scale > 1.0 {
+            imageops::resize(
+                gray,
+                (gray.width() as f32 / scale) as u32,
+                (gray.height() as f32 / scale) as u32,
+                imageops::FilterType::Triangle,
+            )
+        } else {
+            gray.clone()
+        };
+
+        let dark: Vec<(f32, f32)> = small
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[0] < 128)
+            .map(|(x, y, _)| (x as f32, y as f32))
+            .collect();
+        if dark.is_empty() {
+            return 0.0;
+        }
+
+        let height = small.height() as f32;
+        let width = small.width() as f32;
+        let score = |angle: f32| -> f64 {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let offset = width * sin.abs();
+            let bins = (height + offset * 2.0).ceil() as usize + 1;
+            let mut rows = vec![0u32; bins];
+            for &(x, y) in &dark {
+                let projected = y * cos - x * sin + offset;
+                let bin = projected.round().clamp(0.0, (bins - 1) as f32) as usize;
+                rows[bin] += 1;
+            }
+            let mean = dark.len() as f64 / bins as f64;
+            rows.iter().map(|&c| (c as f64 - mean).powi(2)).sum::<f64>()
+        };
+
+        let search = |from: f32, to: f32, step: f32| -> f32 {
+            let mut best_angle = 0.0;
+            let mut best_score = f64::MIN;
+            let mut angle = from;
+            while angle <= to + f32::EPSILON {
+                let s = score(angle);
+                if s > best_score {
+                    best_score = s;
+                    best_angle = angle;
+                }
+                angle += step;
+            }
+            best_angle
+        };
+
+        // The projection above undoes a rotation, so the skew is its negation
+        let coarse = search(-MAX_SKEW_DEGREES, MAX_SKEW_DEGREES, 0.5);
+        -search(coarse - 0.5, coarse + 0.5, 0.1)
+    }
+
+    /// Rotate a grayscale image around its centre, filling uncovered areas with white
+    pub fn rotate_gray(gray: &GrayImage, degrees: f32) -> GrayImage {
+        let (width, height) = gray.dimensions();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let cx = width as f32 / 2.0;
+        let cy = height as f32 / 2.0;
+
+        let mut output = GrayImage::from_pixel(width, height, Luma([255]));
+        for y in 0..height {
+            for x in 0..width {
+                // Inverse mapping: find the source pixel for each destination pixel
+                let dx = x as f32 - cx;
+                let dy = y as f32 - cy;
+                let sx = dx * cos - dy * sin + cx;
+                let sy = dx * sin + dy * cos + cy;
+                if sx >= 0.0 && sy >= 0.0 && sx < (width - 1) as f32 && sy < (height - 1) as f32 {
+                    output.put_pixel(x, y, Luma([bilinear(gray, sx, sy)]));
+                }
+            }
+        }
+
+        output
+    }
+}
+
+fn integral_images(gray: &GrayImage) -> (Vec<f64>, Vec<f64>) {
+    let (width, height) = gray.dimensions();
+    let stride = width as usize + 1;
+    let mut sum = vec![0.0; stride * (height as usize + 1)];
+    let mut sum_sq = vec![0.0; stride * (height as usize + 1)];
+
+    for y in 0..height as usize {
+        let mut row_sum = 0.0;
+        let mut row_sq = 0.0;
+        for x in 0..width as usize {
+            let value = gray.get_pixel(x as u32, y as u32)[0] as f64;
+            row_sum += value;
+            row_sq += value * value;
+            sum[(y + 1) * stride + x + 1] = sum[y * stride + x + 1] + row_sum;
+            sum_sq[(y + 1) * stride + x + 1] = sum_sq[y * stride + x + 1] + row_sq;
+        }
+    }
+
+    (sum, sum_sq)
+}
+
+fn bilinear(gray: &GrayImage, x: f32, y: f32) -> u8 {
+    let x0 = x.floor() as u32;
+    let y0 = y.floor() as u32;
+    let fx = x - x0 as f32;
+    let fy = y - y0 as f32;
+
+    let p = |px: u32, py: u32| gray.get_pixel(px, py)[0] as f32;
+    let top = p(x0, y0) * (1.0 - fx) + p(x0 + 1, y0) * fx;
+    let bottom = p(x0, y0 + 1) * (1.0 - fx) + p(x0 + 1, y0 + 1) * fx;
+    (top * (1.0 - fy) + bottom * fy).round() as u8
+}
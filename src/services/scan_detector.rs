@@ -0,0 +1,387 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::HashSet;
+use tracing::debug;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{PageAnalysis, PageKind};
+
+/// Image coverage above which a page is considered to be a page image
+const FULL_PAGE_COVERAGE: f32 = 0.6;
+/// A scan may still carry a few text operators (page numbers, stamps)
+const MAX_SCAN_TEXT_OPERATORS: usize = 3;
+/// Nested form XObjects deeper than this are ignored
+const MAX_FORM_DEPTH: usize = 8;
+/// US Letter, used when a page has no usable MediaBox
+const DEFAULT_PAGE_SIZE: (f32, f32) = (612.0, 792.0);
+
+const SCAN_APPS: [&str; 5] = [
+    "CamScanner",
+    "Adobe Scan",
+    "TinyScanner",
+    "Scanner Pro",
+    "Genius Scan",
+];
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Classifies PDF pages as scanned or native by walking their content streams
+pub struct ScanDetector;
+
+#[derive(Debug, Default)]
+struct PageStats {
+    image_area: f32,
+    image_count: usize,
+    text_operators: usize,
+    invisible_text_operators: usize,
+    undecodable_streams: usize,
+}
+
+#[derive(Clone, Copy)]
+struct GraphicsState {
+    ctm: Matrix,
+    render_mode: i64,
+}
+
+impl ScanDetector {
+    pub fn analyze(pdf_data: &[u8]) -> AppResult<Vec<PageAnalysis>> {
+        let doc = Document::load_mem(pdf_data).map_err(|e| AppError::InvalidFile {
+            message: format!("Failed to parse PDF: {}", e),
+        })?;
+        Ok(Self::analyze_document(&doc))
+    }
+
+    pub fn analyze_document(doc: &Document) -> Vec<PageAnalysis> {
+        let scan_app = Self::scan_app(doc);
+
+        doc.get_pages()
+            .into_iter()
+            .map(|(number, page_id)| {
+                let analysis = Self::analyze_page(doc, number, page_id, scan_app.as_deref());
+                debug!(
+                    page = number,
+                    kind = ?analysis.kind,
+                    coverage = analysis.image_coverage,
+                    text_operators = analysis.text_operators,
+                    "Page classified"
+                );
+                analysis
+            })
+            .collect()
+    }
+
+    /// True if any page has no usable text and must be OCR'd
+    pub fn needs_ocr(analysis: &[PageAnalysis]) -> bool {
+        analysis.iter().any(PageAnalysis::needs_ocr)
+    }
+
+    fn analyze_page(doc: &Document, number: u32, page_id: ObjectId, scan_app: Option<&str>) -> PageAnalysis {
+        let (width, height) = page_size(doc, page_id);
+        let page_area = (width * height).abs().max(1.0);
+
+        let (page_resources, inherited) = doc.get_page_resources(page_id);
+        let mut resources: Vec<&Dictionary> = page_resources.into_iter().collect();
+        resources.extend(inherited.into_iter().filter_map(|id| doc.get_dictionary(id).ok()));
+
+        let mut stats = PageStats::default();
+        match doc.get_page_content(page_id) {
+            Ok(data) => {
+                let mut visited = HashSet::new();
+                Self::walk_content(doc, &data, &resources, IDENTITY, 0, &mut visited, &mut stats);
+            }
+            Err(_) => stats.undecodable_streams += 1,
+        }
+
+        let image_coverage = (stats.image_area / page_area).clamp(0.0, 1.0);
+        let visible_text = stats.text_operators - stats.invisible_text_operators;
+        let mut reasons = Vec::new();
+
+        let kind = if stats.image_count == 0 && stats.text_operators == 0 {
+            reasons.push("no text-showing operators and no images".to_string());
+            PageKind::Empty
+        } else if image_coverage >= FULL_PAGE_COVERAGE {
+            reasons.push(format!("images cover {:.0}% of the page", image_coverage * 100.0));
+            if stats.invisible_text_operators > 0 {
+                reasons.push(format!(
+                    "{} invisible text operators over the image (existing OCR layer)",
+                    stats.invisible_text_operators
+                ));
+                PageKind::Mixed
+            } else if visible_text <= MAX_SCAN_TEXT_OPERATORS {
+                reasons.push(format!("only {} text-showing operators", visible_text));
+                PageKind::Scanned
+            } else {
+                reasons.push(format!("{} text-showing operators drawn over the image", visible_text));
+                PageKind::Mixed
+            }
+        } else if stats.text_operators > 0 {
+            reasons.push(format!("{} text-showing operators", stats.text_operators));
+            if stats.image_count > 0 {
+                reasons.push(format!(
+                    "{} images covering {:.0}% of the page",
+                    stats.image_count,
+                    image_coverage * 100.0
+                ));
+            }
+            PageKind::Native
+        } else {
+            reasons.push(format!(
+                "{} images covering {:.0}% of the page and no text",
+                stats.image_count,
+                image_coverage * 100.0
+            ));
+            PageKind::Scanned
+        };
+
+        if stats.undecodable_streams > 0 {
+            reasons.push(format!("{} content streams could not be decoded", stats.undecodable_streams));
+        }
+        if let Some(app) = scan_app {
+            if kind != PageKind::Native {
+                reasons.push(format!("document produced by {}", app));
+            }
+        }
+
+        PageAnalysis {
+            page: number,
+            kind,
+            image_coverage,
+            image_count: stats.image_count,
+            text_operators: stats.text_operators,
+            invisible_text_operators: stats.invisible_text_operators,
+            reasons,
+        }
+    }
+
+    fn walk_content(
+        doc: &Document,
+        data: &[u8],
+        resources: &[&Dictionary],
+        base: Matrix,
+        depth: usize,
+        visited: &mut HashSet<ObjectId>,
+        stats: &mut PageStats,
+    ) {
+        let content = match Content::decode(data) {
+            Ok(content) => content,
+            Err(e) => {
+                debug!("Failed to decode content stream: {}", e);
+                stats.undecodable_streams += 1;
+                return;
+            }
+        };
+
+        let mut state = GraphicsState { ctm: base, render_mode: 0 };
+        let mut stack = Vec::new();
+
+        for operation in &content.operations {
+            match operation.operator.as_str() {
+                "q" => stack.push(state),
+                "Q" => {
+                    if let Some(saved) = stack.pop() {
+                        state = saved;
+                    }
+                }
+                "cm" => {
+                    if let Some(matrix) = matrix_from(&operation.operands) {
+                        state.ctm = multiply(&matrix, &state.ctm);
+                    }
+                }
+                "Tr" => {
+                    if let Some(mode) = operation.operands.first().and_then(|o| o.as_i64().ok()) {
+                        state.render_mode = mode;
+                    }
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    stats.text_operators += 1;
+                    if state.render_mode == 3 {
+                        stats.invisible_text_operators += 1;
+                    }
+                }
+                "BI" => {
+                    // Inline images are drawn into the unit square like image XObjects
+                    stats.image_count += 1;
+                    stats.image_area += area(&state.ctm);
+                }
+                "Do" => {
+                    let name = match operation.operands.first().and_then(|o| o.as_name().ok()) {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    Self::draw_xobject(doc, name, resources, &state, depth, visited, stats);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw_xobject(
+        doc: &Document,
+        name: &[u8],
+        resources: &[&Dictionary],
+        state: &GraphicsState,
+        depth: usize,
+        visited: &mut HashSet<ObjectId>,
+        stats: &mut PageStats,
+    ) {
+        let Some((id, stream)) = find_xobject(doc, resources, name) else {
+            return;
+        };
+
+        match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => {
+                stats.image_count += 1;
+                stats.image_area += area(&state.ctm);
+            }
+            Ok(b"Form") => {
+                if depth >= MAX_FORM_DEPTH {
+                    return;
+                }
+                if let Some(id) = id {
+                    if !visited.insert(id) {
+                        return;
+                    }
+                }
+
+                let form_matrix = stream
+                    .dict
+                    .get(b"Matrix")
+                    .ok()
+                    .and_then(|m| m.as_array().ok())
+                    .and_then(|m| matrix_from(m))
+                    .unwrap_or(IDENTITY);
+                let ctm = multiply(&form_matrix, &state.ctm);
+
+                let form_resources = stream
+                    .dict
+                    .get(b"Resources")
+                    .ok()
+                    .and_then(|r| doc.dereference(r).ok())
+                    .and_then(|(_, r)| r.as_dict().ok());
+                let mut nested: Vec<&Dictionary> = form_resources.into_iter().collect();
+                nested.extend_from_slice(resources);
+
+                let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                Self::walk_content(doc, &data, &nested, ctm, depth + 1, visited, stats);
+
+                if let Some(id) = id {
+                    visited.remove(&id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Name of the scanning app that produced the document, if recognisable
+    fn scan_app(doc: &Document) -> Option<String> {
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .ok()
+            .and_then(|info| doc.dereference(info).ok())
+            .and_then(|(_, info)| info.as_dict().ok())?;
+
+        [b"Producer".as_slice(), b"Creator".as_slice()]
+            .iter()
+            .filter_map(|key| info.get(key).ok().and_then(|v| v.as_str().ok()))
+            .map(|value| String::from_utf8_lossy(value).to_string())
+            .find_map(|value| {
+                SCAN_APPS
+                    .iter()
+                    .find(|app| value.contains(*app))
+                    .map(|app| app.to_string())
+            })
+    }
+}
+
+fn find_xobject<'a>(
+    doc: &'a Document,
+    resources: &[&'a Dictionary],
+    name: &[u8],
+) -> Option<(Option<ObjectId>, &'a lopdf::Stream)> {
+    resources.iter().find_map(|dict| {
+        let (_, xobjects) = doc.dereference(dict.get(b"XObject").ok()?).ok()?;
+        let entry = xobjects.as_dict().ok()?.get(name).ok()?;
+        let (id, object) = doc.dereference(entry).ok()?;
+        Some((id, object.as_stream().ok()?))
+    })
+}
+
+/// Width and height of a page in points
+pub(crate) fn page_size(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    let [x0, y0, x1, y1] = media_box(doc, page_id);
+    ((x1 - x0).abs(), (y1 - y0).abs())
+}
+
+/// A page's MediaBox as `[x0, y0, x1, y1]`, defaulting to US Letter
+pub(crate) fn media_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    inherited_page_attribute(doc, page_id, b"MediaBox")
+        .and_then(|media_box| {
+            let values: Vec<f32> = media_box.as_array().ok()?.iter().filter_map(number).collect();
+            <[f32; 4]>::try_from(values).ok()
+        })
+        .unwrap_or([0.0, 0.0, DEFAULT_PAGE_SIZE.0, DEFAULT_PAGE_SIZE.1])
+}
+
+/// Look up an inheritable page attribute (MediaBox, Rotate, ...) walking up the page tree
+pub(crate) fn inherited_page_attribute<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok();
+    let mut hops = 0;
+    while let Some(dict) = node {
+        if let Ok(value) = dict.get(key) {
+            return doc.dereference(value).ok().map(|(_, value)| value);
+        }
+        hops += 1;
+        if hops > 32 {
+            break;
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    None
+}
+
+pub(crate) fn number(object: &Object) -> Option<f32> {
+    match object {
+        Object::Integer(i) => Some(*i as f32),
+        Object::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+fn matrix_from(operands: &[Object]) -> Option<Matrix> {
+    if operands.len() != 6 {
+        return None;
+    }
+    let mut matrix = [0.0; 6];
+    for (slot, operand) in matrix.iter_mut().zip(operands) {
+        *slot = number(operand)?;
+    }
+    Some(matrix)
+}
+
+/// `m × ctm`, the PDF convention for concatenating a matrix onto the CTM
+fn multiply(m: &Matrix, ctm: &Matrix) -> Matrix {
+    [
+        m[0] * ctm[0] + m[1] * ctm[2],
+        m[0] * ctm[1] + m[1] * ctm[3],
+        m[2] * ctm[0] + m[3] * ctm[2],
+        m[2] * ctm[1] + m[3] * ctm[3],
+        m[4] * ctm[0] + m[5] * ctm[2] + ctm[4],
+        m[4] * ctm[1] + m[5] * ctm[3] + ctm[5],
+    ]
+}
+
+/// Area of the unit square after transformation by `m`
+fn area(m: &Matrix) -> f32 {
+    (m[0] * m[3] - m[1] * m[2]).abs()
+}
@@ -0,0 +1,263 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{BoundingBox, DocumentLayout, QueryResult, SearchHit, SearchQuery};
+
+/// Most queries one request can carry
+pub const MAX_SEARCH_QUERIES: usize = 50;
+/// Hits returned per query; the result is marked truncated beyond this
+pub const MAX_HITS_PER_QUERY: usize = 1000;
+/// Longest pattern accepted, in bytes
+const MAX_PATTERN_LENGTH: usize = 1000;
+/// Upper bound on the compiled size of one regular expression
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Characters of context on each side of a hit in its snippet
+const SNIPPET_CONTEXT: usize = 60;
+
+/// A word of the page layout and the characters it covers in the page text
+#[derive(Debug, Clone)]
+struct WordSpan {
+    start: usize,
+    end: usize,
+    /// Index of the word's line on the page
+    line: usize,
+    bbox: BoundingBox,
+}
+
+/// The searchable text of one page, with the layout word behind each stretch of it
+#[derive(Debug, Clone)]
+pub struct PageText {
+    /// 1-based page number
+    pub number: u32,
+    /// Lines separated by `\n`, blocks by a blank line
+    pub text: String,
+    chars: Vec<char>,
+    words: Vec<WordSpan>,
+}
+
+impl PageText {
+    /// Page text without a layout, so hits get no bounding boxes
+    pub fn new(number: u32, text: &str) -> Self {
+        Self {
+            number,
+            text: text.to_string(),
+            chars: text.chars().collect(),
+            words: Vec::new(),
+        }
+    }
+
+    /// Approximate heap memory the page takes
+    pub fn memory_bytes(&self) -> usize {
+        self.text.len()
+            + self.chars.len() * std::mem::size_of::<char>()
+            + self.words.len() * std::mem::size_of::<WordSpan>()
+    }
+
+    fn hit(&self, start: usize, end: usize) -> SearchHit {
+        let mut snippet_start = start.saturating_sub(SNIPPET_CONTEXT);
+        if snippet_start > 0 {
+            // Don't start the snippet in the middle of a word
+            if let Some(space) = self.chars[snippet_start..start].iter().position(|c| c.is_whitespace()) {
+                snippet_start += space + 1;
+            }
+        }
+        let mut snippet_end = (end + SNIPPET_CONTEXT).min(self.chars.len());
+        if snippet_end < self.chars.len() {
+            if let Some(space) = self.chars[end..snippet_end].iter().rposition(|c| c.is_whitespace()) {
+                snippet_end = end + space;
+            }
+        }
+
+        // One box per line the hit touches; words are in text order, so the first
+        // one the hit reaches is found by binary search
+        let first = self.words.partition_point(|word| word.end <= start);
+        let mut bboxes: Vec<(usize, BoundingBox)> = Vec::new();
+        for word in self.words[first..].iter().take_while(|word| word.start < end) {
+            match bboxes.last_mut() {
+                Some((line, bbox)) if *line == word.line => *bbox = bbox.union(&word.bbox),
+                _ => bboxes.push((word.line, word.bbox)),
+            }
+        }
+
+        SearchHit {
+            page: self.number,
+            start,
+            end,
+            text: self.chars[start..end].iter().collect(),
+            // Line breaks become spaces one for one, so offsets into the snippet still line up
+            snippet: self.chars[snippet_start..snippet_end]
+                .iter()
+                .map(|&c| if c == '\n' { ' ' } else { c })
+                .collect(),
+            snippet_start,
+            bboxes: bboxes.into_iter().map(|(_, bbox)| bbox).collect(),
+        }
+    }
+}
+
+/// The pages of an extraction: built from the layout when it has words, so
+/// hits can be located, otherwise the text split at form feeds
+pub fn page_texts(text: &str, layout: Option<&DocumentLayout>) -> Vec<PageText> {
+    let Some(layout) = layout.filter(|layout| !layout.is_empty()) else {
+        return text.split('\x0c').zip(1u32..).map(|(page, number)| PageText::new(number, page)).collect();
+    };
+
+    layout
+        .pages
+        .iter()
+        .map(|page| {
+            let (mut text, mut length) = (String::new(), 0);
+            let mut words = Vec::new();
+            let mut line_index = 0;
+            for (block_index, block) in page.blocks.iter().enumerate() {
+                if block_index > 0 {
+                    append(&mut text, &mut length, "\n\n");
+                }
+                for (index, line) in block.lines.iter().enumerate() {
+                    if index > 0 {
+                        append(&mut text, &mut length, "\n");
+                    }
+                    for (word_index, word) in line.words.iter().enumerate() {
+                        if word_index > 0 {
+                            append(&mut text, &mut length, " ");
+                        }
+                        let start = length;
+                        append(&mut text, &mut length, &word.text);
+                        words.push(WordSpan {
+                            start,
+                            end: length,
+                            line: line_index,
+                            bbox: word.bbox,
+                        });
+                    }
+                    line_index += 1;
+                }
+            }
+            PageText {
+                number: page.number,
+                chars: text.chars().collect(),
+                text,
+                words,
+            }
+        })
+        .collect()
+}
+
+/// Add `s` to `text`, keeping its length in characters
+fn append(text: &mut String, length: &mut usize, s: &str) {
+    text.push_str(s);
+    *length += s.chars().count();
+}
+
+/// Compile a query, rejecting empty or oversized patterns and invalid regular expressions.
+///
+/// Whitespace in a literal pattern matches any run of whitespace, line breaks
+/// included, so a phrase is found even when it wraps.
+pub fn compile_query(query: &SearchQuery) -> AppResult<Regex> {
+    if query.pattern.trim().is_empty() {
+        return Err(AppError::validation("Search patterns must not be empty"));
+    }
+    if query.pattern.len() > MAX_PATTERN_LENGTH {
+        return Err(AppError::validation(format!(
+            "Search patterns are limited to {} bytes",
+            MAX_PATTERN_LENGTH
+        )));
+    }
+
+    let mut pattern = if query.regex {
+        query.pattern.clone()
+    } else {
+        query
+            .pattern
+            .split_whitespace()
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(r"\s+")
+    };
+    if query.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .multi_line(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| AppError::validation(format!("Invalid search pattern '{}': {}", query.pattern, e)))
+}
+
+/// Queries that passed validation, compiled and ready to run against the pages
+#[derive(Debug, Clone)]
+pub struct CompiledQueries {
+    queries: Vec<SearchQuery>,
+    regexes: Vec<Regex>,
+}
+
+impl CompiledQueries {
+    /// Check the number of queries and compile each of them, see [`compile_query`]
+    pub fn new(queries: Vec<SearchQuery>) -> AppResult<Self> {
+        if queries.is_empty() {
+            return Err(AppError::validation("Send at least one search query"));
+        }
+        if queries.len() > MAX_SEARCH_QUERIES {
+            return Err(AppError::validation(format!(
+                "At most {} search queries are allowed per request",
+                MAX_SEARCH_QUERIES
+            )));
+        }
+        let regexes = queries.iter().map(compile_query).collect::<AppResult<Vec<_>>>()?;
+        Ok(Self { queries, regexes })
+    }
+
+    pub fn queries(&self) -> &[SearchQuery] {
+        &self.queries
+    }
+
+    /// Every hit of every query, page by page; empty matches are skipped
+    pub fn search(&self, pages: &[PageText]) -> Vec<QueryResult> {
+        self.queries
+            .iter()
+            .zip(&self.regexes)
+            .map(|(query, regex)| search_query(pages, query, regex))
+            .collect()
+    }
+}
+
+/// Every hit of every query, page by page; empty matches are skipped
+pub fn search_pages(pages: &[PageText], queries: &[SearchQuery]) -> AppResult<Vec<QueryResult>> {
+    Ok(CompiledQueries::new(queries.to_vec())?.search(pages))
+}
+
+fn search_query(pages: &[PageText], query: &SearchQuery, regex: &Regex) -> QueryResult {
+
+    let mut hits = Vec::new();
+    let mut truncated = false;
+    'pages: for page in pages {
+        // Matches come in order, so byte offsets convert to characters in one pass
+        let (mut byte, mut chars) = (0, 0);
+        let mut char_offset = |to: usize| {
+            chars += page.text[byte..to].chars().count();
+            byte = to;
+            chars
+        };
+        for found in regex.find_iter(&page.text).filter(|found| !found.is_empty()) {
+            if hits.len() == MAX_HITS_PER_QUERY {
+                truncated = true;
+                break 'pages;
+            }
+            let start = char_offset(found.start());
+            let end = char_offset(found.end());
+            hits.push(page.hit(start, end));
+        }
+    }
+
+    let mut hit_pages: Vec<u32> = hits.iter().map(|hit: &SearchHit| hit.page).collect();
+    hit_pages.dedup();
+    QueryResult {
+        query: query.clone(),
+        hit_count: hits.len(),
+        pages: hit_pages,
+        truncated,
+        hits,
+    }
+}
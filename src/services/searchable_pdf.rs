@@ -0,0 +1,258 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tracing::{debug, info, warn};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{PreprocessingOptions, PreprocessingStep};
+use crate::services::ocr_cache::OcrCache;
+use crate::services::ocr_engine::{OcrEngine, OcrSettings, RecognizedPage};
+use crate::services::ocr_service::OcrService;
+use crate::services::scan_detector::{inherited_page_attribute, media_box, number, ScanDetector};
+
+/// Resolution scanned pages are rasterized at before recognition
+const RENDER_DPI: u32 = 300;
+/// Resource name of the font used for the text layer
+const OCR_FONT: &[u8] = b"FOcr";
+/// Average Helvetica glyph width as a fraction of the font size
+const AVERAGE_GLYPH_WIDTH: f32 = 0.5;
+
+/// A PDF with an invisible OCR text layer on its scanned pages
+#[derive(Debug, Clone)]
+pub struct SearchablePdf {
+    pub content: Vec<u8>,
+    /// Pages (1-based) that received a text layer
+    pub ocr_pages: Vec<u32>,
+    /// Scanned pages that were left untouched, with the reason
+    pub skipped_pages: Vec<(u32, String)>,
+}
+
+/// Adds an invisible text layer to the scanned pages of a PDF.
+///
+/// Pages that already carry native text are left alone.
+pub struct SearchablePdfBuilder {
+    ocr: OcrService,
+    dpi: u32,
+}
+
+impl SearchablePdfBuilder {
+    pub fn new(engine: Arc<dyn OcrEngine>, preprocessing: PreprocessingOptions) -> Self {
+        // Word boxes must line up with the original page, so only pixel-level cleanup is allowed
+        let steps = preprocessing
+            .steps
+            .into_iter()
+            .filter(|step| !matches!(step, PreprocessingStep::AutoRotate | PreprocessingStep::Deskew))
+            .collect();
+
+        Self {
+            ocr: OcrService::with_engine(engine).with_preprocessing(PreprocessingOptions { steps }),
+            dpi: RENDER_DPI,
+        }
+    }
+
+    /// Language and page segmentation mode for the text layer
+    pub fn with_settings(mut self, settings: OcrSettings) -> Self {
+        self.ocr = self.ocr.with_settings(settings);
+        self
+    }
+
+    /// Reuse OCR results for page renders seen before
+    pub fn with_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.ocr = self.ocr.with_cache(cache);
+        self
+    }
+
+    pub fn with_dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    pub async fn build(&self, pdf_data: &[u8]) -> AppResult<SearchablePdf> {
+        let mut doc = Document::load_mem(pdf_data).map_err(|e| AppError::InvalidFile {
+            message: format!("Failed to parse PDF: {}", e),
+        })?;
+
+        let analysis = ScanDetector::analyze_document(&doc);
+        let scanned: Vec<u32> = analysis
+            .iter()
+            .filter(|page| page.needs_ocr())
+            .map(|page| page.page)
+            .collect();
+
+        if scanned.is_empty() {
+            info!("No scanned pages found, returning the PDF unchanged");
+            return Ok(SearchablePdf {
+                content: pdf_data.to_vec(),
+                ocr_pages: Vec::new(),
+                skipped_pages: Vec::new(),
+            });
+        }
+
+        let engine = self.ocr.engine();
+        if let Err(e) = engine.health_check().await {
+            warn!(engine = engine.name(), "OCR engine is not available: {}", e);
+            return Err(AppError::OcrError {
+                message: format!("The {} OCR engine is not available: {}", engine.name(), e),
+            });
+        }
+
+        let work_dir = TempDir::new()
+            .map_err(|e| AppError::internal(format!("Failed to create temp directory: {}", e)))?;
+        let pdf_path = work_dir.path().join("input.pdf");
+        std::fs::write(&pdf_path, pdf_data)
+            .map_err(|e| AppError::internal(format!("Failed to write temp PDF: {}", e)))?;
+
+        let pages = doc.get_pages();
+        let mut ocr_pages = Vec::new();
+        let mut skipped_pages = Vec::new();
+
+        for (index, number) in scanned.into_iter().enumerate() {
+            let Some(&page_id) = pages.get(&number) else {
+                continue;
+            };
+
+            // Rendered pixels of a rotated page don't map onto its unrotated MediaBox
+            let rotation = page_rotation(&doc, page_id);
+            if rotation.rem_euclid(360) != 0 {
+                debug!(page = number, rotation, "Skipping rotated page");
+                skipped_pages.push((number, format!("page is rotated by {} degrees", rotation)));
+                continue;
+            }
+
+            let image = OcrService::render_pdf_page(&pdf_path, number, self.dpi, work_dir.path()).await?;
+            match self.ocr.recognize_image(&image, work_dir.path(), index).await {
+                Ok((page, _)) => {
+                    let (width, height) = image::image_dimensions(&image).unwrap_or((0, 0));
+                    let page = RecognizedPage {
+                        width: if page.width > 0 { page.width } else { width },
+                        height: if page.height > 0 { page.height } else { height },
+                        ..page
+                    };
+                    add_text_layer(&mut doc, page_id, &page)?;
+                    ocr_pages.push(number);
+                }
+                Err(e @ AppError::Timeout { .. }) => return Err(e),
+                Err(e) => {
+                    warn!(page = number, "OCR failed: {}", e);
+                    skipped_pages.push((number, format!("OCR failed: {}", e)));
+                }
+            }
+        }
+
+        let mut content = Vec::new();
+        doc.save_to(&mut content)
+            .map_err(|e| AppError::processing(format!("Failed to write searchable PDF: {}", e)))?;
+
+        info!(
+            ocr_pages = ocr_pages.len(),
+            skipped_pages = skipped_pages.len(),
+            "Searchable PDF created"
+        );
+
+        Ok(SearchablePdf {
+            content,
+            ocr_pages,
+            skipped_pages,
+        })
+    }
+}
+
+/// Draw the recognised words of `page` as invisible text over a page.
+///
+/// The page image is assumed to cover the whole MediaBox, word boxes are scaled
+/// from image pixels to page space. The original content is wrapped in `q`/`Q`
+/// so its graphics state cannot shift the text layer.
+pub fn add_text_layer(doc: &mut Document, page_id: ObjectId, page: &RecognizedPage) -> AppResult<()> {
+    if page.width == 0 || page.height == 0 {
+        return Err(AppError::processing("Recognised page has no image dimensions"));
+    }
+
+    let [x0, y0, x1, y1] = media_box(doc, page_id);
+    let scale_x = (x1 - x0).abs() / page.width as f32;
+    let scale_y = (y1 - y0).abs() / page.height as f32;
+    let (left_edge, top_edge) = (x0.min(x1), y0.max(y1));
+
+    let mut operations = vec![Operation::new("Q", vec![])];
+    for word in &page.words {
+        let characters = word.text.chars().count();
+        if characters == 0 || word.height == 0 {
+            continue;
+        }
+
+        let size = word.height as f32 * scale_y;
+        let target_width = word.width as f32 * scale_x;
+        let horizontal_scale = 100.0 * target_width / (AVERAGE_GLYPH_WIDTH * size * characters as f32);
+        let x = left_edge + word.left as f32 * scale_x;
+        // Put the baseline near the bottom of the word box
+        let y = top_edge - (word.top + word.height) as f32 * scale_y + size * 0.2;
+
+        operations.extend([
+            Operation::new("BT", vec![]),
+            Operation::new("Tr", vec![3.into()]),
+            Operation::new("Tf", vec![Object::Name(OCR_FONT.to_vec()), size.into()]),
+            Operation::new("Tz", vec![horizontal_scale.into()]),
+            Operation::new("Tm", vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()]),
+            Operation::new(
+                "Tj",
+                vec![Object::string_literal(Document::encode_text(Some("WinAnsiEncoding"), &word.text))],
+            ),
+            Operation::new("ET", vec![]),
+        ]);
+    }
+
+    let layer = Content { operations }
+        .encode()
+        .map_err(|e| AppError::processing(format!("Failed to encode text layer: {}", e)))?;
+
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let resources = resources_with_font(doc, page_id, font_id);
+
+    let mut contents: Vec<Object> = vec![doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec())).into()];
+    contents.extend(doc.get_page_contents(page_id).into_iter().map(Object::Reference));
+    let mut layer_stream = Stream::new(Dictionary::new(), layer);
+    let _ = layer_stream.compress();
+    contents.push(doc.add_object(layer_stream).into());
+
+    let page_dict = doc
+        .get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| AppError::processing(format!("Failed to update page: {}", e)))?;
+    page_dict.set("Contents", contents);
+    page_dict.set("Resources", resources);
+
+    Ok(())
+}
+
+/// The page's effective resources as a direct dictionary, with the OCR font added
+fn resources_with_font(doc: &Document, page_id: ObjectId, font_id: ObjectId) -> Dictionary {
+    let mut resources = inherited_page_attribute(doc, page_id, b"Resources")
+        .and_then(|resources| resources.as_dict().ok())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut fonts = resources
+        .get(b"Font")
+        .ok()
+        .and_then(|fonts| doc.dereference(fonts).ok())
+        .and_then(|(_, fonts)| fonts.as_dict().ok())
+        .cloned()
+        .unwrap_or_default();
+    fonts.set(OCR_FONT.to_vec(), font_id);
+    resources.set("Font", fonts);
+
+    resources
+}
+
+/// The page's `/Rotate` value in degrees
+fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    inherited_page_attribute(doc, page_id, b"Rotate")
+        .and_then(number)
+        .map(|rotation| rotation as i64)
+        .unwrap_or(0)
+}
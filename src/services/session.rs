@@ -0,0 +1,179 @@
+use std::mem::size_of;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    LayoutBlock, LayoutLine, LayoutWord, OutputFormat, SearchQuery, SessionDocument, SessionMessage, SessionPage,
+    Table,
+};
+use crate::services::export::render_extraction;
+use crate::services::pdf_processor::ExtractionResult;
+use crate::services::search::{page_texts, search_pages, PageText};
+use crate::services::tables::detect_tables;
+
+/// Limits an interactive session runs under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// The session is closed after this long without a message from the client
+    pub idle_timeout: Duration,
+    /// Most memory a loaded document may take
+    pub max_memory_bytes: usize,
+}
+
+impl SessionLimits {
+    pub fn new(idle_timeout: Duration, max_memory_bytes: usize) -> Self {
+        Self {
+            idle_timeout,
+            max_memory_bytes,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            Duration::from_secs(config.session_idle_timeout_seconds),
+            config.session_max_memory_mb * 1024 * 1024,
+        )
+    }
+}
+
+/// A document extracted once and kept to answer the commands of a session
+#[derive(Debug)]
+pub struct DocumentSession {
+    file_name: String,
+    result: ExtractionResult,
+    pages: Vec<PageText>,
+    memory_bytes: usize,
+}
+
+impl DocumentSession {
+    /// Keep an extraction, refusing it when it would take more than `max_memory_bytes`
+    pub fn new(file_name: String, result: ExtractionResult, max_memory_bytes: usize) -> AppResult<Self> {
+        let pages = page_texts(&result.text, result.layout.as_ref());
+        let memory_bytes = result.text.len()
+            + layout_memory_bytes(&result)
+            + pages.iter().map(PageText::memory_bytes).sum::<usize>();
+        if memory_bytes > max_memory_bytes {
+            return Err(AppError::session_limit(format!(
+                "{} needs about {:.1}MB, more than the {:.1}MB a session may hold",
+                file_name,
+                megabytes(memory_bytes),
+                megabytes(max_memory_bytes)
+            )));
+        }
+
+        Ok(Self {
+            file_name,
+            result,
+            pages,
+            memory_bytes,
+        })
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_bytes
+    }
+
+    pub fn document(&self) -> SessionDocument {
+        SessionDocument {
+            file_name: self.file_name.clone(),
+            pages: self.result.pages,
+            memory_bytes: self.memory_bytes,
+            metadata: self.result.metadata.clone(),
+        }
+    }
+
+    /// The whole text in one of the document formats
+    pub fn text(&self, format: OutputFormat) -> AppResult<SessionMessage> {
+        if format == OutputFormat::Json {
+            return Err(AppError::validation("Text is available as text, markdown, alto or hocr"));
+        }
+        let text = render_extraction(format, &self.result, &self.file_name)
+            .ok_or_else(|| AppError::validation(format!("The document has no layout to export as {}", format.as_str())))?;
+        Ok(SessionMessage::Text {
+            format,
+            content_type: format.content_type().to_string(),
+            text,
+        })
+    }
+
+    /// The text of every page, or of the pages in `selection`, in page order
+    pub fn pages(&self, selection: Option<&[u32]>) -> AppResult<Vec<SessionPage>> {
+        self.check_pages(selection)?;
+        Ok(self
+            .pages
+            .iter()
+            .filter(|page| selection.is_none_or(|selection| selection.contains(&page.number)))
+            .map(|page| SessionPage {
+                page: page.number,
+                text: page.text.clone(),
+                source: self
+                    .result
+                    .layout
+                    .as_ref()
+                    .and_then(|layout| layout.pages.iter().find(|layout_page| layout_page.number == page.number))
+                    .map(|layout_page| layout_page.source),
+            })
+            .collect())
+    }
+
+    /// Tables on every page, or on the pages in `selection`
+    pub fn tables(&self, selection: Option<&[u32]>) -> AppResult<SessionMessage> {
+        self.check_pages(selection)?;
+        let layout = self.result.layout.clone().unwrap_or_default();
+        let tables: Vec<Table> = detect_tables(&layout)
+            .into_iter()
+            .filter(|table| selection.is_none_or(|selection| selection.contains(&table.page)))
+            .collect();
+        Ok(SessionMessage::Tables {
+            tables,
+            unit: layout.unit,
+        })
+    }
+
+    pub fn search(&self, queries: &[SearchQuery]) -> AppResult<SessionMessage> {
+        let results = search_pages(&self.pages, queries)?;
+        let total_hits = results.iter().map(|result| result.hit_count).sum();
+        let located = results.iter().flat_map(|result| &result.hits).any(|hit| !hit.bboxes.is_empty());
+        Ok(SessionMessage::Search {
+            results,
+            total_hits,
+            unit: self.result.layout.as_ref().filter(|_| located).map(|layout| layout.unit),
+        })
+    }
+
+    fn check_pages(&self, selection: Option<&[u32]>) -> AppResult<()> {
+        let count = self.pages.len() as u32;
+        match selection.into_iter().flatten().find(|&&page| page == 0 || page > count) {
+            Some(page) => Err(AppError::validation(format!(
+                "Page {} is out of range, the document has {} pages",
+                page, count
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Approximate heap memory of the word layout
+fn layout_memory_bytes(result: &ExtractionResult) -> usize {
+    let Some(layout) = &result.layout else {
+        return 0;
+    };
+    layout
+        .pages
+        .iter()
+        .map(|page| {
+            let structure: usize = page
+                .blocks
+                .iter()
+                .map(|block| size_of::<LayoutBlock>() + block.lines.len() * size_of::<LayoutLine>())
+                .sum();
+            let words: usize = page.words().map(|word| size_of::<LayoutWord>() + word.text.len()).sum();
+            structure + words
+        })
+        .sum()
+}
+
+fn megabytes(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
@@ -0,0 +1,341 @@
+use lopdf::Document;
+use std::sync::Arc;
+use std::time::Instant;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    ErrorDetail, ExtractResponse, ExtractionOptions, PdfMetadata, PreprocessingStep, ProcessedFile,
+    StreamEvent, TextSource,
+};
+use crate::services::ocr_cache::OcrCache;
+use crate::services::ocr_engine::{OcrEngine, OcrSettings};
+use crate::services::ocr_service::{spawn_write_image_pages, OcrService};
+use crate::services::pdf_processor::PdfProcessor;
+use crate::services::scan_detector::ScanDetector;
+
+/// Events buffered before extraction waits for a slow client
+pub const STREAM_EVENT_BUFFER: usize = 16;
+
+/// Why a stream ended early
+enum StreamStop {
+    Failed(AppError),
+    /// The receiving end went away, e.g. the client disconnected
+    Disconnected,
+}
+
+impl From<AppError> for StreamStop {
+    fn from(error: AppError) -> Self {
+        StreamStop::Failed(error)
+    }
+}
+
+/// Extracts a document page by page, sending each page as soon as it is done.
+///
+/// Unlike [`PdfProcessor`], which decides between native text and OCR for the
+/// whole document, pages are judged one at a time: scanned pages are rendered
+/// and OCRed, the rest use their native text.
+pub struct PageStreamer {
+    options: ExtractionOptions,
+    ocr_engine: Option<Arc<dyn OcrEngine>>,
+    ocr_cache: Option<Arc<OcrCache>>,
+    dpi: u32,
+}
+
+/// Running totals for the summary event
+#[derive(Default)]
+struct StreamTotals {
+    pages: Vec<String>,
+    ocr_used: bool,
+    preprocessing_applied: Vec<PreprocessingStep>,
+    cache_hits: usize,
+}
+
+impl StreamTotals {
+    fn add_ocr_steps(&mut self, steps: Vec<PreprocessingStep>) {
+        self.ocr_used = true;
+        for step in steps {
+            if !self.preprocessing_applied.contains(&step) {
+                self.preprocessing_applied.push(step);
+            }
+        }
+    }
+
+    fn text(&self) -> String {
+        self.pages
+            .iter()
+            .map(|page| page.trim())
+            .filter(|page| !page.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl PageStreamer {
+    pub fn new() -> Self {
+        Self {
+            options: ExtractionOptions::default(),
+            ocr_engine: None,
+            ocr_cache: None,
+            dpi: 300,
+        }
+    }
+
+    pub fn with_options(mut self, options: ExtractionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_ocr_engine(mut self, engine: Arc<dyn OcrEngine>) -> Self {
+        self.ocr_engine = Some(engine);
+        self
+    }
+
+    pub fn with_ocr_cache(mut self, cache: Arc<OcrCache>) -> Self {
+        self.ocr_cache = Some(cache);
+        self
+    }
+
+    /// Resolution scanned PDF pages are rendered at for OCR
+    pub fn with_dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    fn ocr_service(&self) -> AppResult<OcrService> {
+        let service = match &self.ocr_engine {
+            Some(engine) => OcrService::with_engine(engine.clone()),
+            None => OcrService::new()?,
+        };
+        let service = service
+            .with_preprocessing(self.options.preprocessing())
+            .with_settings(OcrSettings::from(&self.options));
+        Ok(match &self.ocr_cache {
+            Some(cache) => service.with_cache(cache.clone()),
+            None => service,
+        })
+    }
+
+    /// Send the events for `file` to `events`.
+    ///
+    /// Always ends with a `summary` or an `error` event, unless the receiver is
+    /// dropped first, in which case work stops at the next page.
+    pub async fn run(&self, file: ProcessedFile, events: mpsc::Sender<StreamEvent>) {
+        let start = Instant::now();
+        let file_name = file.name.clone();
+
+        let outcome = if file.is_image() {
+            self.stream_image(file, &events, start).await
+        } else if file.is_pdf() {
+            self.stream_pdf(file, &events, start).await
+        } else {
+            Err(StreamStop::Failed(AppError::InvalidFile {
+                message: "File is not a valid PDF document or supported image (PNG, JPEG, TIFF, WebP)".to_string(),
+            }))
+        };
+
+        match outcome {
+            Ok(()) => info!(
+                file_name = %file_name,
+                elapsed_ms = start.elapsed().as_millis() as u64,
+                "Streamed extraction completed"
+            ),
+            Err(StreamStop::Failed(e)) => {
+                warn!(file_name = %file_name, error = %e, "Streamed extraction failed");
+                let error = ErrorDetail::new(e.error_code(), e.to_string());
+                let _ = events.send(StreamEvent::Error { error }).await;
+            }
+            Err(StreamStop::Disconnected) => {
+                info!(file_name = %file_name, "Stream receiver went away, stopping extraction")
+            }
+        }
+    }
+
+    async fn stream_pdf(
+        &self,
+        file: ProcessedFile,
+        events: &mpsc::Sender<StreamEvent>,
+        start: Instant,
+    ) -> Result<(), StreamStop> {
+        let doc = Document::load_mem(&file.content).map_err(|e| AppError::InvalidFile {
+            message: format!("Failed to parse PDF: {}", e),
+        })?;
+        let page_analysis = ScanDetector::analyze_document(&doc);
+        let pages_total = doc.get_pages().len();
+        emit(events, StreamEvent::Started { file_name: file.name.clone(), pages_total }).await?;
+
+        let native_pages = match pdf_extract::extract_text_from_mem_by_pages(&file.content) {
+            Ok(pages) => pages,
+            Err(e) => {
+                warn!("Per-page text extraction failed: {}, relying on OCR", e);
+                Vec::new()
+            }
+        };
+
+        // Rendering needs the PDF on disk; only set that up once a page needs OCR
+        let mut ocr: Option<(OcrService, TempDir, std::path::PathBuf)> = None;
+        let mut totals = StreamTotals::default();
+
+        for index in 0..pages_total {
+            let number = index + 1;
+            let native_text = native_pages.get(index).cloned().unwrap_or_default();
+            let analysis = page_analysis.iter().find(|a| a.page as usize == number);
+            let needs_ocr = analysis.is_some_and(|a| {
+                a.needs_ocr() || (a.image_count > 0 && self.options.wants_ocr_for(native_text.trim()))
+            });
+
+            let (text, source) = if needs_ocr {
+                emit(events, StreamEvent::Progress {
+                    stage: "ocr".to_string(),
+                    pages_done: index,
+                    pages_total,
+                })
+                .await?;
+
+                if ocr.is_none() {
+                    ocr = Some(prepare_pdf_ocr(self, &file.content)?);
+                }
+                let (service, work_dir, pdf_path) = ocr.as_ref().expect("OCR prepared above");
+
+                match ocr_pdf_page(service, pdf_path, work_dir, number, self.dpi).await {
+                    Ok((text, steps, cached)) => {
+                        totals.add_ocr_steps(steps);
+                        totals.cache_hits += usize::from(cached);
+                        (text, TextSource::Ocr)
+                    }
+                    Err(e @ AppError::Timeout { .. }) => return Err(e.into()),
+                    Err(e) => {
+                        warn!(page = number, "OCR failed, using native text: {}", e);
+                        (native_text, TextSource::Native)
+                    }
+                }
+            } else {
+                (native_text, TextSource::Native)
+            };
+
+            totals.pages.push(text.clone());
+            emit(events, StreamEvent::Page { page: number, text: text.trim().to_string(), source }).await?;
+        }
+
+        let processor = PdfProcessor::new();
+        let mut metadata = PdfMetadata::new(file.size)
+            .with_title(processor.extract_title(&file.content))
+            .with_author(processor.extract_author(&file.content))
+            .with_page_analysis(page_analysis);
+        if totals.ocr_used {
+            metadata = metadata.with_ocr();
+        }
+        let summary = summary(&mut totals, metadata, &self.options, pages_total, start);
+        emit(events, summary).await
+    }
+
+    async fn stream_image(
+        &self,
+        file: ProcessedFile,
+        events: &mpsc::Sender<StreamEvent>,
+        start: Instant,
+    ) -> Result<(), StreamStop> {
+        let service = self.ocr_service()?;
+        let work_dir = TempDir::new()
+            .map_err(|e| AppError::OcrError { message: format!("Failed to create temp directory: {}", e) })?;
+        let frames = spawn_write_image_pages(file.content.clone(), work_dir.path().to_path_buf()).await?;
+        service.engine().health_check().await.map_err(|e| AppError::OcrError {
+            message: format!("The {} OCR engine is not available: {}", service.engine().name(), e),
+        })?;
+
+        let pages_total = frames.len();
+        emit(events, StreamEvent::Started { file_name: file.name.clone(), pages_total }).await?;
+
+        let mut totals = StreamTotals::default();
+        for (index, path) in frames.iter().enumerate() {
+            emit(events, StreamEvent::Progress {
+                stage: "ocr".to_string(),
+                pages_done: index,
+                pages_total,
+            })
+            .await?;
+
+            let text = match service.recognize_image_with_cache_status(path, work_dir.path(), index).await {
+                Ok((entry, cached)) => {
+                    totals.add_ocr_steps(entry.preprocessing_applied);
+                    totals.cache_hits += usize::from(cached);
+                    entry.page.text
+                }
+                Err(e @ AppError::Timeout { .. }) => return Err(e.into()),
+                Err(e) => {
+                    warn!(page = index + 1, "OCR failed for page: {}", e);
+                    totals.ocr_used = true;
+                    String::new()
+                }
+            };
+
+            totals.pages.push(text.clone());
+            let page = StreamEvent::Page {
+                page: index + 1,
+                text: text.trim().to_string(),
+                source: TextSource::Ocr,
+            };
+            emit(events, page).await?;
+        }
+
+        let metadata = PdfMetadata::new(file.size).with_ocr();
+        let summary = summary(&mut totals, metadata, &self.options, pages_total, start);
+        emit(events, summary).await
+    }
+}
+
+impl Default for PageStreamer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn emit(events: &mpsc::Sender<StreamEvent>, event: StreamEvent) -> Result<(), StreamStop> {
+    events.send(event).await.map_err(|_| StreamStop::Disconnected)
+}
+
+fn summary(
+    totals: &mut StreamTotals,
+    metadata: PdfMetadata,
+    options: &ExtractionOptions,
+    pages: usize,
+    start: Instant,
+) -> StreamEvent {
+    totals.preprocessing_applied.sort();
+    let metadata = metadata
+        .with_preprocessing(std::mem::take(&mut totals.preprocessing_applied))
+        .with_cache_hits(totals.cache_hits);
+    let response = ExtractResponse::new(totals.text(), pages, metadata, start.elapsed().as_millis() as u64)
+        .with_options(options.clone());
+    StreamEvent::Summary(Box::new(response))
+}
+
+/// OCR service plus a temporary copy of the PDF for the page renderer
+fn prepare_pdf_ocr(
+    streamer: &PageStreamer,
+    content: &[u8],
+) -> AppResult<(OcrService, TempDir, std::path::PathBuf)> {
+    let service = streamer.ocr_service()?;
+    let work_dir = TempDir::new()
+        .map_err(|e| AppError::OcrError { message: format!("Failed to create temp directory: {}", e) })?;
+    let pdf_path = work_dir.path().join("input.pdf");
+    std::fs::write(&pdf_path, content)
+        .map_err(|e| AppError::OcrError { message: format!("Failed to write PDF for rendering: {}", e) })?;
+    Ok((service, work_dir, pdf_path))
+}
+
+async fn ocr_pdf_page(
+    service: &OcrService,
+    pdf_path: &std::path::Path,
+    work_dir: &TempDir,
+    number: usize,
+    dpi: u32,
+) -> AppResult<(String, Vec<PreprocessingStep>, bool)> {
+    let image = OcrService::render_pdf_page(pdf_path, number as u32, dpi, work_dir.path()).await?;
+    let (entry, cached) = service
+        .recognize_image_with_cache_status(&image, work_dir.path(), number - 1)
+        .await?;
+    Ok((entry.page.text, entry.preprocessing_applied, cached))
+}
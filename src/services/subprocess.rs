@@ -0,0 +1,160 @@
+use once_cell::sync::Lazy;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+
+/// Request timeout and memory limit used when a caller doesn't pass explicit limits
+static DEFAULT_SETTINGS: Lazy<(u64, u64)> = Lazy::new(|| match Config::from_env() {
+    Ok(config) => (config.request_timeout_seconds, config.subprocess_memory_limit_mb),
+    Err(e) => {
+        warn!("Failed to load config for subprocess limits, using defaults: {}", e);
+        (30, 2048)
+    }
+});
+
+/// Steps of the pipeline that shell out to external tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubprocessStage {
+    /// `pdfimages` / `convert` pulling page images out of a PDF
+    ImageExtraction,
+    /// `pdftoppm` / `convert` rasterizing a single page
+    PageRendering,
+    /// `tesseract` recognising one page image
+    Recognition,
+    /// `tesseract --psm 0` orientation detection
+    OrientationDetection,
+    /// Version and language probes
+    Probe,
+}
+
+impl SubprocessStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubprocessStage::ImageExtraction => "image_extraction",
+            SubprocessStage::PageRendering => "page_rendering",
+            SubprocessStage::Recognition => "ocr",
+            SubprocessStage::OrientationDetection => "orientation_detection",
+            SubprocessStage::Probe => "probe",
+        }
+    }
+
+    /// Fraction of the request timeout a single invocation may use
+    fn timeout_share(&self) -> f64 {
+        match self {
+            SubprocessStage::ImageExtraction | SubprocessStage::Recognition => 0.5,
+            SubprocessStage::PageRendering | SubprocessStage::OrientationDetection => 0.25,
+            SubprocessStage::Probe => 0.1,
+        }
+    }
+}
+
+/// Wall clock, CPU and memory limits for one subprocess
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubprocessLimits {
+    pub timeout: Duration,
+    /// `RLIMIT_CPU`, in seconds of CPU time
+    pub cpu_seconds: u64,
+    /// `RLIMIT_AS`, in bytes of address space
+    pub memory_bytes: u64,
+}
+
+impl SubprocessLimits {
+    pub fn for_stage(stage: SubprocessStage, request_timeout_seconds: u64, memory_limit_mb: u64) -> Self {
+        let timeout = Duration::from_secs_f64((request_timeout_seconds as f64 * stage.timeout_share()).max(1.0));
+        Self {
+            timeout,
+            // CPU time accumulates across threads, leave room for a few busy cores
+            cpu_seconds: timeout.as_secs().max(1) * 4,
+            memory_bytes: memory_limit_mb * 1024 * 1024,
+        }
+    }
+
+    /// Limits derived from `REQUEST_TIMEOUT_SECONDS` and `SUBPROCESS_MEMORY_LIMIT_MB`
+    pub fn from_env(stage: SubprocessStage) -> Self {
+        let (request_timeout_seconds, memory_limit_mb) = *DEFAULT_SETTINGS;
+        Self::for_stage(stage, request_timeout_seconds, memory_limit_mb)
+    }
+}
+
+/// Run a command to completion with the configured limits for `stage`
+pub async fn run_command(command: Command, stage: SubprocessStage) -> AppResult<Output> {
+    run_command_with_limits(command, stage, SubprocessLimits::from_env(stage)).await
+}
+
+/// Run a command to completion, killing it if it outlives `limits.timeout`.
+///
+/// Failing to spawn is reported as an `OcrError`, running out of time as
+/// `AppError::Timeout` with the stage name. A non-zero exit status is not an
+/// error here, callers inspect the returned output.
+pub async fn run_command_with_limits(
+    mut command: Command,
+    stage: SubprocessStage,
+    limits: SubprocessLimits,
+) -> AppResult<Output> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_resource_limits(&mut command, &limits);
+
+    let child = command.spawn().map_err(|e| AppError::OcrError {
+        message: format!("Failed to run {}: {}", program, e),
+    })?;
+    debug!(program = %program, stage = stage.as_str(), timeout_ms = limits.timeout.as_millis() as u64, "Spawned subprocess");
+
+    // Dropping the future on timeout drops the child, which kills it
+    match tokio::time::timeout(limits.timeout, child.wait_with_output()).await {
+        Ok(output) => output.map_err(|e| AppError::OcrError {
+            message: format!("Failed to wait for {}: {}", program, e),
+        }),
+        Err(_) => {
+            warn!(
+                program = %program,
+                stage = stage.as_str(),
+                timeout_ms = limits.timeout.as_millis() as u64,
+                "Subprocess timed out and was killed"
+            );
+            Err(AppError::timeout(stage.as_str()))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn apply_resource_limits(command: &mut Command, limits: &SubprocessLimits) {
+    let cpu = limits.cpu_seconds as libc::rlim_t;
+    let memory = limits.memory_bytes as libc::rlim_t;
+
+    // Only ever lower a limit: raising the hard limit needs privileges we don't have
+    macro_rules! lower_limit {
+        ($resource:expr, $value:expr) => {{
+            let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            if libc::getrlimit($resource, &mut current) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let value = $value.min(current.rlim_max);
+            let limit = libc::rlimit { rlim_cur: value, rlim_max: value };
+            if libc::setrlimit($resource, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }};
+    }
+
+    // SAFETY: the hook only calls getrlimit/setrlimit, which are async-signal-safe, between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            lower_limit!(libc::RLIMIT_CPU, cpu);
+            lower_limit!(libc::RLIMIT_AS, memory);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_resource_limits(_command: &mut Command, _limits: &SubprocessLimits) {}
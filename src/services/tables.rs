@@ -0,0 +1,133 @@
+use crate::models::{BoundingBox, DocumentLayout, LayoutLine, Table};
+
+/// A gap between words wider than this many line heights separates two cells
+const CELL_GAP: f32 = 1.0;
+/// How far, in line heights, a cell may stick out of its column
+const COLUMN_TOLERANCE: f32 = 0.5;
+/// A vertical gap of more than this many line heights ends a table
+const ROW_GAP: f32 = 2.0;
+const MIN_TABLE_ROWS: usize = 2;
+const MIN_TABLE_COLUMNS: usize = 2;
+
+/// Words of a line close enough together to read as one cell
+struct Cell {
+    text: String,
+    left: f32,
+    right: f32,
+}
+
+/// Split a line into cells at the wide gaps between its words
+fn split_cells(line: &LayoutLine) -> Vec<Cell> {
+    let gap = line.bbox.height.max(1.0) * CELL_GAP;
+    let mut cells: Vec<Cell> = Vec::new();
+    for word in &line.words {
+        match cells.last_mut() {
+            Some(cell) if word.bbox.x - cell.right <= gap => {
+                cell.text.push(' ');
+                cell.text.push_str(&word.text);
+                cell.right = cell.right.max(word.bbox.right());
+            }
+            _ => cells.push(Cell {
+                text: word.text.clone(),
+                left: word.bbox.x,
+                right: word.bbox.right(),
+            }),
+        }
+    }
+    cells
+}
+
+/// A table being grown row by row
+struct TableBuilder {
+    page: u32,
+    /// Horizontal extent of each column so far
+    columns: Vec<(f32, f32)>,
+    rows: Vec<Vec<String>>,
+    bbox: BoundingBox,
+}
+
+impl TableBuilder {
+    fn start(page: u32, line: &LayoutLine, cells: Vec<Cell>) -> Self {
+        Self {
+            page,
+            columns: cells.iter().map(|cell| (cell.left, cell.right)).collect(),
+            rows: vec![cells.into_iter().map(|cell| cell.text).collect()],
+            bbox: line.bbox,
+        }
+    }
+
+    /// The column of each cell when the row fits the table: below it, and
+    /// every cell under its own column, left to right
+    fn fit(&self, line: &LayoutLine, cells: &[Cell]) -> Option<Vec<usize>> {
+        let height = line.bbox.height.max(1.0);
+        if line.bbox.y - self.bbox.bottom() > height * ROW_GAP {
+            return None;
+        }
+
+        let tolerance = height * COLUMN_TOLERANCE;
+        let mut next = 0;
+        let mut assigned = Vec::with_capacity(cells.len());
+        for cell in cells {
+            let column = self.columns[next..]
+                .iter()
+                .position(|&(left, right)| cell.left <= right + tolerance && cell.right >= left - tolerance)?
+                + next;
+            // A cell reaching into the next column spans both, which the rows can't show
+            if self.columns.get(column + 1).is_some_and(|&(left, _)| cell.right > left) {
+                return None;
+            }
+            assigned.push(column);
+            next = column + 1;
+        }
+        Some(assigned)
+    }
+
+    fn push(&mut self, line: &LayoutLine, cells: Vec<Cell>, assigned: Vec<usize>) {
+        let mut row = vec![String::new(); self.columns.len()];
+        for (cell, column) in cells.into_iter().zip(assigned) {
+            let (left, right) = &mut self.columns[column];
+            *left = left.min(cell.left);
+            *right = right.max(cell.right);
+            row[column] = cell.text;
+        }
+        self.rows.push(row);
+        self.bbox = self.bbox.union(&line.bbox);
+    }
+
+    fn finish(self) -> Option<Table> {
+        (self.rows.len() >= MIN_TABLE_ROWS).then_some(Table {
+            page: self.page,
+            bbox: self.bbox,
+            columns: self.columns.len(),
+            rows: self.rows,
+        })
+    }
+}
+
+/// Tables in a layout: runs of lines whose words fall into the same columns.
+///
+/// Columns are told apart by whitespace alone, so ruled tables come through
+/// the same as unruled ones, while a cell spanning several columns ends the table.
+pub fn detect_tables(layout: &DocumentLayout) -> Vec<Table> {
+    let mut tables = Vec::new();
+    for page in &layout.pages {
+        let mut current: Option<TableBuilder> = None;
+        for line in page.blocks.iter().flat_map(|block| &block.lines) {
+            let cells = split_cells(line);
+            if cells.len() < MIN_TABLE_COLUMNS {
+                tables.extend(current.take().and_then(TableBuilder::finish));
+                continue;
+            }
+
+            match current.as_mut().and_then(|table| table.fit(line, &cells).map(|assigned| (table, assigned))) {
+                Some((table, assigned)) => table.push(line, cells, assigned),
+                None => {
+                    tables.extend(current.take().and_then(TableBuilder::finish));
+                    current = Some(TableBuilder::start(page.number, line, cells));
+                }
+            }
+        }
+        tables.extend(current.and_then(TableBuilder::finish));
+    }
+    tables
+}
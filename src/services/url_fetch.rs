@@ -0,0 +1,326 @@
+use reqwest::{header, StatusCode, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::ProcessedFile;
+
+/// Content types we download; anything else (HTML error pages, JSON, ...) is refused
+const ACCEPTED_CONTENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "image/png",
+    "image/jpeg",
+    "image/jpg",
+    "image/tiff",
+    "image/webp",
+    // Plenty of storage buckets serve everything like this; the bytes are checked afterwards
+    "application/octet-stream",
+    "binary/octet-stream",
+];
+
+/// Where documents may be fetched from and how much of them
+#[derive(Debug, Clone)]
+pub struct FetchPolicy {
+    pub max_bytes: usize,
+    pub max_redirects: usize,
+    pub timeout: Duration,
+    /// When non-empty, only these hosts (and their subdomains) may be fetched
+    pub allowed_hosts: Vec<String>,
+    /// Hosts (and their subdomains) that may never be fetched
+    pub denied_hosts: Vec<String>,
+    /// Allow loopback, private, link-local and other non-public addresses
+    pub allow_private: bool,
+}
+
+impl FetchPolicy {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            max_redirects: 3,
+            timeout: Duration::from_secs(15),
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            allow_private: false,
+        }
+    }
+
+    /// Policy from `MAX_FILE_SIZE_MB` and the `URL_FETCH_*` variables
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_bytes: config.max_file_size_mb * 1024 * 1024,
+            max_redirects: config.url_fetch_max_redirects,
+            timeout: Duration::from_secs(config.url_fetch_timeout_seconds),
+            allowed_hosts: config.url_fetch_allowed_hosts.clone(),
+            denied_hosts: config.url_fetch_denied_hosts.clone(),
+            allow_private: config.url_fetch_allow_private,
+        }
+    }
+
+    /// Policy for webhook callbacks from the `WEBHOOK_*` variables. Callbacks are
+    /// never redirected and have no body to limit.
+    pub fn for_callbacks(config: &Config) -> Self {
+        Self {
+            max_bytes: 0,
+            max_redirects: 0,
+            timeout: Duration::from_secs(config.webhook_timeout_seconds),
+            allowed_hosts: config.webhook_allowed_hosts.clone(),
+            denied_hosts: config.webhook_denied_hosts.clone(),
+            allow_private: config.webhook_allow_private,
+        }
+    }
+
+    pub fn with_allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.allowed_hosts = hosts;
+        self
+    }
+
+    pub fn with_denied_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.denied_hosts = hosts;
+        self
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn allow_private_addresses(mut self) -> Self {
+        self.allow_private = true;
+        self
+    }
+
+    /// Check the scheme and host of `url` against the policy, before any lookup
+    fn check_url(&self, url: &Url) -> AppResult<String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::url_not_allowed(format!("scheme {} is not supported", url.scheme())));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| AppError::url_not_allowed("URL has no host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_end_matches('.')
+            .to_lowercase();
+
+        if self.denied_hosts.iter().any(|pattern| host_matches(&host, pattern)) {
+            return Err(AppError::url_not_allowed(format!("host {} is denied", host)));
+        }
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.iter().any(|pattern| host_matches(&host, pattern)) {
+            return Err(AppError::url_not_allowed(format!("host {} is not in the allow list", host)));
+        }
+        Ok(host)
+    }
+
+    /// Check `url` and resolve its host, returning the host and the checked address
+    /// to pin the connection to
+    pub async fn check_destination(&self, url: &Url) -> AppResult<(String, SocketAddr)> {
+        let host = self.check_url(url)?;
+        let address = self.resolve(url, &host).await?;
+        Ok((host, address))
+    }
+
+    /// Resolve the host and make sure every address it points to may be contacted
+    async fn resolve(&self, url: &Url, host: &str) -> AppResult<SocketAddr> {
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| AppError::fetch_failed(format!("could not resolve {}: {}", host, e)))?
+            .collect();
+
+        if !self.allow_private {
+            if let Some(address) = addresses.iter().find(|address| !is_public_address(address.ip())) {
+                warn!(host, address = %address.ip(), "Refusing to connect to a non-public address");
+                return Err(AppError::url_not_allowed(format!(
+                    "host {} resolves to a non-public address",
+                    host
+                )));
+            }
+        }
+
+        addresses
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::fetch_failed(format!("{} has no addresses", host)))
+    }
+}
+
+/// `pattern` matches the host itself and any of its subdomains
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim_start_matches("*.").trim_end_matches('.');
+    host == pattern || host.strip_suffix(pattern).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Whether an address is reachable on the public internet
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(embedded) => embedded.into_iter().all(is_public_ipv4),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+/// IPv4 addresses an IPv6 address carries for one of the transition mechanisms,
+/// any of which may end up routed to that IPv4 address
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Vec<Ipv4Addr>> {
+    let low = Ipv4Addr::from(u128::from(ip) as u32);
+    let pair = |high: u16, low: u16| Ipv4Addr::from(u32::from(high) << 16 | u32::from(low));
+    match ip.segments() {
+        // ::a.b.c.d IPv4-compatible and ::ffff:a.b.c.d IPv4-mapped
+        [0, 0, 0, 0, 0, 0 | 0xffff, _, _] => Some(vec![low]),
+        // 64:ff9b::/96 NAT64
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(vec![low]),
+        // 2002::/16 6to4
+        [0x2002, a, b, ..] => Some(vec![pair(a, b)]),
+        // 2001::/32 Teredo: the server address, then the client's with its bits flipped
+        [0x2001, 0, a, b, ..] => Some(vec![pair(a, b), Ipv4Addr::from(!u32::from(low))]),
+        _ => None,
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network"
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24 protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15 benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4 reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // 64:ff9b:1::/48 local-use NAT64
+        || (first == 0x64 && ip.segments()[1] == 0xff9b && ip.segments()[2] == 1)
+        // fc00::/7 unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 link local
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// Download a document, enforcing the policy on every redirect hop.
+///
+/// Each hop is resolved and checked before connecting, and the connection is
+/// pinned to the checked address so a second DNS answer can't swap in an
+/// internal one. The body is read in chunks and abandoned as soon as it goes
+/// over `max_bytes`.
+pub async fn fetch_document(url: &str, policy: &FetchPolicy) -> AppResult<ProcessedFile> {
+    let mut url = Url::parse(url.trim()).map_err(|e| AppError::validation(format!("Invalid url: {}", e)))?;
+    let mut redirects = 0;
+
+    loop {
+        let (host, address) = policy.check_destination(&url).await?;
+        debug!(url = %url, address = %address, "Fetching document");
+
+        // No proxy: it would resolve the host itself and bypass the pinned address
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .timeout(policy.timeout)
+            .resolve(&host, address)
+            .user_agent(concat!("elicit/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| AppError::internal(format!("Failed to build HTTP client: {}", e)))?;
+
+        let mut response = client.get(url.clone()).send().await.map_err(|e| {
+            if e.is_timeout() {
+                AppError::timeout("url_fetch")
+            } else {
+                AppError::fetch_failed(format!("request to {} failed: {}", host, e))
+            }
+        })?;
+
+        let status = response.status();
+        if status.is_redirection() {
+            if redirects == policy.max_redirects {
+                return Err(AppError::fetch_failed(format!(
+                    "more than {} redirects",
+                    policy.max_redirects
+                )));
+            }
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| AppError::fetch_failed(format!("{} redirect without a Location", status)))?;
+            url = url
+                .join(location)
+                .map_err(|e| AppError::fetch_failed(format!("invalid redirect target: {}", e)))?;
+            redirects += 1;
+            continue;
+        }
+        if status != StatusCode::OK {
+            return Err(AppError::fetch_failed(format!("{} answered {}", host, status)));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(';').next().unwrap_or("").trim().to_lowercase());
+        if let Some(content_type) = &content_type {
+            if !ACCEPTED_CONTENT_TYPES.contains(&content_type.as_str()) {
+                return Err(AppError::InvalidFile {
+                    message: format!("URL returned unsupported content type {}", content_type),
+                });
+            }
+        }
+
+        let too_large = |size: usize| AppError::FileTooLarge {
+            size: size / (1024 * 1024),
+            limit: policy.max_bytes / (1024 * 1024),
+        };
+        if let Some(length) = response.content_length() {
+            if length as usize > policy.max_bytes {
+                return Err(too_large(length as usize));
+            }
+        }
+
+        let mut content = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::fetch_failed(format!("download from {} failed: {}", host, e)))?
+        {
+            if content.len() + chunk.len() > policy.max_bytes {
+                return Err(too_large(content.len() + chunk.len()));
+            }
+            content.extend_from_slice(&chunk);
+        }
+
+        info!(url = %url, bytes = content.len(), redirects, "Document fetched");
+
+        let mut file = ProcessedFile::new(file_name_from_url(&url), content);
+        if let Some(content_type) = content_type.filter(|ct| !ct.ends_with("octet-stream")) {
+            file = file.with_mime_type(content_type);
+        }
+        return Ok(file);
+    }
+}
+
+/// Last path segment of the URL, or `download` when it has none
+fn file_name_from_url(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|segments| segments.rev().find(|s| !s.is_empty()))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| "download".to_string())
+}
@@ -0,0 +1,237 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::WebhookDelivery;
+use crate::services::url_fetch::FetchPolicy;
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>` with the API key's webhook secret
+pub const SIGNATURE_HEADER: &str = "x-elicit-signature";
+/// Unix time the delivery was signed at
+pub const TIMESTAMP_HEADER: &str = "x-elicit-timestamp";
+pub const EVENT_HEADER: &str = "x-elicit-event";
+pub const DELIVERY_HEADER: &str = "x-elicit-delivery";
+
+/// Longest wait between two attempts, however many have failed
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// How often and how patiently a callback is attempted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every retry after it
+    pub base_delay: Duration,
+    /// Per-attempt request timeout
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration, timeout: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            timeout,
+        }
+    }
+
+    /// Policy from `WEBHOOK_MAX_ATTEMPTS`, `WEBHOOK_RETRY_BASE_MS` and `WEBHOOK_TIMEOUT_SECONDS`
+    pub fn from_env() -> Self {
+        match Config::from_env() {
+            Ok(config) => Self::new(
+                config.webhook_max_attempts,
+                Duration::from_millis(config.webhook_retry_base_ms),
+                Duration::from_secs(config.webhook_timeout_seconds),
+            ),
+            Err(e) => {
+                warn!("Failed to load config for webhooks, using defaults: {}", e);
+                Self::new(5, Duration::from_secs(1), Duration::from_secs(10))
+            }
+        }
+    }
+
+    /// Wait before attempt number `attempt` (1-based); the first attempt goes out immediately
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        if attempt <= 1 {
+            return Duration::ZERO;
+        }
+        let factor = 2u32.saturating_pow(attempt - 2);
+        self.base_delay.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(1), Duration::from_secs(10))
+    }
+}
+
+/// Callback URL and the secret its deliveries are signed with
+#[derive(Clone)]
+pub struct WebhookTarget {
+    url: Url,
+    secret: String,
+    policy: RetryPolicy,
+    /// Where callbacks may go; only public addresses unless configured otherwise
+    destinations: FetchPolicy,
+}
+
+impl WebhookTarget {
+    /// Only absolute `http` and `https` URLs are accepted
+    pub fn new(url: &str, secret: impl Into<String>) -> AppResult<Self> {
+        let url = Url::parse(url.trim())
+            .map_err(|e| AppError::validation(format!("Invalid callback_url: {}", e)))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err(AppError::validation("callback_url must be an http or https URL"));
+        }
+        Ok(Self {
+            url,
+            secret: secret.into(),
+            policy: RetryPolicy::default(),
+            destinations: FetchPolicy::new(0).with_max_redirects(0),
+        })
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Check the callback host against these rules instead of the public-only default
+    pub fn with_destinations(mut self, destinations: FetchPolicy) -> Self {
+        self.destinations = destinations;
+        self
+    }
+
+    /// Fail with `URL_NOT_ALLOWED` unless the callback host passes the destination rules.
+    ///
+    /// Run when the job is submitted; every delivery attempt checks again, since
+    /// the host may resolve elsewhere by then.
+    pub async fn check(&self) -> AppResult<()> {
+        self.destinations.check_destination(&self.url).await.map(|_| ())
+    }
+
+    /// Client for one attempt, connecting only to the address that was just checked
+    async fn client(&self) -> AppResult<reqwest::Client> {
+        let (host, address) = self.destinations.check_destination(&self.url).await?;
+        // Callbacks are not redirected: the signed payload should only go where the client asked
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            // A proxy would resolve the host itself and bypass the pinned address
+            .no_proxy()
+            .resolve(&host, address)
+            .user_agent(concat!("elicit-webhooks/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| AppError::internal(format!("Failed to build webhook HTTP client: {}", e)))
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+}
+
+impl std::fmt::Debug for WebhookTarget {
+    // Keep the secret out of logs
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookTarget")
+            .field("url", &self.url.as_str())
+            .field("policy", &self.policy)
+            .field("destinations", &self.destinations)
+            .finish()
+    }
+}
+
+/// Signature header value for a delivery body sent at `timestamp`
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// POST `body` to the target until it answers 2xx or the attempts run out.
+///
+/// `record` is called after every attempt. Connection errors, timeouts, 408,
+/// 429 and 5xx responses are retried; any other status is final, as is a
+/// callback host that no longer passes the destination rules.
+pub async fn deliver_webhook(
+    target: &WebhookTarget,
+    job_id: &str,
+    event: &str,
+    body: Vec<u8>,
+    mut record: impl FnMut(WebhookDelivery),
+) -> bool {
+    let delivery_id = uuid::Uuid::new_v4().to_string();
+
+    for attempt in 1..=target.policy.max_attempts {
+        tokio::time::sleep(target.policy.delay_before(attempt)).await;
+
+        let timestamp = Utc::now().timestamp();
+        let started = Instant::now();
+        let response = match target.client().await {
+            Ok(client) => client
+                .post(target.url.clone())
+                .timeout(target.policy.timeout)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, sign_payload(&target.secret, timestamp, &body))
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(EVENT_HEADER, event)
+                .header(DELIVERY_HEADER, delivery_id.as_str())
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|e| (e.to_string(), true)),
+            Err(e @ AppError::UrlNotAllowed { .. }) => Err((e.to_string(), false)),
+            Err(e) => Err((e.to_string(), true)),
+        };
+
+        let (status_code, error, retryable) = match response {
+            Ok(response) => {
+                let status = response.status();
+                let retryable = status.is_server_error()
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                let error = (!status.is_success()).then(|| format!("Callback answered {}", status));
+                (Some(status.as_u16()), error, retryable)
+            }
+            Err((error, retryable)) => (None, Some(error), retryable),
+        };
+        let success = error.is_none();
+
+        record(WebhookDelivery {
+            delivery_id: delivery_id.clone(),
+            job_id: job_id.to_string(),
+            event: event.to_string(),
+            url: target.url().to_string(),
+            attempt,
+            attempted_at: Utc::now(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            status_code,
+            error: error.clone(),
+            success,
+        });
+
+        if success {
+            info!(job_id, event, attempt, "Webhook delivered");
+            return true;
+        }
+        warn!(
+            job_id,
+            event,
+            attempt,
+            error = error.as_deref().unwrap_or_default(),
+            "Webhook delivery attempt failed"
+        );
+        if !retryable {
+            break;
+        }
+    }
+
+    warn!(job_id, event, "Giving up on webhook delivery");
+    false
+}
@@ -0,0 +1,174 @@
+//! Helpers shared by the test suites
+
+#![allow(dead_code)]
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+
+/// What to draw on a generated test page
+pub enum TestPage {
+    /// Lines of text drawn with Helvetica
+    Text(Vec<&'static str>),
+    /// A single image XObject stretched over the whole page
+    Image,
+    /// A full-page image with an invisible (render mode 3) text layer
+    ImageWithInvisibleText(&'static str),
+    /// A full-page image wrapped in a form XObject
+    ImageInForm,
+    /// Two images side by side, each covering half the page
+    TwoImages,
+    /// One image over the top half of the page
+    HalfPageImage,
+    /// Nothing at all
+    Blank,
+}
+
+/// Build an in-memory PDF with one Letter-sized page per entry
+pub fn build_pdf(pages: Vec<TestPage>) -> Vec<u8> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    // 2x2 grayscale image
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 2,
+            "Height" => 2,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        vec![0, 255, 255, 0],
+    ));
+
+    let form_content = Content {
+        operations: vec![
+            Operation::new("q", vec![]),
+            Operation::new("cm", vec![612.into(), 0.into(), 0.into(), 792.into(), 0.into(), 0.into()]),
+            Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]),
+            Operation::new("Q", vec![]),
+        ],
+    };
+    let form_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im1" => image_id },
+            },
+        },
+        form_content.encode().unwrap(),
+    ));
+
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "XObject" => dictionary! { "Im1" => image_id, "Fm1" => form_id },
+    });
+
+    let mut kids = Vec::new();
+    for page in &pages {
+        let mut operations = Vec::new();
+        match page {
+            TestPage::Text(lines) => {
+                operations.push(Operation::new("BT", vec![]));
+                operations.push(Operation::new("Tf", vec!["F1".into(), 12.into()]));
+                operations.push(Operation::new("TL", vec![14.into()]));
+                operations.push(Operation::new("Td", vec![72.into(), 720.into()]));
+                for line in lines {
+                    operations.push(Operation::new("Tj", vec![Object::string_literal(*line)]));
+                    operations.push(Operation::new("T*", vec![]));
+                }
+                operations.push(Operation::new("ET", vec![]));
+            }
+            TestPage::Image => {
+                operations.push(Operation::new("q", vec![]));
+                operations.push(Operation::new(
+                    "cm",
+                    vec![612.into(), 0.into(), 0.into(), 792.into(), 0.into(), 0.into()],
+                ));
+                operations.push(Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]));
+                operations.push(Operation::new("Q", vec![]));
+            }
+            TestPage::ImageWithInvisibleText(text) => {
+                operations.push(Operation::new("q", vec![]));
+                operations.push(Operation::new(
+                    "cm",
+                    vec![612.into(), 0.into(), 0.into(), 792.into(), 0.into(), 0.into()],
+                ));
+                operations.push(Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]));
+                operations.push(Operation::new("Q", vec![]));
+                operations.push(Operation::new("BT", vec![]));
+                operations.push(Operation::new("Tr", vec![3.into()]));
+                operations.push(Operation::new("Tf", vec!["F1".into(), 12.into()]));
+                operations.push(Operation::new("Td", vec![72.into(), 720.into()]));
+                operations.push(Operation::new("Tj", vec![Object::string_literal(*text)]));
+                operations.push(Operation::new("ET", vec![]));
+            }
+            TestPage::ImageInForm => {
+                operations.push(Operation::new("Do", vec![Object::Name(b"Fm1".to_vec())]));
+            }
+            TestPage::TwoImages => {
+                for x in [0, 306] {
+                    operations.push(Operation::new("q", vec![]));
+                    operations.push(Operation::new(
+                        "cm",
+                        vec![306.into(), 0.into(), 0.into(), 792.into(), x.into(), 0.into()],
+                    ));
+                    operations.push(Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]));
+                    operations.push(Operation::new("Q", vec![]));
+                }
+            }
+            TestPage::HalfPageImage => {
+                operations.push(Operation::new("q", vec![]));
+                operations.push(Operation::new(
+                    "cm",
+                    vec![612.into(), 0.into(), 0.into(), 396.into(), 0.into(), 396.into()],
+                ));
+                operations.push(Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]));
+                operations.push(Operation::new("Q", vec![]));
+            }
+            TestPage::Blank => {}
+        }
+
+        let content = Content { operations };
+        let mut stream = Stream::new(dictionary! {}, content.encode().unwrap());
+        // Compressed streams are what defeated the old string-matching heuristic
+        stream.compress().unwrap();
+        let content_id = doc.add_object(stream);
+
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => resources_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => pages.len() as i64,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        }),
+    );
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut buffer = Vec::new();
+    doc.save_to(&mut buffer).unwrap();
+    buffer
+}
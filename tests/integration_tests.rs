@@ -1,9 +1,33 @@
 //! Integration tests for the Elicit PDF extraction service
 
+mod common;
+
 use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Router,
+};
+use hmac::{Hmac, Mac};
+use image::{DynamicImage, GrayImage, Luma};
+use sha2::Sha256;
+use utoipa::OpenApi;
 use elicit::{
     config::Config,
     error::AppError,
+    middleware::idempotency_middleware,
+    models::{ApiKeyIdentity, ErrorResponse, ExtractionOptions, ProcessedFile, Scope},
+    openapi::ApiDoc,
+    routes,
+    services::{
+        fetch_document, hash_key, key_prefix, FetchPolicy, JobQueue, JobRequest, MockOcrEngine, RetryPolicy, WebhookTarget,
+    },
 };
 
 #[tokio::test]
@@ -115,4 +139,806 @@ async fn test_concurrent_request_limits() {
     // Test that we can create the semaphore with the configured limit
     let semaphore = tokio::sync::Semaphore::new(config.max_concurrent_requests);
     assert_eq!(semaphore.available_permits(), 5);
-}
\ No newline at end of file
+}
+
+/// Requests received by the stand-in callback server
+type Received = Arc<Mutex<Vec<(HeaderMap, Vec<u8>)>>>;
+
+/// Local callback endpoint that fails its first `failures` requests with a 503
+async fn spawn_callback_server(failures: usize) -> (String, Received) {
+    let received: Received = Arc::default();
+    let app = Router::new()
+        .route(
+            "/hook",
+            post(move |State(received): State<Received>, headers: HeaderMap, body: axum::body::Bytes| async move {
+                let mut received = received.lock().unwrap();
+                received.push((headers, body.to_vec()));
+                if received.len() <= failures {
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        )
+        .with_state(received.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, received)
+}
+
+#[tokio::test]
+async fn test_job_webhook_is_signed_and_retried() {
+    let (url, received) = spawn_callback_server(2).await;
+
+    let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(32, 32, Luma([255])));
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).unwrap();
+    let file = ProcessedFile::new("scan.png".to_string(), png).with_mime_type("image/png".to_string());
+
+    // The stand-in receiver listens on loopback, which needs the explicit opt-in
+    let callback = WebhookTarget::new(&url, "whsec-test")
+        .unwrap()
+        .with_policy(RetryPolicy::new(4, Duration::from_millis(10), Duration::from_secs(5)))
+        .with_destinations(FetchPolicy::new(0).allow_private_addresses());
+    let queue = JobQueue::new(1, 4, Duration::from_secs(60));
+    let client = ApiKeyIdentity { id: "client".to_string(), name: "client".to_string(), scopes: vec![Scope::Jobs] };
+    let job = queue
+        .submit(
+            &client,
+            JobRequest::new(file)
+                .with_ocr_engine(Arc::new(MockOcrEngine::new("webhook text")))
+                .with_callback(callback),
+        )
+        .unwrap();
+    assert_eq!(job.callback_url.as_deref(), Some(url.as_str()));
+
+    let mut deliveries = Vec::new();
+    for _ in 0..200 {
+        deliveries = queue.deliveries(&client, &job.job_id).unwrap();
+        if deliveries.iter().any(|d| d.success) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Two 503s, then a successful delivery, all under the same delivery id
+    assert_eq!(deliveries.len(), 3);
+    let codes: Vec<_> = deliveries.iter().map(|d| d.status_code).collect();
+    assert_eq!(codes, vec![Some(503), Some(503), Some(200)]);
+    assert_eq!(deliveries.iter().map(|d| d.attempt).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert!(deliveries.iter().all(|d| d.delivery_id == deliveries[0].delivery_id));
+    assert_eq!(deliveries[2].event, "job.completed");
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 3);
+    let (headers, body) = &received[2];
+    assert_eq!(headers["x-elicit-event"], "job.completed");
+    assert_eq!(headers["x-elicit-delivery"], deliveries[0].delivery_id.as_str());
+
+    // The signature covers the timestamp and the exact body
+    let timestamp = headers["x-elicit-timestamp"].to_str().unwrap();
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"whsec-test").unwrap();
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+    assert_eq!(headers["x-elicit-signature"], expected.as_str());
+
+    let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(payload["event"], "job.completed");
+    assert_eq!(payload["job"]["job_id"], job.job_id.as_str());
+    assert_eq!(payload["job"]["result"]["data"]["text"], "webhook text");
+}
+
+#[tokio::test]
+async fn test_webhooks_are_not_sent_to_internal_addresses() {
+    let (url, received) = spawn_callback_server(0).await;
+    let not_allowed = |result: Result<(), AppError>| matches!(result, Err(AppError::UrlNotAllowed { .. }));
+
+    // Loopback, link-local metadata and private addresses are refused when the job is submitted
+    for internal in [url.as_str(), "http://169.254.169.254/latest/meta-data", "http://10.0.0.7/hook", "http://[::1]/hook"] {
+        let target = WebhookTarget::new(internal, "whsec-test").unwrap();
+        assert!(not_allowed(target.check().await), "{}", internal);
+    }
+    let denied = WebhookTarget::new(&url, "whsec-test")
+        .unwrap()
+        .with_destinations(FetchPolicy::new(0).allow_private_addresses().with_denied_hosts(vec!["127.0.0.1".to_string()]));
+    assert!(not_allowed(denied.check().await));
+    let allowed = WebhookTarget::new(&url, "whsec-test")
+        .unwrap()
+        .with_destinations(FetchPolicy::new(0).allow_private_addresses());
+    allowed.check().await.unwrap();
+
+    // Deliveries check again, and a refused host isn't retried
+    let target = WebhookTarget::new(&url, "whsec-test")
+        .unwrap()
+        .with_policy(RetryPolicy::new(3, Duration::from_millis(10), Duration::from_secs(5)));
+    let mut deliveries = Vec::new();
+    let delivered = elicit::services::deliver_webhook(&target, "job-1", "job.completed", b"{}".to_vec(), |delivery| {
+        deliveries.push(delivery)
+    })
+    .await;
+    assert!(!delivered);
+    assert_eq!(deliveries.len(), 1);
+    assert!(deliveries[0].error.as_deref().unwrap().contains("non-public address"));
+    assert!(received.lock().unwrap().is_empty());
+}
+
+/// Local document host for the URL fetcher, returning its base URL
+async fn spawn_document_server() -> String {
+    let app = Router::new()
+        .route("/files/report.pdf", get(|| async {
+            ([(header::CONTENT_TYPE, "application/pdf")], "%PDF-1.4 stand-in")
+        }))
+        .route("/moved", get(|| async { Redirect::temporary("/files/report.pdf") }))
+        .route("/loop", get(|| async { Redirect::temporary("/loop") }))
+        .route("/page", get(|| async {
+            ([(header::CONTENT_TYPE, "text/html")], "<html></html>")
+        }))
+        .route("/huge", get(|| async {
+            // Chunked with no Content-Length, so only the streaming check can catch it
+            let chunks = (0..64).map(|_| Ok::<_, std::io::Error>(vec![0u8; 64 * 1024]));
+            ([(header::CONTENT_TYPE, "application/pdf")], Body::from_stream(tokio_stream::iter(chunks)))
+                .into_response()
+        }));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+#[tokio::test]
+async fn test_url_fetch_blocks_private_addresses_by_default() {
+    let base = spawn_document_server().await;
+
+    let error = fetch_document(&format!("{}/files/report.pdf", base), &FetchPolicy::new(1024 * 1024))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AppError::UrlNotAllowed { .. }));
+    assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+
+    let error = fetch_document("file:///etc/passwd", &FetchPolicy::new(1024 * 1024))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AppError::UrlNotAllowed { .. }));
+}
+
+#[tokio::test]
+async fn test_url_fetch_follows_redirects_and_names_the_file() {
+    let base = spawn_document_server().await;
+    let policy = FetchPolicy::new(1024 * 1024).allow_private_addresses();
+
+    let file = fetch_document(&format!("{}/moved", base), &policy).await.unwrap();
+    assert_eq!(file.name, "report.pdf");
+    assert_eq!(file.mime_type.as_deref(), Some("application/pdf"));
+    assert_eq!(file.content, b"%PDF-1.4 stand-in");
+
+    let error = fetch_document(&format!("{}/loop", base), &policy).await.unwrap_err();
+    assert!(matches!(error, AppError::FetchFailed { .. }));
+
+    let error = fetch_document(&format!("{}/moved", base), &policy.clone().with_max_redirects(0))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AppError::FetchFailed { .. }));
+}
+
+#[tokio::test]
+async fn test_url_fetch_enforces_size_and_content_type() {
+    let base = spawn_document_server().await;
+    let policy = FetchPolicy::new(1024 * 1024).allow_private_addresses();
+
+    let error = fetch_document(&format!("{}/huge", base), &policy).await.unwrap_err();
+    assert!(matches!(error, AppError::FileTooLarge { .. }));
+
+    let error = fetch_document(&format!("{}/page", base), &policy).await.unwrap_err();
+    assert!(matches!(error, AppError::InvalidFile { .. }));
+}
+
+#[tokio::test]
+async fn test_url_fetch_applies_host_lists() {
+    let base = spawn_document_server().await;
+    let url = format!("{}/files/report.pdf", base);
+
+    let denied = FetchPolicy::new(1024 * 1024)
+        .allow_private_addresses()
+        .with_denied_hosts(vec!["127.0.0.1".to_string()]);
+    let error = fetch_document(&url, &denied).await.unwrap_err();
+    assert!(matches!(error, AppError::UrlNotAllowed { .. }));
+
+    let allow_listed = FetchPolicy::new(1024 * 1024)
+        .allow_private_addresses()
+        .with_allowed_hosts(vec!["docs.example.com".to_string()]);
+    let error = fetch_document(&url, &allow_listed).await.unwrap_err();
+    assert!(matches!(error, AppError::UrlNotAllowed { .. }));
+
+    let allowed = FetchPolicy::new(1024 * 1024)
+        .allow_private_addresses()
+        .with_allowed_hosts(vec!["127.0.0.1".to_string()]);
+    assert!(fetch_document(&format!("{}/moved", base), &allowed).await.is_ok());
+}
+
+/// (method, path) of every operation in the OpenAPI document, with whether it requires an API key
+fn documented_operations(spec: &serde_json::Value) -> Vec<(String, String, bool)> {
+    let mut operations = Vec::new();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            let secured = operation["security"]
+                .as_array()
+                .is_some_and(|schemes| schemes.iter().any(|scheme| scheme.get("api_key").is_some()));
+            operations.push((method.to_uppercase(), path.clone(), secured));
+        }
+    }
+    operations.sort();
+    operations
+}
+
+#[tokio::test]
+async fn test_openapi_spec_matches_routes() {
+    let config = Config::from_env().unwrap();
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+    let mut routed: Vec<(String, String, bool)> = routes::endpoints(&config)
+        .iter()
+        .map(|endpoint| (endpoint.method.to_string(), endpoint.openapi_path(), !endpoint.public))
+        .collect();
+    routed.sort();
+
+    // Fails when a route isn't documented, a documented path isn't routed,
+    // or the documented security doesn't match the auth middleware
+    assert_eq!(routed, documented_operations(&spec));
+
+    // Idempotent routes, and only those, document the Idempotency-Key header
+    for endpoint in routes::endpoints(&config) {
+        let operation = &spec["paths"][endpoint.openapi_path()][endpoint.method.as_str().to_lowercase()];
+
+        // Protected routes need a scope, and document it as the role of the `api_key` scheme
+        if !endpoint.public {
+            let scope = endpoint.scope.unwrap_or_else(|| panic!("{} {} has no scope", endpoint.method, endpoint.path));
+            assert_eq!(operation["security"][0]["api_key"], serde_json::json!([scope.as_str()]), "{} {}", endpoint.method, endpoint.path);
+        }
+
+        let documented = operation["parameters"]
+            .as_array()
+            .is_some_and(|params| params.iter().any(|param| param["name"] == "Idempotency-Key"));
+        assert_eq!(documented, endpoint.idempotent, "{} {}", endpoint.method, endpoint.path);
+    }
+
+    // Every documented operation can fail with the shared error body
+    for (_, item) in spec["paths"].as_object().unwrap() {
+        for (_, operation) in item.as_object().unwrap() {
+            assert_eq!(
+                operation["responses"]["default"]["content"]["application/json"]["schema"]["$ref"],
+                "#/components/schemas/ErrorResponse"
+            );
+        }
+    }
+
+    // The query parameters of the binary endpoint are the extraction options
+    let mut params: Vec<&str> = spec["paths"]["/api/v1/extract/binary"]["post"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|param| param["in"] == "query")
+        .map(|param| param["name"].as_str().unwrap())
+        .collect();
+    let mut names = ExtractionOptions::NAMES.to_vec();
+    params.sort();
+    names.sort();
+    assert_eq!(params, names);
+}
+
+#[tokio::test]
+async fn test_openapi_document_and_docs_are_served() {
+    let config = Config::from_env().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = routes::app(&config);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+
+    let response = client.get(format!("{}/api/openapi.json", base)).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), StatusCode::OK.as_u16());
+    let spec: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(spec["components"]["schemas"]["ExtractResponse"].is_object());
+    assert!(spec["components"]["schemas"]["PdfMetadata"].is_object());
+
+    let response = client.get(format!("{}/api/docs/", base)).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), StatusCode::OK.as_u16());
+    assert!(response.text().await.unwrap().contains("swagger"));
+
+    // Every documented operation is routed: protected ones answer 401 with the documented error body
+    for (method, path, secured) in documented_operations(&spec) {
+        let url = format!("{}{}", base, path.replace("{id}", "unknown"));
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        let response = client.request(method.clone(), &url).send().await.unwrap();
+        assert_ne!(response.status().as_u16(), StatusCode::NOT_FOUND.as_u16(), "{} {} is not routed", method, path);
+        assert_ne!(response.status().as_u16(), StatusCode::METHOD_NOT_ALLOWED.as_u16(), "{} {} is not routed", method, path);
+
+        if secured {
+            assert_eq!(response.status().as_u16(), StatusCode::UNAUTHORIZED.as_u16(), "{} {}", method, path);
+            let body: ErrorResponse = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+            assert!(!body.success);
+            assert_eq!(body.error.code, "INVALID_API_KEY");
+            assert!(body.error.request_id.is_some());
+            assert!(body.error.timestamp.is_some());
+            assert!(body.data.is_none());
+        }
+    }
+}
+
+/// Server whose handlers count their calls, behind the idempotency middleware
+async fn spawn_idempotent_server() -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route("/work", post(|State(calls): State<Arc<AtomicUsize>>| async move {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            (StatusCode::CREATED, [(header::LOCATION, "/work/1")], format!("call {}", call))
+        }))
+        .route("/fail", post(|State(calls): State<Arc<AtomicUsize>>| async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            AppError::processing("flaky")
+        }))
+        .route("/slow", post(|State(calls): State<Arc<AtomicUsize>>| async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            "done"
+        }))
+        .with_state(calls.clone())
+        .layer(axum::middleware::from_fn_with_state(1024 * 1024, idempotency_middleware));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (base, calls)
+}
+
+#[tokio::test]
+async fn test_idempotency_key_replays_successful_responses() {
+    let (base, calls) = spawn_idempotent_server().await;
+    let client = reqwest::Client::new();
+    let send = |api_key: &str, key: &str, body: &'static str| {
+        client
+            .post(format!("{}/work", base))
+            .bearer_auth(api_key)
+            .header("Idempotency-Key", key)
+            .body(body)
+            .send()
+    };
+
+    let first = send("key-a", "order-1", "document").await.unwrap();
+    assert_eq!(first.status().as_u16(), 201);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    assert_eq!(first.text().await.unwrap(), "call 1");
+
+    let retry = send("key-a", "order-1", "document").await.unwrap();
+    assert_eq!(retry.status().as_u16(), 201);
+    assert_eq!(retry.headers()["idempotent-replayed"], "true");
+    assert_eq!(retry.headers()["location"], "/work/1");
+    assert_eq!(retry.text().await.unwrap(), "call 1");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Keys are scoped per API key
+    let other_client = send("key-b", "order-1", "document").await.unwrap();
+    assert_eq!(other_client.text().await.unwrap(), "call 2");
+
+    // Same key, different request
+    let conflict = send("key-a", "order-1", "another document").await.unwrap();
+    assert_eq!(conflict.status().as_u16(), 422);
+    let body: ErrorResponse = serde_json::from_slice(&conflict.bytes().await.unwrap()).unwrap();
+    assert_eq!(body.error.code, "IDEMPOTENCY_KEY_REUSED");
+
+    // No key, no idempotency
+    client.post(format!("{}/work", base)).bearer_auth("key-a").body("document").send().await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let invalid = send("key-a", "", "document").await.unwrap();
+    assert_eq!(invalid.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_matches_multipart_retries() {
+    let (base, calls) = spawn_idempotent_server().await;
+    let client = reqwest::Client::new();
+    let send = || {
+        // reqwest picks a new random boundary for every form
+        let form = reqwest::multipart::Form::new()
+            .text("language", "eng")
+            .part("file", reqwest::multipart::Part::bytes(b"%PDF-1.4 stand-in".to_vec()).file_name("a.pdf"));
+        client
+            .post(format!("{}/work", base))
+            .bearer_auth("key-a")
+            .header("Idempotency-Key", "upload-1")
+            .multipart(form)
+            .send()
+    };
+
+    send().await.unwrap();
+    let retry = send().await.unwrap();
+    assert_eq!(retry.headers()["idempotent-replayed"], "true");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_failures_and_concurrent_retries() {
+    let (base, calls) = spawn_idempotent_server().await;
+    let client = reqwest::Client::new();
+    let send = |path: &str| {
+        client
+            .post(format!("{}{}", base, path))
+            .bearer_auth("key-a")
+            .header("Idempotency-Key", format!("key-for-{}", path))
+            .send()
+    };
+
+    // Failures release the key, so the retry runs again
+    assert_eq!(send("/fail").await.unwrap().status().as_u16(), 500);
+    assert_eq!(send("/fail").await.unwrap().status().as_u16(), 500);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let (first, second) = tokio::join!(send("/slow"), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send("/slow").await
+    });
+    assert_eq!(first.unwrap().status().as_u16(), 200);
+    let second = second.unwrap();
+    assert_eq!(second.status().as_u16(), 409);
+    let body: ErrorResponse = serde_json::from_slice(&second.bytes().await.unwrap()).unwrap();
+    assert_eq!(body.error.code, "IDEMPOTENCY_KEY_IN_USE");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_grpc_server_answers_health_and_rejects_missing_keys() {
+    use elicit::grpc::{self, proto, proto::elicit_client::ElicitClient};
+
+    let config = Config::from_env().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { grpc::serve(listener, &config).await.unwrap() });
+
+    let mut client = ElicitClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    // Health needs no key
+    let health = client.health(proto::HealthRequest {}).await.unwrap().into_inner();
+    assert_eq!(health.version, env!("CARGO_PKG_VERSION"));
+    assert!(!health.ocr_engine.is_empty());
+
+    let status = client
+        .extract(proto::ExtractRequest {
+            document: Some(proto::Document {
+                file_name: "memo.pdf".to_string(),
+                content: b"%PDF-1.4 stand-in".to_vec(),
+                content_type: "application/pdf".to_string(),
+            }),
+            options: Default::default(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(status.metadata().get("x-elicit-error-code").unwrap(), "INVALID_API_KEY");
+
+    let status = client
+        .get_job(proto::GetJobRequest { job_id: "missing".to_string() })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_searchable_pdf_reports_skipped_pages() {
+    use common::{build_pdf, TestPage};
+
+    // A scanned page turned a quarter can't get a text layer that lines up with it
+    let mut doc = lopdf::Document::load_mem(&build_pdf(vec![TestPage::Image])).unwrap();
+    let page_id = doc.get_pages()[&1];
+    doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap().set("Rotate", 90);
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf).unwrap();
+    elicit::services::register_ocr_engine(Arc::new(elicit::services::MockOcrEngine::new("never used")));
+
+    let app = Router::new().route("/api/v1/extract/searchable", post(elicit::handlers::searchable_pdf_handler));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/v1/extract/searchable", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let form = reqwest::multipart::Form::new()
+        .text("ocr_engine", "mock")
+        .part("file", reqwest::multipart::Part::bytes(pdf).file_name("scan.pdf"));
+    let response = reqwest::Client::new().post(url).multipart(form).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["x-elicit-ocr-pages"], "");
+    assert_eq!(response.headers()["x-elicit-skipped-pages"], "1");
+}
+
+#[tokio::test]
+async fn test_session_websocket_loads_once_and_answers_commands() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+    use common::{build_pdf, TestPage};
+
+    /// The next session reply, parsed
+    async fn reply<S>(socket: &mut S) -> serde_json::Value
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("expected a text message, got {:?}", other),
+        }
+    }
+
+    env::set_var("SESSION_IDLE_TIMEOUT_SECONDS", "1");
+    env::set_var("SESSION_MAX_CONCURRENT", "1");
+    let app = Router::new().route("/api/v1/sessions", get(elicit::handlers::session_handler));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/api/v1/sessions", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+
+    let ready = reply(&mut socket).await;
+    assert_eq!(ready["type"], "ready");
+    assert_eq!(ready["idle_timeout_seconds"], 1);
+
+    // The only session slot is taken, so another client is turned away before upgrading
+    match tokio_tungstenite::connect_async(url.as_str()).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => assert_eq!(response.status().as_u16(), 503),
+        other => panic!("expected the session limit, got {:?}", other.map(|_| ())),
+    }
+
+    // Commands before a document is loaded fail without ending the session
+    socket.send(Message::Text(r#"{"id": 1, "type": "info"}"#.into())).await.unwrap();
+    let error = reply(&mut socket).await;
+    assert_eq!((error["id"].clone(), error["type"].clone()), (1.into(), "error".into()));
+    assert_eq!(error["error"]["code"], "VALIDATION_ERROR");
+
+    // A load without content takes the next binary message
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Session page one"]), TestPage::Text(vec!["Session page two"])]);
+    let load = serde_json::json!({
+        "id": "load-1",
+        "type": "load",
+        "file_name": "session.pdf",
+        "options": {"ocr_min_chars": 0, "ocr_min_letters": 0},
+    });
+    socket.send(Message::Text(load.to_string())).await.unwrap();
+    socket.send(Message::Binary(pdf)).await.unwrap();
+    let loaded = reply(&mut socket).await;
+    assert_eq!(loaded["id"], "load-1");
+    assert_eq!(loaded["type"], "loaded");
+    assert_eq!(loaded["pages"], 2);
+    assert_eq!(loaded["file_name"], "session.pdf");
+
+    socket.send(Message::Text(r#"{"id": 2, "type": "pages", "pages": [2]}"#.into())).await.unwrap();
+    let pages = reply(&mut socket).await;
+    assert_eq!(pages["pages"][0]["text"], "Session page two");
+    assert_eq!(pages["pages"][0]["source"], "native");
+
+    socket
+        .send(Message::Text(r#"{"id": 3, "type": "search", "queries": ["page one"]}"#.into()))
+        .await
+        .unwrap();
+    let search = reply(&mut socket).await;
+    assert_eq!(search["total_hits"], 1);
+    assert_eq!(search["results"][0]["hits"][0]["page"], 1);
+
+    socket.send(Message::Text("not json".into())).await.unwrap();
+    assert_eq!(reply(&mut socket).await["error"]["code"], "VALIDATION_ERROR");
+
+    socket.send(Message::Text(r#"{"id": 4, "type": "close"}"#.into())).await.unwrap();
+    let closed = reply(&mut socket).await;
+    assert_eq!((closed["id"].clone(), closed["reason"].clone()), (4.into(), "client".into()));
+
+    // The closed session gives its slot back; a silent client is disconnected after the idle timeout
+    let mut attempts = 0;
+    let mut socket = loop {
+        match tokio_tungstenite::connect_async(url.as_str()).await {
+            Ok((socket, _)) => break socket,
+            Err(_) if attempts < 20 => attempts += 1,
+            Err(e) => panic!("session slot was not released: {}", e),
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(reply(&mut socket).await["type"], "ready");
+    let closed = tokio::time::timeout(Duration::from_secs(5), reply(&mut socket)).await.unwrap();
+    assert_eq!(closed["type"], "closed");
+    assert_eq!(closed["reason"], "idle_timeout");
+    assert!(matches!(socket.next().await, Some(Ok(Message::Close(_)))));
+}
+
+/// Point the key registry at a file of test keys. The registry is loaded once per process,
+/// so every test sending a key must call this first and use these keys.
+fn use_test_keys() {
+    static KEYS_FILE: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+    let path = KEYS_FILE.get_or_init(|| {
+        let path = env::temp_dir().join(format!("elicit-test-keys-{}.json", std::process::id()));
+        let record = |id: &str, key: &str, scopes: &[&str]| {
+            serde_json::json!({
+                "id": id, "name": id, "prefix": key_prefix(key), "key_hash": hash_key(key), "scopes": scopes,
+            })
+        };
+        let mut revoked = record("revoked", "revoked-key", &["extract"]);
+        revoked["disabled"] = true.into();
+        let mut lapsed = record("lapsed", "lapsed-key", &["extract"]);
+        lapsed["expires_at"] = "2020-01-01T00:00:00Z".into();
+        let keys = serde_json::json!([
+            record("reader", "reader-key", &["extract"]),
+            record("batch", "jobs-key", &["jobs"]),
+            record("other", "other-jobs-key", &["jobs"]),
+            record("support", "support-key", &["jobs", "admin"]),
+            record("ops", "admin-key", &["admin"]),
+            revoked,
+            lapsed,
+        ]);
+        std::fs::write(&path, keys.to_string()).unwrap();
+        path
+    });
+    env::set_var("API_KEYS_FILE", path);
+}
+
+#[tokio::test]
+async fn test_api_keys_are_checked_against_route_scopes() {
+    use_test_keys();
+    let config = Config::from_env().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = routes::app(&config);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+
+    let get = |path: &str, key: &str| {
+        client
+            .get(format!("{}{}", base, path))
+            .header("authorization", format!("Bearer {}", key))
+            .send()
+    };
+    let error_code = |response: reqwest::Response| async move {
+        let status = response.status().as_u16();
+        let body: ErrorResponse = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+        (status, body.error.code)
+    };
+
+    // The jobs key gets past auth to the handler, the extraction key is turned away
+    let response = get("/api/v1/jobs/unknown", "jobs-key").await.unwrap();
+    assert_eq!(error_code(response).await, (404, "NOT_FOUND".to_string()));
+    let response = get("/api/v1/jobs/unknown", "reader-key").await.unwrap();
+    assert_eq!(error_code(response).await, (403, "INSUFFICIENT_SCOPE".to_string()));
+
+    let response = client
+        .post(format!("{}/api/v1/extract/searchable", base))
+        .header("authorization", "Bearer reader-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(error_code(response).await, (403, "INSUFFICIENT_SCOPE".to_string()));
+    let response = client
+        .post(format!("{}/api/v1/extract/binary", base))
+        .header("authorization", "Bearer reader-key")
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(response.status().as_u16(), 403);
+
+    // Disabled, expired and unknown keys are rejected with their own codes
+    let response = get("/api/v1/jobs/unknown", "revoked-key").await.unwrap();
+    assert_eq!(error_code(response).await, (401, "API_KEY_DISABLED".to_string()));
+    let response = get("/api/v1/jobs/unknown", "lapsed-key").await.unwrap();
+    assert_eq!(error_code(response).await, (401, "API_KEY_EXPIRED".to_string()));
+    let response = get("/api/v1/jobs/unknown", "no-such-key").await.unwrap();
+    assert_eq!(error_code(response).await, (401, "INVALID_API_KEY".to_string()));
+}
+
+#[tokio::test]
+async fn test_jobs_are_only_visible_to_the_key_that_created_them() {
+    use_test_keys();
+    let config = Config::from_env().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = routes::app(&config);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+
+    let form = reqwest::multipart::Form::new()
+        .part("file", reqwest::multipart::Part::bytes(b"%PDF-1.4 stand-in".to_vec()).file_name("a.pdf"));
+    let response = client
+        .post(format!("{}/api/v1/jobs", base))
+        .header("authorization", "Bearer jobs-key")
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 202);
+    let job: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    let job_id = job["job_id"].as_str().unwrap().to_string();
+
+    let status = |path: String, key: &str| {
+        let request = client
+            .get(format!("{}/api/v1/jobs/{}", base, path))
+            .header("authorization", format!("Bearer {}", key));
+        async move { request.send().await.unwrap().status().as_u16() }
+    };
+    for path in [job_id.clone(), format!("{}/deliveries", job_id)] {
+        assert_eq!(status(path.clone(), "jobs-key").await, 200);
+        // Another client with the jobs scope can't tell the job exists
+        assert_eq!(status(path.clone(), "other-jobs-key").await, 404);
+        // Admin keys can look into any job
+        assert_eq!(status(path, "support-key").await, 200);
+    }
+}
+
+#[tokio::test]
+async fn test_admin_api_manages_keys_without_a_restart() {
+    use_test_keys();
+    let config = Config::from_env().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = routes::app(&config);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+    let admin = |method: reqwest::Method, path: &str| {
+        client
+            .request(method, format!("{}/api/v1/admin{}", base, path))
+            .header("authorization", "Bearer admin-key")
+    };
+    let job_status = |key: String| {
+        let request = client
+            .get(format!("{}/api/v1/jobs/unknown", base))
+            .header("authorization", format!("Bearer {}", key));
+        async move { request.send().await.unwrap().status().as_u16() }
+    };
+
+    // Only admin keys reach the admin API
+    let response = client
+        .get(format!("{}/api/v1/admin/keys", base))
+        .header("authorization", "Bearer jobs-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = admin(reqwest::Method::POST, "/keys")
+        .header("content-type", "application/json")
+        .body(serde_json::json!({"id": "partner", "name": "Partner Inc", "scopes": ["jobs"]}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let issued: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    let key = issued["key"].as_str().unwrap().to_string();
+    assert!(issued["api_key"].get("key_hash").is_none());
+
+    // The new key works at once and its use shows up
+    assert_eq!(job_status(key.clone()).await, 404);
+    let response = admin(reqwest::Method::GET, "/keys/partner").send().await.unwrap();
+    let view: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(view["usage"]["requests"], 1);
+    let response = admin(reqwest::Method::GET, "/keys").send().await.unwrap();
+    let keys: Vec<serde_json::Value> = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert!(keys.iter().any(|key| key["id"] == "partner" && key["name"] == "Partner Inc"));
+
+    let response = admin(reqwest::Method::POST, "/keys/partner/rotate").send().await.unwrap();
+    let rotated: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    let new_key = rotated["key"].as_str().unwrap().to_string();
+    assert_eq!(job_status(key).await, 401);
+    assert_eq!(job_status(new_key.clone()).await, 404);
+
+    let response = admin(reqwest::Method::POST, "/keys/partner/disable").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(job_status(new_key.clone()).await, 401);
+
+    let response = admin(reqwest::Method::DELETE, "/keys/partner").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+    let response = admin(reqwest::Method::GET, "/keys/partner").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Every change is in the audit trail, newest first, with the admin key that made it
+    let response = admin(reqwest::Method::GET, "/audit?key_id=partner").send().await.unwrap();
+    let events: Vec<serde_json::Value> = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    let actions: Vec<&str> = events.iter().map(|event| event["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, vec!["deleted", "disabled", "rotated", "created"]);
+    assert!(events.iter().all(|event| event["actor"] == "ops"));
+    let response = admin(reqwest::Method::GET, "/audit?limit=0").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,11 +1,31 @@
 //! Unit tests for individual components
 
+mod common;
+
 use elicit::{
     config::Config,
     error::AppError,
-    models::{PdfMetadata, ExtractResponse, ExtractData},
-    services::{PdfProcessor, OcrService},
+    models::{
+        PdfMetadata, ExtractResponse, ExtractData, PreprocessingOptions, PreprocessingStep,
+        ProcessedFile, PageKind, OutputFormat, LayoutUnit, TextSource, JobResponse, JobStatus,
+        StreamEvent, ExtractRequest, ExtractionOptions, Scope, ApiKeyIdentity,
+    },
+    services::{
+        PdfProcessor, OcrService, ImagePreprocessor, parse_osd_rotation, decode_image_pages, write_image_pages,
+        MockOcrEngine, register_ocr_engine, ocr_engine_by_name, parse_language_list,
+        ScanDetector, SearchablePdfBuilder, add_text_layer, parse_tesseract_tsv,
+        native_layout, ocr_layout, to_alto, to_hocr,
+        run_command_with_limits, SubprocessLimits, SubprocessStage,
+        OcrCache, CachedPage, OcrSettings, RecognizedPage, JobQueue, JobRequest,
+        RetryPolicy, WebhookTarget, sign_payload, BatchEntry, BatchExtractor, expand_zip, PageStreamer,
+        ApiKeyRegistry, KeySource, api_keys, parse_scopes, generate_key, hash_key, key_prefix, verify_key,
+    },
 };
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use common::{build_pdf, TestPage};
+use image::{DynamicImage, GrayImage, Luma};
 use chrono::Utc;
 use std::env;
 
@@ -75,10 +95,11 @@ fn test_pdf_processor_availability() {
     assert!(processor.is_available());
 }
 
-#[test]
-fn test_ocr_service_availability() {
+#[tokio::test]
+#[allow(clippy::bool_comparison)]
+async fn test_ocr_service_availability() {
     // OCR service availability depends on system setup
-    let available = OcrService::is_available();
+    let available = OcrService::is_available().await;
     // Just ensure it returns a boolean without panicking
     assert!(available == true || available == false);
 }
@@ -114,6 +135,7 @@ fn test_extract_response_creation() {
         success: true,
         data: extract_data,
         processing_time_ms: 150,
+        options: None,
     };
     
     assert!(response.success);
@@ -178,4 +200,2166 @@ fn test_error_conversions() {
         AppError::ValidationError { message } => assert!(message.contains("JSON parsing error")),
         _ => panic!("Expected ValidationError"),
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_preprocessing_options_parsing() {
+    let options = PreprocessingOptions::parse("deskew, grayscale,binarize").unwrap();
+    assert_eq!(
+        options.steps,
+        vec![PreprocessingStep::Grayscale, PreprocessingStep::Deskew, PreprocessingStep::Binarize]
+    );
+
+    assert!(PreprocessingOptions::parse("none").unwrap().is_empty());
+    assert_eq!(PreprocessingOptions::parse("all").unwrap().steps.len(), 6);
+    assert!(PreprocessingOptions::parse("sharpen").is_err());
+}
+
+#[test]
+fn test_preprocessing_binarize_and_despeckle() {
+    // Dark text on a gradient background with a single noise pixel
+    let mut gray = GrayImage::from_fn(60, 60, |x, _| Luma([150 + (x as u8)]));
+    for x in 10..50 {
+        for y in 28..32 {
+            gray.put_pixel(x, y, Luma([20]));
+        }
+    }
+    gray.put_pixel(5, 5, Luma([0]));
+
+    let binary = ImagePreprocessor::binarize(&gray);
+    assert!(binary.pixels().all(|p| p[0] == 0 || p[0] == 255));
+    assert_eq!(binary.get_pixel(30, 30)[0], 0);
+    assert_eq!(binary.get_pixel(30, 5)[0], 255);
+
+    let clean = ImagePreprocessor::despeckle(&binary);
+    assert_eq!(clean.get_pixel(5, 5)[0], 255);
+    assert_eq!(clean.get_pixel(30, 30)[0], 0);
+}
+
+#[test]
+fn test_preprocessing_deskew() {
+    // Horizontal "text lines", then rotated by 3 degrees
+    let mut gray = GrayImage::from_pixel(400, 400, Luma([255]));
+    for line in 0..8 {
+        let y = 60 + line * 40;
+        for x in 50..350 {
+            for dy in 0..6 {
+                gray.put_pixel(x, y + dy, Luma([0]));
+            }
+        }
+    }
+    let skewed = ImagePreprocessor::rotate_gray(&gray, 3.0);
+
+    let angle = ImagePreprocessor::detect_skew(&skewed);
+    assert!((angle.abs() - 3.0).abs() < 0.5, "detected {}", angle);
+
+    let straightened = ImagePreprocessor::rotate_gray(&skewed, -angle);
+    assert!(ImagePreprocessor::detect_skew(&straightened).abs() < 0.5);
+}
+
+#[test]
+fn test_preprocessing_pipeline_reports_steps() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(800, 1000, Luma([255])));
+    let options = PreprocessingOptions::parse("upscale,grayscale,auto_rotate").unwrap();
+
+    let result = ImagePreprocessor::apply(image, &options, Some(90));
+    assert_eq!(
+        result.applied,
+        vec![PreprocessingStep::Upscale, PreprocessingStep::AutoRotate, PreprocessingStep::Grayscale]
+    );
+    // Upscaled from ~97 DPI and rotated a quarter turn
+    assert!(result.image.width() > 1000);
+    assert!(result.image.height() > result.image.width() / 2);
+    assert!(result.image.width() > result.image.height());
+
+    let untouched = ImagePreprocessor::apply(
+        DynamicImage::ImageLuma8(GrayImage::new(10, 10)),
+        &PreprocessingOptions::none(),
+        None,
+    );
+    assert!(untouched.applied.is_empty());
+}
+
+#[test]
+fn test_parse_osd_rotation() {
+    let osd = "Page number: 0\nOrientation in degrees: 270\nRotate: 90\nOrientation confidence: 4.62\n";
+    assert_eq!(parse_osd_rotation(osd), Some(90));
+    assert_eq!(parse_osd_rotation("garbage"), None);
+}
+
+fn encode_png(image: &DynamicImage) -> Vec<u8> {
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut buffer, image::ImageOutputFormat::Png).unwrap();
+    buffer.into_inner()
+}
+
+#[test]
+fn test_processed_file_image_detection() {
+    let png = encode_png(&DynamicImage::ImageLuma8(GrayImage::new(4, 4)));
+
+    let image_file = ProcessedFile::new("scan.png".to_string(), png.clone())
+        .with_mime_type("image/png".to_string());
+    assert!(image_file.is_image());
+    assert!(!image_file.is_pdf());
+    assert_eq!(image_file.image_format(), Some(image::ImageFormat::Png));
+
+    // Detection relies on content, not on the file name
+    let unnamed = ProcessedFile::new("upload".to_string(), png);
+    assert!(unnamed.is_image());
+
+    let pdf = ProcessedFile::new("doc.pdf".to_string(), b"%PDF-1.4".to_vec());
+    assert!(pdf.is_pdf());
+    assert!(!pdf.is_image());
+}
+
+#[test]
+fn test_decode_multi_page_tiff() {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    {
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        encoder.write_image::<colortype::Gray8>(8, 6, &[0u8; 48]).unwrap();
+        encoder.write_image::<colortype::RGB8>(5, 4, &[255u8; 60]).unwrap();
+    }
+
+    let pages = decode_image_pages(buffer.get_ref()).unwrap();
+    assert_eq!(pages.len(), 2);
+    assert_eq!((pages[0].width(), pages[0].height()), (8, 6));
+    assert_eq!((pages[1].width(), pages[1].height()), (5, 4));
+
+    let single = decode_image_pages(&encode_png(&DynamicImage::ImageLuma8(GrayImage::new(3, 3)))).unwrap();
+    assert_eq!(single.len(), 1);
+
+    assert!(decode_image_pages(b"not an image").is_err());
+}
+
+#[test]
+fn test_tiff_pages_are_written_one_at_a_time_within_budget() {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let tiff_with_frames = |frames: usize| {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        for _ in 0..frames {
+            encoder.write_image::<colortype::Gray8>(4, 4, &[255u8; 16]).unwrap();
+        }
+        buffer.into_inner()
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let paths = write_image_pages(&tiff_with_frames(3), dir.path()).unwrap();
+    assert_eq!(paths.len(), 3);
+    assert!(paths[2].ends_with("page-0002.png"));
+    assert_eq!(image::image_dimensions(&paths[0]).unwrap(), (4, 4));
+
+    match decode_image_pages(&tiff_with_frames(501)) {
+        Err(AppError::InvalidFile { message }) => assert!(message.contains("more than 500 frames")),
+        other => panic!("Expected InvalidFile, got {:?}", other.map(|pages| pages.len())),
+    }
+}
+
+#[test]
+fn test_decode_bilevel_group4_tiff() {
+    use fax::{encoder::Encoder, Color, VecWriter};
+
+    // A fax scan: white page with a black square, width not a multiple of 8
+    let (width, height) = (20u16, 10u16);
+    let black = |x: u16, y: u16| (4..12).contains(&x) && (2..6).contains(&y);
+    let mut encoder = Encoder::new(VecWriter::new());
+    for y in 0..height {
+        let line = (0..width).map(|x| if black(x, y) { Color::Black } else { Color::White });
+        encoder.encode_line(line, width).unwrap();
+    }
+    let strip = encoder.finish().unwrap().finish();
+    // Group 4, PhotometricInterpretation WhiteIsZero
+    let white_is_zero = fax::tiff::wrap(&strip, width.into(), height.into());
+
+    let pages = decode_image_pages(&white_is_zero).unwrap();
+    assert_eq!(pages.len(), 1);
+    let page = pages[0].as_luma8().expect("bilevel frames decode to 8-bit gray");
+    assert_eq!(page.dimensions(), (20, 10));
+    for (x, y, pixel) in page.enumerate_pixels() {
+        let expected = if black(x as u16, y as u16) { 0 } else { 255 };
+        assert_eq!(pixel.0[0], expected, "pixel {},{}", x, y);
+    }
+
+    // The same strip read as BlackIsZero comes out the other way round
+    let mut black_is_zero = white_is_zero.clone();
+    let photometric = 8 + 2 + 12 * 3;
+    assert_eq!(&black_is_zero[photometric..photometric + 2], &262u16.to_le_bytes());
+    black_is_zero[photometric + 8] = 1;
+    let inverted = decode_image_pages(&black_is_zero).unwrap();
+    assert_eq!(inverted[0].as_luma8().unwrap().get_pixel(5, 3).0[0], 255);
+    assert_eq!(inverted[0].as_luma8().unwrap().get_pixel(0, 0).0[0], 0);
+
+    // A tiny strip claiming an enormous frame is refused before decoding
+    let bomb = fax::tiff::wrap(&strip, 60_000, 60_000);
+    match decode_image_pages(&bomb) {
+        Err(AppError::InvalidFile { message }) => assert!(message.contains("too large")),
+        other => panic!("Expected InvalidFile, got {:?}", other.map(|pages| pages.len())),
+    }
+}
+
+#[test]
+fn test_ocr_engine_registry() {
+    assert_eq!(ocr_engine_by_name("tesseract").unwrap().name(), "tesseract");
+    assert_eq!(ocr_engine_by_name(" Tesseract ").unwrap().name(), "tesseract");
+
+    match ocr_engine_by_name("no-such-engine") {
+        Err(AppError::ValidationError { message }) => assert!(message.contains("tesseract")),
+        _ => panic!("Expected ValidationError"),
+    }
+
+    register_ocr_engine(Arc::new(MockOcrEngine::new("registered")));
+    assert_eq!(ocr_engine_by_name("mock").unwrap().name(), "mock");
+}
+
+#[test]
+fn test_parse_tesseract_language_list() {
+    let listing = "List of available languages in \"/usr/share/tessdata/\" (3):\neng\nosd\nspa\n";
+    assert_eq!(parse_language_list(listing), vec!["eng", "osd", "spa"]);
+}
+
+#[tokio::test]
+async fn test_image_extraction_with_mock_engine() {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    {
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        encoder.write_image::<colortype::Gray8>(8, 8, &[255u8; 64]).unwrap();
+        encoder.write_image::<colortype::Gray8>(8, 8, &[255u8; 64]).unwrap();
+    }
+
+    let engine = Arc::new(MockOcrEngine::new("mock page text"));
+    let processor = PdfProcessor::new().with_ocr_engine(engine.clone());
+    let file = ProcessedFile::new("scan.tiff".to_string(), buffer.into_inner())
+        .with_mime_type("image/tiff".to_string());
+
+    let result = processor.extract_text(file, &ExtractionOptions::default()).await.unwrap();
+    assert_eq!(result.pages, 2);
+    assert_eq!(result.text, "mock page text\n\nmock page text");
+    assert!(result.metadata.ocr_used);
+    assert_eq!(engine.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_image_extraction_with_unhealthy_engine() {
+    let png = encode_png(&DynamicImage::ImageLuma8(GrayImage::new(4, 4)));
+    let processor = PdfProcessor::new()
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("unused").unhealthy()));
+    let file = ProcessedFile::new("scan.png".to_string(), png);
+
+    match processor.extract_text(file, &ExtractionOptions::default()).await {
+        Err(AppError::OcrError { message }) => assert!(message.contains("not available")),
+        other => panic!("Expected OcrError, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_scan_detector_classifies_pages() {
+    let pdf = build_pdf(vec![
+        TestPage::Text(vec!["Invoice 1234", "Total due: 99.00"]),
+        TestPage::Image,
+        TestPage::ImageWithInvisibleText("Already OCR'd"),
+        TestPage::ImageInForm,
+        TestPage::Blank,
+    ]);
+
+    let analysis = ScanDetector::analyze(&pdf).unwrap();
+    let kinds: Vec<PageKind> = analysis.iter().map(|p| p.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![PageKind::Native, PageKind::Scanned, PageKind::Mixed, PageKind::Scanned, PageKind::Empty]
+    );
+
+    assert_eq!(analysis[0].text_operators, 2);
+    assert_eq!(analysis[0].image_coverage, 0.0);
+    assert!((analysis[1].image_coverage - 1.0).abs() < 0.001);
+    assert_eq!(analysis[2].invisible_text_operators, 1);
+    assert_eq!(analysis[3].image_count, 1);
+    assert!(analysis.iter().all(|p| !p.reasons.is_empty()));
+    assert!(ScanDetector::needs_ocr(&analysis));
+}
+
+#[test]
+fn test_scan_detector_native_document() {
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Hello"]), TestPage::Text(vec!["World"])]);
+    let analysis = ScanDetector::analyze(&pdf).unwrap();
+    assert_eq!(analysis.len(), 2);
+    assert!(!ScanDetector::needs_ocr(&analysis));
+
+    assert!(ScanDetector::analyze(b"not a pdf").is_err());
+}
+
+#[tokio::test]
+async fn test_pdf_extraction_exposes_page_analysis() {
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Quarterly report", "Revenue grew"])]);
+    let file = ProcessedFile::new("report.pdf".to_string(), pdf)
+        .with_mime_type("application/pdf".to_string());
+
+    let result = PdfProcessor::new().extract_text(file, &ExtractionOptions::default()).await.unwrap();
+    assert!(result.text.contains("Quarterly report"));
+    assert_eq!(result.pages, 1);
+    assert_eq!(result.metadata.page_analysis.len(), 1);
+    assert_eq!(result.metadata.page_analysis[0].kind, PageKind::Native);
+    assert!(!result.metadata.ocr_used);
+}
+
+const SAMPLE_TSV: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t2550\t3300\t-1\t
+5\t1\t1\t1\t1\t1\t300\t300\t400\t60\t96.5\tInvoice
+5\t1\t1\t1\t1\t2\t740\t300\t300\t60\t91.2\tnumber
+5\t1\t1\t1\t2\t1\t300\t400\t200\t60\t88.0\t2024
+5\t1\t2\t1\t1\t1\t300\t900\t300\t60\t90.0\tTotal
+";
+
+#[test]
+fn test_parse_tesseract_tsv() {
+    let page = parse_tesseract_tsv(SAMPLE_TSV);
+    assert_eq!((page.width, page.height), (2550, 3300));
+    assert_eq!(page.words.len(), 4);
+    assert_eq!(page.words[0].text, "Invoice");
+    assert_eq!(page.words[1].left, 740);
+    assert!((page.words[0].confidence - 96.5).abs() < f32::EPSILON);
+    assert_eq!(page.text, "Invoice number\n2024\n\nTotal");
+}
+
+#[test]
+fn test_text_layer_makes_scanned_page_searchable() {
+    let pdf = build_pdf(vec![TestPage::Image]);
+    let mut doc = lopdf::Document::load_mem(&pdf).unwrap();
+    let page_id = doc.get_pages()[&1];
+
+    add_text_layer(&mut doc, page_id, &parse_tesseract_tsv(SAMPLE_TSV)).unwrap();
+    let mut searchable = Vec::new();
+    doc.save_to(&mut searchable).unwrap();
+
+    let text = pdf_extract::extract_text_from_mem(&searchable).unwrap();
+    assert!(text.contains("Invoice"));
+    assert!(text.contains("Total"));
+
+    // The page image is still there, the text over it is invisible
+    let analysis = ScanDetector::analyze(&searchable).unwrap();
+    assert_eq!(analysis[0].kind, PageKind::Mixed);
+    assert_eq!(analysis[0].invisible_text_operators, 4);
+}
+
+#[tokio::test]
+async fn test_searchable_pdf_leaves_native_pdf_untouched() {
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Already searchable"])]);
+    let engine = Arc::new(MockOcrEngine::new("unused"));
+
+    let searchable = SearchablePdfBuilder::new(engine.clone(), PreprocessingOptions::none())
+        .build(&pdf)
+        .await
+        .unwrap();
+
+    assert_eq!(searchable.content, pdf);
+    assert!(searchable.ocr_pages.is_empty());
+    assert_eq!(engine.calls(), 0);
+}
+
+#[test]
+fn test_output_format_parsing() {
+    assert_eq!("alto".parse::<OutputFormat>().unwrap(), OutputFormat::Alto);
+    assert_eq!("hOCR".parse::<OutputFormat>().unwrap(), OutputFormat::Hocr);
+    assert_eq!("".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+    assert!(!OutputFormat::Json.needs_layout());
+    assert!(OutputFormat::Alto.needs_layout());
+    assert!("pdf".parse::<OutputFormat>().is_err());
+}
+
+#[test]
+fn test_ocr_layout_groups_words_into_blocks_and_lines() {
+    let page = parse_tesseract_tsv(SAMPLE_TSV);
+
+    let layout = ocr_layout(std::slice::from_ref(&page), None);
+    assert_eq!(layout.unit, LayoutUnit::Pixel);
+    let blocks = &layout.pages[0].blocks;
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].lines.len(), 2);
+    assert_eq!(blocks[0].lines[0].text(), "Invoice number");
+    assert_eq!(blocks[0].lines[0].bbox.x, 300.0);
+    assert_eq!(blocks[0].lines[0].bbox.width, 740.0);
+
+    // On a PDF page the pixel boxes are scaled to points
+    let layout = ocr_layout(&[page], Some(&[(612.0, 792.0)]));
+    assert_eq!(layout.unit, LayoutUnit::Point);
+    let word = &layout.pages[0].blocks[0].lines[0].words[0];
+    assert!((word.bbox.x - 72.0).abs() < 0.01);
+    assert_eq!(layout.pages[0].source, TextSource::Ocr);
+}
+
+#[test]
+fn test_native_layout_has_word_positions() {
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Quarterly report", "Revenue grew"])]);
+
+    let layout = native_layout(&pdf).unwrap();
+    assert_eq!(layout.unit, LayoutUnit::Point);
+    let page = &layout.pages[0];
+    assert_eq!(page.source, TextSource::Native);
+    assert_eq!((page.width, page.height), (612.0, 792.0));
+
+    let lines: Vec<_> = page.blocks.iter().flat_map(|b| b.lines.iter()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].text(), "Quarterly report");
+    assert_eq!(lines[1].text(), "Revenue grew");
+
+    // Text starts at (72, 720) in PDF space, i.e. 72pt from the top with a 12pt font
+    let first = &lines[0].words[0];
+    assert!((first.bbox.x - 72.0).abs() < 0.5);
+    assert!(first.bbox.y > 60.0 && first.bbox.y < 72.0);
+    assert!(lines[1].bbox.y > lines[0].bbox.y);
+    assert!(first.confidence.is_none());
+}
+
+#[test]
+fn test_alto_and_hocr_export() {
+    let layout = ocr_layout(&[parse_tesseract_tsv(SAMPLE_TSV)], None);
+
+    let alto = to_alto(&layout, "scan <1>.png");
+    assert!(alto.contains("<MeasurementUnit>pixel</MeasurementUnit>"));
+    assert!(alto.contains("<fileName>scan &lt;1&gt;.png</fileName>"));
+    assert!(alto.contains(r#"<Page ID="page_1" PHYSICAL_IMG_NR="1" WIDTH="2550" HEIGHT="3300">"#));
+    assert!(alto.contains(r#"CONTENT="Invoice" HPOS="300" VPOS="300" WIDTH="400" HEIGHT="60" WC="0.96""#));
+    assert_eq!(alto.matches("<TextBlock ").count(), 2);
+    assert_eq!(alto.matches("<SP/>").count(), 1);
+
+    let hocr = to_hocr(&layout, "scan.png");
+    assert!(hocr.contains(r#"class="ocr_page" id="page_1" title="image &quot;scan.png&quot;; bbox 0 0 2550 3300; ppageno 0""#));
+    assert!(hocr.contains(r#"<span class="ocrx_word" id="word_1_1_1_1" title="bbox 300 300 700 360; x_wconf 97">Invoice</span>"#));
+    assert_eq!(hocr.matches("class=\"ocr_line\"").count(), 3);
+}
+
+#[tokio::test]
+async fn test_image_extraction_builds_pixel_layout() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(400, 200, Luma([255])));
+    let file = ProcessedFile::new("scan.png".to_string(), encode_png(&image))
+        .with_mime_type("image/png".to_string());
+
+    let result = PdfProcessor::new()
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("hello world")))
+        .extract_text(file, &ExtractionOptions::default().with_output_format(OutputFormat::Hocr))
+        .await
+        .unwrap();
+
+    let layout = result.layout.unwrap();
+    assert_eq!(layout.unit, LayoutUnit::Pixel);
+    assert_eq!((layout.pages[0].width, layout.pages[0].height), (400.0, 200.0));
+    let words: Vec<_> = layout.pages[0].words().map(|w| w.text.as_str()).collect();
+    assert_eq!(words, vec!["hello", "world"]);
+}
+
+#[tokio::test]
+async fn test_pdf_ocr_layout_follows_pages_not_images() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Stand-in for pdftoppm that "renders" every page as a blank Letter page at 300 dpi
+    let bin = tempfile::tempdir().unwrap();
+    let render = bin.path().join("render.png");
+    DynamicImage::ImageLuma8(GrayImage::from_pixel(2550, 3300, Luma([255]))).save(&render).unwrap();
+    let pdftoppm = bin.path().join("pdftoppm");
+    std::fs::write(
+        &pdftoppm,
+        format!("#!/bin/sh\nfor prefix; do :; done\ncp '{}' \"$prefix.png\"\n", render.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&pdftoppm, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = env::var("PATH").unwrap_or_default();
+    env::set_var("PATH", format!("{}:{}", bin.path().display(), path));
+
+    // Three images over two pages, one of them covering only half its page
+    let pdf = build_pdf(vec![TestPage::TwoImages, TestPage::HalfPageImage]);
+    let engine = Arc::new(MockOcrEngine::new("Scanned words"));
+    let result = PdfProcessor::new()
+        .with_ocr_engine(engine.clone())
+        .extract_text(
+            ProcessedFile::new("scan.pdf".to_string(), pdf),
+            &ExtractionOptions::default().with_output_format(OutputFormat::Alto),
+        )
+        .await
+        .unwrap();
+    env::set_var("PATH", path);
+
+    assert_eq!(engine.calls(), 2);
+    let layout = result.layout.unwrap();
+    assert_eq!(layout.unit, LayoutUnit::Point);
+    let numbers: Vec<u32> = layout.pages.iter().map(|page| page.number).collect();
+    assert_eq!(numbers, vec![1, 2]);
+    for page in &layout.pages {
+        assert_eq!((page.width, page.height), (612.0, 792.0));
+        // The mock's first word sits 10px in on the render, 2.4pt at 300 dpi
+        let word = page.words().next().unwrap();
+        assert_eq!(word.text, "Scanned");
+        assert!((word.bbox.x - 2.4).abs() < 0.01);
+        assert!((word.bbox.width - 140.0 * 72.0 / 300.0).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_subprocess_limits_follow_request_timeout() {
+    let ocr = SubprocessLimits::for_stage(SubprocessStage::Recognition, 30, 512);
+    assert_eq!(ocr.timeout, std::time::Duration::from_secs(15));
+    assert_eq!(ocr.memory_bytes, 512 * 1024 * 1024);
+    assert!(ocr.cpu_seconds >= 15);
+
+    // Very short request timeouts still leave each stage at least a second
+    let probe = SubprocessLimits::for_stage(SubprocessStage::Probe, 1, 512);
+    assert_eq!(probe.timeout, std::time::Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_subprocess_timeout_reports_stage() {
+    let limits = SubprocessLimits {
+        timeout: std::time::Duration::from_millis(200),
+        cpu_seconds: 5,
+        memory_bytes: 512 * 1024 * 1024,
+    };
+    let mut command = tokio::process::Command::new("sleep");
+    command.arg("10");
+
+    let started = std::time::Instant::now();
+    let err = run_command_with_limits(command, SubprocessStage::Recognition, limits)
+        .await
+        .unwrap_err();
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(matches!(err, AppError::Timeout { ref stage } if stage == "ocr"));
+    assert_eq!(err.error_code(), "REQUEST_TIMEOUT");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_subprocess_resource_limits_are_applied() {
+    let limits = SubprocessLimits {
+        timeout: std::time::Duration::from_secs(5),
+        cpu_seconds: 7,
+        memory_bytes: 256 * 1024 * 1024,
+    };
+    let mut command = tokio::process::Command::new("sh");
+    command.arg("-c").arg("ulimit -t; ulimit -v");
+
+    let output = run_command_with_limits(command, SubprocessStage::Probe, limits)
+        .await
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let values: Vec<&str> = stdout.lines().collect();
+    assert_eq!(values, vec!["7", "262144"]);
+}
+
+#[tokio::test]
+async fn test_missing_subprocess_is_not_a_timeout() {
+    let command = tokio::process::Command::new("elicit-no-such-binary");
+    let err = run_command_with_limits(
+        command,
+        SubprocessStage::Probe,
+        SubprocessLimits::for_stage(SubprocessStage::Probe, 30, 512),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, AppError::OcrError { .. }));
+}
+
+#[test]
+fn test_ocr_cache_key_covers_settings() {
+    let image = b"page image bytes";
+    let settings = OcrSettings::default();
+    let none = PreprocessingOptions::none();
+    let key = OcrCache::key(image, "tesseract", &settings, &none);
+
+    assert_eq!(key.len(), 64);
+    assert_eq!(key, OcrCache::key(image, "tesseract", &settings, &none));
+    assert_ne!(key, OcrCache::key(b"other page", "tesseract", &settings, &none));
+    assert_ne!(key, OcrCache::key(image, "mock", &settings, &none));
+    assert_ne!(key, OcrCache::key(image, "tesseract", &settings, &PreprocessingOptions::all()));
+
+    let german = OcrSettings {
+        language: "deu".to_string(),
+        ..OcrSettings::default()
+    };
+    assert_ne!(key, OcrCache::key(image, "tesseract", &german, &none));
+}
+
+fn cached_page(text: &str) -> CachedPage {
+    CachedPage {
+        page: RecognizedPage {
+            text: text.to_string(),
+            ..RecognizedPage::default()
+        },
+        preprocessing_applied: vec![PreprocessingStep::Grayscale],
+    }
+}
+
+#[test]
+fn test_ocr_cache_memory_lru_is_bounded() {
+    let cache = OcrCache::new(2, None);
+    cache.put("a", cached_page("first"));
+    cache.put("b", cached_page("second"));
+    assert!(cache.get("a").is_some()); // "b" is now least recently used
+    cache.put("c", cached_page("third"));
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("b").is_none());
+    assert_eq!(cache.get("a").unwrap().page.text, "first");
+    assert_eq!(cache.get("c").unwrap().page.text, "third");
+}
+
+#[test]
+fn test_ocr_cache_disk_store_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = OcrCache::key(b"scan", "mock", &OcrSettings::default(), &PreprocessingOptions::none());
+
+    OcrCache::new(4, Some(dir.path().to_path_buf())).put(&key, cached_page("from disk"));
+
+    // A fresh cache without memory entries finds it on disk
+    let cache = OcrCache::new(4, Some(dir.path().to_path_buf()));
+    let entry = cache.get(&key).unwrap();
+    assert_eq!(entry.page.text, "from disk");
+    assert_eq!(entry.preprocessing_applied, vec![PreprocessingStep::Grayscale]);
+    assert_eq!(cache.len(), 1);
+
+    // Disk-only caches work too
+    let disk_only = OcrCache::new(0, Some(dir.path().to_path_buf()));
+    assert!(disk_only.get(&key).is_some());
+    assert!(disk_only.is_empty());
+}
+
+#[tokio::test]
+async fn test_repeated_upload_is_served_from_ocr_cache() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 32, Luma([255])));
+    let png = encode_png(&image);
+    let engine = Arc::new(MockOcrEngine::new("cached text"));
+    let cache = Arc::new(OcrCache::new(8, None));
+
+    let processor = PdfProcessor::new()
+        .with_ocr_engine(engine.clone())
+        .with_ocr_cache(cache.clone());
+    let upload = || {
+        ProcessedFile::new("scan.png".to_string(), png.clone())
+            .with_mime_type("image/png".to_string())
+    };
+
+    let first = processor.extract_text(upload(), &ExtractionOptions::default()).await.unwrap();
+    assert_eq!(first.metadata.ocr_cache_hits, 0);
+    assert_eq!(engine.calls(), 1);
+
+    let second = processor.extract_text(upload(), &ExtractionOptions::default()).await.unwrap();
+    assert_eq!(second.text, "cached text");
+    assert_eq!(second.metadata.ocr_cache_hits, 1);
+    assert_eq!(engine.calls(), 1);
+}
+
+/// The API key submitting jobs in these tests
+fn job_client(id: &str, scopes: Vec<Scope>) -> ApiKeyIdentity {
+    ApiKeyIdentity { id: id.to_string(), name: id.to_string(), scopes }
+}
+
+/// Poll a job until a worker has finished with it
+async fn wait_for_job(queue: &JobQueue, id: &str) -> JobResponse {
+    let client = job_client("client", vec![Scope::Jobs]);
+    for _ in 0..200 {
+        let job = queue.get(&client, id).unwrap();
+        if job.status.is_finished() {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("job {} did not finish", id);
+}
+
+fn png_upload() -> ProcessedFile {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 32, Luma([255])));
+    ProcessedFile::new("scan.png".to_string(), encode_png(&image))
+        .with_mime_type("image/png".to_string())
+}
+
+#[tokio::test]
+async fn test_job_runs_in_background_and_reports_result() {
+    let queue = JobQueue::new(1, 4, Duration::from_secs(60));
+    let request = JobRequest::new(png_upload())
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("queued text")));
+
+    let job = queue.submit(&job_client("client", vec![Scope::Jobs]), request).unwrap();
+    assert_eq!(job.status, JobStatus::Queued);
+    assert_eq!(job.file_name, "scan.png");
+    assert!(job.result.is_none());
+
+    let job = wait_for_job(&queue, &job.job_id).await;
+    assert_eq!(job.status, JobStatus::Completed);
+    assert_eq!((job.progress.pages_done, job.progress.pages_total), (1, Some(1)));
+    assert!(job.started_at.is_some() && job.finished_at.is_some());
+    assert!(job.expires_at.unwrap() > job.finished_at.unwrap());
+    assert_eq!(job.result.unwrap().data.text, "queued text");
+    assert!(job.error.is_none());
+}
+
+#[tokio::test]
+async fn test_failed_job_reports_error() {
+    let queue = JobQueue::new(1, 4, Duration::from_secs(60));
+    let request = JobRequest::new(png_upload())
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("unused").unhealthy()));
+
+    let job = queue.submit(&job_client("client", vec![Scope::Jobs]), request).unwrap();
+    let job = wait_for_job(&queue, &job.job_id).await;
+
+    assert_eq!(job.status, JobStatus::Failed);
+    assert!(job.result.is_none());
+    assert_eq!(job.error.unwrap().code, "OCR_ERROR");
+}
+
+#[tokio::test]
+async fn test_unknown_and_expired_jobs_are_not_found() {
+    let queue = JobQueue::new(1, 4, Duration::from_millis(50));
+    let client = job_client("client", vec![Scope::Jobs]);
+    assert!(matches!(queue.get(&client, "missing"), Err(AppError::NotFound { .. })));
+
+    let request = JobRequest::new(png_upload())
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("short lived")));
+    let job = queue.submit(&job_client("client", vec![Scope::Jobs]), request).unwrap();
+    wait_for_job(&queue, &job.job_id).await;
+    assert_eq!(queue.len(), 1);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(matches!(queue.get(&client, &job.job_id), Err(AppError::NotFound { .. })));
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_jobs_are_only_visible_to_their_key() {
+    let queue = JobQueue::new(1, 4, Duration::from_secs(60));
+    let (owner, other) = (job_client("client", vec![Scope::Jobs]), job_client("other", vec![Scope::Jobs]));
+    let request = JobRequest::new(png_upload())
+        .with_ocr_engine(Arc::new(MockOcrEngine::new("private text")));
+    let job = queue.submit(&owner, request).unwrap();
+    wait_for_job(&queue, &job.job_id).await;
+
+    // Another client's job looks like a missing one
+    assert!(matches!(queue.get(&other, &job.job_id), Err(AppError::NotFound { .. })));
+    assert!(matches!(queue.deliveries(&other, &job.job_id), Err(AppError::NotFound { .. })));
+
+    // Admin keys see every job
+    let admin = job_client("ops", vec![Scope::Admin]);
+    assert_eq!(queue.get(&admin, &job.job_id).unwrap().result.unwrap().data.text, "private text");
+    assert!(queue.deliveries(&admin, &job.job_id).unwrap().is_empty());
+}
+
+#[test]
+fn test_public_address_checks_see_through_ipv6_transition_addresses() {
+    use elicit::services::is_public_address;
+
+    let public = |address: &str| is_public_address(address.parse().unwrap());
+    assert!(public("93.184.216.34"));
+    assert!(public("2606:2800:220:1::248"));
+    assert!(!public("169.254.169.254"));
+    assert!(!public("::1"));
+
+    // Each of these carries 169.254.169.254 or 10.0.0.1
+    for internal in [
+        "::ffff:169.254.169.254",
+        "::169.254.169.254",
+        "64:ff9b::a9fe:a9fe",
+        "2002:a9fe:a9fe::1",
+        "2001:0:a00:1::1",
+        "2001:0:5db8:1:0:ffff:f5ff:fffe",
+        "64:ff9b:1::5db8:1",
+    ] {
+        assert!(!public(internal), "{} should not be public", internal);
+    }
+    // The same mechanisms wrapping public addresses are fine
+    assert!(public("64:ff9b::5db8:d822"));
+    assert!(public("2002:5db8:d822::1"));
+}
+
+#[test]
+fn test_webhook_retry_backoff_doubles() {
+    let policy = RetryPolicy::new(6, Duration::from_millis(500), Duration::from_secs(10));
+    let delays: Vec<_> = (1..=5).map(|attempt| policy.delay_before(attempt).as_millis()).collect();
+    assert_eq!(delays, vec![0, 500, 1000, 2000, 4000]);
+
+    // Capped however many attempts are allowed
+    let policy = RetryPolicy::new(40, Duration::from_secs(1), Duration::from_secs(10));
+    assert_eq!(policy.delay_before(40), Duration::from_secs(300));
+}
+
+#[test]
+fn test_webhook_signature_and_target_validation() {
+    let signature = sign_payload("secret", 1_700_000_000, b"{}");
+    assert!(signature.starts_with("sha256="));
+    assert_eq!(signature.len(), "sha256=".len() + 64);
+    assert_eq!(signature, sign_payload("secret", 1_700_000_000, b"{}"));
+    assert_ne!(signature, sign_payload("other", 1_700_000_000, b"{}"));
+    assert_ne!(signature, sign_payload("secret", 1_700_000_001, b"{}"));
+
+    assert!(WebhookTarget::new("https://example.com/hooks/elicit", "secret").is_ok());
+    assert!(matches!(
+        WebhookTarget::new("ftp://example.com/hook", "secret"),
+        Err(AppError::ValidationError { .. })
+    ));
+    assert!(matches!(WebhookTarget::new("not a url", "secret"), Err(AppError::ValidationError { .. })));
+}
+
+fn build_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in entries {
+        if name.ends_with('/') {
+            writer.add_directory(*name, options).unwrap();
+        } else {
+            writer.start_file(*name, options).unwrap();
+            std::io::Write::write_all(&mut writer, content).unwrap();
+        }
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_expand_zip_lists_files_and_bounds_entries() {
+    let png = png_upload().content;
+    let big = vec![b'a'; 4096];
+    let archive = ProcessedFile::new(
+        "invoices.zip".to_string(),
+        build_zip(&[
+            ("scans/", b""),
+            ("scans/one.png", &png),
+            ("__MACOSX/scans/._one.png", b"junk"),
+            (".DS_Store", b"junk"),
+            ("huge.pdf", &big),
+        ]),
+    );
+    assert!(archive.is_zip());
+
+    let entries = expand_zip(&archive, 10, 1024, 1 << 20).unwrap();
+    let names: Vec<_> = entries.iter().map(|e| e.file_name()).collect();
+    assert_eq!(names, vec!["invoices.zip/scans/one.png", "invoices.zip/huge.pdf"]);
+    assert!(matches!(&entries[0], BatchEntry::File(file) if file.content == png));
+    assert!(matches!(&entries[1], BatchEntry::Rejected { error: AppError::FileTooLarge { .. }, .. }));
+
+    assert!(matches!(expand_zip(&archive, 1, 1024, 1 << 20), Err(AppError::ValidationError { .. })));
+    let not_zip = ProcessedFile::new("broken.zip".to_string(), b"PK\x03\x04 not really".to_vec());
+    assert!(matches!(expand_zip(&not_zip, 10, 1024, 1 << 20), Err(AppError::InvalidFile { .. })));
+
+    // Entries that each fit can still add up to more than the archive may decompress to
+    let page = vec![b'a'; 600];
+    let archive = ProcessedFile::new(
+        "pages.zip".to_string(),
+        build_zip(&[("1.pdf", &page), ("2.pdf", &page), ("3.pdf", &page)]),
+    );
+    let entries = expand_zip(&archive, 10, 1024, 1000).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(matches!(&entries[0], BatchEntry::File(file) if file.size == 600));
+    for entry in &entries[1..] {
+        assert!(matches!(entry, BatchEntry::Rejected { error: AppError::ValidationError { .. }, .. }));
+    }
+}
+
+#[tokio::test]
+async fn test_batch_failures_stay_per_file() {
+    let engine = Arc::new(MockOcrEngine::new("batch text"));
+    let entries = vec![
+        BatchEntry::File(png_upload()),
+        BatchEntry::File(ProcessedFile::new("notes.txt".to_string(), b"plain text".to_vec())),
+        BatchEntry::Rejected {
+            file_name: "huge.pdf".to_string(),
+            error: AppError::FileTooLarge { size: 50, limit: 10 },
+        },
+        BatchEntry::File(png_upload()),
+    ];
+
+    let response = BatchExtractor::new()
+        .with_ocr_engine(engine.clone())
+        .with_concurrency(2)
+        .extract_all(entries)
+        .await;
+
+    assert!(response.success);
+    assert_eq!((response.succeeded, response.failed), (2, 2));
+    let names: Vec<_> = response.files.iter().map(|f| f.file_name.as_str()).collect();
+    assert_eq!(names, vec!["scan.png", "notes.txt", "huge.pdf", "scan.png"]);
+
+    assert_eq!(response.files[0].data.as_ref().unwrap().text, "batch text");
+    assert_eq!(response.files[1].error.as_ref().unwrap().code, "INVALID_FILE");
+    assert_eq!(response.files[2].error.as_ref().unwrap().code, "FILE_TOO_LARGE");
+    assert!(response.files[3].success);
+    assert_eq!(engine.calls(), 2);
+}
+
+/// Run a streamed extraction to the end and collect its events
+async fn stream_events(streamer: PageStreamer, file: ProcessedFile) -> Vec<StreamEvent> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+    tokio::spawn(async move { streamer.run(file, sender).await });
+    let mut events = Vec::new();
+    while let Some(event) = receiver.recv().await {
+        events.push(event);
+    }
+    events
+}
+
+#[tokio::test]
+async fn test_stream_native_pdf_emits_pages_then_summary() {
+    let pdf = build_pdf(vec![
+        TestPage::Text(vec!["First page of the report"]),
+        TestPage::Text(vec!["Second page of the report"]),
+    ]);
+    let file = ProcessedFile::new("report.pdf".to_string(), pdf);
+
+    let events = stream_events(PageStreamer::new(), file).await;
+    let kinds: Vec<_> = events.iter().map(|e| e.name()).collect();
+    assert_eq!(kinds, vec!["started", "page", "page", "summary"]);
+
+    assert!(matches!(&events[0], StreamEvent::Started { pages_total: 2, .. }));
+    match &events[2] {
+        StreamEvent::Page { page, text, source } => {
+            assert_eq!(*page, 2);
+            assert!(text.contains("Second page"));
+            assert_eq!(*source, TextSource::Native);
+        }
+        other => panic!("Expected a page event, got {:?}", other),
+    }
+    match &events[3] {
+        StreamEvent::Summary(response) => {
+            assert_eq!(response.data.pages, 2);
+            assert!(response.data.text.contains("First page"));
+            assert!(!response.data.metadata.ocr_used);
+            assert_eq!(response.data.metadata.page_analysis.len(), 2);
+        }
+        other => panic!("Expected the summary, got {:?}", other),
+    }
+
+    // NDJSON lines and SSE data carry the event type alongside the payload
+    let json = serde_json::to_value(&events[1]).unwrap();
+    assert_eq!(json["type"], "page");
+    assert_eq!(json["source"], "native");
+}
+
+#[tokio::test]
+async fn test_stream_image_reports_ocr_progress() {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    {
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        encoder.write_image::<colortype::Gray8>(8, 8, &[255u8; 64]).unwrap();
+        encoder.write_image::<colortype::Gray8>(8, 8, &[255u8; 64]).unwrap();
+    }
+    let file = ProcessedFile::new("scan.tiff".to_string(), buffer.into_inner())
+        .with_mime_type("image/tiff".to_string());
+    let streamer = PageStreamer::new().with_ocr_engine(Arc::new(MockOcrEngine::new("streamed text")));
+
+    let events = stream_events(streamer, file).await;
+    let kinds: Vec<_> = events.iter().map(|e| e.name()).collect();
+    assert_eq!(kinds, vec!["started", "progress", "page", "progress", "page", "summary"]);
+    assert!(matches!(&events[3], StreamEvent::Progress { pages_done: 1, pages_total: 2, .. }));
+    assert!(matches!(&events[4], StreamEvent::Page { page: 2, source: TextSource::Ocr, .. }));
+    match &events[5] {
+        StreamEvent::Summary(response) => {
+            assert_eq!(response.data.text, "streamed text\n\nstreamed text");
+            assert!(response.data.metadata.ocr_used);
+        }
+        other => panic!("Expected the summary, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_stream_ends_with_error_event() {
+    let streamer = PageStreamer::new().with_ocr_engine(Arc::new(MockOcrEngine::new("unused").unhealthy()));
+
+    let events = stream_events(streamer, png_upload()).await;
+    assert_eq!(events.len(), 1);
+    match &events[0] {
+        StreamEvent::Error { error } => assert_eq!(error.code, "OCR_ERROR"),
+        other => panic!("Expected an error event, got {:?}", other),
+    }
+}
+
+fn base64_request(body: serde_json::Value) -> ExtractRequest {
+    serde_json::from_value(body).unwrap()
+}
+
+#[test]
+fn test_extract_request_decodes_base64_content() {
+    use base64::Engine;
+
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Encoded page"])]);
+    let mut encoded = base64::engine::general_purpose::STANDARD.encode(&pdf);
+    // Encoders that wrap lines at 76 characters are fine
+    encoded.insert(76, '\n');
+
+    let request = base64_request(serde_json::json!({
+        "file_name": "encoded.pdf",
+        "file_size": pdf.len(),
+        "content": encoded,
+        "output_format": "json",
+    }));
+    assert_eq!(request.options().unwrap().output_format, OutputFormat::Json);
+
+    let file = request.into_file(1024 * 1024).unwrap();
+    assert_eq!(file.name, "encoded.pdf");
+    assert_eq!(file.content, pdf);
+    assert!(file.is_pdf());
+}
+
+#[test]
+fn test_extract_request_validation() {
+    let error = base64_request(serde_json::json!({ "content": "not base64!" }))
+        .into_file(1024)
+        .unwrap_err();
+    assert!(matches!(error, AppError::ValidationError { .. }));
+
+    let error = base64_request(serde_json::json!({ "content": "" })).into_file(1024).unwrap_err();
+    assert!(matches!(error, AppError::ValidationError { .. }));
+
+    let error = base64_request(serde_json::json!({ "content": "aGVsbG8=", "file_size": 4 }))
+        .into_file(1024)
+        .unwrap_err();
+    assert!(matches!(error, AppError::ValidationError { .. }));
+
+    // Rejected from the encoded length, before anything is decoded
+    let error = base64_request(serde_json::json!({ "content": "A".repeat(4096) }))
+        .into_file(1024)
+        .unwrap_err();
+    assert!(matches!(error, AppError::FileTooLarge { .. }));
+
+    // Misspelled options are an error rather than silently ignored
+    let parsed = serde_json::from_value::<ExtractRequest>(serde_json::json!({
+        "content": "aGVsbG8=",
+        "preprocessing": "all",
+    }));
+    assert!(matches!(parsed.unwrap().options(), Err(AppError::ValidationError { .. })));
+}
+
+#[tokio::test]
+async fn test_extract_handler_accepts_json_body() {
+    use axum::{body::Body, http::Request};
+    use base64::Engine;
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Sent as JSON"])]);
+    let body = serde_json::json!({
+        "file_name": "lambda.pdf",
+        "content": base64::engine::general_purpose::STANDARD.encode(&pdf),
+    });
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/extract")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = elicit::handlers::extract_handler(request).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(json["data"]["text"].as_str().unwrap().contains("Sent as JSON"));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/extract")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"content": "aGVsbG8="}"#))
+        .unwrap();
+    let error = elicit::handlers::extract_handler(request).await.unwrap_err();
+    assert!(matches!(error, AppError::InvalidFile { .. }));
+}
+
+#[test]
+fn test_extraction_options_parse_and_validate() {
+    let options = ExtractionOptions::parse([
+        ("preprocess", "deskew,grayscale"),
+        ("language", "deu+eng"),
+        ("psm", "6"),
+        ("ocr_min_chars", "20"),
+    ])
+    .unwrap();
+    assert_eq!(options.preprocess, vec![PreprocessingStep::Grayscale, PreprocessingStep::Deskew]);
+    assert_eq!(options.language, "deu+eng");
+    assert_eq!(options.psm, 6);
+    assert_eq!(options.ocr_min_chars, 20);
+    assert_eq!(options.ocr_min_letters, ExtractionOptions::default().ocr_min_letters);
+
+    for (name, value) in [
+        ("psm", "0"),
+        ("psm", "14"),
+        ("language", "eng; rm -rf"),
+        ("language", "deu++eng"),
+        ("ocr_min_letters", "-1"),
+        ("output_format", "pdf"),
+        ("lang", "eng"),
+    ] {
+        let error = ExtractionOptions::parse([(name, value)]).unwrap_err();
+        assert!(matches!(error, AppError::ValidationError { .. }), "{}={} was accepted", name, value);
+    }
+
+    // The echoed form parses back to the same options
+    let json = serde_json::to_value(&options).unwrap();
+    assert_eq!(json["preprocess"], serde_json::json!(["grayscale", "deskew"]));
+    let parsed: ExtractionOptions = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, options);
+
+    assert!(options.wants_ocr_for("too short"));
+    assert!(!options.wants_ocr_for(&"plenty of letters ".repeat(5)));
+}
+
+#[tokio::test]
+async fn test_options_from_headers_and_query_are_echoed() {
+    use axum::{extract::Query, http::HeaderMap};
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Options travel with the request"])]);
+
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", "application/pdf".parse().unwrap());
+    headers.insert("x-elicit-language", "deu".parse().unwrap());
+    headers.insert("x-elicit-psm", "4".parse().unwrap());
+    // The query string wins over headers
+    let params = HashMap::from([("psm".to_string(), "6".to_string())]);
+
+    let response = elicit::handlers::extract_binary_handler(headers, Query(params), pdf.into())
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(json["options"]["language"], "deu");
+    assert_eq!(json["options"]["psm"], 6);
+    assert_eq!(json["options"]["output_format"], "json");
+    assert_eq!(json["options"]["ocr_min_chars"], 100);
+
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", "application/pdf".parse().unwrap());
+    let params = HashMap::from([("psm".to_string(), "99".to_string())]);
+    let error = elicit::handlers::extract_binary_handler(headers, Query(params), b"%PDF-1.4".to_vec().into())
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AppError::ValidationError { .. }));
+}
+
+#[test]
+fn test_output_format_from_accept() {
+    assert_eq!(OutputFormat::from_accept("text/plain"), Some(OutputFormat::Text));
+    assert_eq!(OutputFormat::from_accept("text/markdown; charset=utf-8"), Some(OutputFormat::Markdown));
+    assert_eq!(OutputFormat::from_accept("*/*"), Some(OutputFormat::Json));
+    assert_eq!(
+        OutputFormat::from_accept("application/json;q=0.5, text/markdown;q=0.9"),
+        Some(OutputFormat::Markdown)
+    );
+    assert_eq!(OutputFormat::from_accept("text/plain;q=0, application/json"), Some(OutputFormat::Json));
+    // Browsers: nothing we serve is named, so the default applies
+    assert_eq!(OutputFormat::from_accept("text/html, image/webp"), None);
+    assert_eq!("md".parse::<OutputFormat>().unwrap(), OutputFormat::Markdown);
+    assert!(!OutputFormat::Text.needs_layout());
+}
+
+#[test]
+fn test_markdown_output() {
+    let markdown = elicit::services::to_markdown("  First line\nsecond line \n\n\n\x0cNext page\n", "Annual\nReport");
+    assert_eq!(markdown, "# Annual Report\n\nFirst line\nsecond line\n\nNext page\n");
+}
+
+#[tokio::test]
+async fn test_accept_header_selects_text_with_metadata_headers() {
+    use axum::{extract::Query, http::HeaderMap};
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Plain text for the shell"])]);
+    let request = |accept: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/pdf".parse().unwrap());
+        headers.insert("accept", accept.parse().unwrap());
+        headers
+    };
+
+    let response = elicit::handlers::extract_binary_handler(request("text/plain"), Query(HashMap::new()), pdf.clone().into())
+        .await
+        .unwrap();
+    let headers = response.headers().clone();
+    assert_eq!(headers["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(headers["vary"], "accept");
+    assert_eq!(headers["x-elicit-pages"], "1");
+    assert_eq!(headers["x-elicit-ocr-used"], "false");
+    assert!(headers.contains_key("x-elicit-processing-time-ms"));
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(text.contains("Plain text for the shell"));
+    assert!(text.ends_with('\n'));
+
+    let response = elicit::handlers::extract_binary_handler(request("text/markdown"), Query(HashMap::new()), pdf.clone().into())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "text/markdown; charset=utf-8");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8(bytes.to_vec()).unwrap().starts_with("# "));
+
+    // An explicit output_format beats Accept
+    let params = HashMap::from([("output_format".to_string(), "json".to_string())]);
+    let response = elicit::handlers::extract_binary_handler(request("text/plain"), Query(params), pdf.into())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert!(!response.headers().contains_key("x-elicit-pages"));
+}
+
+#[test]
+fn test_idempotency_store_lifecycle() {
+    use elicit::services::{IdempotencyOutcome, IdempotencyStore, StoredResponse};
+
+    let store = IdempotencyStore::new(10, Duration::from_millis(50));
+    let response = StoredResponse { status: 200, headers: Vec::new(), body: b"done".to_vec() };
+
+    assert_eq!(store.begin("client", "k", "fp").unwrap(), IdempotencyOutcome::Proceed);
+    assert!(matches!(store.begin("client", "k", "fp"), Err(AppError::IdempotencyKeyInUse)));
+    assert!(matches!(store.begin("client", "k", "other"), Err(AppError::IdempotencyKeyReused)));
+
+    // Released keys can be claimed again
+    store.release("client", "k");
+    assert_eq!(store.begin("client", "k", "fp").unwrap(), IdempotencyOutcome::Proceed);
+    store.complete("client", "k", "fp", response.clone());
+    assert_eq!(store.begin("client", "k", "fp").unwrap(), IdempotencyOutcome::Replay(response.clone()));
+    // Releasing never drops a stored response
+    store.release("client", "k");
+    assert_eq!(store.begin("client", "k", "fp").unwrap(), IdempotencyOutcome::Replay(response));
+
+    // Past the retention window the key is new again
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(store.begin("client", "k", "other").unwrap(), IdempotencyOutcome::Proceed);
+}
+
+#[test]
+fn test_idempotency_store_memory_budget() {
+    use elicit::services::{IdempotencyOutcome, IdempotencyStore, StoredResponse};
+
+    // Each entry takes 110 bytes: the scope, key and fingerprint plus a 100-byte body
+    let store = IdempotencyStore::new(100, Duration::from_secs(60)).with_memory_limits(300, 150);
+    let response = |size: usize| StoredResponse { status: 200, headers: Vec::new(), body: vec![b'x'; size] };
+    for key in ["k1", "k2", "k3"] {
+        assert_eq!(store.begin("client", key, "fp").unwrap(), IdempotencyOutcome::Proceed);
+        store.complete("client", key, "fp", response(100));
+    }
+
+    // The third response pushed the least recently used one out
+    assert_eq!(store.memory_used(), 220);
+    assert_eq!(store.begin("client", "k3", "fp").unwrap(), IdempotencyOutcome::Replay(response(100)));
+    assert_eq!(store.begin("client", "k2", "fp").unwrap(), IdempotencyOutcome::Replay(response(100)));
+    assert_eq!(store.begin("client", "k1", "fp").unwrap(), IdempotencyOutcome::Proceed);
+
+    // A response over the per-response limit releases its key instead of being kept
+    store.complete("client", "k1", "fp", response(200));
+    assert_eq!(store.begin("client", "k1", "fp").unwrap(), IdempotencyOutcome::Proceed);
+    assert_eq!(store.memory_used(), 230);
+}
+
+#[test]
+fn test_request_fingerprint_ignores_multipart_boundary() {
+    use elicit::services::{request_fingerprint, validate_idempotency_key};
+
+    let body = |boundary: &str| format!("--{b}\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nPDF\r\n--{b}--\r\n", b = boundary);
+    let parts = ["POST", "/api/v1/extract"];
+    assert_eq!(
+        request_fingerprint(&parts, body("aaaa").as_bytes(), Some("aaaa")),
+        request_fingerprint(&parts, body("bbbbbb").as_bytes(), Some("bbbbbb"))
+    );
+    assert_ne!(
+        request_fingerprint(&parts, b"one", None),
+        request_fingerprint(&parts, b"two", None)
+    );
+    assert_ne!(
+        request_fingerprint(&["POST", "/api/v1/extract"], b"same", None),
+        request_fingerprint(&["POST", "/api/v1/jobs"], b"same", None)
+    );
+
+    assert!(validate_idempotency_key("3f1c-retry").is_ok());
+    assert!(validate_idempotency_key("has space").is_err());
+    assert!(validate_idempotency_key(&"k".repeat(256)).is_err());
+}
+
+#[test]
+fn test_document_diff_reports_paragraphs_words_and_pages() {
+    use elicit::models::{ChangeKind, WordChange, WordOp};
+    use elicit::services::{DocumentDiff, Paragraph};
+
+    let original = vec![
+        Paragraph::new(1, "Intro clause."),
+        Paragraph::new(1, "The term is 30 days\n from signing."),
+        Paragraph::new(2, "Old clause removed entirely here."),
+        Paragraph::new(2, "Governing law is Spain."),
+    ];
+    let revised = vec![
+        Paragraph::new(1, "Intro clause."),
+        Paragraph::new(1, "The term is 45 days from signing."),
+        Paragraph::new(3, "Governing law is Spain."),
+        Paragraph::new(3, "New confidentiality clause applies."),
+    ];
+    let diff = DocumentDiff::new(original, revised);
+
+    let changes = diff.changes();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes[0].kind, ChangeKind::Modified);
+    assert_eq!((changes[0].original.paragraph, changes[0].original.page), (2, 1));
+    assert_eq!(changes[0].revised.text.as_deref(), Some("The term is 45 days from signing."));
+    let word = |op, text: &str| WordChange { op, text: text.to_string() };
+    assert_eq!(
+        changes[0].words,
+        vec![
+            word(WordOp::Equal, "The term is"),
+            word(WordOp::Delete, "30"),
+            word(WordOp::Insert, "45"),
+            word(WordOp::Equal, "days from signing."),
+        ]
+    );
+
+    // A removed paragraph points at the page of the text before it in the revision
+    assert_eq!(changes[1].kind, ChangeKind::Removed);
+    assert_eq!((changes[1].original.paragraph, changes[1].original.page), (3, 2));
+    assert_eq!((changes[1].revised.paragraph, changes[1].revised.page), (3, 1));
+    assert!(changes[1].revised.text.is_none());
+
+    assert_eq!(changes[2].kind, ChangeKind::Added);
+    assert_eq!((changes[2].original.paragraph, changes[2].original.page), (5, 2));
+    assert_eq!((changes[2].revised.paragraph, changes[2].revised.page), (4, 3));
+
+    let summary = diff.summary();
+    assert_eq!(
+        (summary.paragraphs_unchanged, summary.paragraphs_added, summary.paragraphs_removed, summary.paragraphs_modified),
+        (2, 1, 1, 1)
+    );
+    assert_eq!((summary.words_added, summary.words_removed), (5, 6));
+    // 12 shared words out of 18 + 17
+    assert_eq!(diff.similarity(), 0.6857);
+
+    assert_eq!(
+        diff.unified("v1.pdf", "v2.pdf"),
+        "--- v1.pdf\n+++ v2.pdf\n\
+         @@ -1,4 +1,4 @@ original page 1, revised page 1\n \
+         Intro clause.\n\
+         -The term is 30 days from signing.\n\
+         +The term is 45 days from signing.\n\
+         -Old clause removed entirely here.\n \
+         Governing law is Spain.\n\
+         +New confidentiality clause applies.\n"
+    );
+
+    let same = DocumentDiff::new(vec![Paragraph::new(1, "Same")], vec![Paragraph::new(4, "Same")]);
+    assert_eq!(same.similarity(), 1.0);
+    assert!(same.changes().is_empty());
+    assert_eq!(same.unified("a.pdf", "b.pdf"), "--- a.pdf\n+++ b.pdf\n");
+}
+
+#[test]
+fn test_unified_diff_splits_distant_changes_into_hunks() {
+    use elicit::services::{DocumentDiff, Paragraph};
+
+    let paragraphs = |first: &str, last: &str| {
+        let mut paragraphs = vec![Paragraph::new(1, first)];
+        paragraphs.extend((2..=5).map(|page| Paragraph::new(page, &format!("Unchanged section {}", page))));
+        paragraphs.push(Paragraph::new(6, last));
+        paragraphs
+    };
+    let diff = DocumentDiff::new(
+        paragraphs("Parties: Acme and Widget", "Signed in Madrid on Monday"),
+        paragraphs("Parties: Acme and Gadget", "Signed in Madrid on Friday"),
+    );
+
+    let unified = diff.unified("v1.pdf", "v2.pdf");
+    let hunks: Vec<&str> = unified.lines().filter(|line| line.starts_with("@@")).collect();
+    assert_eq!(
+        hunks,
+        vec![
+            "@@ -1,2 +1,2 @@ original page 1, revised page 1",
+            "@@ -5,2 +5,2 @@ original page 5, revised page 5",
+        ]
+    );
+    assert!(!unified.contains("Unchanged section 3"));
+}
+
+#[tokio::test]
+async fn test_document_paragraphs_follow_layout_blocks_and_pages() {
+    use elicit::services::{document_paragraphs, Paragraph};
+
+    // Two blank lines leave a gap wide enough to start a new block
+    let pdf = build_pdf(vec![
+        TestPage::Text(vec!["First paragraph of the contract", "continues here", "", "", "Second paragraph"]),
+        TestPage::Text(vec!["Third paragraph"]),
+    ]);
+    let options = ExtractionOptions::parse([("ocr_min_chars", "0"), ("ocr_min_letters", "0")]).unwrap();
+    let result = PdfProcessor::new()
+        .with_layout()
+        .extract_text(ProcessedFile::new("contract.pdf".to_string(), pdf), &options)
+        .await
+        .unwrap();
+    assert!(result.layout.is_some());
+    assert_eq!(
+        document_paragraphs(&result.text, result.layout.as_ref()),
+        vec![
+            Paragraph::new(1, "First paragraph of the contract continues here"),
+            Paragraph::new(1, "Second paragraph"),
+            Paragraph::new(2, "Third paragraph"),
+        ]
+    );
+
+    // Without a layout, blank lines separate paragraphs and form feeds pages
+    assert_eq!(
+        document_paragraphs("a b\nc\n\nd\x0ce\n", None),
+        vec![Paragraph::new(1, "a b c"), Paragraph::new(1, "d"), Paragraph::new(2, "e")]
+    );
+}
+
+/// A multipart form with `(field, file name, content)` files and text fields, as a handler receives it
+async fn multipart_form(files: &[(&str, &str, &[u8])], fields: &[(&str, &str)]) -> axum::extract::Multipart {
+    use axum::{body::Body, extract::{FromRequest, Multipart}, http::Request};
+
+    let boundary = "test-boundary";
+    let mut body = Vec::new();
+    for (name, file_name, content) in files {
+        body.extend(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/pdf\r\n\r\n",
+            boundary, name, file_name
+        ).as_bytes());
+        body.extend(content.iter());
+        body.extend(b"\r\n");
+    }
+    for (name, value) in fields {
+        body.extend(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            boundary, name, value
+        ).as_bytes());
+    }
+    body.extend(format!("--{}--\r\n", boundary).as_bytes());
+    let request = Request::builder()
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(body))
+        .unwrap();
+    Multipart::from_request(request, &()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_compare_handler_returns_json_and_unified_diff() {
+    use axum::http::HeaderMap;
+    use elicit::models::{ChangeKind, CompareResponse};
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let original = build_pdf(vec![TestPage::Text(vec!["Payment is due within 30 days of the invoice date"])]);
+    let revised = build_pdf(vec![TestPage::Text(vec!["Payment is due within 45 days of the invoice date"])]);
+
+    let files = [("original", "v1.pdf", original.as_slice()), ("revised", "v2.pdf", revised.as_slice())];
+    let fields = [("ocr_min_chars", "0"), ("ocr_min_letters", "0")];
+
+    let response = elicit::handlers::compare_handler(HeaderMap::new(), multipart_form(&files, &fields).await).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["vary"], "accept");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: CompareResponse = serde_json::from_slice(&bytes).unwrap();
+    assert!(body.success);
+    assert_eq!(body.data.original.file_name, "v1.pdf");
+    assert_eq!((body.data.original.pages, body.data.revised.paragraphs), (1, 1));
+    assert_eq!(body.data.changes.len(), 1);
+    assert_eq!(body.data.changes[0].kind, ChangeKind::Modified);
+    assert_eq!(body.data.changes[0].revised.page, 1);
+    assert!(body.data.similarity > 0.8 && body.data.similarity < 1.0);
+
+    let mut headers = HeaderMap::new();
+    headers.insert("accept", "text/plain".parse().unwrap());
+    let response = elicit::handlers::compare_handler(headers, multipart_form(&files, &fields).await).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(response.headers()["x-elicit-changes"], "1");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let diff = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(diff.starts_with("--- v1.pdf\n+++ v2.pdf\n@@ -1 +1 @@ original page 1, revised page 1\n"));
+    assert!(diff.contains("\n-Payment is due within 30 days"));
+    assert!(diff.contains("\n+Payment is due within 45 days"));
+
+    let hocr = [("ocr_min_chars", "0"), ("output_format", "hocr")];
+    let error = elicit::handlers::compare_handler(HeaderMap::new(), multipart_form(&files, &hocr).await).await.unwrap_err();
+    assert_eq!(error.error_code(), "VALIDATION_ERROR");
+}
+
+#[test]
+fn test_search_literal_regex_and_case_options() {
+    use elicit::models::SearchQuery;
+    use elicit::services::{search_pages, PageText};
+
+    let pages = vec![
+        PageText::new(1, "Payment is due within 30 days.\nThe Supplier shall indemnify\nthe Buyer. Late payment accrues 2% interest."),
+        PageText::new(2, "No payment after 90 days"),
+    ];
+    let queries = vec![
+        SearchQuery::literal("payment"),
+        SearchQuery::literal("payment").case_sensitive(),
+        SearchQuery::regex(r"\d+ days"),
+        SearchQuery::literal("indemnify the buyer"),
+        SearchQuery::literal("pay").whole_word(),
+    ];
+    let results = search_pages(&pages, &queries).unwrap();
+
+    assert_eq!(results[0].hit_count, 3);
+    assert_eq!(results[0].pages, vec![1, 2]);
+    assert_eq!((results[0].hits[0].start, results[0].hits[0].end), (0, 7));
+    assert_eq!(results[0].hits[0].text, "Payment");
+    assert_eq!(results[1].hit_count, 2);
+    assert!(!results[1].truncated);
+
+    let days = &results[2].hits[0];
+    assert_eq!((days.page, days.start, days.end, days.text.as_str()), (1, 22, 29, "30 days"));
+    assert_eq!(results[2].hits[1].text, "90 days");
+
+    // Literal whitespace also matches a line break
+    let phrase = &results[3].hits[0];
+    assert_eq!(phrase.text, "indemnify\nthe Buyer");
+    assert!(phrase.snippet.contains("indemnify the Buyer"));
+    let offset = phrase.start - phrase.snippet_start;
+    assert_eq!(
+        phrase.snippet.chars().skip(offset).take(phrase.end - phrase.start).collect::<String>(),
+        "indemnify the Buyer"
+    );
+    // Snippets stop at word boundaries
+    assert!(!phrase.snippet.starts_with("ayment"));
+    assert!(phrase.bboxes.is_empty());
+
+    assert_eq!(results[4].hit_count, 0);
+
+    let error = search_pages(&pages, &[SearchQuery::regex("(unclosed")]).unwrap_err();
+    assert_eq!(error.error_code(), "VALIDATION_ERROR");
+    assert!(search_pages(&pages, &[]).is_err());
+    assert!(search_pages(&pages, &[SearchQuery::literal("  ")]).is_err());
+}
+
+#[test]
+fn test_search_queries_parse_from_strings_and_objects() {
+    use elicit::models::SearchQuery;
+
+    let queries: Vec<SearchQuery> =
+        serde_json::from_str(r#"["Indemnify", {"pattern": "\\d+ days", "regex": true, "case_sensitive": true}]"#).unwrap();
+    assert_eq!(queries[0], SearchQuery::literal("Indemnify"));
+    assert_eq!(queries[1], SearchQuery::regex(r"\d+ days").case_sensitive());
+}
+
+#[tokio::test]
+async fn test_search_handler_locates_hits_with_layout() {
+    use axum::http::HeaderMap;
+    use elicit::models::LayoutUnit;
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let pdf = build_pdf(vec![
+        TestPage::Text(vec!["Master services agreement"]),
+        TestPage::Text(vec!["The Supplier shall indemnify", "the Buyer against all claims"]),
+    ]);
+    let fields = [
+        ("queries", r#"["indemnify the buyer", {"pattern": "^the", "regex": true, "case_sensitive": true}]"#),
+        ("ocr_min_chars", "0"),
+        ("ocr_min_letters", "0"),
+    ];
+    let form = multipart_form(&[("file", "msa.pdf", &pdf)], &fields).await;
+    let response = elicit::handlers::search_handler(HeaderMap::new(), form).await.unwrap().0;
+
+    assert!(response.success);
+    assert_eq!(response.data.pages, 2);
+    assert_eq!(response.data.total_hits, 2);
+    assert_eq!(response.data.unit, Some(LayoutUnit::Point));
+
+    // A phrase wrapping onto the next line gets one box per line
+    let phrase = &response.data.results[0].hits[0];
+    assert_eq!(phrase.page, 2);
+    assert_eq!(phrase.bboxes.len(), 2);
+    assert!(phrase.bboxes[0].y < phrase.bboxes[1].y);
+    assert!(phrase.bboxes.iter().all(|bbox| bbox.x >= 72.0 - 1.0 && bbox.right() <= 612.0));
+
+    // Regex anchors work per line
+    let line_start = &response.data.results[1].hits[0];
+    assert_eq!((line_start.page, line_start.text.as_str()), (2, "the"));
+    assert_eq!(line_start.bboxes.len(), 1);
+
+    let form = multipart_form(&[("file", "msa.pdf", &pdf)], &[("ocr_min_chars", "0")]).await;
+    let error = elicit::handlers::search_handler(HeaderMap::new(), form).await.unwrap_err();
+    assert_eq!(error.error_code(), "VALIDATION_ERROR");
+
+    // Queries are compiled before the document is touched, so a bad one wins over a bad file
+    let bad_regex = [("queries", r#"[{"pattern": "(unclosed", "regex": true}]"#)];
+    let form = multipart_form(&[("file", "msa.pdf", b"not a pdf")], &bad_regex).await;
+    let error = elicit::handlers::search_handler(HeaderMap::new(), form).await.unwrap_err();
+    assert_eq!(error.error_code(), "VALIDATION_ERROR");
+}
+
+#[test]
+fn test_app_error_grpc_codes() {
+    use elicit::error::{grpc_status, GRPC_ERROR_CODE_KEY};
+    use tonic::Code;
+
+    for (error, code) in [
+        (AppError::InvalidApiKey, Code::Unauthenticated),
+        (AppError::ApiKeyExpired, Code::Unauthenticated),
+        (AppError::insufficient_scope(Scope::Jobs), Code::PermissionDenied),
+        (AppError::FileTooLarge { size: 60, limit: 50 }, Code::InvalidArgument),
+        (AppError::validation("bad psm"), Code::InvalidArgument),
+        (AppError::RateLimitExceeded, Code::ResourceExhausted),
+        (AppError::not_found("job abc"), Code::NotFound),
+        (AppError::url_not_allowed("private address"), Code::PermissionDenied),
+        (AppError::timeout("ocr"), Code::DeadlineExceeded),
+        (AppError::IdempotencyKeyInUse, Code::Aborted),
+        (AppError::IdempotencyKeyReused, Code::FailedPrecondition),
+        (AppError::conflict("last admin key"), Code::FailedPrecondition),
+        (AppError::service_unavailable("ocr"), Code::Unavailable),
+        (AppError::processing("broken xref"), Code::Internal),
+    ] {
+        assert_eq!(error.grpc_code(), code, "{}", error.error_code());
+        let error_code = error.error_code();
+        let message = error.to_string();
+        let status = tonic::Status::from(error);
+        assert_eq!(status.code(), code);
+        assert_eq!(status.message(), message);
+        assert_eq!(status.metadata().get(GRPC_ERROR_CODE_KEY).unwrap(), error_code);
+    }
+
+    // Stream errors only carry the code and map the same way
+    assert_eq!(grpc_status("OCR_ERROR", "tesseract crashed").code(), Code::Internal);
+    assert_eq!(grpc_status("SOMETHING_NEW", "?").code(), Code::Unknown);
+}
+
+/// A gRPC request carrying `key` as its bearer token
+fn grpc_request<T>(message: T, key: Option<&str>) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    if let Some(key) = key {
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {}", key).parse().unwrap());
+    }
+    request
+}
+
+fn grpc_extract_request(pdf: &[u8], options: &[(&str, &str)]) -> elicit::grpc::proto::ExtractRequest {
+    elicit::grpc::proto::ExtractRequest {
+        document: Some(elicit::grpc::proto::Document {
+            file_name: "memo.pdf".to_string(),
+            content: pdf.to_vec(),
+            content_type: "application/pdf".to_string(),
+        }),
+        options: options.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+    }
+}
+
+#[tokio::test]
+async fn test_grpc_extract_checks_keys_and_renders_formats() {
+    use elicit::grpc::{proto::elicit_server::Elicit, ElicitService};
+    use elicit::error::GRPC_ERROR_CODE_KEY;
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let service = ElicitService::new();
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Memo over gRPC"]), TestPage::Text(vec!["Second page"])]);
+    let options = [("ocr_min_chars", "0"), ("ocr_min_letters", "0")];
+
+    for key in [None, Some("wrong-key")] {
+        let status = service
+            .extract(grpc_request(grpc_extract_request(&pdf, &options), key))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert_eq!(status.metadata().get(GRPC_ERROR_CODE_KEY).unwrap(), "INVALID_API_KEY");
+    }
+
+    let response = service
+        .extract(grpc_request(grpc_extract_request(&pdf, &options), Some("valid-key-123")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.text.contains("Memo over gRPC"));
+    assert_eq!(response.pages, 2);
+    assert!(!response.metadata.unwrap().ocr_used);
+    assert_eq!(response.body, None);
+    assert_eq!(response.options.get("ocr_min_chars").map(String::as_str), Some("0"));
+    assert_eq!(response.options.get("output_format").map(String::as_str), Some("json"));
+    assert_eq!(response.options.get("preprocess").map(String::as_str), Some("none"));
+
+    // Other output formats come back rendered, as over HTTP
+    let response = service
+        .extract(grpc_request(
+            grpc_extract_request(&pdf, &[("ocr_min_chars", "0"), ("ocr_min_letters", "0"), ("output_format", "markdown")]),
+            Some("valid-key-123"),
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.body.unwrap().starts_with("# memo.pdf"));
+    assert_eq!(response.content_type.as_deref(), Some(OutputFormat::Markdown.content_type()));
+
+    let status = service
+        .extract(grpc_request(grpc_extract_request(&pdf, &[("psm", "99")]), Some("valid-key-123")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert_eq!(status.metadata().get(GRPC_ERROR_CODE_KEY).unwrap(), "VALIDATION_ERROR");
+
+    let mut request = grpc_extract_request(&pdf, &options);
+    request.document = None;
+    let status = service.extract(grpc_request(request, Some("valid-key-123"))).await.unwrap_err();
+    assert_eq!(status.metadata().get(GRPC_ERROR_CODE_KEY).unwrap(), "MISSING_FILE");
+}
+
+#[tokio::test]
+async fn test_grpc_extract_stream_and_jobs() {
+    use elicit::grpc::{proto, proto::elicit_server::Elicit, ElicitService};
+    use tokio_stream::StreamExt;
+
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+    let service = ElicitService::new();
+    let pdf = build_pdf(vec![TestPage::Text(vec!["Streamed one"]), TestPage::Text(vec!["Streamed two"])]);
+    let options = [("ocr_min_chars", "0"), ("ocr_min_letters", "0")];
+
+    let stream = service
+        .extract_stream(grpc_request(grpc_extract_request(&pdf, &options), Some("valid-key-123")))
+        .await
+        .unwrap()
+        .into_inner();
+    let events: Vec<_> = stream
+        .map(|event| event.unwrap().event.unwrap())
+        .collect()
+        .await;
+    assert!(matches!(&events[0], proto::extract_event::Event::Started(started) if started.pages_total == 2));
+    let pages: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            proto::extract_event::Event::Page(page) => Some((page.page, page.source)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(pages, vec![(1, proto::TextSource::Native as i32), (2, proto::TextSource::Native as i32)]);
+    match events.last().unwrap() {
+        proto::extract_event::Event::Summary(summary) => assert!(summary.text.contains("Streamed two")),
+        other => panic!("stream ended with {:?}", other),
+    }
+
+    let status = service
+        .extract_stream(grpc_request(
+            grpc_extract_request(&pdf, &[("output_format", "text")]),
+            Some("valid-key-123"),
+        ))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let job = service
+        .create_job(grpc_request(
+            proto::CreateJobRequest {
+                document: grpc_extract_request(&pdf, &[]).document,
+                options: options.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+                callback_url: None,
+            },
+            Some("valid-key-123"),
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(job.file_name, "memo.pdf");
+
+    let mut finished = None;
+    for _ in 0..200 {
+        let current = service
+            .get_job(grpc_request(proto::GetJobRequest { job_id: job.job_id.clone() }, Some("valid-key-123")))
+            .await
+            .unwrap()
+            .into_inner();
+        if current.status == proto::JobStatus::Completed as i32 {
+            finished = Some(current);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let finished = finished.expect("job did not complete");
+    assert!(finished.result.unwrap().text.contains("Streamed one"));
+    assert_eq!(finished.pages_total, Some(2));
+
+    let deliveries = service
+        .list_job_deliveries(grpc_request(proto::GetJobRequest { job_id: job.job_id }, Some("valid-key-123")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(deliveries.deliveries.is_empty());
+
+    let status = service
+        .get_job(grpc_request(proto::GetJobRequest { job_id: "missing".to_string() }, Some("valid-key-123")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[test]
+fn test_detect_tables_from_aligned_columns() {
+    use elicit::models::{BoundingBox, DocumentLayout, LayoutBlock, LayoutLine, LayoutWord, PageLayout};
+    use elicit::services::detect_tables;
+
+    let line = |y: f32, words: &[(&str, f32)]| {
+        LayoutLine::new(
+            words
+                .iter()
+                .map(|&(text, x)| LayoutWord {
+                    text: text.to_string(),
+                    bbox: BoundingBox::new(x, y, text.len() as f32 * 6.0, 12.0),
+                    confidence: None,
+                })
+                .collect(),
+        )
+    };
+    let page = PageLayout {
+        number: 1,
+        width: 612.0,
+        height: 792.0,
+        source: TextSource::Native,
+        blocks: vec![
+            LayoutBlock::new(vec![line(60.0, &[("Price", 72.0), ("list", 108.0)])]),
+            LayoutBlock::new(vec![
+                line(100.0, &[("Item", 72.0), ("Qty", 200.0), ("Price", 300.0)]),
+                line(114.0, &[("Green", 72.0), ("apples", 108.0), ("3", 204.0), ("1.20", 300.0)]),
+                // No quantity: the price still lands in the third column
+                line(128.0, &[("Pears", 72.0), ("2.50", 302.0)]),
+            ]),
+            LayoutBlock::new(vec![line(
+                160.0,
+                &[("Prices", 72.0), ("include", 112.0), ("tax", 160.0), ("and", 182.0), ("delivery", 206.0)],
+            )]),
+            // Same columns, but far below: a separate table
+            LayoutBlock::new(vec![
+                line(400.0, &[("Total", 72.0), ("5", 200.0), ("3.70", 300.0)]),
+                line(414.0, &[("Paid", 72.0), ("5", 200.0), ("3.70", 300.0)]),
+            ]),
+        ],
+    };
+    let layout = DocumentLayout {
+        unit: LayoutUnit::Point,
+        pages: vec![page],
+    };
+
+    let tables = detect_tables(&layout);
+    assert_eq!(tables.len(), 2);
+    let table = &tables[0];
+    assert_eq!((table.page, table.columns), (1, 3));
+    assert_eq!(
+        table.rows,
+        vec![
+            vec!["Item", "Qty", "Price"],
+            vec!["Green apples", "3", "1.20"],
+            vec!["Pears", "", "2.50"],
+        ]
+    );
+    assert_eq!(table.bbox.y, 100.0);
+    assert_eq!(table.bbox.bottom(), 140.0);
+    assert_eq!(tables[1].rows[1], vec!["Paid", "5", "3.70"]);
+}
+
+#[tokio::test]
+async fn test_document_session_answers_views_within_memory_limit() {
+    use elicit::models::{SessionMessage, SearchQuery};
+    use elicit::services::DocumentSession;
+
+    let pdf = build_pdf(vec![
+        TestPage::Text(vec!["Quarterly report", "Revenue grew"]),
+        TestPage::Text(vec!["Item        Qty        Price", "Apples        3          1.20", "Pears         5          2.50"]),
+    ]);
+    let options = ExtractionOptions::parse([("ocr_min_chars", "0"), ("ocr_min_letters", "0")]).unwrap();
+    let extract = || async {
+        PdfProcessor::new()
+            .with_layout()
+            .extract_text(ProcessedFile::new("report.pdf".to_string(), pdf.clone()), &options)
+            .await
+            .unwrap()
+    };
+
+    let session = DocumentSession::new("report.pdf".to_string(), extract().await, 1024 * 1024).unwrap();
+    let document = session.document();
+    assert_eq!((document.file_name.as_str(), document.pages), ("report.pdf", 2));
+    assert!(document.memory_bytes > 0 && document.memory_bytes == session.memory_bytes());
+
+    let pages = session.pages(Some(&[2])).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].page, 2);
+    assert_eq!(pages[0].source, Some(TextSource::Native));
+    assert!(pages[0].text.starts_with("Item"));
+    let error = session.pages(Some(&[3])).unwrap_err();
+    assert_eq!(error.error_code(), "VALIDATION_ERROR");
+
+    match session.text(OutputFormat::Markdown).unwrap() {
+        SessionMessage::Text { text, content_type, .. } => {
+            assert!(text.starts_with("# report.pdf"));
+            assert_eq!(content_type, OutputFormat::Markdown.content_type());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(session.text(OutputFormat::Json).is_err());
+
+    match session.tables(None).unwrap() {
+        SessionMessage::Tables { tables, unit } => {
+            assert_eq!(unit, LayoutUnit::Point);
+            assert_eq!(tables.len(), 1);
+            assert_eq!(tables[0].page, 2);
+            assert_eq!(tables[0].rows[0], vec!["Item", "Qty", "Price"]);
+            assert_eq!(tables[0].rows[2], vec!["Pears", "5", "2.50"]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match session.search(&[SearchQuery::literal("revenue")]).unwrap() {
+        SessionMessage::Search { results, total_hits, unit } => {
+            assert_eq!(total_hits, 1);
+            assert_eq!(results[0].hits[0].page, 1);
+            assert_eq!(unit, Some(LayoutUnit::Point));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // Too big for the session's memory budget
+    let error = DocumentSession::new("report.pdf".to_string(), extract().await, 64).unwrap_err();
+    assert_eq!(error.error_code(), "SESSION_LIMIT_EXCEEDED");
+    assert_eq!(error.status_code(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn test_api_key_registry_from_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.json");
+    let record = |id: &str, key: &str, extra: serde_json::Value| {
+        let mut record = serde_json::json!({
+            "id": id, "name": format!("{} client", id), "prefix": key_prefix(key), "key_hash": hash_key(key),
+            "scopes": ["extract"], "created_at": "2026-01-01T00:00:00Z",
+        });
+        record.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        record
+    };
+    let acme_key = generate_key();
+    let keys = serde_json::json!([
+        record("acme", &acme_key, serde_json::json!({"scopes": ["extract", "ocr"], "expires_at": "2999-01-01T00:00:00Z"})),
+        record("old", "old-secret", serde_json::json!({"expires_at": "2020-01-01T00:00:00Z"})),
+        record("revoked", "revoked-secret", serde_json::json!({"disabled": true})),
+    ]);
+    std::fs::write(&path, keys.to_string()).unwrap();
+
+    // Only the prefix of a generated key is stored in the clear
+    let stored = std::fs::read_to_string(&path).unwrap();
+    assert!(!stored.contains(&acme_key) && stored.contains(key_prefix(&acme_key)));
+
+    let registry = ApiKeyRegistry::load_file(&path).unwrap();
+    assert_eq!(registry.len(), 3);
+    assert_eq!(registry.source(), &KeySource::File(path.clone()));
+
+    let identity = registry.resolve(&acme_key).unwrap();
+    assert_eq!(identity.id, "acme");
+    assert_eq!(identity.name, "acme client");
+    assert!(identity.has_scope(Scope::Ocr));
+    identity.require(Scope::Extract).unwrap();
+    let denied = identity.require(Scope::Jobs).unwrap_err();
+    assert_eq!(denied.error_code(), "INSUFFICIENT_SCOPE");
+    assert_eq!(denied.status_code().as_u16(), 403);
+
+    assert_eq!(registry.resolve("old-secret").unwrap_err().error_code(), "API_KEY_EXPIRED");
+    assert_eq!(registry.resolve("revoked-secret").unwrap_err().error_code(), "API_KEY_DISABLED");
+    assert_eq!(registry.resolve("unknown").unwrap_err().error_code(), "INVALID_API_KEY");
+    // Same prefix, different key
+    let forged = format!("{}_{}", key_prefix(&acme_key), "x".repeat(32));
+    assert_eq!(registry.resolve(&forged).unwrap_err().error_code(), "INVALID_API_KEY");
+
+    // Records must not share ids, hashes must be readable and scopes known
+    let keys = serde_json::json!([record("a", "k1", serde_json::json!({})), record("a", "k2", serde_json::json!({}))]);
+    std::fs::write(&path, keys.to_string()).unwrap();
+    assert!(ApiKeyRegistry::load_file(&path).is_err());
+    let keys = serde_json::json!([record("a", "k1", serde_json::json!({"key_hash": "md5$00$11"}))]);
+    std::fs::write(&path, keys.to_string()).unwrap();
+    assert!(ApiKeyRegistry::load_file(&path).is_err());
+    let keys = serde_json::json!([record("a", "k1", serde_json::json!({"scopes": ["everything"]}))]);
+    std::fs::write(&path, keys.to_string()).unwrap();
+    assert!(ApiKeyRegistry::load_file(&path).is_err());
+    assert!(ApiKeyRegistry::load_file(dir.path().join("missing.json")).is_err());
+}
+
+#[test]
+fn test_api_key_registry_from_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.db");
+
+    // The table is created when the database doesn't have it yet
+    assert!(ApiKeyRegistry::load_sqlite(&path).unwrap().is_empty());
+
+    let insert = "INSERT INTO api_keys (id, name, prefix, key_hash, scopes, created_at, expires_at, disabled) \
+                  VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+    let db = rusqlite::Connection::open(&path).unwrap();
+    let ci_key = generate_key();
+    db.execute(
+        insert,
+        rusqlite::params!["ci", "CI pipeline", key_prefix(&ci_key), hash_key(&ci_key), "jobs, extract",
+            "2026-01-01T00:00:00Z", None::<String>, false],
+    )
+    .unwrap();
+    let gone_key = generate_key();
+    db.execute(
+        insert,
+        rusqlite::params!["gone", "Former partner", key_prefix(&gone_key), hash_key(&gone_key), "extract",
+            "2026-01-01T00:00:00Z", None::<String>, true],
+    )
+    .unwrap();
+    drop(db);
+
+    let registry = ApiKeyRegistry::load_sqlite(&path).unwrap();
+    assert_eq!(registry.len(), 2);
+    let identity = registry.resolve(&ci_key).unwrap();
+    assert_eq!(identity.name, "CI pipeline");
+    assert_eq!(identity.scopes, vec![Scope::Extract, Scope::Jobs]);
+    assert_eq!(registry.resolve(&gone_key).unwrap_err().error_code(), "API_KEY_DISABLED");
+
+    assert_eq!(parse_scopes("ocr,admin,ocr").unwrap(), vec![Scope::Ocr, Scope::Admin]);
+    assert!(parse_scopes("extract,delete").is_err());
+}
+
+#[test]
+fn test_api_key_hashes_are_salted_and_verified() {
+    let key = generate_key();
+    assert!(key.starts_with("elk_"));
+    assert_eq!(key_prefix(&key).len(), 12);
+    assert_ne!(generate_key(), key);
+
+    // Salted: the same key hashes differently each time, and both hashes verify
+    let (first, second) = (hash_key(&key), hash_key(&key));
+    assert_ne!(first, second);
+    assert!(first.starts_with("sha256$"));
+    assert!(verify_key(&key, &first) && verify_key(&key, &second));
+    assert!(!verify_key("elk_wrong", &first));
+    assert!(!verify_key(&key, "sha256$zz$00"));
+    assert!(!verify_key(&key, "plaintext"));
+
+    // Short legacy keys are their own prefix
+    assert_eq!(key_prefix("abc"), "abc");
+}
+
+#[test]
+fn test_generate_key_command() {
+    use elicit::cli::{generate_keys, render_generated_key, GenerateKeyArgs, RecordFormat};
+
+    let args: Vec<String> = ["--name", "Acme Corp", "--scopes", "jobs,extract", "--count", "2", "--format", "sql"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let args = GenerateKeyArgs::parse(&args).unwrap();
+    assert_eq!(args.format, RecordFormat::Sql);
+
+    let generated = generate_keys(&args);
+    assert_eq!(generated.len(), 2);
+    assert_ne!(generated[0].record.id, generated[1].record.id);
+    for key in &generated {
+        assert_eq!(key.record.scopes, vec![Scope::Extract, Scope::Jobs]);
+        assert!(verify_key(&key.key, &key.record.key_hash));
+        let printed = render_generated_key(key, args.format);
+        assert_eq!(printed.matches(&key.key).count(), 1);
+        assert!(printed.contains("INSERT INTO api_keys") && printed.contains(&key.record.key_hash));
+    }
+
+    // The printed JSON record loads into a registry that accepts the key
+    let args: Vec<String> = ["--id", "acme", "--name", "Acme", "--scopes", "extract"].iter().map(|a| a.to_string()).collect();
+    let generated = generate_keys(&GenerateKeyArgs::parse(&args).unwrap()).remove(0);
+    let printed = render_generated_key(&generated, RecordFormat::Json);
+    let record = &printed[printed.find('{').unwrap()..];
+    let record: elicit::models::ApiKey = serde_json::from_str(record).unwrap();
+    let registry = ApiKeyRegistry::new(vec![record], KeySource::Env).unwrap();
+    assert_eq!(registry.resolve(&generated.key).unwrap().id, "acme");
+
+    for bad in [&["--name", "x"][..], &["--scopes", "extract"], &["--name", "x", "--scopes", "extract", "--count", "0"],
+        &["--id", "a", "--name", "x", "--scopes", "extract", "--count", "2"], &["--name"],
+        &["--id", "team/acme", "--name", "x", "--scopes", "extract"], &["--id", "acme corp", "--name", "x", "--scopes", "extract"]] {
+        let bad: Vec<String> = bad.iter().map(|arg| arg.to_string()).collect();
+        assert!(GenerateKeyArgs::parse(&bad).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn test_api_key_registry_from_env_keys() {
+    env::set_var("VALID_API_KEYS", "valid-key-123,another-key");
+
+    // Keys from VALID_API_KEYS may do everything but manage keys
+    let identity = api_keys().resolve("valid-key-123").unwrap();
+    assert!(identity.id.starts_with("env-"));
+    assert_eq!(identity.scopes, vec![Scope::Extract, Scope::Ocr, Scope::Jobs]);
+    assert!(!identity.has_scope(Scope::Admin));
+
+    // Ids are derived from the key, so they survive restarts
+    let keys = ["valid-key-123".to_string()];
+    let registry = ApiKeyRegistry::from_env_keys(keys.iter()).unwrap();
+    assert_eq!(registry.resolve("valid-key-123").unwrap().id, identity.id);
+}
+
+#[test]
+fn test_api_key_registry_changes_are_saved_and_audited() {
+    use elicit::models::{ApiKeyIdentity, AuditAction, CreateApiKeyRequest};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.json");
+    let admin_key = generate_key();
+    let admin = serde_json::json!([{
+        "id": "root", "name": "Operations", "prefix": key_prefix(&admin_key), "key_hash": hash_key(&admin_key),
+        "scopes": ["admin"],
+    }]);
+    std::fs::write(&path, admin.to_string()).unwrap();
+
+    let registry = ApiKeyRegistry::load_file(&path).unwrap();
+    let actor: ApiKeyIdentity = registry.resolve(&admin_key).unwrap();
+    let create = |id: &str| CreateApiKeyRequest {
+        id: Some(id.to_string()),
+        name: "Acme Corp".to_string(),
+        scopes: vec![Scope::Jobs, Scope::Extract, Scope::Jobs],
+        expires_at: None,
+    };
+
+    // A new key works straight away and counts its requests
+    let issued = registry.create(&actor, create("acme")).unwrap();
+    assert_eq!(issued.api_key.scopes, vec![Scope::Extract, Scope::Jobs]);
+    registry.resolve(&issued.key).unwrap();
+    registry.resolve(&issued.key).unwrap();
+    let view = registry.get("acme").unwrap();
+    assert_eq!(view.usage.requests, 2);
+    assert!(view.usage.last_used_at.is_some());
+    assert_eq!(registry.create(&actor, create("acme")).unwrap_err().error_code(), "CONFLICT");
+    assert_eq!(registry.create(&actor, create("no/slashes")).unwrap_err().error_code(), "VALIDATION_ERROR");
+
+    // Rotation replaces the key behind the id
+    let rotated = registry.rotate(&actor, "acme").unwrap();
+    assert_eq!(registry.resolve(&issued.key).unwrap_err().error_code(), "INVALID_API_KEY");
+    assert_eq!(registry.resolve(&rotated.key).unwrap().id, "acme");
+
+    registry.disable(&actor, "acme").unwrap();
+    assert_eq!(registry.resolve(&rotated.key).unwrap_err().error_code(), "API_KEY_DISABLED");
+
+    // The last admin key can't lock everyone out
+    assert_eq!(registry.disable(&actor, "root").unwrap_err().error_code(), "CONFLICT");
+    assert_eq!(registry.delete(&actor, "root").unwrap_err().error_code(), "CONFLICT");
+    assert_eq!(registry.delete(&actor, "missing").unwrap_err().error_code(), "NOT_FOUND");
+
+    let temp = registry.create(&actor, create("temp")).unwrap();
+    registry.delete(&actor, "temp").unwrap();
+    assert_eq!(registry.resolve(&temp.key).unwrap_err().error_code(), "INVALID_API_KEY");
+    assert_eq!(registry.list().iter().map(|key| key.id.as_str()).collect::<Vec<_>>(), vec!["root", "acme"]);
+
+    let actions = |events: Vec<elicit::models::AuditEvent>| events.iter().map(|event| event.action).collect::<Vec<_>>();
+    let acme_events = registry.audit(Some("acme"), 100);
+    assert!(acme_events.iter().all(|event| event.actor == "root"));
+    assert_eq!(actions(acme_events), vec![AuditAction::Disabled, AuditAction::Rotated, AuditAction::Created]);
+    assert_eq!(registry.audit(None, 2).len(), 2);
+
+    // Everything survives a restart, and the file still holds no key
+    let stored = std::fs::read_to_string(&path).unwrap();
+    assert!(!stored.contains(&rotated.key) && !stored.contains(&issued.key));
+    let reloaded = ApiKeyRegistry::load_file(&path).unwrap();
+    assert_eq!(reloaded.len(), 2);
+    assert_eq!(reloaded.resolve(&rotated.key).unwrap_err().error_code(), "API_KEY_DISABLED");
+    assert_eq!(reloaded.audit(None, 100), registry.audit(None, 100));
+
+    // The same holds for a SQLite store
+    let db_path = dir.path().join("keys.db");
+    let db = ApiKeyRegistry::load_sqlite(&db_path).unwrap();
+    let first = db.create(&actor, create("ci")).unwrap();
+    let second = db.rotate(&actor, "ci").unwrap();
+    let reloaded = ApiKeyRegistry::load_sqlite(&db_path).unwrap();
+    assert_eq!(reloaded.resolve(&first.key).unwrap_err().error_code(), "INVALID_API_KEY");
+    assert_eq!(reloaded.resolve(&second.key).unwrap().scopes, vec![Scope::Extract, Scope::Jobs]);
+    assert_eq!(actions(reloaded.audit(Some("ci"), 100)), vec![AuditAction::Rotated, AuditAction::Created]);
+
+    // A change that can't be saved never reaches the keys in use
+    let gone = tempfile::tempdir().unwrap();
+    let gone_path = gone.path().join("keys.json");
+    std::fs::write(&gone_path, admin.to_string()).unwrap();
+    let unsaved = ApiKeyRegistry::load_file(&gone_path).unwrap();
+    drop(gone);
+    assert!(unsaved.create(&actor, create("lost")).is_err());
+    assert_eq!(unsaved.get("lost").unwrap_err().error_code(), "NOT_FOUND");
+    assert_eq!(unsaved.len(), 1);
+}